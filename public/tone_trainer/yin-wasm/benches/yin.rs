@@ -0,0 +1,49 @@
+//! Native benchmarks of the YIN core:
+//! `cargo bench --features native --bench yin`
+
+use std::f32::consts::PI;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use yin_wasm::native::{analyze, analyze_frame, Settings};
+
+/// A voiced-like test signal: 220 Hz with two harmonics
+fn test_audio(samples: usize, sample_rate: f32) -> Vec<f32> {
+    (0..samples)
+        .map(|i| {
+            let phase = 2.0 * PI * 220.0 * i as f32 / sample_rate;
+            phase.sin() + 0.5 * (2.0 * phase).sin() + 0.25 * (3.0 * phase).sin()
+        })
+        .collect()
+}
+
+fn frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    for frame_size in [256, 1024, 2048, 4096] {
+        let settings = Settings {
+            frame_size,
+            ..Settings::default()
+        };
+        let audio = test_audio(frame_size, settings.sample_rate);
+        group.throughput(Throughput::Elements(frame_size as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(frame_size),
+            &audio,
+            |b, audio| b.iter(|| analyze_frame(black_box(audio), &settings)),
+        );
+    }
+    group.finish();
+}
+
+fn clip(c: &mut Criterion) {
+    let settings = Settings::default();
+    // A typical two-second reference clip
+    let audio = test_audio((2.0 * settings.sample_rate) as usize, settings.sample_rate);
+    let mut group = c.benchmark_group("clip");
+    group.throughput(Throughput::Elements(audio.len() as u64));
+    group.bench_function("2s", |b| b.iter(|| analyze(black_box(&audio), &settings)));
+    group.finish();
+}
+
+criterion_group!(benches, frame, clip);
+criterion_main!(benches);
@@ -0,0 +1,74 @@
+//! Dump the pitch contours of WAV files as JSON, e.g. to precompute the
+//! reference contours of a deck:
+//!
+//! ```text
+//! cargo run --release --features native --example contours -- \
+//!     [--sample-rate HZ] [--frame-size N] [--hop-size N] FILE.wav... > contours.json
+//! ```
+//!
+//! Prints one JSON object mapping every file to its contour; files that
+//! fail to analyze are reported on stderr and left out.
+
+use std::process::ExitCode;
+
+use yin_wasm::native::{analyze_wav_file, Settings};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: contours [--sample-rate HZ] [--frame-size N] [--hop-size N] FILE.wav...");
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let mut settings = Settings::default();
+    let mut files = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--sample-rate" | "--frame-size" | "--hop-size" => args.next(),
+            "-h" | "--help" => return usage(),
+            _ => {
+                files.push(arg);
+                continue;
+            }
+        };
+        let parsed = match (arg.as_str(), value) {
+            ("--sample-rate", Some(v)) => v.parse().map(|v| settings.sample_rate = v).is_ok(),
+            ("--frame-size", Some(v)) => v.parse().map(|v| settings.frame_size = v).is_ok(),
+            ("--hop-size", Some(v)) => v.parse().map(|v| settings.hop_size = v).is_ok(),
+            _ => false,
+        };
+        if !parsed {
+            eprintln!("invalid value for {}", arg);
+            return usage();
+        }
+    }
+    if files.is_empty() {
+        return usage();
+    }
+
+    let mut failed = false;
+    let mut first = true;
+    print!("{{");
+    for file in &files {
+        match analyze_wav_file(file, &settings) {
+            Ok(contour) => {
+                if !first {
+                    print!(",");
+                }
+                first = false;
+                print!("{:?}:{}", file, contour.to_json());
+            }
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                failed = true;
+            }
+        }
+    }
+    println!("}}");
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,61 @@
+//! Compensation for automatic gain control. Mobile browsers apply AGC that
+//! slowly pumps the gain up in pauses and down during loud speech, which
+//! warps the energy contour that segmentation and stress detection rely on.
+//! The gain trend is followed by the loudest speech within a window longer
+//! than a syllable and divided out, so syllable-scale energy differences
+//! stay while the slow gain changes go.
+
+use wasm_bindgen::prelude::*;
+
+use crate::input::frame_levels;
+use crate::noise::estimate_noise;
+
+/// Width (seconds) of the window the gain trend is taken over; longer than
+/// a couple of syllables, shorter than typical AGC adaptation
+const TREND_WINDOW_SECONDS: f32 = 1.0;
+
+/// Level above the noise floor (dB) a frame needs to steer the trend
+const SPEECH_ABOVE_NOISE_DB: f32 = 6.0;
+
+/// Lowest level considered when computing decibels
+const LEVEL_FLOOR: f32 = 1e-9;
+
+/// `levels` (per-frame RMS, frames `frame_period` seconds apart) with the
+/// slow gain trend removed. Each frame is scaled by the ratio between the
+/// loudest speech frame of the whole recording and the loudest speech frame
+/// within `TREND_WINDOW_SECONDS` around it; frames with no speech nearby
+/// keep their level.
+pub fn detrend_levels(levels: &[f32], noise_rms: f32, frame_period: f32) -> Vec<f32> {
+    let to_db = |level: f32| 20.0 * level.max(LEVEL_FLOOR).log10();
+    let speech_db = to_db(noise_rms) + SPEECH_ABOVE_NOISE_DB;
+    let db: Vec<f32> = levels.iter().map(|&l| to_db(l)).collect();
+    let speech = |d: &f32| *d > speech_db;
+    let Some(reference) = db.iter().copied().filter(speech).reduce(f32::max) else {
+        return levels.to_vec();
+    };
+
+    let half = ((TREND_WINDOW_SECONDS / 2.0 / frame_period.max(f32::EPSILON)) as usize).max(1);
+    (0..levels.len())
+        .map(|i| {
+            let window = &db[i.saturating_sub(half)..(i + half + 1).min(db.len())];
+            match window.iter().copied().filter(speech).reduce(f32::max) {
+                Some(trend) => levels[i] * 10f32.powf((reference - trend) / 20.0),
+                None => levels[i],
+            }
+        })
+        .collect()
+}
+
+/// Per-frame RMS levels like `frame_levels`, with the gain changes of AGC
+/// removed; pass them to `syllable_prominence` for recordings from phones
+#[wasm_bindgen]
+pub fn agc_compensated_levels(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> Vec<f32> {
+    let levels = frame_levels(audio_data, frame_size, hop_size);
+    let noise = estimate_noise(audio_data, frame_size, hop_size);
+    detrend_levels(&levels, noise.noise_rms, hop_size as f32 / sample_rate)
+}
@@ -0,0 +1,228 @@
+//! Structured view of a YIN analysis. The flat [pitch, confidence, tau, ...]
+//! layout is easy to misread on the JS side (e.g. taking tau for pitch);
+//! `YinAnalysis` keeps the columns apart behind typed accessors.
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::YinConfig;
+use crate::input::resample_linear;
+use crate::scale::PitchScale;
+use crate::typed::{push_frames_with_voicing, to_js};
+use crate::warnings::{audio_warnings, AnalysisWarning, WarningCode};
+
+/// Per-frame pitch, confidence and tau of one analysis
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct YinAnalysis {
+    results: Vec<f32>,
+    frame_period: f32,
+    /// Per-frame RMS level and voicing probability; empty unless the
+    /// analysis went through the voicing gate (see `gate`)
+    energy: Vec<f32>,
+    voicing: Vec<f32>,
+    warnings: Vec<AnalysisWarning>,
+    /// Unit of the pitch in `to_json`
+    scale: PitchScale,
+}
+
+impl YinAnalysis {
+    /// Wrap a flat [pitch, confidence, tau, ...] array; `frame_period` is
+    /// the hop between frames in seconds
+    pub fn from_flat(results: Vec<f32>, frame_period: f32) -> Self {
+        YinAnalysis {
+            results,
+            frame_period,
+            energy: Vec::new(),
+            voicing: Vec::new(),
+            warnings: Vec::new(),
+            scale: PitchScale::Hz,
+        }
+    }
+
+    /// Attach the per-frame RMS levels and voicing probabilities
+    pub fn with_voicing(mut self, energy: Vec<f32>, voicing: Vec<f32>) -> Self {
+        self.energy = energy;
+        self.voicing = voicing;
+        self
+    }
+
+    /// Attach caveats about the analysis
+    pub fn with_warnings(mut self, warnings: Vec<AnalysisWarning>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Report the pitch on `scale` in `to_json`
+    pub fn with_scale(mut self, scale: PitchScale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    fn column(&self, offset: usize) -> Vec<f32> {
+        self.results.chunks_exact(3).map(|f| f[offset]).collect()
+    }
+
+    fn value(&self, i: usize, offset: usize) -> Option<f32> {
+        self.results.get(i * 3 + offset).copied()
+    }
+}
+
+#[wasm_bindgen]
+impl YinAnalysis {
+    /// Analyze `audio_data` with `config`; throws when the settings are
+    /// invalid or the audio is shorter than one frame. Clipping, a dropped
+    /// tail and a low SNR are reported in `warnings`.
+    pub fn run(audio_data: &[f32], config: &YinConfig) -> Result<YinAnalysis, JsError> {
+        let results = config
+            .analyze_checked(audio_data)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(
+            YinAnalysis::from_flat(results, config.hop_size as f32 / config.sample_rate)
+                .with_warnings(audio_warnings(
+                    audio_data,
+                    config.sample_rate,
+                    config.frame_size,
+                    config.hop_size,
+                ))
+                .with_scale(config.scale),
+        )
+    }
+
+    /// `run` for audio recorded at `input_rate`, resampled to
+    /// `config.sample_rate` first when they differ (with a `Resampled`
+    /// warning)
+    pub fn run_at_rate(
+        audio_data: &[f32],
+        input_rate: f32,
+        config: &YinConfig,
+    ) -> Result<YinAnalysis, JsError> {
+        if input_rate <= 0.0 || input_rate == config.sample_rate {
+            return YinAnalysis::run(audio_data, config);
+        }
+        let resampled = resample_linear(audio_data, input_rate, config.sample_rate);
+        let mut analysis = YinAnalysis::run(&resampled, config)?;
+        analysis
+            .warnings
+            .push(AnalysisWarning::new(WarningCode::Resampled, input_rate));
+        Ok(analysis)
+    }
+
+    /// Structured view of a flat array from `perform_yin_analysis`
+    pub fn from_results(results: &[f32], frame_period: f32) -> YinAnalysis {
+        YinAnalysis::from_flat(results.to_vec(), frame_period)
+    }
+
+    /// Number of frames
+    pub fn len(&self) -> usize {
+        self.results.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Seconds between frames
+    #[wasm_bindgen(getter)]
+    pub fn frame_period(&self) -> f32 {
+        self.frame_period
+    }
+
+    /// Pitch of frame `i` in Hz (0 when unvoiced), undefined past the end
+    pub fn pitch_at(&self, i: usize) -> Option<f32> {
+        self.value(i, 0)
+    }
+
+    /// Confidence (0-1) of frame `i`, undefined past the end
+    pub fn confidence_at(&self, i: usize) -> Option<f32> {
+        self.value(i, 1)
+    }
+
+    /// Period in samples found for frame `i` (-1 when none), undefined past
+    /// the end
+    pub fn tau_at(&self, i: usize) -> Option<f32> {
+        self.value(i, 2)
+    }
+
+    /// RMS level of frame `i`; undefined past the end or when the analysis
+    /// wasn't gated
+    pub fn energy_at(&self, i: usize) -> Option<f32> {
+        self.energy.get(i).copied()
+    }
+
+    /// Voicing probability (0-1) of frame `i`; undefined past the end or
+    /// when the analysis wasn't gated
+    pub fn voicing_at(&self, i: usize) -> Option<f32> {
+        self.voicing.get(i).copied()
+    }
+
+    /// Whether frame `i` has a pitch
+    pub fn is_voiced(&self, i: usize) -> bool {
+        self.pitch_at(i).is_some_and(|p| p > 0.0)
+    }
+
+    /// Start time of every frame in seconds
+    pub fn timestamps(&self) -> Vec<f32> {
+        (0..self.len())
+            .map(|i| i as f32 * self.frame_period)
+            .collect()
+    }
+
+    /// Pitch of every frame in Hz
+    pub fn pitches(&self) -> Vec<f32> {
+        self.column(0)
+    }
+
+    /// Confidence of every frame
+    pub fn confidences(&self) -> Vec<f32> {
+        self.column(1)
+    }
+
+    /// Tau of every frame
+    pub fn taus(&self) -> Vec<f32> {
+        self.column(2)
+    }
+
+    /// RMS level of every frame (empty unless gated)
+    pub fn energies(&self) -> Vec<f32> {
+        self.energy.clone()
+    }
+
+    /// Voicing probability of every frame (empty unless gated)
+    pub fn voicing_probabilities(&self) -> Vec<f32> {
+        self.voicing.clone()
+    }
+
+    /// Non-fatal caveats about the analysis (empty for a flat array wrapped
+    /// with `from_results`)
+    #[wasm_bindgen(getter)]
+    pub fn warnings(&self) -> Vec<AnalysisWarning> {
+        self.warnings.clone()
+    }
+
+    /// The flat [pitch, confidence, tau, ...] array
+    pub fn to_flat(&self) -> Vec<f32> {
+        self.results.clone()
+    }
+
+    /// Frames as a JSON array of `YinFrame` objects, with the pitch on the
+    /// config's `scale`
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        push_frames_with_voicing(
+            &mut out,
+            &self.results,
+            &self.energy,
+            &self.voicing,
+            self.frame_period,
+            0.0,
+            self.scale,
+        );
+        out
+    }
+
+    /// Frames as `YinFrame` objects
+    #[wasm_bindgen(unchecked_return_type = "YinFrame[]")]
+    pub fn to_object(&self) -> JsValue {
+        to_js(&self.to_json())
+    }
+}
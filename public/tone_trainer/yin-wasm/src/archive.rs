@@ -0,0 +1,234 @@
+//! Saved analysis sessions for reviewing past attempts. A session keeps the
+//! settings, the per-frame results, the voicing decision of every frame and
+//! the tone verdicts in a compact binary form, a fraction of the size of
+//! the same data as JSON, plus its timing against the reference recording
+//! when one was given. The settings are stored with the cache version
+//! of their layout, so sessions saved by older builds keep loading.
+
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{BinaryError, ByteReader, ByteWriter};
+use crate::cache::CACHE_VERSION;
+use crate::classify::{classify_tones, ToneGuess};
+use crate::config::YinConfig;
+use crate::migrate::read_config;
+use crate::replay::{replay_alignment, ReplayAlignment};
+use crate::segments::pitch_column;
+
+const SESSION_MAGIC: &[u8; 4] = b"YINA";
+
+/// Bump when the session encoding itself changes; settings changes are
+/// covered by the cache version stored alongside them
+const SESSION_VERSION: u8 = 2;
+
+/// One analyzed attempt with everything needed to review it later
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalysisSession {
+    config: YinConfig,
+    results: Vec<f32>,
+    voiced: Vec<bool>,
+    tones: Vec<ToneGuess>,
+    alignment: Option<ReplayAlignment>,
+}
+
+#[wasm_bindgen]
+impl AnalysisSession {
+    /// Session of `results` (flat [pitch, confidence, tau, ...]) analyzed
+    /// with `config`. `voiced` holds one flag per frame (non-zero when
+    /// voiced), e.g. from the voicing gate; when empty, frames with a pitch
+    /// count as voiced. The tones are classified from the voiced pitch.
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: &YinConfig, results: Vec<f32>, voiced: &[u8]) -> AnalysisSession {
+        let pitch = pitch_column(&results);
+        let voiced: Vec<bool> = if voiced.is_empty() {
+            pitch.iter().map(|&p| p > 0.0).collect()
+        } else {
+            (0..pitch.len())
+                .map(|i| voiced.get(i).is_some_and(|&v| v != 0))
+                .collect()
+        };
+        let period = config.hop_size as f32 / config.sample_rate;
+        let voiced_pitch: Vec<f32> = pitch
+            .iter()
+            .zip(&voiced)
+            .map(|(&p, &v)| if v { p } else { 0.0 })
+            .collect();
+        let timestamps: Vec<f32> = (0..pitch.len()).map(|i| i as f32 * period).collect();
+        AnalysisSession {
+            config: *config,
+            tones: classify_tones(&voiced_pitch, &timestamps),
+            results,
+            voiced,
+            alignment: None,
+        }
+    }
+
+    /// Analyze `audio_data` with `config` into a session; throws when the
+    /// settings are invalid or the audio is shorter than one frame
+    pub fn analyze(audio_data: &[f32], config: &YinConfig) -> Result<AnalysisSession, JsError> {
+        let results = config
+            .analyze_checked(audio_data)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(AnalysisSession::new(config, results, &[]))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn config(&self) -> YinConfig {
+        self.config
+    }
+
+    /// Flat [pitch, confidence, tau, ...] array
+    #[wasm_bindgen(getter)]
+    pub fn results(&self) -> Vec<f32> {
+        self.results.clone()
+    }
+
+    /// One flag per frame, 1 when voiced
+    #[wasm_bindgen(getter)]
+    pub fn voiced(&self) -> Vec<u8> {
+        self.voiced.iter().map(|&v| v as u8).collect()
+    }
+
+    /// Tone verdict of every syllable
+    #[wasm_bindgen(getter)]
+    pub fn tones(&self) -> Vec<ToneGuess> {
+        self.tones.clone()
+    }
+
+    /// Timing against the reference recording, see `set_reference`
+    #[wasm_bindgen(getter)]
+    pub fn alignment(&self) -> Option<ReplayAlignment> {
+        self.alignment
+    }
+
+    /// Align the attempt to the reference's Hz pitch track (frames
+    /// `reference_frame_period` seconds apart) for synchronized A/B
+    /// playback; the alignment is saved with the session
+    pub fn set_reference(&mut self, reference_pitch: &[f32], reference_frame_period: f32) {
+        let period = self.config.hop_size as f32 / self.config.sample_rate;
+        let pitch: Vec<f32> = pitch_column(&self.results)
+            .iter()
+            .zip(&self.voiced)
+            .map(|(&p, &v)| if v { p } else { 0.0 })
+            .collect();
+        self.alignment = Some(replay_alignment(
+            &pitch,
+            period,
+            reference_pitch,
+            reference_frame_period,
+        ));
+    }
+
+    /// Versioned binary form for storage (e.g. in IndexedDB)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = ByteWriter::with_header(SESSION_MAGIC, SESSION_VERSION);
+        out.u8(CACHE_VERSION);
+        self.config.write_to(&mut out);
+        out.f32s(&self.results);
+        // Voicing flags packed eight to a byte
+        out.usize(self.voiced.len());
+        for chunk in self.voiced.chunks(8) {
+            out.u8(chunk
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (i, &v)| bits | (v as u8) << i));
+        }
+        out.usize(self.tones.len());
+        for guess in &self.tones {
+            out.u8(guess.tone);
+            out.f32(guess.confidence);
+            out.f32(guess.start_time);
+            out.f32(guess.end_time);
+            out.f32s(&guess.probabilities);
+        }
+        out.bool(self.alignment.is_some());
+        if let Some(alignment) = self.alignment {
+            out.f32(alignment.offset());
+            out.f32(alignment.stretch());
+            out.bool(alignment.aligned());
+        }
+        out.bytes
+    }
+
+    /// Inverse of `to_bytes`, for sessions saved by this or any earlier
+    /// version
+    pub fn from_bytes(bytes: &[u8]) -> Result<AnalysisSession, JsError> {
+        Self::decode(bytes).map_err(|err| JsError::new(&err.to_string()))
+    }
+}
+
+impl AnalysisSession {
+    fn decode(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let (mut input, version) = ByteReader::with_header(bytes, SESSION_MAGIC)?;
+        if version == 0 || version > SESSION_VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+        let config_version = input.u8()?;
+        if config_version == 0 || config_version > CACHE_VERSION {
+            return Err(BinaryError::UnsupportedVersion(config_version));
+        }
+        let config = read_config(&mut input, config_version)?;
+        let results = input.f32s()?;
+
+        let frames = input.usize()?;
+        let mut voiced = Vec::new();
+        for _ in 0..frames.div_ceil(8) {
+            let bits = input.u8()?;
+            voiced.extend((0..8).map(|i| bits & (1 << i) != 0));
+        }
+        voiced.truncate(frames);
+
+        let count = input.usize()?;
+        let mut tones = Vec::new();
+        for _ in 0..count {
+            tones.push(ToneGuess {
+                tone: input.u8()?,
+                confidence: input.f32()?,
+                start_time: input.f32()?,
+                end_time: input.f32()?,
+                probabilities: input.f32s()?,
+            });
+        }
+        // Version 1 predates alignment metadata
+        let alignment = if version >= 2 && input.bool()? {
+            Some(ReplayAlignment::new(
+                input.f32()?,
+                input.f32()?,
+                input.bool()?,
+            ))
+        } else {
+            None
+        };
+        input.finish()?;
+
+        Ok(AnalysisSession {
+            config,
+            results,
+            voiced,
+            tones,
+            alignment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::AnalysisState;
+
+    #[test]
+    fn sessions_and_stream_states_reject_each_other() {
+        let config = YinConfig::new();
+        let session = AnalysisSession::new(&config, vec![200.0, 0.9, 220.0], &[]);
+        let state = AnalysisState::new(44100.0, 2048, 512, 0.15, 80.0, 500.0, true).unwrap();
+        assert_eq!(
+            AnalysisSession::decode(&state.to_bytes()),
+            Err(BinaryError::BadMagic)
+        );
+        assert!(matches!(
+            AnalysisState::decode(&session.to_bytes()),
+            Err(BinaryError::BadMagic)
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+use wasm_bindgen::prelude::*;
+
+use crate::config::{frame_count_error, framing_error};
+use crate::logging::log_warn;
+use crate::{
+    yin_cumulative_mean_normalized_difference, yin_difference_function, yin_parabolic_interpolation,
+};
+
+/// aubio's default yin tolerance
+pub const AUBIO_DEFAULT_TOLERANCE: f32 = 0.15;
+
+/// aubio's default silence gate (dB SPL)
+pub const AUBIO_DEFAULT_SILENCE_DB: f32 = -90.0;
+
+/// aubio-style sound pressure level of a block: 10 * log10(mean square)
+fn block_db_spl(block: &[f32]) -> f32 {
+    if block.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let energy: f32 = block.iter().map(|x| x * x).sum::<f32>() / block.len() as f32;
+    10.0 * energy.log10()
+}
+
+/// Pick tau the way aubio's pitchyin does: the first local minimum below the
+/// tolerance, otherwise the global minimum of the CMNDF
+fn aubio_pick_tau(cmndf: &[f32], tolerance: f32) -> usize {
+    for tau in 2..cmndf.len().saturating_sub(1) {
+        if cmndf[tau] < tolerance && cmndf[tau] < cmndf[tau + 1] {
+            return tau;
+        }
+    }
+    (1..cmndf.len())
+        .min_by(|&a, &b| cmndf[a].total_cmp(&cmndf[b]))
+        .unwrap_or(0)
+}
+
+/// Analyze audio following aubio's yin conventions, so results stay comparable
+/// with decks that stored aubio-derived reference values:
+///
+/// - The audio is consumed in blocks of `hop_size`; each frame analyzes the
+///   most recent `buf_size` samples, zero-padded before the start of the file.
+///   Frame `i` is stamped at `i * hop_size / sample_rate`, like aubio's CLI.
+/// - A pitch is always reported unless the block is below `silence_db`, even
+///   when no minimum beats the tolerance (aubio then uses the global minimum).
+/// - Confidence is `1 - CMNDF(tau)` at the chosen tau.
+///
+/// Returns a flat array: [time1, pitch1, confidence1, time2, ...]
+#[wasm_bindgen]
+pub fn perform_aubio_analysis(
+    audio_data: &[f32],
+    sample_rate: f32,
+    buf_size: usize,
+    hop_size: usize,
+    tolerance: f32,
+    silence_db: f32,
+) -> Vec<f32> {
+    let mut results = Vec::new();
+    if buf_size < 4 || hop_size == 0 || hop_size > buf_size {
+        return results;
+    }
+
+    let num_frames = audio_data.len().div_ceil(hop_size);
+    if let Some(err) = framing_error(buf_size, hop_size)
+        .or_else(|| frame_count_error(audio_data.len(), sample_rate, num_frames))
+    {
+        log_warn!("{}", err);
+        return results;
+    }
+    results.reserve(num_frames * 3);
+
+    let mut window = vec![0.0f32; buf_size];
+    for i in 0..num_frames {
+        let start = i * hop_size;
+        let end = (start + hop_size).min(audio_data.len());
+        let block = &audio_data[start..end];
+
+        // Slide the analysis buffer by one hop, zero-padding a short final block
+        window.copy_within(hop_size.., 0);
+        let tail = buf_size - hop_size;
+        window[tail..tail + block.len()].copy_from_slice(block);
+        window[tail + block.len()..].fill(0.0);
+
+        let cmndf = yin_cumulative_mean_normalized_difference(&yin_difference_function(&window));
+        let tau = aubio_pick_tau(&cmndf, tolerance);
+        let confidence = (1.0 - cmndf[tau]).max(0.0);
+
+        let pitch = if tau == 0 || block_db_spl(block) < silence_db {
+            0.0
+        } else {
+            sample_rate / yin_parabolic_interpolation(&cmndf, tau as i32)
+        };
+
+        results.push(start as f32 / sample_rate);
+        results.push(pitch);
+        results.push(confidence);
+    }
+
+    results
+}
@@ -0,0 +1,119 @@
+//! In-browser micro-benchmarks, to check performance where it matters: in
+//! the WASM build on the learner's device
+
+use wasm_bindgen::prelude::*;
+
+use crate::governor::now_ms;
+use crate::simd::{set_simd_enabled, simd_enabled, simd_supported};
+use crate::{
+    yin_cmndf_with, yin_difference_function, yin_difference_function_fft,
+    yin_difference_function_naive, CmndfSummation,
+};
+
+/// Deterministic test frame: a voiced-like tone with harmonics and noise
+fn test_frame(frame_size: usize) -> Vec<f32> {
+    let mut seed = 0x2545_f491_u32;
+    (0..frame_size)
+        .map(|i| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let noise = seed as f32 / u32::MAX as f32 - 0.5;
+            let phase = i as f32 * 2.0 * std::f32::consts::PI * 220.0 / 44100.0;
+            phase.sin() + 0.5 * (2.0 * phase).sin() + 0.05 * noise
+        })
+        .collect()
+}
+
+/// Time the direct and FFT difference functions on a `frame_size` frame.
+/// Returns [direct_ms, fft_ms, max_error] where the times are per frame
+/// averaged over `iterations` runs and max_error is the largest difference
+/// between the two relative to the largest value.
+#[wasm_bindgen]
+pub fn benchmark_difference_function(frame_size: usize, iterations: usize) -> Vec<f64> {
+    let frame = test_frame(frame_size);
+    let iterations = iterations.max(1);
+
+    let started = now_ms();
+    let mut direct = Vec::new();
+    for _ in 0..iterations {
+        direct = yin_difference_function_naive(std::hint::black_box(&frame));
+    }
+    let direct_ms = (now_ms() - started) / iterations as f64;
+
+    let started = now_ms();
+    let mut fast = Vec::new();
+    for _ in 0..iterations {
+        fast = yin_difference_function_fft(std::hint::black_box(&frame));
+    }
+    let fft_ms = (now_ms() - started) / iterations as f64;
+
+    let scale = direct.iter().copied().fold(f32::EPSILON, f32::max);
+    let max_error = direct
+        .iter()
+        .zip(&fast)
+        .map(|(a, b)| (a - b).abs() / scale)
+        .fold(0.0, f32::max);
+    vec![direct_ms, fft_ms, max_error as f64]
+}
+
+/// Time the direct difference function with and without `simd128` on a
+/// `frame_size` frame. Returns [scalar_ms, simd_ms, max_error] like
+/// `benchmark_difference_function`; simd_ms is NaN in builds without SIMD.
+#[wasm_bindgen]
+pub fn benchmark_simd_difference(frame_size: usize, iterations: usize) -> Vec<f64> {
+    let frame = test_frame(frame_size);
+    let iterations = iterations.max(1);
+    let was_enabled = simd_enabled();
+
+    let time = |simd: bool| {
+        set_simd_enabled(simd);
+        let started = now_ms();
+        let mut out = Vec::new();
+        for _ in 0..iterations {
+            out = yin_difference_function_naive(std::hint::black_box(&frame));
+        }
+        ((now_ms() - started) / iterations as f64, out)
+    };
+    let (scalar_ms, scalar) = time(false);
+    let (simd_ms, vectorized) = time(true);
+    set_simd_enabled(was_enabled);
+
+    if !simd_supported() {
+        return vec![scalar_ms, f64::NAN, 0.0];
+    }
+    let scale = scalar.iter().copied().fold(f32::EPSILON, f32::max);
+    let max_error = scalar
+        .iter()
+        .zip(&vectorized)
+        .map(|(a, b)| (a - b).abs() / scale)
+        .fold(0.0, f32::max);
+    vec![scalar_ms, simd_ms, max_error as f64]
+}
+
+/// Precision of the CMNDF running sum on a `frame_size` frame: returns
+/// [plain_error, compensated_error], the largest relative deviation of the
+/// plain and Kahan-compensated f32 CMNDF from one accumulated in f64
+#[wasm_bindgen]
+pub fn benchmark_cmndf_summation(frame_size: usize) -> Vec<f64> {
+    let difference_function = yin_difference_function(&test_frame(frame_size));
+
+    let mut reference = vec![1.0f64; difference_function.len()];
+    let mut running_sum = 0.0f64;
+    for tau in 1..difference_function.len() {
+        running_sum += difference_function[tau] as f64;
+        reference[tau] = difference_function[tau] as f64 / (running_sum / tau as f64);
+    }
+
+    let error = |summation| {
+        yin_cmndf_with(&difference_function, summation)
+            .iter()
+            .zip(&reference)
+            .map(|(&value, &exact)| (value as f64 - exact).abs() / exact.abs().max(f64::EPSILON))
+            .fold(0.0, f64::max)
+    };
+    vec![
+        error(CmndfSummation::Plain),
+        error(CmndfSummation::Compensated),
+    ]
+}
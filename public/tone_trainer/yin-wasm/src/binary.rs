@@ -0,0 +1,163 @@
+//! Minimal little-endian binary encoding for saved state and sessions
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BinaryError {
+    UnexpectedEnd,
+    BadMagic,
+    UnsupportedVersion(u8),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryError::UnexpectedEnd => write!(f, "unexpected end of data"),
+            BinaryError::BadMagic => write!(f, "unrecognized data header"),
+            BinaryError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            BinaryError::Invalid(what) => write!(f, "invalid {}", what),
+        }
+    }
+}
+
+/// Append-only encoder
+#[derive(Default)]
+pub(crate) struct ByteWriter {
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl ByteWriter {
+    /// Start with a 4-byte magic tag and a version byte
+    pub(crate) fn with_header(magic: &[u8; 4], version: u8) -> Self {
+        let mut writer = ByteWriter::default();
+        writer.bytes.extend_from_slice(magic);
+        writer.u8(version);
+        writer
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    pub(crate) fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Length-prefixed f32 array
+    pub(crate) fn f32s(&mut self, values: &[f32]) {
+        self.usize(values.len());
+        for &v in values {
+            self.f32(v);
+        }
+    }
+
+    /// Length-prefixed u16 array
+    pub(crate) fn u16s(&mut self, values: &[u16]) {
+        self.usize(values.len());
+        for &v in values {
+            self.u16(v);
+        }
+    }
+}
+
+/// Cursor over encoded bytes
+pub(crate) struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    /// Check the magic tag and return the reader with the version byte
+    pub(crate) fn with_header(bytes: &'a [u8], magic: &[u8; 4]) -> Result<(Self, u8), BinaryError> {
+        let mut reader = ByteReader { bytes };
+        if reader.take(4)? != magic {
+            return Err(BinaryError::BadMagic);
+        }
+        let version = reader.u8()?;
+        Ok((reader, version))
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BinaryError> {
+        if self.bytes.len() < n {
+            return Err(BinaryError::UnexpectedEnd);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, BinaryError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, BinaryError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(BinaryError::Invalid("boolean")),
+        }
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, BinaryError> {
+        let mut buf = [0u8; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, BinaryError> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub(crate) fn usize(&mut self) -> Result<usize, BinaryError> {
+        usize::try_from(self.u64()?).map_err(|_| BinaryError::Invalid("length"))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, BinaryError> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(f32::from_le_bytes(buf))
+    }
+
+    pub(crate) fn f32s(&mut self) -> Result<Vec<f32>, BinaryError> {
+        let len = self.usize()?;
+        if len > self.bytes.len() / 4 {
+            return Err(BinaryError::UnexpectedEnd);
+        }
+        (0..len).map(|_| self.f32()).collect()
+    }
+
+    pub(crate) fn u16s(&mut self) -> Result<Vec<u16>, BinaryError> {
+        let len = self.usize()?;
+        if len > self.bytes.len() / 2 {
+            return Err(BinaryError::UnexpectedEnd);
+        }
+        (0..len).map(|_| self.u16()).collect()
+    }
+
+    /// Fail unless every byte was consumed
+    pub(crate) fn finish(self) -> Result<(), BinaryError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(BinaryError::Invalid("trailing data"))
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Versioned container for cached reference analyses. The header records
+//! the schema version and hashes of the config and audio the analysis was
+//! computed from, so a stale entry is detected and recomputed instead of
+//! being trusted.
+
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{BinaryError, ByteReader, ByteWriter};
+use crate::config::YinConfig;
+use crate::hash::{hash_bytes, hash_samples};
+
+pub(crate) const CACHE_MAGIC: &[u8; 4] = b"YINC";
+
+/// Bump whenever the analysis or the encoding changes in a way that makes
+/// old entries wrong
+pub(crate) const CACHE_VERSION: u8 = 7;
+
+/// Hash identifying the settings an analysis was computed with
+pub fn config_hash(config: &YinConfig) -> u64 {
+    let mut out = ByteWriter::default();
+    config.write_to(&mut out);
+    hash_bytes(&out.bytes)
+}
+
+/// Encode `results` (flat [pitch, confidence, tau, ...]) as a cache entry
+/// for `audio` analyzed with `config`
+#[wasm_bindgen]
+pub fn cache_put(audio: &[f32], config: &YinConfig, results: &[f32]) -> Vec<u8> {
+    let mut out = ByteWriter::with_header(CACHE_MAGIC, CACHE_VERSION);
+    out.u64(config_hash(config));
+    out.u64(hash_samples(audio));
+    config.write_to(&mut out);
+    out.f32s(results);
+    out.bytes
+}
+
+fn decode_entry(
+    bytes: &[u8],
+    audio: &[f32],
+    config: &YinConfig,
+) -> Result<Option<Vec<f32>>, BinaryError> {
+    let (mut input, version) = ByteReader::with_header(bytes, CACHE_MAGIC)?;
+    if version != CACHE_VERSION {
+        return Ok(None);
+    }
+    if input.u64()? != config_hash(config) || input.u64()? != hash_samples(audio) {
+        return Ok(None);
+    }
+    YinConfig::read_from(&mut input)?;
+    let results = input.f32s()?;
+    input.finish()?;
+    Ok(Some(results))
+}
+
+/// Results stored in a cache entry, or None when the entry is stale (other
+/// schema version, config or audio) or unreadable
+#[wasm_bindgen]
+pub fn cache_get(bytes: &[u8], audio: &[f32], config: &YinConfig) -> Option<Vec<f32>> {
+    decode_entry(bytes, audio, config).ok().flatten()
+}
+
+/// Outcome of `analyze_cached`
+#[wasm_bindgen]
+pub struct CachedAnalysis {
+    results: Vec<f32>,
+    entry: Vec<u8>,
+    recomputed: bool,
+}
+
+#[wasm_bindgen]
+impl CachedAnalysis {
+    /// Flat [pitch, confidence, tau, ...] array
+    #[wasm_bindgen(getter)]
+    pub fn results(&self) -> Vec<f32> {
+        self.results.clone()
+    }
+
+    /// Cache entry to store (unchanged when the cached one was still valid)
+    #[wasm_bindgen(getter)]
+    pub fn entry(&self) -> Vec<u8> {
+        self.entry.clone()
+    }
+
+    /// True if the cached entry was missing or stale
+    #[wasm_bindgen(getter)]
+    pub fn recomputed(&self) -> bool {
+        self.recomputed
+    }
+}
+
+/// Use the cached analysis if `cached` is a valid entry for this audio and
+/// config, otherwise analyze again and return a fresh entry to store.
+/// Pass an empty array when nothing is cached yet.
+#[wasm_bindgen]
+pub fn analyze_cached(cached: &[u8], audio: &[f32], config: &YinConfig) -> CachedAnalysis {
+    match cache_get(cached, audio, config) {
+        Some(results) => CachedAnalysis {
+            results,
+            entry: cached.to_vec(),
+            recomputed: false,
+        },
+        None => {
+            let results = config.analyze(audio);
+            CachedAnalysis {
+                entry: cache_put(audio, config, &results),
+                results,
+                recomputed: true,
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Incremental tone classification for rapid-fire drills: a provisional
+//! label is available as soon as enough of the syllable has been voiced,
+//! and is refined as more frames arrive.
+
+use wasm_bindgen::prelude::*;
+
+use crate::pinyin::NEUTRAL_TONE;
+use crate::segments::voiced_runs;
+use crate::tones::{
+    expand_template, hz_to_chao, resample_syllable, tone_template, voiced_median, SYLLABLE_POINTS,
+};
+
+/// Voiced frames needed before the first label is emitted
+pub const MIN_CLASSIFY_FRAMES: usize = 8;
+
+/// Spread (Chao steps) of the RMS distance used to turn distances into
+/// tone probabilities
+const DISTANCE_SPREAD: f32 = 1.0;
+
+/// Unvoiced gaps shorter than this stay inside a syllable
+const SYLLABLE_GAP_SECONDS: f32 = 0.05;
+
+/// Voiced runs shorter than this are ignored as blips
+const MIN_SYLLABLE_SECONDS: f32 = 0.06;
+
+/// Syllables shorter than this fraction of the typical syllable length may
+/// be neutral; the shorter, the more likely
+const NEUTRAL_MAX_LENGTH_RATIO: f32 = 0.6;
+
+/// Classifies a single syllable's tone from pitch frames as they arrive.
+/// Frames are compared with the opening part of each tone's template, in
+/// proportion to how much of `expected_frames` has been voiced so far, so an
+/// early tone 3 (still falling) isn't mistaken for a tone 4 that has fallen
+/// too little.
+#[wasm_bindgen]
+pub struct ToneClassifier {
+    speaker_median_hz: f32,
+    expected_frames: usize,
+    voiced: Vec<f32>,
+    tone: u8,
+    confidence: f32,
+}
+
+#[wasm_bindgen]
+impl ToneClassifier {
+    /// `speaker_median_hz` is the learner's typical pitch (a lone syllable
+    /// can't be judged high or low against itself; 0 uses the syllable's own
+    /// median). `expected_frames` is the typical voiced length of a syllable.
+    #[wasm_bindgen(constructor)]
+    pub fn new(speaker_median_hz: f32, expected_frames: usize) -> ToneClassifier {
+        ToneClassifier {
+            speaker_median_hz,
+            expected_frames: expected_frames.max(1),
+            voiced: Vec::new(),
+            tone: 0,
+            confidence: 0.0,
+        }
+    }
+
+    /// Add pitch frames (Hz, <= 0 for unvoiced). Returns [tone, confidence]
+    /// once at least `MIN_CLASSIFY_FRAMES` frames are voiced, empty before.
+    pub fn push(&mut self, pitch: &[f32]) -> Vec<f32> {
+        self.voiced
+            .extend(pitch.iter().copied().filter(|&p| p > 0.0));
+        if self.voiced.len() < MIN_CLASSIFY_FRAMES {
+            return Vec::new();
+        }
+        self.classify();
+        vec![self.tone as f32, self.confidence]
+    }
+
+    /// Current label (1-4), or 0 before enough frames are voiced
+    #[wasm_bindgen(getter)]
+    pub fn tone(&self) -> u8 {
+        self.tone
+    }
+
+    /// Probability (0-1) of the current label among the four tones
+    #[wasm_bindgen(getter)]
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Voiced frames seen so far
+    #[wasm_bindgen(getter)]
+    pub fn voiced_frames(&self) -> usize {
+        self.voiced.len()
+    }
+
+    /// Start on the next syllable
+    pub fn reset(&mut self) {
+        self.voiced.clear();
+        self.tone = 0;
+        self.confidence = 0.0;
+    }
+}
+
+/// Probabilities of tones 1-4 for a resampled Chao contour, compared with
+/// the opening `progress` (0-1] of each tone's template
+fn tone_probabilities(observed: &[f32], progress: f32) -> [f32; 4] {
+    // Template points covering the whole syllable, so that its first
+    // SYLLABLE_POINTS cover the part voiced so far
+    let full_points = (SYLLABLE_POINTS as f32 / progress).round() as usize;
+    let mut likelihoods = [1u8, 2, 3, 4].map(|tone| {
+        let expected = expand_template(tone_template(tone), full_points);
+        let sum_sq: f32 = observed
+            .iter()
+            .zip(&expected)
+            .map(|(o, e)| (o - e) * (o - e))
+            .sum();
+        let rms = (sum_sq / observed.len() as f32).sqrt() / DISTANCE_SPREAD;
+        (-0.5 * rms * rms).exp()
+    });
+    let total: f32 = likelihoods.iter().sum();
+    for l in &mut likelihoods {
+        *l = if total > 0.0 { *l / total } else { 0.25 };
+    }
+    likelihoods
+}
+
+/// Index and value of the largest entry
+fn best_of(values: &[f32]) -> (usize, f32) {
+    values
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, 0.0))
+}
+
+impl ToneClassifier {
+    fn classify(&mut self) {
+        let median = if self.speaker_median_hz > 0.0 {
+            self.speaker_median_hz
+        } else {
+            voiced_median(&self.voiced)
+        };
+        let observed = resample_syllable(&hz_to_chao(&self.voiced, median));
+        let progress = (self.voiced.len() as f32 / self.expected_frames as f32).min(1.0);
+        let (best, probability) = best_of(&tone_probabilities(&observed, progress));
+        self.tone = best as u8 + 1;
+        self.confidence = probability;
+    }
+}
+
+/// Tone verdict for one syllable found by `classify_tones`
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToneGuess {
+    pub(crate) tone: u8,
+    pub(crate) confidence: f32,
+    pub(crate) start_time: f32,
+    pub(crate) end_time: f32,
+    pub(crate) probabilities: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl ToneGuess {
+    /// Predicted tone 1-4, or 5 for neutral
+    #[wasm_bindgen(getter)]
+    pub fn tone(&self) -> u8 {
+        self.tone
+    }
+
+    /// Probability (0-1) of the predicted tone
+    #[wasm_bindgen(getter)]
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Timestamp of the syllable's first voiced frame
+    #[wasm_bindgen(getter)]
+    pub fn start_time(&self) -> f32 {
+        self.start_time
+    }
+
+    /// Timestamp of the syllable's last voiced frame
+    #[wasm_bindgen(getter)]
+    pub fn end_time(&self) -> f32 {
+        self.end_time
+    }
+
+    /// Probabilities of tones 1, 2, 3, 4 and neutral
+    #[wasm_bindgen(getter)]
+    pub fn probabilities(&self) -> Vec<f32> {
+        self.probabilities.clone()
+    }
+}
+
+/// Split a Hz pitch contour into syllables and classify each one's tone.
+/// `timestamps` gives each frame's time in seconds. Syllables are voiced
+/// runs, with short gaps bridged and blips dropped; levels are judged
+/// against the median of the whole contour. Short syllables are likely
+/// neutral: below `NEUTRAL_MAX_LENGTH_RATIO` of the median syllable length,
+/// the neutral probability grows as the syllable gets shorter.
+///
+/// ```
+/// use yin_wasm::classify::classify_tones;
+/// use yin_wasm::fixtures::fixture;
+/// use yin_wasm::segments::pitch_column;
+///
+/// let phrase = fixture("phrase_1234").unwrap();
+/// let config = phrase.config();
+/// let pitch = pitch_column(&config.analyze(&phrase.audio()));
+/// let period = config.hop_size as f32 / config.sample_rate;
+/// let times: Vec<f32> = (0..pitch.len()).map(|i| i as f32 * period).collect();
+/// assert_eq!(classify_tones(&pitch, &times).len(), phrase.tones().len());
+/// ```
+#[wasm_bindgen]
+pub fn classify_tones(pitch_contour: &[f32], timestamps: &[f32]) -> Vec<ToneGuess> {
+    let n = pitch_contour.len().min(timestamps.len());
+    let pitch = &pitch_contour[..n];
+    if n < 2 {
+        return Vec::new();
+    }
+    let mut steps: Vec<f32> = timestamps[..n].windows(2).map(|w| w[1] - w[0]).collect();
+    steps.sort_by(|a, b| a.total_cmp(b));
+    let frame_period = steps[steps.len() / 2].max(f32::EPSILON);
+    let frames = |seconds: f32| (seconds / frame_period).ceil() as usize;
+
+    let runs = voiced_runs(
+        pitch,
+        frames(SYLLABLE_GAP_SECONDS),
+        frames(MIN_SYLLABLE_SECONDS).max(1),
+    );
+    classify_ranges(pitch, &timestamps[..n], &runs)
+}
+
+/// Classify the syllables at the frame ranges [start, end) of `runs`, e.g.
+/// from `segment_syllables`. Levels are judged against the median of the
+/// whole contour and syllable lengths against the median syllable.
+pub(crate) fn classify_ranges(
+    pitch: &[f32],
+    timestamps: &[f32],
+    runs: &[(usize, usize)],
+) -> Vec<ToneGuess> {
+    let mut lengths: Vec<usize> = runs.iter().map(|&(s, e)| e - s).collect();
+    lengths.sort_unstable();
+    let typical = lengths.get(lengths.len() / 2).copied().unwrap_or(1) as f32;
+    let median = voiced_median(pitch);
+
+    runs.iter()
+        .map(|&(start, end)| {
+            let observed = resample_syllable(&hz_to_chao(&pitch[start..end], median));
+            let ratio = (end - start) as f32 / typical;
+            let neutral = (1.0 - ratio / NEUTRAL_MAX_LENGTH_RATIO).max(0.0);
+
+            let mut probabilities: Vec<f32> = tone_probabilities(&observed, 1.0)
+                .iter()
+                .map(|p| p * (1.0 - neutral))
+                .collect();
+            probabilities.push(neutral);
+            let (best, confidence) = best_of(&probabilities);
+            ToneGuess {
+                tone: if best == 4 {
+                    NEUTRAL_TONE
+                } else {
+                    best as u8 + 1
+                },
+                confidence,
+                start_time: timestamps[start],
+                end_time: timestamps[end - 1],
+                probabilities,
+            }
+        })
+        .collect()
+}
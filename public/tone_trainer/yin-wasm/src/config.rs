@@ -0,0 +1,886 @@
+use std::cell::Cell;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{BinaryError, ByteReader, ByteWriter};
+use crate::json::{push_key, push_number};
+use crate::noise::analyze_noise_robust;
+use crate::profile::selected_profile;
+use crate::scale::{compare_pitch_tracks, PitchScale};
+use crate::typed::{bool_property, number_property, push_frames, to_js};
+use crate::window::WindowFunction;
+use crate::{analyze_with_params, tau_search_start, CmndfSummation, YinParams};
+
+/// Longest frame (samples) accepted; YIN's cost grows with the square of
+/// the frame, and no speech pitch needs more than this at any sample rate
+pub const MAX_FRAME_SIZE: usize = 16384;
+
+/// Longest recording (seconds) analyzed in one call
+pub const MAX_AUDIO_SECONDS: f32 = 600.0;
+
+/// Most frames analyzed in one call, about 12 minutes at a 512-sample hop
+/// and 44.1 kHz; a hop of a few samples over a long recording would
+/// otherwise keep the page busy for minutes
+pub const MAX_FRAMES: usize = 60_000;
+
+/// YIN analysis settings
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct YinConfig {
+    pub sample_rate: f32,
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub threshold: f32,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub interpolation: bool,
+    /// First lag (samples) the threshold search looks at; 0 derives it from
+    /// `max_freq` (see `tau_search_start`)
+    pub tau_start: usize,
+    /// Accumulation of the CMNDF running sum; `Compensated` keeps long
+    /// frames accurate
+    pub summation: CmndfSummation,
+    /// Window applied to each frame before the difference function
+    pub window: WindowFunction,
+    /// Noise-robust mode for poor microphones: high-pass and spectral
+    /// subtraction of the measured noise, with the threshold relaxed per
+    /// frame by its SNR (see `analyze_noise_robust`). Only whole-recording
+    /// analysis (`analyze`) applies it.
+    pub noise_robust: bool,
+    /// Unit of the pitch in frame objects and JSON exports, and of the
+    /// distance `compare` measures; flat result arrays stay in Hz
+    pub scale: PitchScale,
+}
+
+#[wasm_bindgen]
+impl YinConfig {
+    /// Defaults suited to Mandarin speech
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> YinConfig {
+        YinConfig {
+            sample_rate: 44100.0,
+            frame_size: 2048,
+            hop_size: 512,
+            threshold: 0.15,
+            min_freq: 80.0,
+            max_freq: 500.0,
+            interpolation: true,
+            tau_start: 0,
+            summation: CmndfSummation::Plain,
+            window: WindowFunction::Rectangular,
+            noise_robust: false,
+            scale: PitchScale::Hz,
+        }
+    }
+
+    /// Lag the threshold search actually starts at
+    pub fn effective_tau_start(&self) -> usize {
+        if self.tau_start > 0 {
+            self.tau_start.max(2)
+        } else {
+            tau_search_start(self.sample_rate, self.max_freq)
+        }
+    }
+}
+
+/// Why a `YinConfig` can't be used for analysis
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigError {
+    BadSampleRate(f32),
+    ZeroHopSize,
+    /// The hop is longer than the frame, so samples between frames would be
+    /// skipped; usually the two were swapped
+    HopLargerThanFrame {
+        hop_size: usize,
+        frame_size: usize,
+    },
+    /// Half the frame (the YIN window) can't hold one period of `min_freq`
+    FrameTooShort {
+        frame_size: usize,
+        needed: usize,
+    },
+    BadThreshold(f32),
+    BadFrequencyRange {
+        min_freq: f32,
+        max_freq: f32,
+    },
+    /// `max_freq` is at or above the Nyquist frequency
+    AboveNyquist {
+        max_freq: f32,
+        nyquist: f32,
+    },
+    /// The search starts past the longest period of `min_freq`, so no lag
+    /// is ever searched
+    TauStartTooLarge {
+        tau_start: usize,
+        max_tau: usize,
+    },
+    /// The audio is shorter than one frame, so no frame can be analyzed
+    AudioTooShort {
+        audio_len: usize,
+        frame_size: usize,
+    },
+    /// The frame is longer than `MAX_FRAME_SIZE`
+    FrameTooLong {
+        frame_size: usize,
+        max: usize,
+    },
+    /// The audio is longer than `MAX_AUDIO_SECONDS`
+    AudioTooLong {
+        audio_len: usize,
+        max_len: usize,
+    },
+    /// The hop is so small for the audio length that more than `MAX_FRAMES`
+    /// frames would be analyzed
+    TooManyFrames {
+        frames: usize,
+        max: usize,
+    },
+    /// `noise_robust` was set for streaming analysis, which can't measure
+    /// the noise of the whole recording up front
+    NoiseRobustStreaming,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::BadSampleRate(rate) => write!(f, "invalid sample rate {}", rate),
+            ConfigError::ZeroHopSize => write!(f, "hop size must be at least 1"),
+            ConfigError::HopLargerThanFrame {
+                hop_size,
+                frame_size,
+            } => write!(
+                f,
+                "hop size {} is larger than frame size {} (swapped?)",
+                hop_size, frame_size
+            ),
+            ConfigError::FrameTooShort { frame_size, needed } => write!(
+                f,
+                "frame size {} is too short for min_freq; need at least {}",
+                frame_size, needed
+            ),
+            ConfigError::BadThreshold(threshold) => {
+                write!(f, "threshold {} must be between 0 and 1", threshold)
+            }
+            ConfigError::BadFrequencyRange { min_freq, max_freq } => {
+                write!(f, "invalid frequency range {}-{} Hz", min_freq, max_freq)
+            }
+            ConfigError::AboveNyquist { max_freq, nyquist } => write!(
+                f,
+                "max_freq {} Hz is at or above the Nyquist frequency {} Hz",
+                max_freq, nyquist
+            ),
+            ConfigError::TauStartTooLarge { tau_start, max_tau } => write!(
+                f,
+                "tau start {} is past the longest period searched ({})",
+                tau_start, max_tau
+            ),
+            ConfigError::AudioTooShort {
+                audio_len,
+                frame_size,
+            } => write!(
+                f,
+                "audio shorter than one frame ({} < {} samples)",
+                audio_len, frame_size
+            ),
+            ConfigError::FrameTooLong { frame_size, max } => {
+                write!(f, "frame size {} is above the limit of {}", frame_size, max)
+            }
+            ConfigError::AudioTooLong { audio_len, max_len } => write!(
+                f,
+                "audio of {} samples is longer than the limit of {} samples ({} s)",
+                audio_len, max_len, MAX_AUDIO_SECONDS
+            ),
+            ConfigError::TooManyFrames { frames, max } => write!(
+                f,
+                "{} frames would be analyzed, above the limit of {}; use a larger hop size",
+                frames, max
+            ),
+            ConfigError::NoiseRobustStreaming => {
+                write!(
+                    f,
+                    "noise-robust mode needs the whole recording; not available for streaming"
+                )
+            }
+        }
+    }
+}
+
+/// Builder-style setters, so JS can write
+/// `new YinConfig().with_sample_rate(48000).with_hop_size(256)` and only
+/// name the settings it changes
+#[wasm_bindgen]
+impl YinConfig {
+    pub fn with_sample_rate(mut self, sample_rate: f32) -> YinConfig {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn with_frame_size(mut self, frame_size: usize) -> YinConfig {
+        self.frame_size = frame_size;
+        self
+    }
+
+    pub fn with_hop_size(mut self, hop_size: usize) -> YinConfig {
+        self.hop_size = hop_size;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: f32) -> YinConfig {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the pitch search range in Hz
+    pub fn with_freq_range(mut self, min_freq: f32, max_freq: f32) -> YinConfig {
+        self.min_freq = min_freq;
+        self.max_freq = max_freq;
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: bool) -> YinConfig {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Start the threshold search at `tau_start` samples (0 = derive it
+    /// from `max_freq`)
+    pub fn with_tau_start(mut self, tau_start: usize) -> YinConfig {
+        self.tau_start = tau_start;
+        self
+    }
+
+    pub fn with_summation(mut self, summation: CmndfSummation) -> YinConfig {
+        self.summation = summation;
+        self
+    }
+
+    pub fn with_window(mut self, window: WindowFunction) -> YinConfig {
+        self.window = window;
+        self
+    }
+
+    pub fn with_noise_robust(mut self, noise_robust: bool) -> YinConfig {
+        self.noise_robust = noise_robust;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: PitchScale) -> YinConfig {
+        self.scale = scale;
+        self
+    }
+
+    /// Throw if the settings can't produce a meaningful analysis
+    pub fn validate(&self) -> Result<(), JsError> {
+        self.check().map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// DTW distance between two Hz pitch tracks measured on `scale`, see
+    /// `compare_pitch_tracks`
+    pub fn compare(&self, a: &[f32], b: &[f32], auto_crop: bool) -> f32 {
+        compare_pitch_tracks(a, b, self.scale, auto_crop)
+    }
+}
+
+#[wasm_bindgen]
+impl YinConfig {
+    /// Plain-object copy of the settings
+    #[wasm_bindgen(unchecked_return_type = "YinConfigObject")]
+    pub fn to_object(&self) -> JsValue {
+        to_js(&self.to_json())
+    }
+}
+
+impl YinConfig {
+    /// The settings as a `YinConfigObject` in JSON
+    pub(crate) fn to_json(self) -> String {
+        let mut out = String::from("{");
+        push_key(&mut out, "sampleRate", true);
+        push_number(&mut out, self.sample_rate);
+        push_key(&mut out, "frameSize", false);
+        push_number(&mut out, self.frame_size as f32);
+        push_key(&mut out, "hopSize", false);
+        push_number(&mut out, self.hop_size as f32);
+        push_key(&mut out, "threshold", false);
+        push_number(&mut out, self.threshold);
+        push_key(&mut out, "minFreq", false);
+        push_number(&mut out, self.min_freq);
+        push_key(&mut out, "maxFreq", false);
+        push_number(&mut out, self.max_freq);
+        push_key(&mut out, "interpolation", false);
+        out.push_str(if self.interpolation { "true" } else { "false" });
+        push_key(&mut out, "tauStart", false);
+        push_number(&mut out, self.tau_start as f32);
+        push_key(&mut out, "summation", false);
+        push_number(&mut out, self.summation as u8 as f32);
+        push_key(&mut out, "window", false);
+        push_number(&mut out, self.window as u8 as f32);
+        push_key(&mut out, "noiseRobust", false);
+        out.push_str(if self.noise_robust { "true" } else { "false" });
+        push_key(&mut out, "scale", false);
+        push_number(&mut out, self.scale as u8 as f32);
+        out.push('}');
+        out
+    }
+}
+
+impl Default for YinConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl YinConfig {
+    /// Append the settings in binary form
+    pub(crate) fn write_to(&self, out: &mut ByteWriter) {
+        out.f32(self.sample_rate);
+        out.usize(self.frame_size);
+        out.usize(self.hop_size);
+        out.f32(self.threshold);
+        out.f32(self.min_freq);
+        out.f32(self.max_freq);
+        out.bool(self.interpolation);
+        out.usize(self.tau_start);
+        out.u8(self.summation as u8);
+        out.u8(self.window as u8);
+        out.bool(self.noise_robust);
+        out.u8(self.scale as u8);
+    }
+
+    /// Inverse of `write_to`
+    pub(crate) fn read_from(input: &mut ByteReader) -> Result<Self, BinaryError> {
+        Ok(YinConfig {
+            sample_rate: input.f32()?,
+            frame_size: input.usize()?,
+            hop_size: input.usize()?,
+            threshold: input.f32()?,
+            min_freq: input.f32()?,
+            max_freq: input.f32()?,
+            interpolation: input.bool()?,
+            tau_start: input.usize()?,
+            summation: CmndfSummation::from_u8(input.u8()?)
+                .ok_or(BinaryError::Invalid("summation"))?,
+            window: WindowFunction::from_u8(input.u8()?).ok_or(BinaryError::Invalid("window"))?,
+            noise_robust: input.bool()?,
+            scale: PitchScale::from_u8(input.u8()?).ok_or(BinaryError::Invalid("scale"))?,
+        })
+    }
+
+    /// Per-frame settings for the analysis core
+    pub(crate) fn params(&self) -> YinParams {
+        YinParams {
+            tau_start: self.effective_tau_start(),
+            summation: self.summation,
+            window: self.window,
+            ..YinParams::new(
+                self.sample_rate,
+                self.threshold,
+                self.min_freq,
+                self.max_freq,
+                self.interpolation,
+            )
+        }
+    }
+
+    /// First problem that makes these settings unusable, if any
+    pub fn check(&self) -> Result<(), ConfigError> {
+        if !(self.sample_rate.is_finite() && self.sample_rate > 0.0) {
+            return Err(ConfigError::BadSampleRate(self.sample_rate));
+        }
+        if let Some(err) = framing_error(self.frame_size, self.hop_size) {
+            return Err(err);
+        }
+        if self.hop_size > self.frame_size {
+            return Err(ConfigError::HopLargerThanFrame {
+                hop_size: self.hop_size,
+                frame_size: self.frame_size,
+            });
+        }
+        if !(self.threshold > 0.0 && self.threshold <= 1.0) {
+            return Err(ConfigError::BadThreshold(self.threshold));
+        }
+        if !(self.min_freq > 0.0 && self.max_freq > self.min_freq) {
+            return Err(ConfigError::BadFrequencyRange {
+                min_freq: self.min_freq,
+                max_freq: self.max_freq,
+            });
+        }
+        let nyquist = self.sample_rate / 2.0;
+        if self.max_freq >= nyquist {
+            return Err(ConfigError::AboveNyquist {
+                max_freq: self.max_freq,
+                nyquist,
+            });
+        }
+        // The longest period searched must fit in the first half of the frame
+        let max_tau = (self.sample_rate / self.min_freq).ceil() as usize;
+        if self.effective_tau_start() >= max_tau {
+            return Err(ConfigError::TauStartTooLarge {
+                tau_start: self.effective_tau_start(),
+                max_tau,
+            });
+        }
+        let needed = 2 * (max_tau + 1);
+        if self.frame_size < needed {
+            return Err(ConfigError::FrameTooShort {
+                frame_size: self.frame_size,
+                needed,
+            });
+        }
+        Ok(())
+    }
+
+    /// `analyze` after checking the settings and that the audio holds at
+    /// least one frame and is within the size limits
+    pub fn analyze_checked(&self, audio_data: &[f32]) -> Result<Vec<f32>, ConfigError> {
+        self.check()?;
+        if audio_data.len() < self.frame_size {
+            return Err(ConfigError::AudioTooShort {
+                audio_len: audio_data.len(),
+                frame_size: self.frame_size,
+            });
+        }
+        if let Some(err) = workload_error(
+            audio_data.len(),
+            self.sample_rate,
+            self.frame_size,
+            self.hop_size,
+        ) {
+            return Err(err);
+        }
+        Ok(self.analyze(audio_data))
+    }
+
+    /// Run `perform_yin_analysis` with these settings
+    ///
+    /// ```
+    /// use yin_wasm::fixtures::fixture;
+    ///
+    /// let sine = fixture("sine_220").unwrap();
+    /// let results = sine.config().with_hop_size(256).analyze(&sine.audio());
+    /// assert!((results[0] - 220.0).abs() < 1.0);
+    /// ```
+    pub fn analyze(&self, audio_data: &[f32]) -> Vec<f32> {
+        if self.noise_robust {
+            analyze_noise_robust(audio_data, self.frame_size, self.hop_size, &self.params())
+        } else {
+            analyze_with_params(audio_data, self.frame_size, self.hop_size, &self.params())
+        }
+    }
+}
+
+/// Problem with the framing that no analysis path can work with: a zero
+/// hop, or a frame outside `2..=MAX_FRAME_SIZE`. The one rule shared by the
+/// flat API and the streaming analyzers; shorter frames than `check` asks
+/// for still work, with the lag search limited to half the frame.
+pub(crate) fn framing_error(frame_size: usize, hop_size: usize) -> Option<ConfigError> {
+    if hop_size == 0 {
+        return Some(ConfigError::ZeroHopSize);
+    }
+    if frame_size < 2 {
+        return Some(ConfigError::FrameTooShort {
+            frame_size,
+            needed: 2,
+        });
+    }
+    if frame_size > MAX_FRAME_SIZE {
+        return Some(ConfigError::FrameTooLong {
+            frame_size,
+            max: MAX_FRAME_SIZE,
+        });
+    }
+    None
+}
+
+/// Limit exceeded by analyzing `audio_len` samples at `sample_rate` with
+/// the given framing, if any. `hop_size` must be at least 1.
+pub(crate) fn workload_error(
+    audio_len: usize,
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> Option<ConfigError> {
+    let frames = audio_len.saturating_sub(frame_size) / hop_size + 1;
+    frame_count_error(audio_len, sample_rate, frames)
+}
+
+/// `workload_error` for analyses that count their frames differently from
+/// `perform_yin_analysis` (a time step rather than a hop, or zero-padded
+/// frames)
+pub(crate) fn frame_count_error(
+    audio_len: usize,
+    sample_rate: f32,
+    frames: usize,
+) -> Option<ConfigError> {
+    let max_len = (MAX_AUDIO_SECONDS * sample_rate) as usize;
+    if sample_rate > 0.0 && audio_len > max_len {
+        return Some(ConfigError::AudioTooLong { audio_len, max_len });
+    }
+    if frames > MAX_FRAMES {
+        return Some(ConfigError::TooManyFrames {
+            frames,
+            max: MAX_FRAMES,
+        });
+    }
+    None
+}
+
+thread_local! {
+    static GLOBAL_DEFAULTS: Cell<Option<YinConfig>> = const { Cell::new(None) };
+}
+
+/// Settings used by every subsequent call that doesn't pass its own, so the
+/// app configures frame size and thresholds once at startup
+#[wasm_bindgen]
+pub fn set_global_defaults(config: &YinConfig) {
+    GLOBAL_DEFAULTS.with(|d| d.set(Some(*config)));
+}
+
+/// Current global defaults (the built-in ones until `set_global_defaults`),
+/// with the frequency range narrowed to the selected speaker profile
+#[wasm_bindgen]
+pub fn global_defaults() -> YinConfig {
+    let defaults = GLOBAL_DEFAULTS.with(|d| d.get()).unwrap_or_default();
+    match selected_profile() {
+        Some(profile) => profile.apply_to(&defaults),
+        None => defaults,
+    }
+}
+
+/// Go back to the built-in defaults
+#[wasm_bindgen]
+pub fn reset_global_defaults() {
+    GLOBAL_DEFAULTS.with(|d| d.set(None));
+}
+
+/// `perform_yin_analysis` with the settings of `config`, throwing when they
+/// are invalid or the audio is shorter than one frame instead of returning
+/// an empty array. Returns a flat [pitch, confidence, tau, ...] array.
+#[wasm_bindgen]
+pub fn perform_yin_analysis_with_config(
+    audio_data: &[f32],
+    config: &YinConfig,
+) -> Result<Vec<f32>, JsError> {
+    config
+        .analyze_checked(audio_data)
+        .map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// `perform_yin_analysis` with the global defaults.
+/// Returns a flat [pitch, confidence, tau, ...] array.
+#[wasm_bindgen]
+pub fn analyze_with_defaults(audio_data: &[f32]) -> Vec<f32> {
+    global_defaults().analyze(audio_data)
+}
+
+/// Partial settings for a single call: only the fields that were set replace
+/// the global defaults (e.g. just `min_freq` for a calibrated user)
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct YinConfigOverride {
+    sample_rate: Option<f32>,
+    frame_size: Option<usize>,
+    hop_size: Option<usize>,
+    threshold: Option<f32>,
+    min_freq: Option<f32>,
+    max_freq: Option<f32>,
+    interpolation: Option<bool>,
+    tau_start: Option<usize>,
+    summation: Option<CmndfSummation>,
+    window: Option<WindowFunction>,
+    noise_robust: Option<bool>,
+    scale: Option<PitchScale>,
+}
+
+#[wasm_bindgen]
+impl YinConfigOverride {
+    /// Override that changes nothing
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> YinConfigOverride {
+        YinConfigOverride::default()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sample_rate(&self) -> Option<f32> {
+        self.sample_rate
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_sample_rate(&mut self, value: Option<f32>) {
+        self.sample_rate = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn frame_size(&self) -> Option<usize> {
+        self.frame_size
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_frame_size(&mut self, value: Option<usize>) {
+        self.frame_size = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn hop_size(&self) -> Option<usize> {
+        self.hop_size
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_hop_size(&mut self, value: Option<usize>) {
+        self.hop_size = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn threshold(&self) -> Option<f32> {
+        self.threshold
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_threshold(&mut self, value: Option<f32>) {
+        self.threshold = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn min_freq(&self) -> Option<f32> {
+        self.min_freq
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_min_freq(&mut self, value: Option<f32>) {
+        self.min_freq = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn max_freq(&self) -> Option<f32> {
+        self.max_freq
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_freq(&mut self, value: Option<f32>) {
+        self.max_freq = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn interpolation(&self) -> Option<bool> {
+        self.interpolation
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_interpolation(&mut self, value: Option<bool>) {
+        self.interpolation = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tau_start(&self) -> Option<usize> {
+        self.tau_start
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_tau_start(&mut self, value: Option<usize>) {
+        self.tau_start = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn summation(&self) -> Option<CmndfSummation> {
+        self.summation
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_summation(&mut self, value: Option<CmndfSummation>) {
+        self.summation = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn window(&self) -> Option<WindowFunction> {
+        self.window
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_window(&mut self, value: Option<WindowFunction>) {
+        self.window = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn noise_robust(&self) -> Option<bool> {
+        self.noise_robust
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_noise_robust(&mut self, value: Option<bool>) {
+        self.noise_robust = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn scale(&self) -> Option<PitchScale> {
+        self.scale
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_scale(&mut self, value: Option<PitchScale>) {
+        self.scale = value;
+    }
+
+    /// Override from a partial `YinConfigObject`; missing fields stay unset
+    pub fn from_object(
+        #[wasm_bindgen(unchecked_param_type = "Partial<YinConfigObject>")] object: JsValue,
+    ) -> YinConfigOverride {
+        let number = |key| number_property(&object, key);
+        YinConfigOverride {
+            sample_rate: number("sampleRate").map(|v| v as f32),
+            frame_size: number("frameSize").map(|v| v as usize),
+            hop_size: number("hopSize").map(|v| v as usize),
+            threshold: number("threshold").map(|v| v as f32),
+            min_freq: number("minFreq").map(|v| v as f32),
+            max_freq: number("maxFreq").map(|v| v as f32),
+            interpolation: bool_property(&object, "interpolation"),
+            tau_start: number("tauStart").map(|v| v as usize),
+            summation: number("summation").and_then(|v| CmndfSummation::from_u8(v as u8)),
+            window: number("window").and_then(|v| WindowFunction::from_u8(v as u8)),
+            noise_robust: bool_property(&object, "noiseRobust"),
+            scale: number("scale").and_then(|v| PitchScale::from_u8(v as u8)),
+        }
+    }
+
+    /// `base` with the set fields replaced
+    pub fn apply_to(&self, base: &YinConfig) -> YinConfig {
+        YinConfig {
+            sample_rate: self.sample_rate.unwrap_or(base.sample_rate),
+            frame_size: self.frame_size.unwrap_or(base.frame_size),
+            hop_size: self.hop_size.unwrap_or(base.hop_size),
+            threshold: self.threshold.unwrap_or(base.threshold),
+            min_freq: self.min_freq.unwrap_or(base.min_freq),
+            max_freq: self.max_freq.unwrap_or(base.max_freq),
+            interpolation: self.interpolation.unwrap_or(base.interpolation),
+            tau_start: self.tau_start.unwrap_or(base.tau_start),
+            summation: self.summation.unwrap_or(base.summation),
+            window: self.window.unwrap_or(base.window),
+            noise_robust: self.noise_robust.unwrap_or(base.noise_robust),
+            scale: self.scale.unwrap_or(base.scale),
+        }
+    }
+
+    /// Layer `other` on top: its set fields win, the rest keep this
+    /// override's values
+    pub fn merge(&self, other: &YinConfigOverride) -> YinConfigOverride {
+        YinConfigOverride {
+            sample_rate: other.sample_rate.or(self.sample_rate),
+            frame_size: other.frame_size.or(self.frame_size),
+            hop_size: other.hop_size.or(self.hop_size),
+            threshold: other.threshold.or(self.threshold),
+            min_freq: other.min_freq.or(self.min_freq),
+            max_freq: other.max_freq.or(self.max_freq),
+            interpolation: other.interpolation.or(self.interpolation),
+            tau_start: other.tau_start.or(self.tau_start),
+            summation: other.summation.or(self.summation),
+            window: other.window.or(self.window),
+            noise_robust: other.noise_robust.or(self.noise_robust),
+            scale: other.scale.or(self.scale),
+        }
+    }
+}
+
+/// Global defaults with `overrides` applied
+#[wasm_bindgen]
+pub fn effective_config(overrides: &YinConfigOverride) -> YinConfig {
+    overrides.apply_to(&global_defaults())
+}
+
+/// `perform_yin_analysis` with the global defaults, except for the fields
+/// set in `overrides`. Returns a flat [pitch, confidence, tau, ...] array.
+#[wasm_bindgen]
+pub fn analyze_with_overrides(audio_data: &[f32], overrides: &YinConfigOverride) -> Vec<f32> {
+    effective_config(overrides).analyze(audio_data)
+}
+
+/// `perform_yin_analysis` with the global defaults overridden by a partial
+/// config object, returning `YinFrame` objects
+#[wasm_bindgen(unchecked_return_type = "YinFrame[]")]
+pub fn analyze_to_frames(
+    audio_data: &[f32],
+    #[wasm_bindgen(unchecked_param_type = "Partial<YinConfigObject>")] config: JsValue,
+) -> JsValue {
+    let config = effective_config(&YinConfigOverride::from_object(config));
+    let mut out = String::new();
+    push_frames(
+        &mut out,
+        &config.analyze(audio_data),
+        config.hop_size as f32 / config.sample_rate,
+        0.0,
+        config.scale,
+    );
+    to_js(&out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lone_override_keeps_other_defaults() {
+        let mut overrides = YinConfigOverride::new();
+        overrides.set_min_freq(Some(120.0));
+        let defaults = global_defaults();
+        let config = effective_config(&overrides);
+        assert_eq!(
+            config,
+            YinConfig {
+                min_freq: 120.0,
+                ..defaults
+            }
+        );
+    }
+
+    #[test]
+    fn later_merge_wins() {
+        let mut first = YinConfigOverride::new();
+        first.set_threshold(Some(0.1));
+        let mut second = YinConfigOverride::new();
+        second.set_threshold(Some(0.2));
+        assert_eq!(first.merge(&second).threshold(), Some(0.2));
+        assert_eq!(second.merge(&first).threshold(), Some(0.1));
+    }
+
+    #[test]
+    fn unset_fields_never_overwrite() {
+        let mut first = YinConfigOverride::new();
+        first.set_frame_size(Some(1024));
+        first.set_window(Some(WindowFunction::Hann));
+        let mut second = YinConfigOverride::new();
+        second.set_hop_size(Some(256));
+        let merged = first.merge(&second);
+        assert_eq!(merged.frame_size(), Some(1024));
+        assert_eq!(merged.window(), Some(WindowFunction::Hann));
+        assert_eq!(merged.hop_size(), Some(256));
+        assert_eq!(merged.merge(&YinConfigOverride::new()), merged);
+
+        let base = YinConfig::new();
+        assert_eq!(YinConfigOverride::new().apply_to(&base), base);
+    }
+
+    #[test]
+    fn invalid_merged_config_is_rejected() {
+        let mut first = YinConfigOverride::new();
+        first.set_min_freq(Some(300.0));
+        let mut second = YinConfigOverride::new();
+        second.set_max_freq(Some(200.0));
+        let config = first.merge(&second).apply_to(&YinConfig::new());
+        assert!(matches!(
+            config.check(),
+            Err(ConfigError::BadFrequencyRange { .. })
+        ));
+    }
+
+    #[test]
+    fn scale_is_stored_and_hashed() {
+        let config = YinConfig::new().with_scale(PitchScale::Semitones);
+        let mut out = ByteWriter::with_header(b"TEST", 1);
+        config.write_to(&mut out);
+        let (mut input, _) = ByteReader::with_header(&out.bytes, b"TEST").unwrap();
+        assert_eq!(YinConfig::read_from(&mut input), Ok(config));
+        assert_ne!(
+            crate::cache::config_hash(&config),
+            crate::cache::config_hash(&YinConfig::new())
+        );
+    }
+}
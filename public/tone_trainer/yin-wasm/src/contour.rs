@@ -0,0 +1,179 @@
+use wasm_bindgen::prelude::*;
+
+/// How per-frame confidence is turned into a weight
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeightingMode {
+    /// Every frame counts equally
+    Uniform = 0,
+    /// Weight proportional to confidence
+    Linear = 1,
+    /// Weight proportional to confidence squared, to further suppress shaky frames
+    Squared = 2,
+}
+
+/// Confidence weighting used when resampling or smoothing contours, so a few
+/// low-confidence frames at syllable edges don't distort the normalized shape
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct ConfidenceWeighting {
+    pub mode: WeightingMode,
+    /// Frames below this confidence get zero weight
+    pub min_confidence: f32,
+}
+
+#[wasm_bindgen]
+impl ConfidenceWeighting {
+    #[wasm_bindgen(constructor)]
+    pub fn new(mode: WeightingMode, min_confidence: f32) -> ConfidenceWeighting {
+        ConfidenceWeighting {
+            mode,
+            min_confidence,
+        }
+    }
+}
+
+impl Default for ConfidenceWeighting {
+    fn default() -> Self {
+        ConfidenceWeighting {
+            mode: WeightingMode::Linear,
+            min_confidence: 0.0,
+        }
+    }
+}
+
+impl ConfidenceWeighting {
+    /// Weight of a frame with the given confidence
+    pub fn weight(&self, confidence: f32) -> f32 {
+        if confidence < self.min_confidence {
+            return 0.0;
+        }
+        let confidence = confidence.max(0.0);
+        match self.mode {
+            WeightingMode::Uniform => 1.0,
+            WeightingMode::Linear => confidence,
+            WeightingMode::Squared => confidence * confidence,
+        }
+    }
+}
+
+/// Resample a contour to exactly `n_points` values.
+///
+/// Each output point is a weighted average of the input frames under a
+/// triangular kernel centred on its position. The kernel is one frame wide when
+/// upsampling (plain linear interpolation) and widens to the decimation ratio
+/// when downsampling, so no frames are skipped. Each frame's kernel weight is
+/// multiplied by its confidence weight; if every frame under the kernel has
+/// zero weight the point falls back to unweighted interpolation.
+/// `confidence` may be empty, in which case all frames count equally.
+pub fn resample_weighted(
+    contour: &[f32],
+    confidence: &[f32],
+    n_points: usize,
+    weighting: &ConfidenceWeighting,
+) -> Vec<f32> {
+    let len = contour.len();
+    if len == 0 || n_points == 0 {
+        return Vec::new();
+    }
+    if len == 1 || n_points == 1 {
+        let value = if len == 1 {
+            contour[0]
+        } else {
+            contour[len / 2]
+        };
+        return vec![value; n_points];
+    }
+
+    let step = (len - 1) as f32 / (n_points - 1) as f32;
+    let half_width = step.max(1.0);
+    let weight_of = |i: usize| confidence.get(i).map_or(1.0, |&c| weighting.weight(c));
+
+    (0..n_points)
+        .map(|k| {
+            let centre = k as f32 * step;
+            let first = (centre - half_width).ceil().max(0.0) as usize;
+            let last = ((centre + half_width).floor() as usize).min(len - 1);
+
+            let mut weighted_sum = 0.0;
+            let mut weight_total = 0.0;
+            let mut plain_sum = 0.0;
+            let mut plain_total = 0.0;
+            for (i, &value) in contour.iter().enumerate().take(last + 1).skip(first) {
+                let kernel = 1.0 - (i as f32 - centre).abs() / half_width;
+                if kernel <= 0.0 {
+                    continue;
+                }
+                let w = kernel * weight_of(i);
+                weighted_sum += w * value;
+                weight_total += w;
+                plain_sum += kernel * value;
+                plain_total += kernel;
+            }
+
+            if weight_total > 0.0 {
+                weighted_sum / weight_total
+            } else if plain_total > 0.0 {
+                plain_sum / plain_total
+            } else {
+                contour[centre.round() as usize]
+            }
+        })
+        .collect()
+}
+
+/// Confidence-weighted moving average over a centred window of
+/// `2 * radius + 1` frames. Frames whose whole neighbourhood has zero weight
+/// are left unchanged.
+pub fn smooth_weighted(
+    contour: &[f32],
+    confidence: &[f32],
+    radius: usize,
+    weighting: &ConfidenceWeighting,
+) -> Vec<f32> {
+    let weight_of = |i: usize| confidence.get(i).map_or(1.0, |&c| weighting.weight(c));
+
+    (0..contour.len())
+        .map(|i| {
+            let first = i.saturating_sub(radius);
+            let last = (i + radius).min(contour.len() - 1);
+            let mut sum = 0.0;
+            let mut total = 0.0;
+            for (j, &value) in contour.iter().enumerate().take(last + 1).skip(first) {
+                let w = weight_of(j);
+                sum += w * value;
+                total += w;
+            }
+            if total > 0.0 {
+                sum / total
+            } else {
+                contour[i]
+            }
+        })
+        .collect()
+}
+
+/// Resample a contour to a fixed number of points (e.g. 100) for the
+/// classifier, PCA and template averaging. Frames are weighted by
+/// `confidence` according to `weighting`; pass an empty confidence array to
+/// weight them equally.
+#[wasm_bindgen]
+pub fn resample_contour(
+    contour: &[f32],
+    confidence: &[f32],
+    n_points: usize,
+    weighting: &ConfidenceWeighting,
+) -> Vec<f32> {
+    resample_weighted(contour, confidence, n_points, weighting)
+}
+
+/// Smooth a contour with a confidence-weighted moving average
+#[wasm_bindgen]
+pub fn smooth_contour(
+    contour: &[f32],
+    confidence: &[f32],
+    radius: usize,
+    weighting: &ConfidenceWeighting,
+) -> Vec<f32> {
+    smooth_weighted(contour, confidence, radius, weighting)
+}
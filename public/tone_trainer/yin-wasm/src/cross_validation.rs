@@ -0,0 +1,191 @@
+use wasm_bindgen::prelude::*;
+
+use crate::perform_yin_analysis;
+use crate::praat::{perform_praat_analysis, PraatSettings};
+
+/// Settings for comparing the YIN tracker against the Praat-style estimator
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct CrossValidationConfig {
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub threshold: f32,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    /// Pitch disagreement (cents) above which a frame is flagged
+    pub max_divergence_cents: f32,
+    /// Shortest run of flagged frames reported as a region
+    pub min_region_frames: usize,
+}
+
+#[wasm_bindgen]
+impl CrossValidationConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CrossValidationConfig {
+        CrossValidationConfig {
+            frame_size: 2048,
+            hop_size: 512,
+            threshold: 0.15,
+            min_freq: 75.0,
+            max_freq: 600.0,
+            max_divergence_cents: 50.0,
+            min_region_frames: 3,
+        }
+    }
+}
+
+impl Default for CrossValidationConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Per-frame agreement between two pitch estimators
+#[wasm_bindgen]
+pub struct CrossValidationReport {
+    divergence_cents: Vec<f32>,
+    flagged_regions: Vec<f32>,
+    mean_abs_cents: f32,
+    max_abs_cents: f32,
+    agreement: f32,
+    voicing_disagreement: f32,
+}
+
+#[wasm_bindgen]
+impl CrossValidationReport {
+    /// Per-frame difference (cents, YIN minus reference estimator); NaN where
+    /// either estimator found the frame unvoiced
+    #[wasm_bindgen(getter)]
+    pub fn divergence_cents(&self) -> Vec<f32> {
+        self.divergence_cents.clone()
+    }
+
+    /// Flagged regions as a flat array of [start_s, end_s, ...]
+    #[wasm_bindgen(getter)]
+    pub fn flagged_regions(&self) -> Vec<f32> {
+        self.flagged_regions.clone()
+    }
+
+    /// Mean absolute divergence over frames both estimators call voiced
+    #[wasm_bindgen(getter)]
+    pub fn mean_abs_cents(&self) -> f32 {
+        self.mean_abs_cents
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn max_abs_cents(&self) -> f32 {
+        self.max_abs_cents
+    }
+
+    /// Fraction of frames where both agree (same voicing, pitch within limit)
+    #[wasm_bindgen(getter)]
+    pub fn agreement(&self) -> f32 {
+        self.agreement
+    }
+
+    /// Fraction of frames where only one estimator found voicing
+    #[wasm_bindgen(getter)]
+    pub fn voicing_disagreement(&self) -> f32 {
+        self.voicing_disagreement
+    }
+}
+
+/// Run the YIN tracker and the Praat-style estimator over the same audio and
+/// report where they disagree, to build confidence in an analysis before it
+/// is stored as a canonical reference.
+#[wasm_bindgen]
+pub fn cross_validate(
+    audio_data: &[f32],
+    sample_rate: f32,
+    config: &CrossValidationConfig,
+) -> CrossValidationReport {
+    let yin = perform_yin_analysis(
+        audio_data,
+        sample_rate,
+        config.frame_size,
+        config.hop_size,
+        config.threshold,
+        config.min_freq,
+        config.max_freq,
+        true,
+    );
+    let praat_settings = PraatSettings {
+        time_step: config.hop_size as f32 / sample_rate,
+        pitch_floor: config.min_freq,
+        pitch_ceiling: config.max_freq,
+        ..PraatSettings::new()
+    };
+    let praat = perform_praat_analysis(audio_data, sample_rate, &praat_settings);
+    let praat_frames: Vec<(f32, f32)> = praat.chunks(3).map(|f| (f[0], f[1])).collect();
+
+    let frame_count = yin.len() / 3;
+    let mut divergence_cents = Vec::with_capacity(frame_count);
+    let mut flagged = Vec::with_capacity(frame_count);
+    let mut voicing_mismatches = 0usize;
+    let mut abs_sum = 0.0;
+    let mut abs_count = 0usize;
+    let mut max_abs_cents: f32 = 0.0;
+
+    for i in 0..frame_count {
+        let time = (i * config.hop_size + config.frame_size / 2) as f32 / sample_rate;
+        let yin_pitch = yin[i * 3];
+        // Nearest reference frame in time (reference frames are evenly spaced)
+        let other_pitch = praat_frames.first().map_or(0.0, |first| {
+            let idx = ((time - first.0) / praat_settings.time_step)
+                .round()
+                .max(0.0) as usize;
+            praat_frames[idx.min(praat_frames.len() - 1)].1
+        });
+
+        let (cents, is_flagged) = match (yin_pitch > 0.0, other_pitch > 0.0) {
+            (true, true) => {
+                let cents = 1200.0 * (yin_pitch / other_pitch).log2();
+                abs_sum += cents.abs();
+                abs_count += 1;
+                max_abs_cents = max_abs_cents.max(cents.abs());
+                (cents, cents.abs() > config.max_divergence_cents)
+            }
+            (false, false) => (f32::NAN, false),
+            _ => {
+                voicing_mismatches += 1;
+                (f32::NAN, true)
+            }
+        };
+        divergence_cents.push(cents);
+        flagged.push(is_flagged);
+    }
+
+    let mut flagged_regions = Vec::new();
+    let mut run_start = None;
+    for i in 0..=frame_count {
+        let is_flagged = flagged.get(i).copied().unwrap_or(false);
+        match (is_flagged, run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                if i - start >= config.min_region_frames.max(1) {
+                    let start_s = (start * config.hop_size) as f32 / sample_rate;
+                    let end_s =
+                        ((i - 1) * config.hop_size + config.frame_size) as f32 / sample_rate;
+                    flagged_regions.push(start_s);
+                    flagged_regions.push(end_s);
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+
+    let frames = frame_count.max(1) as f32;
+    CrossValidationReport {
+        divergence_cents,
+        flagged_regions,
+        mean_abs_cents: if abs_count > 0 {
+            abs_sum / abs_count as f32
+        } else {
+            0.0
+        },
+        max_abs_cents,
+        agreement: flagged.iter().filter(|&&f| !f).count() as f32 / frames,
+        voicing_disagreement: voicing_mismatches as f32 / frames,
+    }
+}
@@ -0,0 +1,121 @@
+//! Slow drift of the learner's register over a long session. Fatigue lowers
+//! the voice by a semitone or more over half an hour, so attempts late in a
+//! session judged against the calibration from its start look flat. The
+//! tracker follows the drift with a slow average of per-attempt medians and
+//! maps later attempts back onto the calibrated register.
+
+use wasm_bindgen::prelude::*;
+
+use crate::tones::voiced_median;
+
+/// Weight of each attempt in the running drift estimate; small, since a
+/// single attempt's median also depends on its tones
+const DRIFT_SMOOTHING: f32 = 0.1;
+
+/// Attempts whose median is further than this (semitones) from the current
+/// estimate are left out (shouting, a cough, an octave error)
+const MAX_ATTEMPT_OFFSET: f32 = 6.0;
+
+/// Voiced frames an attempt needs to count
+const MIN_VOICED_FRAMES: usize = 10;
+
+/// Attempts needed before drift is reported
+const MIN_ATTEMPTS: usize = 5;
+
+/// Drift (semitones) at which `is_drifting` reports it
+const DRIFT_ALERT_SEMITONES: f32 = 1.0;
+
+/// Running estimate of how far the learner's register moved from the
+/// calibration
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct PitchDriftTracker {
+    calibrated_median_hz: f32,
+    drift: f32,
+    attempts: usize,
+}
+
+#[wasm_bindgen]
+impl PitchDriftTracker {
+    /// Track drift away from `calibrated_median_hz`, the learner's median
+    /// pitch measured at calibration
+    #[wasm_bindgen(constructor)]
+    pub fn new(calibrated_median_hz: f32) -> PitchDriftTracker {
+        PitchDriftTracker {
+            calibrated_median_hz,
+            drift: 0.0,
+            attempts: 0,
+        }
+    }
+
+    /// Add an attempt's Hz pitch track (0 for unvoiced frames). Returns
+    /// whether it was used: attempts with too few voiced frames or far away
+    /// from the current register are skipped.
+    pub fn observe(&mut self, pitch: &[f32]) -> bool {
+        if self.calibrated_median_hz <= 0.0
+            || pitch.iter().filter(|&&p| p > 0.0).count() < MIN_VOICED_FRAMES
+        {
+            return false;
+        }
+        let offset = 12.0 * (voiced_median(pitch) / self.calibrated_median_hz).log2();
+        if (offset - self.drift).abs() > MAX_ATTEMPT_OFFSET {
+            return false;
+        }
+        // Plain mean until the average settles, then exponential smoothing
+        self.attempts += 1;
+        let weight = (1.0 / self.attempts as f32).max(DRIFT_SMOOTHING);
+        self.drift += weight * (offset - self.drift);
+        true
+    }
+
+    /// Estimated drift in semitones (negative when the voice went down);
+    /// 0 until `MIN_ATTEMPTS` attempts were observed
+    #[wasm_bindgen(getter)]
+    pub fn drift_semitones(&self) -> f32 {
+        if self.attempts >= MIN_ATTEMPTS {
+            self.drift
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether the register moved far enough that scores are compensated
+    /// noticeably
+    #[wasm_bindgen(getter)]
+    pub fn is_drifting(&self) -> bool {
+        self.drift_semitones().abs() >= DRIFT_ALERT_SEMITONES
+    }
+
+    /// Attempts that contributed to the estimate
+    #[wasm_bindgen(getter)]
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn calibrated_median_hz(&self) -> f32 {
+        self.calibrated_median_hz
+    }
+
+    /// The learner's current median pitch: the calibration moved by the
+    /// drift, to use in place of the calibrated median
+    #[wasm_bindgen(getter)]
+    pub fn current_median_hz(&self) -> f32 {
+        self.calibrated_median_hz * 2f32.powf(self.drift_semitones() / 12.0)
+    }
+
+    /// `pitch` (Hz, 0 for unvoiced) shifted by the drift, so it compares
+    /// against the calibration as if the register hadn't moved
+    pub fn compensate(&self, pitch: &[f32]) -> Vec<f32> {
+        let factor = 2f32.powf(-self.drift_semitones() / 12.0);
+        pitch
+            .iter()
+            .map(|&p| if p > 0.0 { p * factor } else { 0.0 })
+            .collect()
+    }
+
+    /// Forget the drift, e.g. after recalibrating at `calibrated_median_hz`
+    pub fn reset(&mut self, calibrated_median_hz: f32) {
+        *self = PitchDriftTracker::new(calibrated_median_hz);
+    }
+}
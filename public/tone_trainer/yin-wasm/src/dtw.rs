@@ -0,0 +1,375 @@
+use wasm_bindgen::prelude::*;
+
+use crate::scale::PitchScale;
+use crate::tones::voiced_median;
+
+/// Local steps DTW may take between aligned points
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepPattern {
+    /// Match, insertion or deletion, each adding the local cost once;
+    /// normalized by path length, so detours can lower the distance
+    Symmetric1 = 0,
+    /// As `Symmetric1` but a match adds the local cost twice, so diagonal
+    /// and detour paths cost the same; normalized by the summed lengths
+    Symmetric2 = 1,
+    /// Every point of the first contour is matched exactly once, advancing
+    /// the second by 0, 1 or 2 points; normalized by the first contour's
+    /// length. The first contour sets the timeline, and a second contour
+    /// more than twice as long can't be aligned.
+    Asymmetric = 2,
+}
+
+/// How contours are aligned for comparison. Single syllables warp freely,
+/// while full sentences should keep close to their own timing, so the
+/// best settings depend on the drill.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DtwConfig {
+    pub step_pattern: StepPattern,
+    /// Extra cost (in the contours' unit) of every step that holds one
+    /// contour still or skips a point, discouraging long stalls
+    pub gap_penalty: f32,
+}
+
+impl Default for DtwConfig {
+    fn default() -> Self {
+        DtwConfig {
+            step_pattern: StepPattern::Symmetric1,
+            gap_penalty: 0.0,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl DtwConfig {
+    /// Symmetric steps without gap penalty, as `dtw_distance` and
+    /// `compare_contours` use
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> DtwConfig {
+        DtwConfig::default()
+    }
+
+    /// Starting point for single syllables: the whole contour may shift,
+    /// so any warp costs the same
+    pub fn syllable() -> DtwConfig {
+        DtwConfig {
+            step_pattern: StepPattern::Symmetric2,
+            gap_penalty: 0.0,
+        }
+    }
+
+    /// Starting point for full sentences: warping is allowed but every
+    /// stall costs half a semitone, so syllables don't slide onto their
+    /// neighbours
+    pub fn sentence() -> DtwConfig {
+        DtwConfig {
+            step_pattern: StepPattern::Symmetric2,
+            gap_penalty: 0.5,
+        }
+    }
+}
+
+/// Predecessor a DTW cell was reached from
+#[derive(Clone, Copy)]
+enum Step {
+    Start,
+    /// (i - 1, j - 1)
+    Diagonal,
+    /// (i - 1, j)
+    Up,
+    /// (i, j - 1)
+    Left,
+    /// (i - 1, j - 2), asymmetric pattern only
+    Skip,
+}
+
+/// Dynamic time warping between two contours using absolute difference as the
+/// local cost and the symmetric (match / insertion / deletion) step pattern.
+/// Returns the path-length-normalized distance and the warping path as
+/// (index_in_a, index_in_b) pairs from start to end.
+pub fn dtw(a: &[f32], b: &[f32]) -> (f32, Vec<(usize, usize)>) {
+    dtw_with(a, b, &DtwConfig::default())
+}
+
+/// `dtw` with the step pattern and gap penalty of `config`. The distance is
+/// Infinity, with an empty path, when a contour is empty or the pattern
+/// can't align them.
+pub fn dtw_with(a: &[f32], b: &[f32], config: &DtwConfig) -> (f32, Vec<(usize, usize)>) {
+    let n = a.len();
+    let m = b.len();
+    if n == 0 || m == 0 {
+        return (f32::INFINITY, Vec::new());
+    }
+    let gap = config.gap_penalty;
+
+    // Accumulated cost and the step that reached each cell, row-major n x m
+    let mut cost = vec![f32::INFINITY; n * m];
+    let mut steps = vec![Step::Start; n * m];
+    let at = |cost: &[f32], i: usize, j: usize, di: usize, dj: usize| {
+        if i >= di && j >= dj {
+            cost[(i - di) * m + j - dj]
+        } else {
+            f32::INFINITY
+        }
+    };
+    for i in 0..n {
+        for j in 0..m {
+            let local = (a[i] - b[j]).abs();
+            if i == 0 && j == 0 {
+                cost[0] = local;
+                continue;
+            }
+            let (best, step) = match config.step_pattern {
+                StepPattern::Symmetric1 | StepPattern::Symmetric2 => {
+                    let weight = if config.step_pattern == StepPattern::Symmetric2 {
+                        2.0
+                    } else {
+                        1.0
+                    };
+                    let diag = at(&cost, i, j, 1, 1) + weight * local;
+                    let up = at(&cost, i, j, 1, 0) + local + gap;
+                    let left = at(&cost, i, j, 0, 1) + local + gap;
+                    if diag <= up && diag <= left {
+                        (diag, Step::Diagonal)
+                    } else if up <= left {
+                        (up, Step::Up)
+                    } else {
+                        (left, Step::Left)
+                    }
+                }
+                StepPattern::Asymmetric => {
+                    let diag = at(&cost, i, j, 1, 1) + local;
+                    let up = at(&cost, i, j, 1, 0) + local + gap;
+                    let skip = at(&cost, i, j, 1, 2) + local + gap;
+                    if diag <= up && diag <= skip {
+                        (diag, Step::Diagonal)
+                    } else if up <= skip {
+                        (up, Step::Up)
+                    } else {
+                        (skip, Step::Skip)
+                    }
+                }
+            };
+            cost[i * m + j] = best;
+            steps[i * m + j] = step;
+        }
+    }
+    let total = cost[n * m - 1];
+    if !total.is_finite() {
+        return (f32::INFINITY, Vec::new());
+    }
+
+    // Backtrack from the end
+    let mut path = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (n - 1, m - 1);
+    path.push((i, j));
+    loop {
+        match steps[i * m + j] {
+            Step::Start => break,
+            Step::Diagonal => {
+                i -= 1;
+                j -= 1;
+            }
+            Step::Up => i -= 1,
+            Step::Left => j -= 1,
+            Step::Skip => {
+                i -= 1;
+                j -= 2;
+            }
+        }
+        path.push((i, j));
+    }
+    path.reverse();
+
+    let normalizer = match config.step_pattern {
+        StepPattern::Symmetric1 => path.len(),
+        StepPattern::Symmetric2 => n + m,
+        StepPattern::Asymmetric => n,
+    };
+    (total / normalizer as f32, path)
+}
+
+/// DTW distance between two contours, normalized by warping-path length
+#[wasm_bindgen]
+pub fn dtw_distance(a: &[f32], b: &[f32]) -> f32 {
+    dtw(a, b).0
+}
+
+/// DTW distance between two contours with the step pattern and gap penalty
+/// of `config`, normalized as the pattern defines
+#[wasm_bindgen]
+pub fn dtw_distance_with(a: &[f32], b: &[f32], config: &DtwConfig) -> f32 {
+    dtw_with(a, b, config).0
+}
+
+/// Map `contour` onto the time axis of `axis` via DTW, averaging the contour
+/// values that align with each axis point
+pub fn align_to(axis: &[f32], contour: &[f32]) -> Vec<f32> {
+    let mut sums = vec![0.0; axis.len()];
+    let mut counts = vec![0usize; axis.len()];
+    for (i, j) in dtw(axis, contour).1 {
+        sums[i] += contour[j];
+        counts[i] += 1;
+    }
+    sums.iter()
+        .zip(&counts)
+        .map(|(s, &c)| if c > 0 { s / c as f32 } else { 0.0 })
+        .collect()
+}
+
+/// Index of the medoid: the contour with the smallest summed DTW distance to
+/// all others
+pub fn medoid_index(contours: &[&[f32]]) -> usize {
+    contours
+        .iter()
+        .enumerate()
+        .map(|(i, a)| (i, contours.iter().map(|b| dtw(a, b).0).sum::<f32>()))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// DTW barycenter averaging (Petitjean et al. 2011). Starts from the medoid
+/// and repeatedly re-estimates each average point as the mean of all contour
+/// values DTW-aligned to it. Empty contours are ignored.
+pub fn dba(contours: &[&[f32]], iterations: usize) -> Vec<f32> {
+    let contours: Vec<&[f32]> = contours.iter().copied().filter(|c| !c.is_empty()).collect();
+    if contours.is_empty() {
+        return Vec::new();
+    }
+
+    let mut average = contours[medoid_index(&contours)].to_vec();
+    for _ in 0..iterations {
+        let mut sums = vec![0.0; average.len()];
+        let mut counts = vec![0usize; average.len()];
+        for contour in &contours {
+            for (i, j) in dtw(&average, contour).1 {
+                sums[i] += contour[j];
+                counts[i] += 1;
+            }
+        }
+
+        let mut changed = false;
+        for (i, value) in average.iter_mut().enumerate() {
+            let updated = sums[i] / counts[i] as f32;
+            changed |= (updated - *value).abs() > f32::EPSILON;
+            *value = updated;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    average
+}
+
+/// Split a flat concatenation of contours into slices using per-contour lengths
+pub fn split_flat<'a>(values: &'a [f32], lengths: &[u32]) -> Vec<&'a [f32]> {
+    let mut parts = Vec::with_capacity(lengths.len());
+    let mut offset = 0;
+    for &len in lengths {
+        let end = (offset + len as usize).min(values.len());
+        parts.push(&values[offset..end]);
+        offset = end;
+    }
+    parts
+}
+
+/// DTW barycenter average of a set of contours (in any unit).
+/// `contours` is the concatenation of all contours and `lengths` gives the
+/// number of points in each one.
+#[wasm_bindgen]
+pub fn dba_average(contours: &[f32], lengths: &[u32], iterations: usize) -> Vec<f32> {
+    dba(&split_flat(contours, lengths), iterations)
+}
+
+/// DTW distance (semitones) at which the similarity score falls to 1/e of
+/// its maximum
+const SIMILARITY_DECAY_SEMITONES: f32 = 3.0;
+
+/// How close a learner's contour is to a reference, see `compare_contours`
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct ContourScore {
+    distance: f32,
+    similarity: f32,
+    path: Vec<u32>,
+    costs: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl ContourScore {
+    /// Path-length-normalized DTW distance in semitones (Infinity when
+    /// either contour has no voiced frames)
+    #[wasm_bindgen(getter)]
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    /// 0-100 similarity for display, 100 for identical contours
+    #[wasm_bindgen(getter)]
+    pub fn similarity(&self) -> f32 {
+        self.similarity
+    }
+
+    /// Warping path as flat [user_frame, reference_frame, ...] pairs, using
+    /// the frame indices of the input tracks
+    #[wasm_bindgen(getter)]
+    pub fn path(&self) -> Vec<u32> {
+        self.path.clone()
+    }
+
+    /// Semitone difference at every step of `path`, to highlight the
+    /// segments that deviate most
+    #[wasm_bindgen(getter)]
+    pub fn costs(&self) -> Vec<f32> {
+        self.costs.clone()
+    }
+}
+
+/// Compare a learner's Hz pitch track with a reference one: both are
+/// normalized to semitones around their own voiced median (so different
+/// voice ranges compare fairly), unvoiced frames are dropped and the rest
+/// aligned with DTW
+#[wasm_bindgen]
+pub fn compare_contours(user: &[f32], reference: &[f32]) -> ContourScore {
+    compare_contours_with(user, reference, &DtwConfig::default())
+}
+
+/// `compare_contours` aligning with `config`, e.g. `DtwConfig::sentence()`
+/// for full sentences. With the asymmetric pattern the learner's frames set
+/// the timeline.
+#[wasm_bindgen]
+pub fn compare_contours_with(user: &[f32], reference: &[f32], config: &DtwConfig) -> ContourScore {
+    let voiced = |track: &[f32]| -> (Vec<usize>, Vec<f32>) {
+        let median = voiced_median(track);
+        track
+            .iter()
+            .enumerate()
+            .filter(|&(_, &hz)| hz > 0.0)
+            .map(|(i, &hz)| (i, PitchScale::Semitones.relative(hz, median)))
+            .unzip()
+    };
+    let (user_frames, user_st) = voiced(user);
+    let (reference_frames, reference_st) = voiced(reference);
+
+    let (distance, path) = dtw_with(&user_st, &reference_st, config);
+    let similarity = if distance.is_finite() {
+        100.0 * (-distance / SIMILARITY_DECAY_SEMITONES).exp()
+    } else {
+        0.0
+    };
+    ContourScore {
+        distance,
+        similarity,
+        path: path
+            .iter()
+            .flat_map(|&(i, j)| [user_frames[i] as u32, reference_frames[j] as u32])
+            .collect(),
+        costs: path
+            .iter()
+            .map(|&(i, j)| (user_st[i] - reference_st[j]).abs())
+            .collect(),
+    }
+}
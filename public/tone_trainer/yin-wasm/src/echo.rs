@@ -0,0 +1,183 @@
+//! Cancellation of the reference recording picked up by the microphone while
+//! the learner shadows it (speakers instead of headphones)
+
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::fft::{fft_in_place, ifft_in_place};
+
+/// Geigel double-talk threshold: the learner is assumed to be speaking when
+/// the mic exceeds this fraction of the recent reference peak
+const DOUBLE_TALK_RATIO: f32 = 0.5;
+
+/// Samples adaptation stays frozen after double-talk is detected
+const DOUBLE_TALK_HOLD: usize = 240;
+
+/// Adapted samples between weight checkpoints. Double-talk is only detected
+/// once the learner is already loud, so on detection the filter rolls back to
+/// a checkpoint taken before their voice started pulling on it.
+const CHECKPOINT_INTERVAL: usize = 240;
+
+/// Regularization of the NLMS step normalization
+const NLMS_EPSILON: f32 = 1e-6;
+
+/// Normalized LMS echo canceller. The filter models the speaker-to-mic path;
+/// its prediction of the echo is subtracted from the mic so only the
+/// learner's voice remains. Adaptation is frozen while the learner talks so
+/// their voice doesn't pull the filter away from the echo path, which means
+/// the path is learned while the reference plays alone (e.g. its lead-in).
+#[wasm_bindgen]
+pub struct EchoCanceller {
+    weights: Vec<f32>,
+    /// Weights at the last two checkpoints; rollback uses the older one
+    checkpoint: Vec<f32>,
+    pending_checkpoint: Vec<f32>,
+    since_checkpoint: usize,
+    /// Most recent reference samples, newest at `pos`
+    history: Vec<f32>,
+    pos: usize,
+    /// Running sum of squares over `history`
+    energy: f32,
+    step_size: f32,
+    /// Reference samples waiting out the playback latency
+    delay_line: VecDeque<f32>,
+    hold: usize,
+}
+
+#[wasm_bindgen]
+impl EchoCanceller {
+    /// `filter_length` taps should cover the room's echo tail (e.g. 512 at
+    /// 16 kHz); `delay_samples` is the playback latency, see
+    /// `estimate_echo_delay`. `step_size` is the NLMS step in (0, 2).
+    #[wasm_bindgen(constructor)]
+    pub fn new(filter_length: usize, step_size: f32, delay_samples: usize) -> EchoCanceller {
+        let filter_length = filter_length.max(1);
+        EchoCanceller {
+            weights: vec![0.0; filter_length],
+            checkpoint: vec![0.0; filter_length],
+            pending_checkpoint: vec![0.0; filter_length],
+            since_checkpoint: 0,
+            history: vec![0.0; filter_length],
+            pos: 0,
+            energy: 0.0,
+            step_size,
+            delay_line: std::iter::repeat_n(0.0, delay_samples).collect(),
+            hold: 0,
+        }
+    }
+
+    /// Remove the echo of `reference` from `mic`. Both chunks cover the same
+    /// time span; a shorter `reference` is padded with silence.
+    pub fn process(&mut self, mic: &[f32], reference: &[f32]) -> Vec<f32> {
+        let len = self.history.len();
+        let mut out = Vec::with_capacity(mic.len());
+
+        for (n, &d) in mic.iter().enumerate() {
+            self.delay_line
+                .push_back(reference.get(n).copied().unwrap_or(0.0));
+            let x = self.delay_line.pop_front().unwrap_or(0.0);
+
+            self.pos = (self.pos + 1) % len;
+            let old = self.history[self.pos];
+            self.history[self.pos] = x;
+            self.energy = (self.energy + x * x - old * old).max(0.0);
+
+            // Newest-first view of the reference history
+            let (recent, older) = self.history.split_at(self.pos + 1);
+            let taps = || recent.iter().rev().chain(older.iter().rev());
+
+            let mut estimate = 0.0;
+            let mut peak = 0.0f32;
+            for (w, &h) in self.weights.iter().zip(taps()) {
+                estimate += w * h;
+                peak = peak.max(h.abs());
+            }
+            let error = d - estimate;
+            out.push(error);
+
+            if d.abs() > DOUBLE_TALK_RATIO * peak {
+                if self.hold == 0 {
+                    self.weights.copy_from_slice(&self.checkpoint);
+                    self.pending_checkpoint.copy_from_slice(&self.checkpoint);
+                    self.since_checkpoint = 0;
+                }
+                self.hold = DOUBLE_TALK_HOLD;
+            }
+            if self.hold > 0 {
+                self.hold -= 1;
+                continue;
+            }
+            let gain = self.step_size * error / (self.energy + NLMS_EPSILON);
+            for (w, &h) in self.weights.iter_mut().zip(taps()) {
+                *w += gain * h;
+            }
+
+            self.since_checkpoint += 1;
+            if self.since_checkpoint == CHECKPOINT_INTERVAL {
+                std::mem::swap(&mut self.checkpoint, &mut self.pending_checkpoint);
+                self.pending_checkpoint.copy_from_slice(&self.weights);
+                self.since_checkpoint = 0;
+            }
+        }
+        out
+    }
+
+    /// Forget the learned echo path and buffered reference
+    pub fn reset(&mut self) {
+        self.weights.iter_mut().for_each(|w| *w = 0.0);
+        self.checkpoint.iter_mut().for_each(|w| *w = 0.0);
+        self.pending_checkpoint.iter_mut().for_each(|w| *w = 0.0);
+        self.since_checkpoint = 0;
+        self.history.iter_mut().for_each(|h| *h = 0.0);
+        self.delay_line.iter_mut().for_each(|h| *h = 0.0);
+        self.energy = 0.0;
+        self.hold = 0;
+    }
+}
+
+/// Lag (in samples, 0..=`max_delay`) at which `reference` best lines up with
+/// its echo in `mic`, from the FFT cross-correlation
+#[wasm_bindgen]
+pub fn estimate_echo_delay(mic: &[f32], reference: &[f32], max_delay: usize) -> usize {
+    if mic.is_empty() || reference.is_empty() {
+        return 0;
+    }
+    let n = (mic.len() + reference.len()).next_power_of_two();
+    let mut mic_re = vec![0.0f32; n];
+    let mut mic_im = vec![0.0f32; n];
+    let mut ref_re = vec![0.0f32; n];
+    let mut ref_im = vec![0.0f32; n];
+    mic_re[..mic.len()].copy_from_slice(mic);
+    ref_re[..reference.len()].copy_from_slice(reference);
+    fft_in_place(&mut mic_re, &mut mic_im);
+    fft_in_place(&mut ref_re, &mut ref_im);
+
+    // mic * conj(reference) gives the correlation at positive lags
+    for k in 0..n {
+        let (a, b) = (mic_re[k], mic_im[k]);
+        let (c, d) = (ref_re[k], -ref_im[k]);
+        mic_re[k] = a * c - b * d;
+        mic_im[k] = a * d + b * c;
+    }
+    ifft_in_place(&mut mic_re, &mut mic_im);
+
+    (0..=max_delay.min(mic.len() - 1))
+        .max_by(|&a, &b| mic_re[a].abs().total_cmp(&mic_re[b].abs()))
+        .unwrap_or(0)
+}
+
+/// Subtract the echo of the reference recording from a whole mic take.
+/// The playback latency is estimated first (up to `max_delay` samples), then
+/// an `EchoCanceller` runs over the take.
+#[wasm_bindgen]
+pub fn cancel_reference_echo(
+    mic: &[f32],
+    reference: &[f32],
+    filter_length: usize,
+    step_size: f32,
+    max_delay: usize,
+) -> Vec<f32> {
+    let delay = estimate_echo_delay(mic, reference, max_delay);
+    EchoCanceller::new(filter_length, step_size, delay).process(mic, reference)
+}
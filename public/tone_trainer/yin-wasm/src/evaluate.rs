@@ -0,0 +1,400 @@
+//! Accuracy evaluation against labeled recordings, so a change to the
+//! defaults or a contributed parameter tweak can be judged on numbers rather
+//! than on a few hand-picked clips. A set holds recordings with either a
+//! ground-truth pitch track or the tone labels of their syllables; every
+//! algorithm/config run over the set yields one `EvaluationReport`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::classify::classify_tones;
+use crate::config::YinConfig;
+use crate::input::resample_linear;
+use crate::pyin::{perform_pyin_analysis, PyinSettings};
+use crate::rubric::ScoringRubric;
+use crate::segments::pitch_column;
+use crate::tones::score_tones;
+
+/// Pitch error (cents) within which a voiced frame counts as correct, the
+/// usual tolerance of raw pitch accuracy
+const CORRECT_PITCH_CENTS: f32 = 50.0;
+
+/// Relative pitch error above which a frame is a gross error (20%, the
+/// usual GPE tolerance)
+const GROSS_ERROR_RATIO: f32 = 0.2;
+
+enum Label {
+    /// Reference pitch (Hz, 0 = unvoiced) every `step` seconds from 0
+    Pitch { truth: Vec<f32>, step: f32 },
+    /// Expected tone of every syllable
+    Tones(Vec<u8>),
+}
+
+struct Item {
+    audio: Vec<f32>,
+    sample_rate: f32,
+    label: Label,
+}
+
+/// Labeled recordings to evaluate analyzers on
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct EvaluationSet {
+    items: Vec<Item>,
+}
+
+#[wasm_bindgen]
+impl EvaluationSet {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> EvaluationSet {
+        EvaluationSet::default()
+    }
+
+    /// Add a recording with its ground-truth pitch track: one value (Hz, 0
+    /// for unvoiced) every `truth_step` seconds, starting at 0
+    pub fn add_pitch_item(
+        &mut self,
+        audio: Vec<f32>,
+        sample_rate: f32,
+        truth: Vec<f32>,
+        truth_step: f32,
+    ) {
+        self.items.push(Item {
+            audio,
+            sample_rate,
+            label: Label::Pitch {
+                truth,
+                step: truth_step,
+            },
+        });
+    }
+
+    /// Add a recording labeled with the tone (1-5) of each syllable
+    pub fn add_tone_item(&mut self, audio: Vec<f32>, sample_rate: f32, tones: Vec<u8>) {
+        self.items.push(Item {
+            audio,
+            sample_rate,
+            label: Label::Tones(tones),
+        });
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// Accuracy of one algorithm/config over an `EvaluationSet`
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct EvaluationReport {
+    pitch_items: usize,
+    tone_items: usize,
+    frames: usize,
+    voicing_accuracy: f32,
+    raw_pitch_accuracy: f32,
+    mean_abs_cents: f32,
+    gross_pitch_error: f32,
+    fine_pitch_error: f32,
+    voicing_miss_rate: f32,
+    voicing_false_alarm_rate: f32,
+    tone_accuracy: f32,
+    mean_tone_score: f32,
+    item_accuracy: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl EvaluationReport {
+    /// Recordings labeled with a pitch track
+    #[wasm_bindgen(getter)]
+    pub fn pitch_items(&self) -> usize {
+        self.pitch_items
+    }
+
+    /// Recordings labeled with tones
+    #[wasm_bindgen(getter)]
+    pub fn tone_items(&self) -> usize {
+        self.tone_items
+    }
+
+    /// Analyzed frames compared with a reference pitch
+    #[wasm_bindgen(getter)]
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Fraction of frames whose voiced/unvoiced decision matches the reference
+    #[wasm_bindgen(getter)]
+    pub fn voicing_accuracy(&self) -> f32 {
+        self.voicing_accuracy
+    }
+
+    /// Fraction of reference-voiced frames with a pitch within 50 cents
+    #[wasm_bindgen(getter)]
+    pub fn raw_pitch_accuracy(&self) -> f32 {
+        self.raw_pitch_accuracy
+    }
+
+    /// Mean absolute error (cents) over frames voiced in both
+    #[wasm_bindgen(getter)]
+    pub fn mean_abs_cents(&self) -> f32 {
+        self.mean_abs_cents
+    }
+
+    /// Gross pitch error: fraction of frames voiced in both whose pitch is
+    /// off by more than 20%
+    #[wasm_bindgen(getter)]
+    pub fn gross_pitch_error(&self) -> f32 {
+        self.gross_pitch_error
+    }
+
+    /// Fine pitch error: standard deviation (cents) of the error over the
+    /// frames voiced in both without a gross error
+    #[wasm_bindgen(getter)]
+    pub fn fine_pitch_error(&self) -> f32 {
+        self.fine_pitch_error
+    }
+
+    /// Fraction of reference-voiced frames reported unvoiced
+    #[wasm_bindgen(getter)]
+    pub fn voicing_miss_rate(&self) -> f32 {
+        self.voicing_miss_rate
+    }
+
+    /// Fraction of reference-unvoiced frames reported voiced
+    #[wasm_bindgen(getter)]
+    pub fn voicing_false_alarm_rate(&self) -> f32 {
+        self.voicing_false_alarm_rate
+    }
+
+    /// Voicing decision error: fraction of frames whose voiced/unvoiced
+    /// decision differs from the reference
+    #[wasm_bindgen(getter)]
+    pub fn voicing_decision_error(&self) -> f32 {
+        if self.frames > 0 {
+            1.0 - self.voicing_accuracy
+        } else {
+            0.0
+        }
+    }
+
+    /// Fraction of labeled syllables classified as their tone; recordings
+    /// split into the wrong number of syllables count as all wrong
+    #[wasm_bindgen(getter)]
+    pub fn tone_accuracy(&self) -> f32 {
+        self.tone_accuracy
+    }
+
+    /// Mean `score_expected_contour` score of the labeled tones
+    #[wasm_bindgen(getter)]
+    pub fn mean_tone_score(&self) -> f32 {
+        self.mean_tone_score
+    }
+
+    /// Per recording, in insertion order: raw pitch accuracy for pitch
+    /// items, tone accuracy for tone items
+    #[wasm_bindgen(getter)]
+    pub fn item_accuracy(&self) -> Vec<f32> {
+        self.item_accuracy.clone()
+    }
+}
+
+/// Frame-by-frame comparison with a reference track
+#[derive(Default)]
+struct PitchTally {
+    frames: usize,
+    voicing_matches: usize,
+    reference_voiced: usize,
+    correct: usize,
+    cents_sum: f32,
+    both_voiced: usize,
+    gross: usize,
+    /// Sum and sum of squares of the signed error (cents) of fine frames
+    fine_sum: f64,
+    fine_sq_sum: f64,
+    misses: usize,
+    false_alarms: usize,
+}
+
+impl PitchTally {
+    /// Compare `estimate` with `truth`; frame `i` is matched with the
+    /// reference value nearest its centre, `offset + i * frame_period`
+    fn add(&mut self, estimate: &[f32], frame_period: f32, offset: f32, truth: &[f32], step: f32) {
+        for (i, &pitch) in estimate.iter().enumerate() {
+            let idx = ((offset + i as f32 * frame_period) / step).round() as usize;
+            let Some(&reference) = truth.get(idx) else {
+                break;
+            };
+            self.frames += 1;
+            if (pitch > 0.0) == (reference > 0.0) {
+                self.voicing_matches += 1;
+            }
+            match (reference > 0.0, pitch > 0.0) {
+                (true, true) => {
+                    self.reference_voiced += 1;
+                    let cents = 1200.0 * (pitch / reference).log2();
+                    self.cents_sum += cents.abs();
+                    self.both_voiced += 1;
+                    if cents.abs() <= CORRECT_PITCH_CENTS {
+                        self.correct += 1;
+                    }
+                    if (pitch - reference).abs() > GROSS_ERROR_RATIO * reference {
+                        self.gross += 1;
+                    } else {
+                        self.fine_sum += cents as f64;
+                        self.fine_sq_sum += cents as f64 * cents as f64;
+                    }
+                }
+                (true, false) => {
+                    self.reference_voiced += 1;
+                    self.misses += 1;
+                }
+                (false, true) => self.false_alarms += 1,
+                (false, false) => {}
+            }
+        }
+    }
+
+    fn merge(&mut self, other: &PitchTally) {
+        self.frames += other.frames;
+        self.voicing_matches += other.voicing_matches;
+        self.reference_voiced += other.reference_voiced;
+        self.correct += other.correct;
+        self.cents_sum += other.cents_sum;
+        self.both_voiced += other.both_voiced;
+        self.gross += other.gross;
+        self.fine_sum += other.fine_sum;
+        self.fine_sq_sum += other.fine_sq_sum;
+        self.misses += other.misses;
+        self.false_alarms += other.false_alarms;
+    }
+
+    /// Standard deviation of the fine errors (cents)
+    fn fine_error(&self) -> f32 {
+        let n = (self.both_voiced - self.gross) as f64;
+        if n < 1.0 {
+            return 0.0;
+        }
+        let mean = self.fine_sum / n;
+        (self.fine_sq_sum / n - mean * mean).max(0.0).sqrt() as f32
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f32 {
+    if total > 0 {
+        count as f32 / total as f32
+    } else {
+        0.0
+    }
+}
+
+/// Run `analyze` (audio at the sample rate of `config` in, flat [pitch,
+/// confidence, tau, ...] out with the frame and hop size of `config`) over
+/// every item of `set`
+fn evaluate_with(
+    set: &EvaluationSet,
+    config: &YinConfig,
+    analyze: impl Fn(&[f32]) -> Vec<f32>,
+) -> EvaluationReport {
+    let sample_rate = config.sample_rate;
+    let frame_period = config.hop_size as f32 / sample_rate;
+    let centre = config.frame_size as f32 / 2.0 / sample_rate;
+    let rubric = ScoringRubric::default();
+    let mut report = EvaluationReport::default();
+    let mut pitch = PitchTally::default();
+    let (mut syllables, mut syllables_correct) = (0, 0);
+    let (mut score_sum, mut scores) = (0.0, 0);
+
+    for item in &set.items {
+        let audio = resample_linear(&item.audio, item.sample_rate, sample_rate);
+        let estimate = pitch_column(&analyze(&audio));
+        match &item.label {
+            Label::Pitch { truth, step } => {
+                let mut tally = PitchTally::default();
+                tally.add(&estimate, frame_period, centre, truth, *step);
+                report.pitch_items += 1;
+                report
+                    .item_accuracy
+                    .push(ratio(tally.correct, tally.reference_voiced));
+                pitch.merge(&tally);
+            }
+            Label::Tones(tones) => {
+                let timestamps: Vec<f32> = (0..estimate.len())
+                    .map(|i| i as f32 * frame_period)
+                    .collect();
+                let guesses = classify_tones(&estimate, &timestamps);
+                let correct = if guesses.len() == tones.len() {
+                    guesses
+                        .iter()
+                        .zip(tones)
+                        .filter(|(guess, &tone)| guess.tone() == tone)
+                        .count()
+                } else {
+                    0
+                };
+                report.tone_items += 1;
+                report.item_accuracy.push(ratio(correct, tones.len()));
+                syllables += tones.len();
+                syllables_correct += correct;
+
+                let item_scores = score_tones(&estimate, tones, &[], &[], true, &rubric);
+                for score in item_scores.chunks_exact(2) {
+                    score_sum += score[0];
+                    scores += 1;
+                }
+            }
+        }
+    }
+
+    report.frames = pitch.frames;
+    report.voicing_accuracy = ratio(pitch.voicing_matches, pitch.frames);
+    report.raw_pitch_accuracy = ratio(pitch.correct, pitch.reference_voiced);
+    report.mean_abs_cents = if pitch.both_voiced > 0 {
+        pitch.cents_sum / pitch.both_voiced as f32
+    } else {
+        0.0
+    };
+    report.gross_pitch_error = ratio(pitch.gross, pitch.both_voiced);
+    report.fine_pitch_error = pitch.fine_error();
+    report.voicing_miss_rate = ratio(pitch.misses, pitch.reference_voiced);
+    report.voicing_false_alarm_rate =
+        ratio(pitch.false_alarms, pitch.frames - pitch.reference_voiced);
+    report.tone_accuracy = ratio(syllables_correct, syllables);
+    report.mean_tone_score = if scores > 0 {
+        score_sum / scores as f32
+    } else {
+        0.0
+    };
+    report
+}
+
+/// Evaluate the YIN tracker with `config` on every recording of `set`
+#[wasm_bindgen]
+pub fn evaluate_yin(set: &EvaluationSet, config: &YinConfig) -> EvaluationReport {
+    evaluate_with(set, config, |audio| config.analyze(audio))
+}
+
+/// Evaluate probabilistic YIN with the frame settings of `config` and the
+/// decode settings of `settings` on every recording of `set`
+#[wasm_bindgen]
+pub fn evaluate_pyin(
+    set: &EvaluationSet,
+    config: &YinConfig,
+    settings: &PyinSettings,
+) -> EvaluationReport {
+    evaluate_with(set, config, |audio| {
+        perform_pyin_analysis(
+            audio,
+            config.sample_rate,
+            config.frame_size,
+            config.hop_size,
+            config.min_freq,
+            config.max_freq,
+            config.interpolation,
+            settings,
+        )
+    })
+}
@@ -0,0 +1,349 @@
+//! Machine-readable explanations of what went wrong in a recording, so the
+//! UI can show targeted tips instead of just a number. Feedback is purely
+//! numeric (code, severity, magnitude, time range) with no embedded text, so
+//! the app localizes the messages itself.
+
+use wasm_bindgen::prelude::*;
+
+use crate::pinyin::{parse_tones, ToneSequence};
+use crate::rubric::ScoringRubric;
+use crate::segments::voiced_core_range;
+use crate::tones::{
+    erhua_syllable_ranges, expected_syllable, hz_to_chao, resample_syllable, voiced_median,
+    Realization,
+};
+
+/// Syllables shorter than this are reported as too short
+const MIN_SYLLABLE_SECONDS: f32 = 0.1;
+
+/// Fraction of the syllable inspected for start/end level issues
+const EDGE_FRACTION: f32 = 0.25;
+
+/// Depth (Chao steps) a tone 3 must dip below its start to count as a dip
+const MIN_DIP_STEPS: f32 = 0.25;
+
+/// Pitch range (Chao steps) a tone 1 may move and still count as level
+const MAX_LEVEL_RANGE_STEPS: f32 = 1.5;
+
+/// Problem found in a recording
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueCode {
+    /// Syllable starts below its target level; magnitude in Chao steps
+    StartTooLow = 1,
+    /// Syllable starts above its target level; magnitude in Chao steps
+    StartTooHigh = 2,
+    /// Syllable ends below its target level; magnitude in Chao steps
+    EndTooLow = 3,
+    /// Syllable ends above its target level; magnitude in Chao steps
+    EndTooHigh = 4,
+    /// Tone 3 without a dip below its starting level; magnitude is the dip
+    /// depth that was reached, in Chao steps
+    NoDip = 5,
+    /// Tone 2 (or full or sandhi tone 3) rises much less than expected; magnitude is
+    /// the missing fraction of the expected rise (0-1)
+    RiseTooShallow = 6,
+    /// Tone 4 falls much less than expected (half as much when truncated
+    /// phrase-finally); magnitude is the missing fraction of the expected
+    /// fall (0-1)
+    FallTooShallow = 7,
+    /// Tone 1 moves too much to sound level; magnitude is its range in Chao
+    /// steps
+    NotLevel = 8,
+    /// Syllable is too short to carry its tone; magnitude is the shortfall
+    /// in seconds
+    TooShort = 9,
+    /// Too few frames of the recording are voiced; magnitude is the missing
+    /// fraction of the rubric's full voicing coverage (0-1)
+    LowVoicing = 10,
+}
+
+/// How much an issue matters
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Noticeable, but the tone is still recognizable
+    Minor = 1,
+    /// Likely to be heard as the wrong tone
+    Major = 2,
+}
+
+/// One issue with the syllable it belongs to (None for the whole recording)
+/// and the frame range [start_frame, end_frame) of the input pitch track.
+/// The unit of `magnitude` depends on the code (see `IssueCode`).
+#[derive(Clone, Copy, Debug)]
+pub struct Issue {
+    pub code: IssueCode,
+    pub severity: Severity,
+    pub magnitude: f32,
+    pub syllable: Option<usize>,
+    pub start_frame: usize,
+    pub end_frame: usize,
+}
+
+/// Severity and missing fraction of a movement that reaches only `measured`
+/// of `expected`
+fn shortfall(measured: f32, expected: f32) -> Option<(Severity, f32)> {
+    let ratio = measured / expected;
+    let missing = (1.0 - ratio).clamp(0.0, 1.0);
+    if ratio < 0.25 {
+        Some((Severity::Major, missing))
+    } else if ratio < 0.5 {
+        Some((Severity::Minor, missing))
+    } else {
+        None
+    }
+}
+
+/// Severity of a deviation measured against the rubric tolerance
+fn deviation_severity(deviation: f32, tolerance: f32) -> Option<Severity> {
+    if deviation >= tolerance {
+        Some(Severity::Major)
+    } else if deviation >= tolerance * 0.5 {
+        Some(Severity::Minor)
+    } else {
+        None
+    }
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+/// Issues of one syllable, as (code, severity, magnitude, position) where
+/// position is 0 for the start, 1 for the end and 2 for the whole syllable.
+/// The edges are judged against the contour expected in context (see
+/// `expected_syllable`), so coarticulation with the neighbouring tones and
+/// the raised onset after an `aspirated` initial aren't reported.
+fn syllable_issues(
+    chao: &[f32],
+    tone: u8,
+    previous_tone: Option<u8>,
+    next_tone: Option<u8>,
+    aspirated: bool,
+    rubric: &ScoringRubric,
+) -> Vec<(IssueCode, Severity, f32, u8)> {
+    let observed = resample_syllable(chao);
+    let (expected, realization, _) = expected_syllable(
+        &observed,
+        tone,
+        previous_tone,
+        next_tone,
+        aspirated,
+        rubric,
+        None,
+    );
+    let tolerance = rubric.tone_tolerance(tone);
+    let edge = ((observed.len() as f32 * EDGE_FRACTION).ceil() as usize).max(1);
+    let n = observed.len();
+    let mut issues = Vec::new();
+
+    let start_offset = mean(&observed[..edge]) - mean(&expected[..edge]);
+    if let Some(severity) = deviation_severity(start_offset.abs(), tolerance) {
+        let code = if start_offset < 0.0 {
+            IssueCode::StartTooLow
+        } else {
+            IssueCode::StartTooHigh
+        };
+        issues.push((code, severity, start_offset.abs(), 0));
+    }
+    let end_offset = mean(&observed[n - edge..]) - mean(&expected[n - edge..]);
+    if let Some(severity) = deviation_severity(end_offset.abs(), tolerance) {
+        let code = if end_offset < 0.0 {
+            IssueCode::EndTooLow
+        } else {
+            IssueCode::EndTooHigh
+        };
+        issues.push((code, severity, end_offset.abs(), 1));
+    }
+
+    let min = observed.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = observed.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let first = observed[0];
+    let last = observed[n - 1];
+    let movement = match (tone, realization) {
+        (1, _) if max - min > MAX_LEVEL_RANGE_STEPS => {
+            let severity = if max - min > 2.0 * MAX_LEVEL_RANGE_STEPS {
+                Severity::Major
+            } else {
+                Severity::Minor
+            };
+            Some((IssueCode::NotLevel, severity, max - min))
+        }
+        (2, _) | (3, Realization::ThirdSandhi) => {
+            shortfall(last - min, 2.0).map(|(s, m)| (IssueCode::RiseTooShallow, s, m))
+        }
+        (3, _) if first - min < MIN_DIP_STEPS => {
+            Some((IssueCode::NoDip, Severity::Major, first - min))
+        }
+        (3, Realization::Citation) => {
+            shortfall(last - min, 3.0).map(|(s, m)| (IssueCode::RiseTooShallow, s, m))
+        }
+        (4, Realization::TruncatedFall) => {
+            shortfall(first - last, 2.0).map(|(s, m)| (IssueCode::FallTooShallow, s, m))
+        }
+        (4, _) => shortfall(first - last, 4.0).map(|(s, m)| (IssueCode::FallTooShallow, s, m)),
+        _ => None,
+    };
+    if let Some((code, severity, magnitude)) = movement {
+        issues.push((code, severity, magnitude, 2));
+    }
+    issues
+}
+
+/// Diagnose a pitch track against the expected contours of a tone sequence,
+/// splitting syllables the same way as `score_tones`
+pub fn diagnose(
+    pitch: &[f32],
+    tones: &[u8],
+    erhua: &[bool],
+    aspirated: &[bool],
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+    frame_period: f32,
+) -> Vec<Issue> {
+    let (core_start, core_end) = if auto_crop {
+        voiced_core_range(pitch)
+    } else {
+        (0, pitch.len())
+    };
+    let track = &pitch[core_start..core_end];
+    let mut issues = Vec::new();
+
+    if let Some(voicing) = rubric.voicing_score(track) {
+        if voicing < 100.0 {
+            issues.push(Issue {
+                code: IssueCode::LowVoicing,
+                severity: if voicing < 50.0 {
+                    Severity::Major
+                } else {
+                    Severity::Minor
+                },
+                magnitude: 1.0 - voicing / 100.0,
+                syllable: None,
+                start_frame: core_start,
+                end_frame: core_end,
+            });
+        }
+    }
+
+    // Original frame index of every voiced frame, matching the chao values
+    let voiced_frames: Vec<usize> = (core_start..core_end).filter(|&f| pitch[f] > 0.0).collect();
+    let chao = hz_to_chao(track, voiced_median(track));
+    let min_frames = (MIN_SYLLABLE_SECONDS / frame_period.max(f32::EPSILON)).ceil() as usize;
+
+    let ranges = erhua_syllable_ranges(chao.len(), tones.len(), erhua);
+    for (i, (&tone, &(start, end))) in tones.iter().zip(&ranges).enumerate() {
+        if start == end {
+            continue;
+        }
+        let span = (voiced_frames[start], voiced_frames[end - 1] + 1);
+        if end - start < min_frames {
+            issues.push(Issue {
+                code: IssueCode::TooShort,
+                severity: if (end - start) * 2 < min_frames {
+                    Severity::Major
+                } else {
+                    Severity::Minor
+                },
+                magnitude: (min_frames - (end - start)) as f32 * frame_period,
+                syllable: Some(i),
+                start_frame: span.0,
+                end_frame: span.1,
+            });
+        }
+
+        let edge = (((end - start) as f32 * EDGE_FRACTION).ceil() as usize).max(1);
+        for (code, severity, magnitude, position) in syllable_issues(
+            &chao[start..end],
+            tone,
+            i.checked_sub(1).map(|previous| tones[previous]),
+            tones.get(i + 1).copied(),
+            aspirated.get(i).copied().unwrap_or(false),
+            rubric,
+        ) {
+            let (start_frame, end_frame) = match position {
+                0 => (span.0, voiced_frames[start + edge - 1] + 1),
+                1 => (voiced_frames[end - edge], span.1),
+                _ => span,
+            };
+            issues.push(Issue {
+                code,
+                severity,
+                magnitude,
+                syllable: Some(i),
+                start_frame,
+                end_frame,
+            });
+        }
+    }
+    issues
+}
+
+/// Flatten issues into (code, severity, magnitude, syllable, start_s, end_s)
+/// tuples; frame times are shifted by `time_offset` seconds
+pub fn issues_to_flat(issues: &[Issue], frame_period: f32, time_offset: f32) -> Vec<f32> {
+    issues
+        .iter()
+        .flat_map(|issue| {
+            [
+                issue.code as u8 as f32,
+                issue.severity as u8 as f32,
+                issue.magnitude,
+                issue.syllable.map_or(-1.0, |s| s as f32),
+                time_offset + issue.start_frame as f32 * frame_period,
+                time_offset + issue.end_frame as f32 * frame_period,
+            ]
+        })
+        .collect()
+}
+
+/// Issue codes for a pitch track scored with `score_expected_contour`.
+/// `frame_period` is the hop between pitch frames in seconds.
+/// Returns a flat array of (code, severity, magnitude, syllable, start_s,
+/// end_s) tuples, where syllable is -1 for issues concerning the whole
+/// recording.
+#[wasm_bindgen]
+pub fn diagnose_expected_contour(
+    pitch: &[f32],
+    tones: &[u8],
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+    frame_period: f32,
+) -> Vec<f32> {
+    issues_to_flat(
+        &diagnose(pitch, tones, &[], &[], auto_crop, rubric, frame_period),
+        frame_period,
+        0.0,
+    )
+}
+
+/// Issue codes for a pitch track scored with `score_expected_pinyin`, in
+/// the layout of `diagnose_expected_contour`
+#[wasm_bindgen]
+pub fn diagnose_expected_pinyin(
+    pitch: &[f32],
+    pinyin: &str,
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+    frame_period: f32,
+) -> Result<Vec<f32>, JsError> {
+    let ToneSequence {
+        tones,
+        erhua,
+        aspirated,
+    } = parse_tones(pinyin).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(issues_to_flat(
+        &diagnose(
+            pitch,
+            &tones,
+            &erhua,
+            &aspirated,
+            auto_crop,
+            rubric,
+            frame_period,
+        ),
+        frame_period,
+        0.0,
+    ))
+}
@@ -0,0 +1,83 @@
+//! Iterative radix-2 FFT and spectrum helpers
+
+use std::f32::consts::PI;
+
+/// In-place complex FFT. `re` and `im` must have the same power-of-two length.
+pub fn fft_in_place(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+    if n < 2 {
+        return;
+    }
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        let (w_im, w_re) = angle.sin_cos();
+        for start in (0..n).step_by(len) {
+            let mut cur_re = 1.0f32;
+            let mut cur_im = 0.0f32;
+            for k in 0..len / 2 {
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im = cur_re * w_im + cur_im * w_re;
+                cur_re = next_re;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Hann-windowed magnitude spectrum of `frame`, zero-padded to the next power
+/// of two. Returns `n_fft / 2 + 1` bins spaced `sample_rate / n_fft` apart.
+pub fn magnitude_spectrum(frame: &[f32]) -> Vec<f32> {
+    let n_fft = frame.len().next_power_of_two().max(2);
+    let mut re = vec![0.0f32; n_fft];
+    let mut im = vec![0.0f32; n_fft];
+    let denom = (frame.len().max(2) - 1) as f32;
+    for (i, &x) in frame.iter().enumerate() {
+        re[i] = x * (0.5 - 0.5 * (2.0 * PI * i as f32 / denom).cos());
+    }
+    fft_in_place(&mut re, &mut im);
+    (0..=n_fft / 2)
+        .map(|k| (re[k] * re[k] + im[k] * im[k]).sqrt())
+        .collect()
+}
+
+/// In-place inverse complex FFT (scaled by 1/n)
+pub fn ifft_in_place(re: &mut [f32], im: &mut [f32]) {
+    im.iter_mut().for_each(|v| *v = -*v);
+    fft_in_place(re, im);
+    let scale = 1.0 / re.len() as f32;
+    re.iter_mut().for_each(|v| *v *= scale);
+    im.iter_mut().for_each(|v| *v = -*v * scale);
+}
+
+/// Periodic Hann window of length `n`
+pub fn hann_window(n: usize) -> Vec<f32> {
+    (0..n)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / n as f32).cos())
+        .collect()
+}
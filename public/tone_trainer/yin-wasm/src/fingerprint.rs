@@ -0,0 +1,174 @@
+//! Compact per-attempt fingerprints for progress history. An attempt is
+//! reduced to its tone, score, duration, median pitch and the first few DCT
+//! coefficients of its contour, quantized to 19 bytes (26 characters of
+//! base64) so many attempts fit in one Anki field.
+
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::binary::BinaryError;
+use crate::contour::{resample_weighted, ConfidenceWeighting};
+use crate::segments::voiced_core;
+use crate::tones::{hz_to_semitones, voiced_median};
+
+const FINGERPRINT_VERSION: u8 = 1;
+
+/// Points the contour is resampled to before the DCT
+const DCT_POINTS: usize = 32;
+
+/// DCT coefficients kept
+const COEFFICIENTS: usize = 12;
+
+/// Quantization step of the coefficients (semitones, orthonormal DCT)
+const COEFFICIENT_STEP: f32 = 0.5;
+
+/// Reference pitch of the median quantization
+const MEDIAN_BASE_HZ: f32 = 50.0;
+
+/// Bytes before the coefficients: version, tone, score, duration (2),
+/// median, coefficient count
+const HEADER_LEN: usize = 7;
+
+/// Orthonormal DCT-II basis function `k` of length `n` at point `i`
+fn dct_basis(k: usize, i: usize, n: usize) -> f32 {
+    let scale = if k == 0 { 1.0 } else { 2.0f32.sqrt() } / (n as f32).sqrt();
+    scale * (PI * k as f32 * (i as f32 + 0.5) / n as f32).cos()
+}
+
+/// Orthonormal DCT-II coefficient `k` of `values`
+fn dct(values: &[f32], k: usize) -> f32 {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| v * dct_basis(k, i, values.len()))
+        .sum()
+}
+
+/// Decoded attempt fingerprint
+#[wasm_bindgen]
+pub struct AttemptFingerprint {
+    tone: u8,
+    score: f32,
+    duration: f32,
+    median_hz: f32,
+    coefficients: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl AttemptFingerprint {
+    pub fn from_bytes(bytes: &[u8]) -> Result<AttemptFingerprint, JsError> {
+        Self::decode(bytes).map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tone(&self) -> u8 {
+        self.tone
+    }
+
+    /// Score (0-100) in half-point steps
+    #[wasm_bindgen(getter)]
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    /// Voiced duration in seconds, to the centisecond
+    #[wasm_bindgen(getter)]
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Median pitch in Hz, to the quarter tone (0 if nothing was voiced)
+    #[wasm_bindgen(getter)]
+    pub fn median_hz(&self) -> f32 {
+        self.median_hz
+    }
+
+    /// Dequantized DCT coefficients of the semitone contour
+    #[wasm_bindgen(getter)]
+    pub fn coefficients(&self) -> Vec<f32> {
+        self.coefficients.clone()
+    }
+
+    /// Approximate contour (semitones relative to the median) rebuilt from
+    /// the coefficients, resampled to `n_points` for charting
+    pub fn contour(&self, n_points: usize) -> Vec<f32> {
+        let rebuilt: Vec<f32> = (0..DCT_POINTS)
+            .map(|i| {
+                self.coefficients
+                    .iter()
+                    .enumerate()
+                    .map(|(k, c)| c * dct_basis(k, i, DCT_POINTS))
+                    .sum()
+            })
+            .collect();
+        resample_weighted(&rebuilt, &[], n_points, &ConfidenceWeighting::default())
+    }
+}
+
+impl AttemptFingerprint {
+    fn decode(bytes: &[u8]) -> Result<Self, BinaryError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(BinaryError::UnexpectedEnd);
+        }
+        if bytes[0] != FINGERPRINT_VERSION {
+            return Err(BinaryError::UnsupportedVersion(bytes[0]));
+        }
+        let count = bytes[6] as usize;
+        let coefficients = bytes
+            .get(HEADER_LEN..HEADER_LEN + count)
+            .ok_or(BinaryError::UnexpectedEnd)?;
+        Ok(AttemptFingerprint {
+            tone: bytes[1],
+            score: bytes[2] as f32 / 2.0,
+            duration: u16::from_le_bytes([bytes[3], bytes[4]]) as f32 / 100.0,
+            median_hz: if bytes[5] == 0 {
+                0.0
+            } else {
+                MEDIAN_BASE_HZ * 2f32.powf(bytes[5] as f32 / 24.0)
+            },
+            coefficients: coefficients
+                .iter()
+                .map(|&c| c as i8 as f32 * COEFFICIENT_STEP)
+                .collect(),
+        })
+    }
+}
+
+/// Fingerprint (at most 64 bytes) of an attempt's Hz pitch track, cropped to
+/// its voiced core, with the `tone` and `score` it was graded with.
+/// `frame_period` is the hop between pitch frames in seconds.
+#[wasm_bindgen]
+pub fn attempt_fingerprint(pitch: &[f32], tone: u8, score: f32, frame_period: f32) -> Vec<u8> {
+    let core = voiced_core(pitch);
+    let median = voiced_median(core);
+    let semitones = hz_to_semitones(core, median);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + COEFFICIENTS);
+    out.push(FINGERPRINT_VERSION);
+    out.push(tone);
+    out.push((score * 2.0).round().clamp(0.0, 200.0) as u8);
+    let centiseconds = (semitones.len() as f32 * frame_period * 100.0).round();
+    out.extend_from_slice(&(centiseconds.clamp(0.0, u16::MAX as f32) as u16).to_le_bytes());
+    out.push(if median > 0.0 {
+        (24.0 * (median / MEDIAN_BASE_HZ).log2())
+            .round()
+            .clamp(1.0, 255.0) as u8
+    } else {
+        0
+    });
+
+    if semitones.is_empty() {
+        out.push(0);
+        return out;
+    }
+    let resampled = resample_weighted(&semitones, &[], DCT_POINTS, &ConfidenceWeighting::default());
+    out.push(COEFFICIENTS as u8);
+    for k in 0..COEFFICIENTS {
+        let q = (dct(&resampled, k) / COEFFICIENT_STEP)
+            .round()
+            .clamp(-128.0, 127.0);
+        out.push(q as i8 as u8);
+    }
+    out
+}
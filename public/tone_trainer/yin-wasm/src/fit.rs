@@ -0,0 +1,168 @@
+//! How well a fitted model (a line, a tone template, a reference template,
+//! a PCA reconstruction) describes the contour it was fitted to. Fits on
+//! fragmented or noisy contours produce numbers that look as confident as
+//! good ones; the residual statistics and the `reliable` flag let callers
+//! ignore them instead, and `FitQuality::reliability` turns them into a
+//! confidence in the score computed alongside the fit.
+
+use wasm_bindgen::prelude::*;
+
+/// Fewest points a fit needs to be reliable
+const MIN_FIT_POINTS: usize = 8;
+
+/// Smallest share of voiced frames over the fitted stretch for a reliable
+/// fit; below it the contour is mostly gaps
+const MIN_FIT_COVERAGE: f32 = 0.6;
+
+/// Largest RMS residual (semitones) of a reliable fit
+pub const MAX_FIT_RMS_SEMITONES: f32 = 2.0;
+
+/// Voiced coverage at or below which a score is unreliable, and at or
+/// above which gaps don't reduce its reliability
+const UNRELIABLE_COVERAGE: f32 = 0.3;
+const RELIABLE_COVERAGE: f32 = 0.9;
+
+/// SNR (dB) at or below which a score is unreliable, and at or above which
+/// noise doesn't reduce its reliability
+const UNRELIABLE_SNR_DB: f32 = 5.0;
+const RELIABLE_SNR_DB: f32 = 25.0;
+
+/// Reliability below which a score shouldn't update mastery statistics
+pub const MIN_SCORE_RELIABILITY: f32 = 0.5;
+
+/// Linear ramp from 0 at `low` to 1 at `high`
+fn ramp(value: f32, low: f32, high: f32) -> f32 {
+    ((value - low) / (high - low)).clamp(0.0, 1.0)
+}
+
+/// Residual statistics of a model fitted to a contour, in the contour's unit
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FitQuality {
+    points: usize,
+    rms: f32,
+    max_residual: f32,
+    r_squared: f32,
+    coverage: f32,
+    reliable: bool,
+    max_rms: f32,
+}
+
+#[wasm_bindgen]
+impl FitQuality {
+    /// Points the model was fitted to
+    #[wasm_bindgen(getter)]
+    pub fn points(&self) -> usize {
+        self.points
+    }
+
+    /// Root mean square of the residuals
+    #[wasm_bindgen(getter)]
+    pub fn rms(&self) -> f32 {
+        self.rms
+    }
+
+    /// Largest absolute residual
+    #[wasm_bindgen(getter)]
+    pub fn max_residual(&self) -> f32 {
+        self.max_residual
+    }
+
+    /// Share of the contour's variance the model explains (1 for a perfect
+    /// fit, 0 or below for one no better than the mean)
+    #[wasm_bindgen(getter)]
+    pub fn r_squared(&self) -> f32 {
+        self.r_squared
+    }
+
+    /// Share of voiced frames over the fitted stretch of the pitch track
+    #[wasm_bindgen(getter)]
+    pub fn coverage(&self) -> f32 {
+        self.coverage
+    }
+
+    /// Whether the fit had enough points and voicing and a small enough
+    /// residual to be trusted
+    #[wasm_bindgen(getter)]
+    pub fn reliable(&self) -> bool {
+        self.reliable
+    }
+
+    /// Confidence (0-1) in a score computed alongside this fit, from a
+    /// recording with `snr_db`: the product of how well the track is voiced,
+    /// how far speech stands above the noise and how well the model
+    /// describes the contour (fewer than the minimum points or an RMS
+    /// residual twice the reliable limit make it 0). Below
+    /// `MIN_SCORE_RELIABILITY` the score says more about the recording than
+    /// about the learner.
+    pub fn reliability(&self, snr_db: f32) -> f32 {
+        let voicing = ramp(self.coverage, UNRELIABLE_COVERAGE, RELIABLE_COVERAGE);
+        let noise = ramp(snr_db, UNRELIABLE_SNR_DB, RELIABLE_SNR_DB);
+        let points = (self.points as f32 / MIN_FIT_POINTS as f32).min(1.0);
+        let residual = if self.max_rms > 0.0 {
+            (1.0 - self.rms / (2.0 * self.max_rms)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        voicing * noise * points * residual
+    }
+}
+
+impl FitQuality {
+    /// Statistics of `observed` against the model's `fitted` values (paired
+    /// up to the shorter length). `max_rms` is the largest RMS residual of a
+    /// reliable fit, in the contour's unit.
+    pub fn from_residuals(observed: &[f32], fitted: &[f32], coverage: f32, max_rms: f32) -> Self {
+        let points = observed.len().min(fitted.len());
+        if points == 0 {
+            return FitQuality {
+                points: 0,
+                rms: 0.0,
+                max_residual: 0.0,
+                r_squared: 0.0,
+                coverage,
+                reliable: false,
+                max_rms,
+            };
+        }
+        let observed = &observed[..points];
+        let mean = observed.iter().sum::<f32>() / points as f32;
+        let (mut residual_sq, mut total_sq, mut max_residual) = (0.0, 0.0, 0.0f32);
+        for (o, f) in observed.iter().zip(fitted) {
+            residual_sq += (o - f) * (o - f);
+            total_sq += (o - mean) * (o - mean);
+            max_residual = max_residual.max((o - f).abs());
+        }
+        let rms = (residual_sq / points as f32).sqrt();
+        let r_squared = if total_sq > 0.0 {
+            1.0 - residual_sq / total_sq
+        } else if residual_sq == 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+        FitQuality {
+            points,
+            rms,
+            max_residual,
+            r_squared,
+            coverage,
+            reliable: points >= MIN_FIT_POINTS && coverage >= MIN_FIT_COVERAGE && rms <= max_rms,
+            max_rms,
+        }
+    }
+}
+
+/// Share of voiced frames (pitch > 0) between the first and last voiced
+/// frame of a Hz pitch track; 0 when nothing is voiced
+pub fn voiced_coverage(pitch: &[f32]) -> f32 {
+    let first = pitch.iter().position(|&p| p > 0.0);
+    let last = pitch.iter().rposition(|&p| p > 0.0);
+    match (first, last) {
+        (Some(first), Some(last)) => {
+            let voiced = pitch[first..=last].iter().filter(|&&p| p > 0.0).count();
+            voiced as f32 / (last - first + 1) as f32
+        }
+        _ => 0.0,
+    }
+}
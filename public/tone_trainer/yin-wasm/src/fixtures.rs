@@ -0,0 +1,175 @@
+//! Ready-made audio and settings pairs with their known answers. The doc
+//! examples of the public API run on them, so the examples are checked by
+//! `cargo test` and can't drift from what the code does; the JS test
+//! harness loads the same fixtures through `fixture` instead of keeping
+//! its own copies of the audio.
+//!
+//! ```
+//! use yin_wasm::fixtures::fixture;
+//!
+//! let sine = fixture("sine_220").unwrap();
+//! let results = sine.config().analyze(&sine.audio());
+//! for frame in results.chunks_exact(3) {
+//!     assert!((frame[0] - sine.expected_pitch_hz()).abs() < 1.0);
+//! }
+//! ```
+
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::YinConfig;
+use crate::dataset::{synthesize_phrase, SAMPLE_RATE};
+use crate::session::{RecordingSession, SessionConfig, SessionReport};
+
+/// Median pitch (Hz) of the demo syllable
+const DEMO_MEDIAN_HZ: f32 = 200.0;
+
+/// Length of the steady fixtures (seconds)
+const STEADY_SECONDS: f32 = 0.5;
+
+/// Names of all fixtures, in the order `fixture_names` lists them
+const NAMES: [&str; 4] = ["sine_220", "voice_110", "silence", "phrase_1234"];
+
+/// An audio clip, the settings to analyze it with and what the analysis
+/// should find
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    name: &'static str,
+    audio: Vec<f32>,
+    config: YinConfig,
+    expected_pitch_hz: f32,
+    tones: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Fixture {
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    /// Mono samples at `config.sample_rate`
+    #[wasm_bindgen(getter)]
+    pub fn audio(&self) -> Vec<f32> {
+        self.audio.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn config(&self) -> YinConfig {
+        self.config
+    }
+
+    /// Pitch (Hz) every frame should report: constant for the steady
+    /// fixtures, 0 for silence and for fixtures whose pitch moves
+    #[wasm_bindgen(getter)]
+    pub fn expected_pitch_hz(&self) -> f32 {
+        self.expected_pitch_hz
+    }
+
+    /// Tones spoken in the clip, empty unless it is a phrase
+    #[wasm_bindgen(getter)]
+    pub fn tones(&self) -> Vec<u8> {
+        self.tones.clone()
+    }
+}
+
+/// `seconds` of a periodic signal at `f0` with `harmonics` harmonics of
+/// amplitude 1/k
+fn periodic(f0: f32, harmonics: usize, seconds: f32) -> Vec<f32> {
+    let samples = (seconds * SAMPLE_RATE) as usize;
+    (0..samples)
+        .map(|i| {
+            let phase = 2.0 * PI * f0 * i as f32 / SAMPLE_RATE;
+            0.3 * (1..=harmonics)
+                .map(|k| (k as f32 * phase).sin() / k as f32)
+                .sum::<f32>()
+        })
+        .collect()
+}
+
+/// Fixture called `name` (see `fixture_names`): "sine_220" is a pure tone,
+/// "voice_110" a low voice with harmonics, "silence" digital silence and
+/// "phrase_1234" the synthesized phrase of tones 1-4 from
+/// `reference_dataset`
+///
+/// ```
+/// use yin_wasm::fixtures::fixture;
+///
+/// let silence = fixture("silence").unwrap();
+/// let results = silence.config().analyze(&silence.audio());
+/// assert!(results.chunks_exact(3).all(|frame| frame[0] == 0.0));
+/// assert!(fixture("unknown").is_none());
+/// ```
+#[wasm_bindgen]
+pub fn fixture(name: &str) -> Option<Fixture> {
+    let config = YinConfig::new();
+    let (name, audio, expected_pitch_hz, tones) = match name {
+        "sine_220" => (
+            "sine_220",
+            periodic(220.0, 1, STEADY_SECONDS),
+            220.0,
+            vec![],
+        ),
+        "voice_110" => (
+            "voice_110",
+            periodic(110.0, 8, STEADY_SECONDS),
+            110.0,
+            vec![],
+        ),
+        "silence" => (
+            "silence",
+            vec![0.0; (STEADY_SECONDS * SAMPLE_RATE) as usize],
+            0.0,
+            vec![],
+        ),
+        "phrase_1234" => {
+            let tones = vec![1, 2, 3, 4];
+            let (audio, _) = synthesize_phrase(&tones, 220.0);
+            ("phrase_1234", audio, 0.0, tones)
+        }
+        _ => return None,
+    };
+    Some(Fixture {
+        name,
+        audio,
+        config,
+        expected_pitch_hz,
+        tones,
+    })
+}
+
+/// Names accepted by `fixture`
+#[wasm_bindgen]
+pub fn fixture_names() -> Vec<String> {
+    NAMES.iter().map(|name| name.to_string()).collect()
+}
+
+/// Full report for a synthesized tone-3 syllable, run through the same
+/// `RecordingSession` pipeline as a real recording (trimming, quality
+/// checks, analysis, scoring against the tone template and feedback). The
+/// onboarding tour shows it as an example, and it doubles as an end-to-end
+/// check that every stage still fits together.
+///
+/// ```
+/// use yin_wasm::fixtures::demo_analysis;
+/// use yin_wasm::voicing::AttemptStatus;
+///
+/// let report = demo_analysis();
+/// assert_eq!(report.status(), AttemptStatus::Voiced);
+/// assert_eq!(report.scores().len(), 2);
+/// assert!(report.scores()[0] > 50.0);
+/// ```
+#[wasm_bindgen]
+pub fn demo_analysis() -> SessionReport {
+    let tones = vec![3];
+    let (audio, _) = synthesize_phrase(&tones, DEMO_MEDIAN_HZ);
+    let mut config = SessionConfig::new(SAMPLE_RATE);
+    config.set_tones(tones);
+    let mut session = RecordingSession::new();
+    session.append(&audio);
+    session
+        .report(&config)
+        .expect("the default settings are valid")
+}
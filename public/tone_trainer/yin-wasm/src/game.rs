@@ -0,0 +1,398 @@
+//! Scoring primitives for the practice games
+
+use wasm_bindgen::prelude::*;
+
+use crate::classify::ToneClassifier;
+
+/// Frame status: learner pitch inside the target band
+pub const BAND_HIT: f32 = 1.0;
+/// Frame status: target voiced but learner outside the band or unvoiced
+pub const BAND_MISS: f32 = 0.0;
+/// Frame status: no target at this frame, not scored
+pub const BAND_UNSCORED: f32 = -1.0;
+
+/// "Stay inside the tube": checks learner pitch frames against a target
+/// contour as they arrive. Target and learner pitch are in Hz (<= 0 for
+/// unvoiced); the band is `tolerance_semitones` either side of the target.
+#[wasm_bindgen]
+pub struct TargetBand {
+    target: Vec<f32>,
+    tolerance_semitones: f32,
+    frame: usize,
+    hits: usize,
+    scored: usize,
+}
+
+#[wasm_bindgen]
+impl TargetBand {
+    #[wasm_bindgen(constructor)]
+    pub fn new(target: &[f32], tolerance_semitones: f32) -> TargetBand {
+        TargetBand {
+            target: target.to_vec(),
+            tolerance_semitones,
+            frame: 0,
+            hits: 0,
+            scored: 0,
+        }
+    }
+
+    /// Score the next learner frames. Returns a flat [status, accuracy, ...]
+    /// array: status is `BAND_HIT`, `BAND_MISS` or `BAND_UNSCORED`, accuracy
+    /// the running percentage of scored frames hit so far.
+    pub fn push(&mut self, pitch: &[f32]) -> Vec<f32> {
+        let mut out = Vec::with_capacity(pitch.len() * 2);
+        for &hz in pitch {
+            let target = self.target.get(self.frame).copied().unwrap_or(0.0);
+            self.frame += 1;
+            let status = if target <= 0.0 {
+                BAND_UNSCORED
+            } else {
+                self.scored += 1;
+                if hz > 0.0 && (12.0 * (hz / target).log2()).abs() <= self.tolerance_semitones {
+                    self.hits += 1;
+                    BAND_HIT
+                } else {
+                    BAND_MISS
+                }
+            };
+            out.push(status);
+            out.push(self.accuracy());
+        }
+        out
+    }
+
+    /// Percentage of scored frames hit so far (0 before any are scored)
+    #[wasm_bindgen(getter)]
+    pub fn accuracy(&self) -> f32 {
+        if self.scored == 0 {
+            0.0
+        } else {
+            100.0 * self.hits as f32 / self.scored as f32
+        }
+    }
+
+    /// Frames consumed so far
+    #[wasm_bindgen(getter)]
+    pub fn frames(&self) -> usize {
+        self.frame
+    }
+
+    /// Start over against the same target
+    pub fn reset(&mut self) {
+        self.frame = 0;
+        self.hits = 0;
+        self.scored = 0;
+    }
+}
+
+/// Score a whole learner pitch track against a target contour of the same
+/// frame rate. Same [status, accuracy, ...] layout as `TargetBand::push`.
+#[wasm_bindgen]
+pub fn target_band_hits(pitch: &[f32], target: &[f32], tolerance_semitones: f32) -> Vec<f32> {
+    TargetBand::new(target, tolerance_semitones).push(pitch)
+}
+
+/// Points for a hit, before the combo multiplier and speed bonus
+pub const HIT_POINTS: f32 = 100.0;
+
+/// Consecutive hits per step of the combo multiplier
+const COMBO_STEP: usize = 4;
+
+/// Largest combo multiplier
+const MAX_MULTIPLIER: f32 = 4.0;
+
+/// Extra share of `HIT_POINTS` for answering at the very start of a window,
+/// falling linearly to nothing at its end
+const SPEED_BONUS: f32 = 0.5;
+
+/// Lowest classifier confidence that counts as a hit
+const MIN_HIT_CONFIDENCE: f32 = 0.5;
+
+/// Share of a window the answer is expected to be voiced for; sets how far
+/// into its tone the classifier is when judging an early answer
+const VOICED_SHARE: f32 = 0.5;
+
+/// Reaction time of a window with no voiced frame
+pub const NO_REACTION: f32 = -1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ToneTarget {
+    tone: u8,
+    start: f32,
+    end: f32,
+}
+
+/// Tones a timed game asks for, each within its own time window (seconds
+/// from the start of the round). Windows are in time order and don't
+/// overlap.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToneSchedule {
+    targets: Vec<ToneTarget>,
+}
+
+#[wasm_bindgen]
+impl ToneSchedule {
+    /// Schedule from a flat [tone, start, end, ...] array; throws when a
+    /// tone isn't 1-4 or a window is empty or overlaps the previous one
+    #[wasm_bindgen(constructor)]
+    pub fn new(windows: &[f32]) -> Result<ToneSchedule, JsError> {
+        let mut targets = Vec::with_capacity(windows.len() / 3);
+        for (i, w) in windows.chunks_exact(3).enumerate() {
+            let (tone, start, end) = (w[0], w[1], w[2]);
+            if !(1.0..=4.0).contains(&tone) || tone.fract() != 0.0 {
+                return Err(JsError::new(&format!("window {}: tone must be 1-4", i)));
+            }
+            let after_previous = targets.last().is_none_or(|p: &ToneTarget| start >= p.end);
+            if !(start >= 0.0 && end > start && after_previous) {
+                return Err(JsError::new(&format!(
+                    "window {}: must end after it starts and not overlap the previous one",
+                    i
+                )));
+            }
+            targets.push(ToneTarget {
+                tone: tone as u8,
+                start,
+                end,
+            });
+        }
+        Ok(ToneSchedule { targets })
+    }
+
+    /// Back-to-back windows of `window_seconds`, separated by
+    /// `gap_seconds`, starting at `lead_in_seconds`
+    pub fn evenly(
+        tones: &[u8],
+        lead_in_seconds: f32,
+        window_seconds: f32,
+        gap_seconds: f32,
+    ) -> ToneSchedule {
+        let step = window_seconds + gap_seconds.max(0.0);
+        let targets = tones
+            .iter()
+            .enumerate()
+            .map(|(i, &tone)| {
+                let start = lead_in_seconds.max(0.0) + i as f32 * step;
+                ToneTarget {
+                    tone,
+                    start,
+                    end: start + window_seconds,
+                }
+            })
+            .collect();
+        ToneSchedule { targets }
+    }
+
+    /// Number of windows
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// The schedule as a flat [tone, start, end, ...] array
+    pub fn windows(&self) -> Vec<f32> {
+        self.targets
+            .iter()
+            .flat_map(|t| [t.tone as f32, t.start, t.end])
+            .collect()
+    }
+}
+
+/// Plays a `ToneSchedule` against live pitch frames: inside each window the
+/// learner's voiced frames are classified as they arrive, and the window is
+/// a hit once the classifier settles on the target tone. When a window
+/// closes it is scored: a hit earns `HIT_POINTS`, times a multiplier that
+/// grows by 1 every `COMBO_STEP` consecutive hits (up to `MAX_MULTIPLIER`),
+/// plus a bonus for a quick reaction; a miss ends the combo. All scoring
+/// happens here so every platform awards the same points for the same
+/// pitch track.
+#[wasm_bindgen]
+pub struct ToneGame {
+    schedule: ToneSchedule,
+    speaker_median_hz: f32,
+    frame_period: f32,
+    window: usize,
+    classifier: ToneClassifier,
+    onset: Option<f32>,
+    hit: bool,
+    score: f32,
+    combo: usize,
+    max_combo: usize,
+    hits: usize,
+}
+
+#[wasm_bindgen]
+impl ToneGame {
+    /// `speaker_median_hz` is the learner's typical pitch (see
+    /// `ToneClassifier`), `frame_period` the seconds between pitch frames
+    #[wasm_bindgen(constructor)]
+    pub fn new(schedule: &ToneSchedule, speaker_median_hz: f32, frame_period: f32) -> ToneGame {
+        let mut game = ToneGame {
+            schedule: schedule.clone(),
+            speaker_median_hz,
+            frame_period: frame_period.max(f32::EPSILON),
+            window: 0,
+            classifier: ToneClassifier::new(speaker_median_hz, 1),
+            onset: None,
+            hit: false,
+            score: 0.0,
+            combo: 0,
+            max_combo: 0,
+            hits: 0,
+        };
+        game.start_window();
+        game
+    }
+
+    /// Feed pitch frames (Hz, <= 0 for unvoiced) with their times (seconds
+    /// from the start of the round). Returns the windows that closed, as a
+    /// flat [window, hit, reaction, points, combo, score, ...] array: hit is
+    /// 1 or 0, reaction the seconds from the window's start to the first
+    /// voiced frame in it (`NO_REACTION` if none), points what the window
+    /// earned, and combo and score the running totals after it.
+    pub fn push(&mut self, pitch: &[f32], timestamps: &[f32]) -> Vec<f32> {
+        let mut out = Vec::new();
+        for (&hz, &time) in pitch.iter().zip(timestamps) {
+            while self.target().is_some_and(|target| time >= target.end) {
+                self.close_window(&mut out);
+            }
+            let Some(target) = self.target() else {
+                break;
+            };
+            if time < target.start || hz <= 0.0 {
+                continue;
+            }
+            self.onset.get_or_insert(time);
+            if !self.hit {
+                self.classifier.push(&[hz]);
+                self.hit = self.classifier.tone() == target.tone
+                    && self.classifier.confidence() >= MIN_HIT_CONFIDENCE;
+            }
+        }
+        out
+    }
+
+    /// Close every window still open (the round was stopped or the audio
+    /// ended); same layout as `push`
+    pub fn finish(&mut self) -> Vec<f32> {
+        let mut out = Vec::new();
+        while self.target().is_some() {
+            self.close_window(&mut out);
+        }
+        out
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    /// Current run of consecutive hits
+    #[wasm_bindgen(getter)]
+    pub fn combo(&self) -> usize {
+        self.combo
+    }
+
+    /// Longest run of consecutive hits so far
+    #[wasm_bindgen(getter)]
+    pub fn max_combo(&self) -> usize {
+        self.max_combo
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Windows closed so far
+    #[wasm_bindgen(getter)]
+    pub fn windows_played(&self) -> usize {
+        self.window
+    }
+
+    /// Whether every window has closed
+    #[wasm_bindgen(getter)]
+    pub fn finished(&self) -> bool {
+        self.window >= self.schedule.len()
+    }
+
+    /// Start the same schedule over
+    pub fn reset(&mut self) {
+        self.window = 0;
+        self.score = 0.0;
+        self.combo = 0;
+        self.max_combo = 0;
+        self.hits = 0;
+        self.start_window();
+    }
+}
+
+impl ToneGame {
+    fn target(&self) -> Option<ToneTarget> {
+        self.schedule.targets.get(self.window).copied()
+    }
+
+    /// Prepare for the current window
+    fn start_window(&mut self) {
+        let expected_frames = self.target().map_or(1, |target| {
+            ((target.end - target.start) * VOICED_SHARE / self.frame_period).round() as usize
+        });
+        self.classifier = ToneClassifier::new(self.speaker_median_hz, expected_frames);
+        self.onset = None;
+        self.hit = false;
+    }
+
+    /// Score the current window, append its result and move to the next
+    fn close_window(&mut self, out: &mut Vec<f32>) {
+        let Some(target) = self.target() else {
+            return;
+        };
+        let reaction = self.onset.map_or(NO_REACTION, |onset| onset - target.start);
+        let points = if self.hit {
+            let multiplier = (1 + self.combo / COMBO_STEP) as f32;
+            let speed = 1.0 - reaction / (target.end - target.start);
+            self.combo += 1;
+            self.max_combo = self.max_combo.max(self.combo);
+            self.hits += 1;
+            (HIT_POINTS
+                * multiplier.min(MAX_MULTIPLIER)
+                * (1.0 + SPEED_BONUS * speed.clamp(0.0, 1.0)))
+            .round()
+        } else {
+            self.combo = 0;
+            0.0
+        };
+        self.score += points;
+        out.extend([
+            self.window as f32,
+            if self.hit { 1.0 } else { 0.0 },
+            reaction,
+            points,
+            self.combo as f32,
+            self.score,
+        ]);
+        self.window += 1;
+        self.start_window();
+    }
+}
+
+/// Play a whole recorded round: `ToneGame::push` over every frame, then
+/// `finish`. Same layout as `push`.
+#[wasm_bindgen]
+pub fn play_tone_schedule(
+    schedule: &ToneSchedule,
+    pitch: &[f32],
+    timestamps: &[f32],
+    speaker_median_hz: f32,
+    frame_period: f32,
+) -> Vec<f32> {
+    let mut game = ToneGame::new(schedule, speaker_median_hz, frame_period);
+    let mut results = game.push(pitch, timestamps);
+    results.extend(game.finish());
+    results
+}
@@ -0,0 +1,189 @@
+//! Per-frame voicing decision. YIN reports a pitch for any frame with a
+//! CMNDF dip under the threshold, including silence and fricatives; the gate
+//! combines the frame's energy above the noise floor with the depth of its
+//! dip into a voicing probability and zeroes the pitch of frames below it.
+
+use wasm_bindgen::prelude::*;
+
+use crate::analysis::YinAnalysis;
+use crate::config::YinConfig;
+use crate::input::frame_levels;
+use crate::noise::estimate_noise;
+
+/// Lowest level considered when computing decibels
+const LEVEL_FLOOR: f32 = 1e-9;
+
+/// Time constant (seconds) with which the tracked noise floor rises during
+/// unvoiced stretches; slow, so a fricative barely moves it but a fan
+/// switched on is followed within a few seconds
+const FLOOR_RISE_SECONDS: f32 = 1.5;
+
+/// Time constant (seconds) with which the tracked noise floor drops to a
+/// quieter frame
+const FLOOR_FALL_SECONDS: f32 = 0.1;
+
+/// Settings of the per-frame voicing gate
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct VoicingGate {
+    /// Frames with a voicing probability below this report pitch 0
+    pub min_probability: f32,
+    /// Level above the noise floor (dB) at which the energy term reaches 1;
+    /// frames at the noise floor get 0
+    pub energy_range_db: f32,
+    /// Frames quieter than this RMS level are unvoiced regardless of the
+    /// noise floor (0 disables the absolute floor)
+    pub min_rms: f32,
+}
+
+#[wasm_bindgen]
+impl VoicingGate {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> VoicingGate {
+        VoicingGate {
+            min_probability: 0.3,
+            energy_range_db: 20.0,
+            min_rms: 1e-4,
+        }
+    }
+}
+
+impl Default for VoicingGate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Noise floor followed through a live recording: it drops quickly to
+/// quieter frames and rises slowly during unvoiced stretches, so the gate
+/// copes with background noise that changes mid-session
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseFloorTracker {
+    floor_rms: Option<f32>,
+    rise: f32,
+    fall: f32,
+}
+
+impl NoiseFloorTracker {
+    /// Tracker for frames `frame_period` seconds apart
+    pub fn new(frame_period: f32) -> Self {
+        let smoothing = |seconds: f32| 1.0 - (-frame_period / seconds).exp();
+        NoiseFloorTracker {
+            floor_rms: None,
+            rise: smoothing(FLOOR_RISE_SECONDS),
+            fall: smoothing(FLOOR_FALL_SECONDS),
+        }
+    }
+
+    /// Current floor (RMS); 0 before the first frame
+    pub fn floor_rms(&self) -> f32 {
+        self.floor_rms.unwrap_or(0.0)
+    }
+
+    /// Take in the RMS level of the next frame and whether it was voiced.
+    /// Voiced frames only ever lower the floor.
+    pub fn update(&mut self, level: f32, voiced: bool) {
+        let floor = match self.floor_rms {
+            None => level,
+            Some(floor) if level < floor => floor + (level - floor) * self.fall,
+            Some(floor) if !voiced => floor + (level - floor) * self.rise,
+            Some(floor) => floor,
+        };
+        self.floor_rms = Some(floor);
+    }
+
+    /// Forget the floor, e.g. for a new recording
+    pub fn reset(&mut self) {
+        self.floor_rms = None;
+    }
+
+    /// Gate one [pitch, confidence, tau] frame against the current floor,
+    /// then update the floor with it. Returns the voicing probability.
+    pub fn gate_frame(&mut self, frame: &mut [f32], level: f32, gate: &VoicingGate) -> f32 {
+        // The first frame has nothing to compare with; take it as noise
+        let floor = self.floor_rms.unwrap_or(level);
+        let probability = voicing_probabilities(frame, &[level], floor, gate)[0];
+        apply_gate(frame, &[probability], gate);
+        self.update(level, frame[0] > 0.0);
+        probability
+    }
+}
+
+/// Voicing probability (0-1) of every frame: the geometric mean of an energy
+/// term (level above the noise floor, see `VoicingGate::energy_range_db`)
+/// and the periodicity YIN found (the frame's confidence, 1 - CMNDF at the
+/// chosen dip). `levels` are the per-frame RMS levels of `frame_levels`.
+pub fn voicing_probabilities(
+    results: &[f32],
+    levels: &[f32],
+    noise_rms: f32,
+    gate: &VoicingGate,
+) -> Vec<f32> {
+    let noise_db = 20.0 * noise_rms.max(LEVEL_FLOOR).log10();
+    results
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, frame)| {
+            let level = levels.get(i).copied().unwrap_or(0.0);
+            if level < gate.min_rms {
+                return 0.0;
+            }
+            let level_db = 20.0 * level.max(LEVEL_FLOOR).log10();
+            let energy = ((level_db - noise_db) / gate.energy_range_db).clamp(0.0, 1.0);
+            let periodicity = frame[1].clamp(0.0, 1.0);
+            (energy * periodicity).sqrt()
+        })
+        .collect()
+}
+
+/// Zero the pitch and confidence of frames whose voicing probability is
+/// below the gate. Returns the number of frames gated.
+pub fn apply_gate(results: &mut [f32], voicing: &[f32], gate: &VoicingGate) -> usize {
+    let mut gated = 0;
+    for (frame, &probability) in results.chunks_exact_mut(3).zip(voicing) {
+        if frame[0] > 0.0 && probability < gate.min_probability {
+            frame[0] = 0.0;
+            frame[1] = 0.0;
+            gated += 1;
+        }
+    }
+    gated
+}
+
+/// Per-frame voicing probability of `perform_yin_analysis` results for the
+/// same audio, frame and hop size
+#[wasm_bindgen]
+pub fn frame_voicing(
+    audio_data: &[f32],
+    results: &[f32],
+    frame_size: usize,
+    hop_size: usize,
+    gate: &VoicingGate,
+) -> Vec<f32> {
+    let levels = frame_levels(audio_data, frame_size, hop_size);
+    let noise = estimate_noise(audio_data, frame_size, hop_size);
+    voicing_probabilities(results, &levels, noise.noise_rms, gate)
+}
+
+/// Analyze `audio_data` with `config` and gate unvoiced frames: they report
+/// pitch 0 and `voiced: false`, and every frame carries its RMS energy and
+/// voicing probability. Throws when the settings are invalid or the audio
+/// is shorter than one frame.
+#[wasm_bindgen]
+pub fn perform_yin_analysis_gated(
+    audio_data: &[f32],
+    config: &YinConfig,
+    gate: &VoicingGate,
+) -> Result<YinAnalysis, JsError> {
+    let mut results = config
+        .analyze_checked(audio_data)
+        .map_err(|err| JsError::new(&err.to_string()))?;
+    let levels = frame_levels(audio_data, config.frame_size, config.hop_size);
+    let noise = estimate_noise(audio_data, config.frame_size, config.hop_size);
+    let voicing = voicing_probabilities(&results, &levels, noise.noise_rms, gate);
+    apply_gate(&mut results, &voicing, gate);
+    Ok(
+        YinAnalysis::from_flat(results, config.hop_size as f32 / config.sample_rate)
+            .with_voicing(levels, voicing),
+    )
+}
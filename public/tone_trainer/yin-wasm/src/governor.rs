@@ -0,0 +1,156 @@
+//! Real-time quality governor: keeps live analysis within a per-callback
+//! time budget by trading temporal resolution for speed
+
+use wasm_bindgen::prelude::*;
+
+use crate::stream::FrameStream;
+
+/// Frame stride used at each quality level (0 = full quality)
+const LEVEL_STRIDES: [usize; 4] = [1, 2, 4, 8];
+
+/// Fraction of the budget below which a call counts as having headroom
+const HEADROOM_FRACTION: f64 = 0.5;
+
+/// Consecutive calls with headroom before quality is raised again
+const CALLS_BEFORE_UPGRADE: usize = 8;
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// Milliseconds from an arbitrary origin
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now_ms() -> f64 {
+    performance_now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    ORIGIN.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+/// Streaming analyzer that measures how long each `push_samples` call takes
+/// and moves between quality levels to stay under `budget_ms`: over budget
+/// drops a level immediately, a run of calls well under budget raises it
+/// again. Lower levels analyze fewer frames and interpolate the rest (see
+/// `FrameStream::set_stride`), so the frame rate of the output never changes.
+#[wasm_bindgen]
+pub struct GovernedAnalyzer {
+    stream: FrameStream,
+    budget_ms: f64,
+    level: usize,
+    calls_with_headroom: usize,
+    last_elapsed_ms: f64,
+}
+
+#[wasm_bindgen]
+impl GovernedAnalyzer {
+    /// Throws on framing `perform_yin_analysis` can't use either (a zero
+    /// hop, or a frame outside 2..=`MAX_FRAME_SIZE`)
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sample_rate: f32,
+        frame_size: usize,
+        hop_size: usize,
+        threshold: f32,
+        min_freq: f32,
+        max_freq: f32,
+        interpolation: bool,
+        budget_ms: f64,
+    ) -> Result<GovernedAnalyzer, JsError> {
+        let stream = FrameStream::new(
+            sample_rate,
+            frame_size,
+            hop_size,
+            threshold,
+            min_freq,
+            max_freq,
+            interpolation,
+        )
+        .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(GovernedAnalyzer {
+            stream,
+            budget_ms,
+            level: 0,
+            calls_with_headroom: 0,
+            last_elapsed_ms: 0.0,
+        })
+    }
+
+    /// Analyze the next chunk of samples. Returns the completed frames as a
+    /// flat [pitch, confidence, tau, ...] array.
+    pub fn push_samples(&mut self, samples: &[f32]) -> Vec<f32> {
+        let started = now_ms();
+        let mut results = Vec::new();
+        self.stream.push(samples, &mut results);
+        self.last_elapsed_ms = now_ms() - started;
+        self.adjust();
+        results
+    }
+
+    /// Emit frames held back for interpolation (call when input ends)
+    pub fn flush(&mut self) -> Vec<f32> {
+        let mut results = Vec::new();
+        self.stream.flush(&mut results);
+        results
+    }
+
+    /// Active quality level: 0 is full quality, higher is faster
+    #[wasm_bindgen(getter)]
+    pub fn quality_level(&self) -> usize {
+        self.level
+    }
+
+    /// One in `stride` frames is analyzed at the active level
+    #[wasm_bindgen(getter)]
+    pub fn stride(&self) -> usize {
+        LEVEL_STRIDES[self.level]
+    }
+
+    /// Time the last `push_samples` call took
+    #[wasm_bindgen(getter)]
+    pub fn last_elapsed_ms(&self) -> f64 {
+        self.last_elapsed_ms
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn budget_ms(&self) -> f64 {
+        self.budget_ms
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_budget_ms(&mut self, budget_ms: f64) {
+        self.budget_ms = budget_ms;
+    }
+}
+
+impl GovernedAnalyzer {
+    fn adjust(&mut self) {
+        if self.last_elapsed_ms > self.budget_ms {
+            self.calls_with_headroom = 0;
+            if self.level + 1 < LEVEL_STRIDES.len() {
+                self.set_level(self.level + 1);
+            }
+        } else if self.last_elapsed_ms < self.budget_ms * HEADROOM_FRACTION {
+            self.calls_with_headroom += 1;
+            if self.calls_with_headroom >= CALLS_BEFORE_UPGRADE && self.level > 0 {
+                self.calls_with_headroom = 0;
+                self.set_level(self.level - 1);
+            }
+        } else {
+            self.calls_with_headroom = 0;
+        }
+    }
+
+    fn set_level(&mut self, level: usize) {
+        self.level = level;
+        self.stream.set_stride(LEVEL_STRIDES[level]);
+    }
+}
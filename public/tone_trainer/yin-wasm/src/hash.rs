@@ -0,0 +1,175 @@
+//! XXH64 content hashing for cache keys and change detection
+
+use wasm_bindgen::prelude::*;
+
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+/// Samples converted to bytes per update when hashing audio
+const SAMPLE_BLOCK: usize = 256;
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn merge_round(acc: u64, value: u64) -> u64 {
+    (acc ^ round(0, value))
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(PRIME64_4)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(buf)
+}
+
+/// Streaming XXH64 (seed 0), so large inputs can be hashed piecewise
+pub struct Xxh64 {
+    acc: [u64; 4],
+    /// Bytes of an incomplete 32-byte stripe
+    buffer: [u8; 32],
+    buffered: usize,
+    total_len: u64,
+}
+
+impl Default for Xxh64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Xxh64 {
+    pub fn new() -> Self {
+        Xxh64 {
+            acc: [
+                PRIME64_1.wrapping_add(PRIME64_2),
+                PRIME64_2,
+                0,
+                0u64.wrapping_sub(PRIME64_1),
+            ],
+            buffer: [0; 32],
+            buffered: 0,
+            total_len: 0,
+        }
+    }
+
+    fn stripe(acc: &mut [u64; 4], stripe: &[u8]) {
+        for (i, lane) in acc.iter_mut().enumerate() {
+            *lane = round(*lane, read_u64(&stripe[i * 8..]));
+        }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.total_len += bytes.len() as u64;
+
+        if self.buffered > 0 {
+            let n = (32 - self.buffered).min(bytes.len());
+            self.buffer[self.buffered..self.buffered + n].copy_from_slice(&bytes[..n]);
+            self.buffered += n;
+            bytes = &bytes[n..];
+            if self.buffered < 32 {
+                return;
+            }
+            let stripe = self.buffer;
+            Self::stripe(&mut self.acc, &stripe);
+            self.buffered = 0;
+        }
+
+        let mut stripes = bytes.chunks_exact(32);
+        for stripe in &mut stripes {
+            Self::stripe(&mut self.acc, stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    pub fn finish(&self) -> u64 {
+        let [v1, v2, v3, v4] = self.acc;
+        let mut hash = if self.total_len >= 32 {
+            let h = v1
+                .rotate_left(1)
+                .wrapping_add(v2.rotate_left(7))
+                .wrapping_add(v3.rotate_left(12))
+                .wrapping_add(v4.rotate_left(18));
+            [v1, v2, v3, v4].into_iter().fold(h, merge_round)
+        } else {
+            PRIME64_5
+        };
+        hash = hash.wrapping_add(self.total_len);
+
+        let mut rest = &self.buffer[..self.buffered];
+        while rest.len() >= 8 {
+            hash = (hash ^ round(0, read_u64(rest)))
+                .rotate_left(27)
+                .wrapping_mul(PRIME64_1)
+                .wrapping_add(PRIME64_4);
+            rest = &rest[8..];
+        }
+        if rest.len() >= 4 {
+            hash = (hash ^ (read_u32(rest) as u64).wrapping_mul(PRIME64_1))
+                .rotate_left(23)
+                .wrapping_mul(PRIME64_2)
+                .wrapping_add(PRIME64_3);
+            rest = &rest[4..];
+        }
+        for &b in rest {
+            hash = (hash ^ (b as u64).wrapping_mul(PRIME64_5))
+                .rotate_left(11)
+                .wrapping_mul(PRIME64_1);
+        }
+
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME64_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME64_3);
+        hash ^ (hash >> 32)
+    }
+}
+
+/// XXH64 of `bytes`
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = Xxh64::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// XXH64 of an audio buffer's little-endian sample bytes, without copying
+/// the whole buffer
+pub fn hash_samples(samples: &[f32]) -> u64 {
+    let mut hasher = Xxh64::new();
+    let mut block = [0u8; SAMPLE_BLOCK * 4];
+    for chunk in samples.chunks(SAMPLE_BLOCK) {
+        for (dst, s) in block.chunks_exact_mut(4).zip(chunk) {
+            dst.copy_from_slice(&s.to_le_bytes());
+        }
+        hasher.update(&block[..chunk.len() * 4]);
+    }
+    hasher.finish()
+}
+
+/// Hash of decoded audio samples as 16 hex digits, e.g. the cache key for a
+/// reference analysis
+#[wasm_bindgen]
+pub fn hash_audio(samples: &[f32]) -> String {
+    format!("{:016x}", hash_samples(samples))
+}
+
+/// Hash of an audio file's raw bytes as 16 hex digits, to detect that a
+/// note's audio file has been replaced without decoding it
+#[wasm_bindgen]
+pub fn hash_audio_file(bytes: &[u8]) -> String {
+    format!("{:016x}", hash_bytes(bytes))
+}
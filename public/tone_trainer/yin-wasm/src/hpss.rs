@@ -0,0 +1,104 @@
+//! Harmonic/percussive source separation (Fitzgerald 2010) used to suppress
+//! keyboard clicks, taps and other percussive interference before pitch analysis
+
+use wasm_bindgen::prelude::*;
+
+use crate::fft::{fft_in_place, hann_window, ifft_in_place};
+
+/// Median of a small scratch buffer
+fn median(values: &mut [f32]) -> f32 {
+    let mid = values.len() / 2;
+    *values.select_nth_unstable_by(mid, |a, b| a.total_cmp(b)).1
+}
+
+/// Keep the harmonic part of `audio_data`.
+///
+/// The STFT magnitude is median-filtered along time (harmonic partials are
+/// steady) and along frequency (clicks are broadband); a soft Wiener-style
+/// mask `H² / (H² + P²)` built from the two is applied to the STFT, which is
+/// then resynthesized by weighted overlap-add. `fft_size` must be a power of
+/// two; `kernel_size` is the median length in frames and bins (made odd).
+#[wasm_bindgen]
+pub fn suppress_percussive(
+    audio_data: &[f32],
+    fft_size: usize,
+    hop_size: usize,
+    kernel_size: usize,
+) -> Vec<f32> {
+    if !fft_size.is_power_of_two() || fft_size < 4 || hop_size == 0 || audio_data.is_empty() {
+        return audio_data.to_vec();
+    }
+    let half_kernel = kernel_size.max(1) / 2;
+    let bins = fft_size / 2 + 1;
+    let window = hann_window(fft_size);
+
+    // Pad so every sample is covered by full windows
+    let pad = fft_size;
+    let mut padded = vec![0.0f32; audio_data.len() + 2 * pad];
+    padded[pad..pad + audio_data.len()].copy_from_slice(audio_data);
+    let num_frames = (padded.len() - fft_size) / hop_size + 1;
+
+    // Forward STFT
+    let mut spec_re = vec![0.0f32; num_frames * fft_size];
+    let mut spec_im = vec![0.0f32; num_frames * fft_size];
+    let mut magnitude = vec![0.0f32; num_frames * bins];
+    for t in 0..num_frames {
+        let re = &mut spec_re[t * fft_size..(t + 1) * fft_size];
+        let im = &mut spec_im[t * fft_size..(t + 1) * fft_size];
+        for (i, value) in re.iter_mut().enumerate() {
+            *value = padded[t * hop_size + i] * window[i];
+        }
+        fft_in_place(re, im);
+        for k in 0..bins {
+            magnitude[t * bins + k] = (re[k] * re[k] + im[k] * im[k]).sqrt();
+        }
+    }
+
+    // Median filters and soft mask
+    let mut scratch = Vec::with_capacity(2 * half_kernel + 1);
+    let mut mask = vec![0.0f32; num_frames * bins];
+    for t in 0..num_frames {
+        for k in 0..bins {
+            scratch.clear();
+            let t0 = t.saturating_sub(half_kernel);
+            let t1 = (t + half_kernel).min(num_frames - 1);
+            scratch.extend((t0..=t1).map(|tt| magnitude[tt * bins + k]));
+            let harmonic = median(&mut scratch);
+
+            scratch.clear();
+            let k0 = k.saturating_sub(half_kernel);
+            let k1 = (k + half_kernel).min(bins - 1);
+            scratch.extend_from_slice(&magnitude[t * bins + k0..=t * bins + k1]);
+            let percussive = median(&mut scratch);
+
+            let h2 = harmonic * harmonic;
+            let p2 = percussive * percussive;
+            mask[t * bins + k] = if h2 + p2 > 0.0 { h2 / (h2 + p2) } else { 1.0 };
+        }
+    }
+
+    // Masked inverse STFT with weighted overlap-add
+    let mut output = vec![0.0f32; padded.len()];
+    let mut norm = vec![0.0f32; padded.len()];
+    for t in 0..num_frames {
+        let re = &mut spec_re[t * fft_size..(t + 1) * fft_size];
+        let im = &mut spec_im[t * fft_size..(t + 1) * fft_size];
+        for k in 0..fft_size {
+            // Mirror the mask for the negative-frequency half
+            let m = mask[t * bins + if k < bins { k } else { fft_size - k }];
+            re[k] *= m;
+            im[k] *= m;
+        }
+        ifft_in_place(re, im);
+        for i in 0..fft_size {
+            output[t * hop_size + i] += re[i] * window[i];
+            norm[t * hop_size + i] += window[i] * window[i];
+        }
+    }
+
+    output[pad..pad + audio_data.len()]
+        .iter()
+        .zip(&norm[pad..pad + audio_data.len()])
+        .map(|(&y, &n)| if n > 1e-6 { y / n } else { 0.0 })
+        .collect()
+}
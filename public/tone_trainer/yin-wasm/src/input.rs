@@ -0,0 +1,518 @@
+use wasm_bindgen::prelude::*;
+
+use crate::config::YinConfig;
+use crate::noise::estimate_noise;
+use crate::perform_yin_analysis;
+use crate::wav::decode_wav;
+
+/// Speech level (RMS) below which a channel counts as dead
+const DEAD_CHANNEL_RMS: f32 = 1e-4;
+
+/// Channels whose SNRs are within this many dB of the best one are mixed
+/// instead of picking one: averaging two equally good microphones keeps the
+/// speech and partly cancels their independent noise
+const MIX_SNR_MARGIN_DB: f32 = 3.0;
+
+/// How several channels are reduced to the mono signal that gets analyzed
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MixdownPolicy {
+    /// Average all channels
+    Average = 0,
+    /// Use only the first channel
+    First = 1,
+    /// Use the channel with the highest RMS level
+    Loudest = 2,
+}
+
+/// Root-mean-square level of a signal
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// RMS level of every analysis frame, aligned with the frames of
+/// `perform_yin_analysis` for the same `frame_size` and `hop_size`
+#[wasm_bindgen]
+pub fn frame_levels(audio_data: &[f32], frame_size: usize, hop_size: usize) -> Vec<f32> {
+    if frame_size == 0 || hop_size == 0 || audio_data.len() < frame_size {
+        return Vec::new();
+    }
+    (0..=(audio_data.len() - frame_size) / hop_size)
+        .map(|i| rms(&audio_data[i * hop_size..i * hop_size + frame_size]))
+        .collect()
+}
+
+/// Reduce planar channels to mono. Channels of different lengths are
+/// truncated to the shortest one.
+pub fn mixdown(channels: &[Vec<f32>], policy: MixdownPolicy) -> Vec<f32> {
+    let Some(len) = channels.iter().map(Vec::len).min() else {
+        return Vec::new();
+    };
+
+    match policy {
+        MixdownPolicy::Average => {
+            let scale = 1.0 / channels.len() as f32;
+            (0..len)
+                .map(|i| channels.iter().map(|c| c[i]).sum::<f32>() * scale)
+                .collect()
+        }
+        MixdownPolicy::First => channels[0][..len].to_vec(),
+        MixdownPolicy::Loudest => channels
+            .iter()
+            .max_by(|a, b| rms(&a[..len]).total_cmp(&rms(&b[..len])))
+            .map(|c| c[..len].to_vec())
+            .unwrap_or_default(),
+    }
+}
+
+/// Average the channels of interleaved audio into mono, converting each
+/// sample with `to_f32`. A trailing partial frame is dropped.
+fn interleaved_mono<T: Copy>(
+    samples: &[T],
+    channels: usize,
+    to_f32: impl Fn(T) -> f32,
+) -> Vec<f32> {
+    let channels = channels.max(1);
+    let scale = 1.0 / channels as f32;
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().map(|&x| to_f32(x)).sum::<f32>() * scale)
+        .collect()
+}
+
+/// Interleaved f32 audio averaged to mono
+pub fn interleaved_to_mono(samples: &[f32], channels: usize) -> Vec<f32> {
+    interleaved_mono(samples, channels, |x| x)
+}
+
+/// Interleaved 16-bit PCM averaged to mono floats in [-1, 1)
+pub fn i16_to_mono(samples: &[i16], channels: usize) -> Vec<f32> {
+    interleaved_mono(samples, channels, |x| x as f32 / 32768.0)
+}
+
+/// Linear-interpolation resampling from `from_rate` to `to_rate`. Good
+/// enough for pitch analysis, which only looks below a few kHz; returns the
+/// input unchanged when the rates match or are invalid.
+#[wasm_bindgen]
+pub fn resample_linear(samples: &[f32], from_rate: f32, to_rate: f32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate <= 0.0 || to_rate <= 0.0 || samples.len() < 2 {
+        return samples.to_vec();
+    }
+    let step = from_rate as f64 / to_rate as f64;
+    let out_len = ((samples.len() - 1) as f64 / step).floor() as usize + 1;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let idx = (pos as usize).min(samples.len() - 2);
+            let frac = (pos - idx as f64) as f32;
+            samples[idx] + (samples[idx + 1] - samples[idx]) * frac
+        })
+        .collect()
+}
+
+/// `resample_linear` for audio arriving in chunks: the last input sample
+/// and the position between samples are carried over, so the output is
+/// continuous across chunk boundaries and across changes of the input rate
+#[derive(Clone, Debug)]
+pub struct LinearResampler {
+    from_rate: f32,
+    to_rate: f32,
+    /// Last sample of the previous chunk
+    last: Option<f32>,
+    /// Position of the next output sample in input samples, counted from
+    /// `last` (or from the chunk start when there is none)
+    position: f64,
+}
+
+impl LinearResampler {
+    pub fn new(from_rate: f32, to_rate: f32) -> Self {
+        LinearResampler {
+            from_rate,
+            to_rate,
+            last: None,
+            position: 0.0,
+        }
+    }
+
+    pub fn from_rate(&self) -> f32 {
+        self.from_rate
+    }
+
+    /// Switch to input recorded at `from_rate` from the next chunk on; the
+    /// pending position is rescaled so no time is gained or lost
+    pub fn set_from_rate(&mut self, from_rate: f32) {
+        if from_rate > 0.0 && self.from_rate > 0.0 {
+            self.position *= (from_rate / self.from_rate) as f64;
+        }
+        self.from_rate = from_rate;
+    }
+
+    /// Resample the next chunk, appending to `out`
+    pub fn process(&mut self, samples: &[f32], out: &mut Vec<f32>) {
+        if self.from_rate <= 0.0 || self.to_rate <= 0.0 {
+            return;
+        }
+        // Same rate and on the sample grid: pass through without holding
+        // back the last sample
+        if self.from_rate == self.to_rate && (self.last.is_none() || self.position == 1.0) {
+            out.extend_from_slice(samples);
+            if let Some(&last) = samples.last() {
+                self.last = Some(last);
+                self.position = 1.0;
+            }
+            return;
+        }
+        let input: Vec<f32> = self.last.iter().chain(samples).copied().collect();
+        if input.is_empty() {
+            return;
+        }
+        let step = self.from_rate as f64 / self.to_rate as f64;
+        let end = (input.len() - 1) as f64;
+        while self.position < end {
+            let idx = self.position as usize;
+            let frac = (self.position - idx as f64) as f32;
+            out.push(input[idx] + (input[idx + 1] - input[idx]) * frac);
+            self.position += step;
+        }
+        self.position -= end;
+        self.last = input.last().copied();
+    }
+
+    /// Forget the carried-over sample, for a new recording
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.position = 0.0;
+    }
+}
+
+/// Analyze mono audio recorded at `input_rate` with `config`, resampling to
+/// `config.sample_rate` first when the rates differ
+pub(crate) fn analyze_at_rate(mono: Vec<f32>, input_rate: f32, config: &YinConfig) -> Vec<f32> {
+    if input_rate > 0.0 && input_rate != config.sample_rate {
+        config.analyze(&resample_linear(&mono, input_rate, config.sample_rate))
+    } else {
+        config.analyze(&mono)
+    }
+}
+
+/// Analyze interleaved 16-bit PCM (e.g. from MediaRecorder or a decoded
+/// file) without converting it in JS: channels are averaged to mono and the
+/// audio is resampled from `input_rate` to `config.sample_rate` when they
+/// differ. Returns a flat [pitch, confidence, tau, ...] array.
+#[wasm_bindgen]
+pub fn perform_yin_analysis_i16(
+    audio_data: &[i16],
+    channels: usize,
+    input_rate: f32,
+    config: &YinConfig,
+) -> Vec<f32> {
+    analyze_at_rate(i16_to_mono(audio_data, channels), input_rate, config)
+}
+
+/// `perform_yin_analysis_i16` for interleaved f32 audio
+#[wasm_bindgen]
+pub fn perform_yin_analysis_interleaved(
+    audio_data: &[f32],
+    channels: usize,
+    input_rate: f32,
+    config: &YinConfig,
+) -> Vec<f32> {
+    analyze_at_rate(
+        interleaved_to_mono(audio_data, channels),
+        input_rate,
+        config,
+    )
+}
+
+/// Analyze a complete WAV file (e.g. a reference clip from the deck)
+/// without decoding it through WebAudio first. PCM (8-32 bit) and float
+/// data are accepted at any sample rate; channels are averaged to mono and
+/// the audio is resampled to `config.sample_rate`. Returns the same flat
+/// [pitch, confidence, tau, ...] array as `perform_yin_analysis`. Throws for
+/// malformed files and for compressed formats such as Ogg, which still have
+/// to be decoded by the browser.
+#[wasm_bindgen]
+pub fn analyze_wav_bytes(bytes: &[u8], config: &YinConfig) -> Result<Vec<f32>, JsError> {
+    let (mono, format) = decode_wav(bytes).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(analyze_at_rate(mono, format.sample_rate as f32, config))
+}
+
+/// Which channel(s) `pick_channel` decided to analyze, with the speech SNR
+/// it measured on every channel
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelPick {
+    channels: Vec<usize>,
+    snr_db: Vec<f32>,
+    dead: Vec<bool>,
+}
+
+#[wasm_bindgen]
+impl ChannelPick {
+    /// Indices of the channels that are used; more than one when mixed
+    #[wasm_bindgen(getter)]
+    pub fn channels(&self) -> Vec<usize> {
+        self.channels.clone()
+    }
+
+    /// The single channel used, undefined when several were mixed
+    #[wasm_bindgen(getter)]
+    pub fn channel(&self) -> Option<usize> {
+        match self.channels[..] {
+            [channel] => Some(channel),
+            _ => None,
+        }
+    }
+
+    /// Whether several channels are averaged
+    #[wasm_bindgen(getter)]
+    pub fn mixed(&self) -> bool {
+        self.channels.len() > 1
+    }
+
+    /// Measured speech SNR of every channel in dB
+    #[wasm_bindgen(getter)]
+    pub fn snr_db(&self) -> Vec<f32> {
+        self.snr_db.clone()
+    }
+
+    /// Number of channels that carried (almost) no signal
+    #[wasm_bindgen(getter)]
+    pub fn dead_channels(&self) -> usize {
+        self.dead.iter().filter(|&&d| d).count()
+    }
+
+    /// Whether channel `i` carried (almost) no signal
+    pub fn is_dead(&self, i: usize) -> bool {
+        self.dead.get(i).copied().unwrap_or(false)
+    }
+}
+
+/// Choose the channel with the best speech SNR, ignoring dead ones. When
+/// other live channels come within `MIX_SNR_MARGIN_DB` of the best they are
+/// mixed in. With no channels at all the pick is empty.
+pub fn pick_channel(channels: &[Vec<f32>], frame_size: usize, hop_size: usize) -> ChannelPick {
+    let estimates: Vec<_> = channels
+        .iter()
+        .map(|c| estimate_noise(c, frame_size, hop_size))
+        .collect();
+    let snr_db: Vec<f32> = estimates.iter().map(|e| e.snr_db).collect();
+    let dead: Vec<bool> = estimates
+        .iter()
+        .map(|e| e.signal_rms < DEAD_CHANNEL_RMS)
+        .collect();
+
+    let live = (0..channels.len()).filter(|&i| !dead[i]);
+    let Some(best) = live
+        .clone()
+        .max_by(|&a, &b| snr_db[a].total_cmp(&snr_db[b]))
+        .or_else(|| (!channels.is_empty()).then_some(0))
+    else {
+        return ChannelPick {
+            channels: Vec::new(),
+            snr_db,
+            dead,
+        };
+    };
+    let mut picked: Vec<usize> = live
+        .filter(|&i| snr_db[best] - snr_db[i] <= MIX_SNR_MARGIN_DB)
+        .collect();
+    if picked.is_empty() {
+        picked.push(best);
+    }
+    ChannelPick {
+        channels: picked,
+        snr_db,
+        dead,
+    }
+}
+
+/// Mono signal for a `pick_channel` decision: the picked channel, or the
+/// average of the picked channels truncated to the shortest one
+pub fn apply_pick(channels: &[Vec<f32>], pick: &ChannelPick) -> Vec<f32> {
+    let picked: Vec<Vec<f32>> = pick
+        .channels
+        .iter()
+        .filter_map(|&i| channels.get(i).cloned())
+        .collect();
+    mixdown(&picked, MixdownPolicy::Average)
+}
+
+/// Planar multi-channel audio as `AudioBuffer.getChannelData` provides it.
+/// Add each channel's Float32Array directly, then analyze with a mixdown
+/// policy, so JS never has to interleave or mix the channels itself.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct PlanarAudio {
+    channels: Vec<Vec<f32>>,
+}
+
+#[wasm_bindgen]
+impl PlanarAudio {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PlanarAudio {
+        PlanarAudio::default()
+    }
+
+    /// Append one channel's samples
+    pub fn add_channel(&mut self, samples: &[f32]) {
+        self.channels.push(samples.to_vec());
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Mono signal produced by `policy`
+    pub fn mixdown(&self, policy: MixdownPolicy) -> Vec<f32> {
+        mixdown(&self.channels, policy)
+    }
+
+    /// Mix down with `policy` and run `perform_yin_analysis` on the result
+    #[allow(clippy::too_many_arguments)]
+    pub fn analyze(
+        &self,
+        policy: MixdownPolicy,
+        sample_rate: f32,
+        frame_size: usize,
+        hop_size: usize,
+        threshold: f32,
+        min_freq: f32,
+        max_freq: f32,
+        interpolation: bool,
+    ) -> Vec<f32> {
+        perform_yin_analysis(
+            &self.mixdown(policy),
+            sample_rate,
+            frame_size,
+            hop_size,
+            threshold,
+            min_freq,
+            max_freq,
+            interpolation,
+        )
+    }
+
+    /// Decide which channel(s) to analyze from their speech SNR, e.g. to
+    /// skip the dead channel of a laptop microphone array
+    pub fn pick_channel(&self, frame_size: usize, hop_size: usize) -> ChannelPick {
+        pick_channel(&self.channels, frame_size, hop_size)
+    }
+
+    /// Mono signal for a decision from `pick_channel`
+    pub fn apply_pick(&self, pick: &ChannelPick) -> Vec<f32> {
+        apply_pick(&self.channels, pick)
+    }
+
+    /// Pick the channel(s) with `pick_channel` and analyze them with
+    /// `config`; call `pick_channel` with the same framing to report the
+    /// decision
+    pub fn analyze_best_channel(&self, config: &YinConfig) -> Vec<f32> {
+        let pick = self.pick_channel(config.frame_size, config.hop_size);
+        config.analyze(&self.apply_pick(&pick))
+    }
+}
+
+/// Audio buffer that lives in wasm linear memory, so long recordings are
+/// analyzed without copying them across the JS/wasm boundary. JS calls
+/// `alloc(len)`, writes the samples through
+/// `new Float32Array(memory.buffer, ptr, len)` and then calls `analyze`.
+/// The buffer keeps its allocation across recordings; `alloc` only grows it
+/// when a longer recording arrives.
+///
+/// Any allocation inside wasm may grow the memory and detach existing views
+/// of `memory.buffer`, so recreate the Float32Array after every `alloc` and
+/// don't keep it across other calls into the module.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct WasmAudioBuffer {
+    samples: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl WasmAudioBuffer {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmAudioBuffer {
+        WasmAudioBuffer::default()
+    }
+
+    /// Make room for `len` samples (zeroed) and return their address in
+    /// wasm memory as a byte offset
+    pub fn alloc(&mut self, len: usize) -> usize {
+        self.samples.clear();
+        self.samples.resize(len, 0.0);
+        self.samples.as_mut_ptr() as usize
+    }
+
+    /// Byte offset of the samples in wasm memory; changes only on `alloc`
+    #[wasm_bindgen(getter)]
+    pub fn ptr(&self) -> usize {
+        self.samples.as_ptr() as usize
+    }
+
+    /// Number of samples
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Samples the allocation holds before `alloc` has to grow it
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> usize {
+        self.samples.capacity()
+    }
+
+    /// Drop the samples but keep the allocation for the next recording
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Give the allocation back, e.g. after a very long recording
+    pub fn release(&mut self) {
+        self.samples = Vec::new();
+    }
+
+    /// Run `perform_yin_analysis` on the samples in place
+    #[allow(clippy::too_many_arguments)]
+    pub fn analyze(
+        &self,
+        sample_rate: f32,
+        frame_size: usize,
+        hop_size: usize,
+        threshold: f32,
+        min_freq: f32,
+        max_freq: f32,
+        interpolation: bool,
+    ) -> Vec<f32> {
+        perform_yin_analysis(
+            &self.samples,
+            sample_rate,
+            frame_size,
+            hop_size,
+            threshold,
+            min_freq,
+            max_freq,
+            interpolation,
+        )
+    }
+
+    /// Run the analysis with `config`
+    pub fn analyze_with(&self, config: &YinConfig) -> Vec<f32> {
+        config.analyze(&self.samples)
+    }
+}
+
+impl WasmAudioBuffer {
+    /// The samples, for Rust callers
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+}
@@ -0,0 +1,194 @@
+//! Sentence-level intonation features, beyond lexical tones: overall
+//! declination, the movement at the end of the sentence (question vs.
+//! statement) and phrase boundaries.
+
+use wasm_bindgen::prelude::*;
+
+use crate::fit::{voiced_coverage, FitQuality, MAX_FIT_RMS_SEMITONES};
+use crate::segments::voiced_runs;
+use crate::tones::voiced_median;
+
+/// Voiced speech at the end of the sentence inspected for the final movement
+const FINAL_SECONDS: f32 = 0.3;
+
+/// Smallest final movement (semitones) that counts as a rise or fall
+const FINAL_MOVEMENT_SEMITONES: f32 = 1.5;
+
+/// Unvoiced gaps shorter than this are consonants, not pauses
+const MIN_PAUSE_SECONDS: f32 = 0.1;
+
+/// A pause at least this long is a phrase boundary on its own
+const BOUNDARY_PAUSE_SECONDS: f32 = 0.25;
+
+/// Pitch reset (semitones) that makes a shorter pause a phrase boundary
+const BOUNDARY_RESET_SEMITONES: f32 = 2.0;
+
+/// Voiced speech either side of a pause compared for the pitch reset
+const RESET_SECONDS: f32 = 0.2;
+
+/// Pitch movement at the end of a sentence
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FinalMovement {
+    Fall = 0,
+    Level = 1,
+    Rise = 2,
+}
+
+/// Intonation features of a whole sentence
+#[wasm_bindgen]
+pub struct IntonationFeatures {
+    declination: f32,
+    declination_fit: FitQuality,
+    final_change: f32,
+    final_movement: FinalMovement,
+    boundaries: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl IntonationFeatures {
+    /// Slope (semitones per second) of a line fitted through all voiced
+    /// frames; statements typically drift down
+    #[wasm_bindgen(getter)]
+    pub fn declination(&self) -> f32 {
+        self.declination
+    }
+
+    /// How well the declination line fits the voiced frames; an unreliable
+    /// fit (fragmented or jumpy contour) makes `declination` meaningless
+    #[wasm_bindgen(getter)]
+    pub fn declination_fit(&self) -> FitQuality {
+        self.declination_fit
+    }
+
+    /// Pitch change (semitones) over the last voiced stretch of the sentence
+    #[wasm_bindgen(getter)]
+    pub fn final_change(&self) -> f32 {
+        self.final_change
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn final_movement(&self) -> FinalMovement {
+        self.final_movement
+    }
+
+    /// Phrase boundaries as a flat [time_s, pause_s, reset_semitones, ...]
+    /// array, where time is the middle of the pause and reset how much
+    /// higher the next phrase starts than the previous one ended
+    #[wasm_bindgen(getter)]
+    pub fn boundaries(&self) -> Vec<f32> {
+        self.boundaries.clone()
+    }
+}
+
+/// Least-squares line (slope, intercept) of `values` against `times`; the
+/// slope is 0 for fewer than two distinct times
+pub(crate) fn line(times: &[f32], values: &[f32]) -> (f32, f32) {
+    let n = times.len() as f32;
+    if times.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean_t = times.iter().sum::<f32>() / n;
+    let mean_v = values.iter().sum::<f32>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (t, v) in times.iter().zip(values) {
+        cov += (t - mean_t) * (v - mean_v);
+        var += (t - mean_t) * (t - mean_t);
+    }
+    let slope = if var > 0.0 { cov / var } else { 0.0 };
+    (slope, mean_v - slope * mean_t)
+}
+
+/// Least-squares slope of `values` against `times` (0 for fewer than two
+/// distinct times)
+fn slope(times: &[f32], values: &[f32]) -> f32 {
+    line(times, values).0
+}
+
+/// Mean semitone value of the voiced frames in `frames`
+fn mean_semitones(semitones: &[f32], frames: impl Iterator<Item = usize>) -> f32 {
+    let (sum, count) = frames
+        .filter(|&i| !semitones[i].is_nan())
+        .fold((0.0, 0), |(s, c), i| (s + semitones[i], c + 1));
+    if count > 0 {
+        sum / count as f32
+    } else {
+        0.0
+    }
+}
+
+/// Sentence intonation features of a Hz pitch track; `frame_period` is the
+/// hop between frames in seconds
+#[wasm_bindgen]
+pub fn analyze_intonation(pitch: &[f32], frame_period: f32) -> IntonationFeatures {
+    let frame_period = frame_period.max(f32::EPSILON);
+    let median = voiced_median(pitch);
+    // Semitones relative to the median, NaN when unvoiced
+    let semitones: Vec<f32> = pitch
+        .iter()
+        .map(|&p| {
+            if p > 0.0 && median > 0.0 {
+                12.0 * (p / median).log2()
+            } else {
+                f32::NAN
+            }
+        })
+        .collect();
+
+    let (times, values): (Vec<f32>, Vec<f32>) = semitones
+        .iter()
+        .enumerate()
+        .filter(|(_, st)| !st.is_nan())
+        .map(|(i, &st)| (i as f32 * frame_period, st))
+        .unzip();
+    let (declination, intercept) = line(&times, &values);
+    let fitted: Vec<f32> = times.iter().map(|t| intercept + declination * t).collect();
+    let declination_fit = FitQuality::from_residuals(
+        &values,
+        &fitted,
+        voiced_coverage(pitch),
+        MAX_FIT_RMS_SEMITONES,
+    );
+
+    let final_frames = ((FINAL_SECONDS / frame_period).ceil() as usize).max(2);
+    let tail = values.len().saturating_sub(final_frames);
+    let final_change =
+        slope(&times[tail..], &values[tail..]) * (times.len() - tail) as f32 * frame_period;
+    let final_movement = if final_change >= FINAL_MOVEMENT_SEMITONES {
+        FinalMovement::Rise
+    } else if final_change <= -FINAL_MOVEMENT_SEMITONES {
+        FinalMovement::Fall
+    } else {
+        FinalMovement::Level
+    };
+
+    let min_pause = (MIN_PAUSE_SECONDS / frame_period).ceil() as usize;
+    let reset_frames = ((RESET_SECONDS / frame_period).ceil() as usize).max(1);
+    let runs = voiced_runs(pitch, min_pause, 1);
+    let mut boundaries = Vec::new();
+    for pair in runs.windows(2) {
+        let ((prev_start, prev_end), (next_start, next_end)) = (pair[0], pair[1]);
+        let pause = (next_start - prev_end) as f32 * frame_period;
+        let before = mean_semitones(
+            &semitones,
+            prev_end.saturating_sub(reset_frames).max(prev_start)..prev_end,
+        );
+        let after = mean_semitones(
+            &semitones,
+            next_start..(next_start + reset_frames).min(next_end),
+        );
+        let reset = after - before;
+        if pause >= BOUNDARY_PAUSE_SECONDS || reset >= BOUNDARY_RESET_SEMITONES {
+            let middle = (prev_end + next_start) as f32 / 2.0 * frame_period;
+            boundaries.extend([middle, pause, reset]);
+        }
+    }
+
+    IntonationFeatures {
+        declination,
+        declination_fit,
+        final_change,
+        final_movement,
+        boundaries,
+    }
+}
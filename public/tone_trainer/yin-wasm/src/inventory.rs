@@ -0,0 +1,505 @@
+//! Tone systems other than Mandarin. A `ToneInventory` describes a
+//! language's tones (canonical Chao contours and scoring tolerances) and
+//! its sandhi rules; inventories are registered from JS as JSON, so a
+//! Vietnamese or Thai deck can be graded without changes to the crate:
+//!
+//! ```json
+//! {
+//!   "name": "thai",
+//!   "tones": [
+//!     { "label": "mid", "contour": [3, 3] },
+//!     { "label": "low", "contour": [2, 1], "tolerance": 1.5 }
+//!   ],
+//!   "sandhi": [{ "tone": 2, "before": "end", "contour": [2, 1, 1] }]
+//! }
+//! ```
+//!
+//! Tones are numbered from 1 in the order given. A sandhi rule adds an
+//! accepted realization of `tone` when followed by the tone `before` (a
+//! number), at the end of the phrase (`"end"`) or, without `before`, before
+//! any tone. The built-in "mandarin" inventory mirrors `tone_template` and
+//! `tone_realizations`.
+
+use std::cell::RefCell;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::json::{parse_json, push_key, push_number, push_string, JsonValue};
+use crate::rubric::{ScoringRubric, DEFAULT_TONE_TOLERANCE};
+use crate::segments::voiced_core;
+use crate::tones::{
+    coarticulate, expand_template, hz_to_chao, resample_syllable, syllable_ranges,
+    syllable_score_within, tone_template, voiced_median, SYLLABLE_POINTS,
+};
+
+/// Name of the built-in inventory
+const MANDARIN: &str = "mandarin";
+
+/// Most tones an inventory may define (Cantonese counts nine)
+const MAX_TONES: usize = 12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InventoryError {
+    Json(String),
+    /// A required field is missing or has the wrong type
+    Missing(&'static str),
+    NoTones,
+    TooManyTones(usize),
+    /// A contour that is empty or leaves the Chao scale (1-5)
+    BadContour(String),
+    BadTolerance(usize),
+    /// A sandhi rule naming a tone the inventory doesn't have
+    UnknownTone(usize),
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InventoryError::Json(err) => write!(f, "{}", err),
+            InventoryError::Missing(field) => write!(f, "tone inventory needs \"{}\"", field),
+            InventoryError::NoTones => write!(f, "tone inventory has no tones"),
+            InventoryError::TooManyTones(n) => {
+                write!(f, "tone inventory has {} tones, at most {}", n, MAX_TONES)
+            }
+            InventoryError::BadContour(what) => write!(
+                f,
+                "contour of {} must be 1 or more Chao levels between 1 and 5",
+                what
+            ),
+            InventoryError::BadTolerance(tone) => {
+                write!(f, "tolerance of tone {} must be positive", tone)
+            }
+            InventoryError::UnknownTone(tone) => {
+                write!(f, "sandhi rule refers to unknown tone {}", tone)
+            }
+        }
+    }
+}
+
+/// Context a sandhi rule applies in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Following {
+    /// Before any tone, but not at the end of the phrase
+    Any,
+    End,
+    Tone(u8),
+}
+
+impl Following {
+    fn matches(self, next_tone: Option<u8>) -> bool {
+        match (self, next_tone) {
+            (Following::Any, Some(_)) => true,
+            (Following::End, None) => true,
+            (Following::Tone(t), Some(next)) => t == next,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ToneDefinition {
+    label: String,
+    contour: Vec<f32>,
+    tolerance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SandhiRule {
+    tone: u8,
+    before: Following,
+    contour: Vec<f32>,
+}
+
+/// A language's tones and sandhi rules, see the module docs
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToneInventory {
+    name: String,
+    tones: Vec<ToneDefinition>,
+    sandhi: Vec<SandhiRule>,
+}
+
+#[wasm_bindgen]
+impl ToneInventory {
+    /// Inventory from its JSON description; throws on malformed JSON or an
+    /// invalid inventory
+    pub fn from_json(json: &str) -> Result<ToneInventory, JsError> {
+        Self::parse(json).map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// The built-in Mandarin inventory: tones 1-4 and the neutral tone as 5,
+    /// with half third, third-tone sandhi and truncated final fall
+    pub fn mandarin() -> ToneInventory {
+        let labels = ["high", "rising", "dipping", "falling", "neutral"];
+        let tones = labels
+            .iter()
+            .zip(1..)
+            .map(|(label, tone)| ToneDefinition {
+                label: label.to_string(),
+                contour: tone_template(tone).to_vec(),
+                tolerance: DEFAULT_TONE_TOLERANCE,
+            })
+            .collect();
+        let mut sandhi = vec![SandhiRule {
+            tone: 3,
+            before: Following::Tone(3),
+            contour: vec![3.0, 5.0],
+        }];
+        for next in [1, 2, 4, 5] {
+            sandhi.push(SandhiRule {
+                tone: 3,
+                before: Following::Tone(next),
+                contour: vec![2.0, 1.0, 1.0],
+            });
+        }
+        sandhi.push(SandhiRule {
+            tone: 4,
+            before: Following::End,
+            contour: vec![5.0, 3.0],
+        });
+        ToneInventory {
+            name: MANDARIN.to_string(),
+            tones,
+            sandhi,
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Number of tones; tones are numbered 1 to this
+    #[wasm_bindgen(getter)]
+    pub fn tone_count(&self) -> usize {
+        self.tones.len()
+    }
+
+    /// Label of `tone`, empty when unknown
+    pub fn label(&self, tone: u8) -> String {
+        self.tone(tone).map_or(String::new(), |t| t.label.clone())
+    }
+
+    /// Canonical Chao contour of `tone`, empty when unknown
+    pub fn contour(&self, tone: u8) -> Vec<f32> {
+        self.tone(tone).map_or(Vec::new(), |t| t.contour.clone())
+    }
+
+    /// Scoring tolerance (Chao steps) of `tone`
+    pub fn tolerance(&self, tone: u8) -> f32 {
+        self.tone(tone)
+            .map_or(DEFAULT_TONE_TOLERANCE, |t| t.tolerance)
+    }
+
+    /// The inventory as JSON, in the format `from_json` reads
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        push_key(&mut out, "name", true);
+        push_string(&mut out, &self.name);
+        push_key(&mut out, "tones", false);
+        out.push('[');
+        for (i, tone) in self.tones.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('{');
+            push_key(&mut out, "label", true);
+            push_string(&mut out, &tone.label);
+            push_key(&mut out, "contour", false);
+            push_levels(&mut out, &tone.contour);
+            push_key(&mut out, "tolerance", false);
+            push_number(&mut out, tone.tolerance);
+            out.push('}');
+        }
+        out.push(']');
+        push_key(&mut out, "sandhi", false);
+        out.push('[');
+        for (i, rule) in self.sandhi.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('{');
+            push_key(&mut out, "tone", true);
+            push_number(&mut out, rule.tone as f32);
+            match rule.before {
+                Following::Any => {}
+                Following::End => {
+                    push_key(&mut out, "before", false);
+                    push_string(&mut out, "end");
+                }
+                Following::Tone(next) => {
+                    push_key(&mut out, "before", false);
+                    push_number(&mut out, next as f32);
+                }
+            }
+            push_key(&mut out, "contour", false);
+            push_levels(&mut out, &rule.contour);
+            out.push('}');
+        }
+        out.push(']');
+        out.push('}');
+        out
+    }
+
+    /// Score a Hz pitch track against a sequence of this inventory's tones,
+    /// as `score_expected_contour` does for Mandarin: voiced frames are
+    /// split evenly between the syllables, each syllable keeps its best
+    /// accepted realization in context, and its score uses the tone's own
+    /// tolerance. Returns [score1, realization1, ...] where realization is
+    /// 0 for the canonical contour and k for the k-th sandhi rule (counted
+    /// from 1 in the inventory's order); unknown tones score 0.
+    pub fn score(
+        &self,
+        pitch: &[f32],
+        tones: &[u8],
+        auto_crop: bool,
+        rubric: &ScoringRubric,
+    ) -> Vec<f32> {
+        let pitch = if auto_crop { voiced_core(pitch) } else { pitch };
+        let voicing = rubric.voicing_score(pitch);
+        let chao = hz_to_chao(pitch, voiced_median(pitch));
+        let mut results = Vec::with_capacity(tones.len() * 2);
+
+        let ranges = syllable_ranges(chao.len(), tones.len());
+        for (i, (&tone, &(start, end))) in tones.iter().zip(&ranges).enumerate() {
+            let Some(definition) = self.tone(tone).filter(|_| start < end) else {
+                results.extend([0.0, 0.0]);
+                continue;
+            };
+            let next_tone = tones.get(i + 1).copied();
+            let previous_end = i
+                .checked_sub(1)
+                .and_then(|p| self.connected_end_level(tones[p], Some(tone)));
+            let next_start = next_tone.and_then(|next| self.tone(next).map(|t| t.contour[0]));
+
+            let observed = resample_syllable(&chao[start..end]);
+            let mut best = (f32::NEG_INFINITY, 0);
+            for (realization, contour) in self.realizations(tone, next_tone) {
+                let mut expected = expand_template(contour, SYLLABLE_POINTS);
+                coarticulate(&mut expected, previous_end, next_start);
+                let score = syllable_score_within(
+                    &observed,
+                    &expected,
+                    definition.tolerance,
+                    rubric,
+                    voicing,
+                );
+                if score > best.0 {
+                    best = (score, realization);
+                }
+            }
+            results.extend([best.0, best.1 as f32]);
+        }
+        results
+    }
+}
+
+impl ToneInventory {
+    fn tone(&self, tone: u8) -> Option<&ToneDefinition> {
+        (tone as usize)
+            .checked_sub(1)
+            .and_then(|i| self.tones.get(i))
+    }
+
+    /// Accepted realizations of `tone` before `next_tone`: (0, canonical
+    /// contour) first, then (k, contour) for every matching k-th sandhi rule
+    fn realizations(&self, tone: u8, next_tone: Option<u8>) -> Vec<(usize, &[f32])> {
+        let mut out = Vec::new();
+        if let Some(definition) = self.tone(tone) {
+            out.push((0, definition.contour.as_slice()));
+        }
+        for (k, rule) in self.sandhi.iter().enumerate() {
+            if rule.tone == tone && rule.before.matches(next_tone) {
+                out.push((k + 1, rule.contour.as_slice()));
+            }
+        }
+        out
+    }
+
+    /// Level `tone` ends on before `next_tone`, from its last matching
+    /// realization (see `connected_end_level` for Mandarin)
+    fn connected_end_level(&self, tone: u8, next_tone: Option<u8>) -> Option<f32> {
+        let realizations = self.realizations(tone, next_tone);
+        let (_, contour) = realizations.last()?;
+        contour.last().copied()
+    }
+
+    /// Parse and validate a JSON description
+    pub fn parse(json: &str) -> Result<Self, InventoryError> {
+        let root = parse_json(json).map_err(InventoryError::Json)?;
+        let name = root
+            .get("name")
+            .and_then(JsonValue::as_str)
+            .filter(|name| !name.is_empty())
+            .ok_or(InventoryError::Missing("name"))?
+            .to_string();
+        let tone_values = root
+            .get("tones")
+            .and_then(JsonValue::as_array)
+            .ok_or(InventoryError::Missing("tones"))?;
+        if tone_values.is_empty() {
+            return Err(InventoryError::NoTones);
+        }
+        if tone_values.len() > MAX_TONES {
+            return Err(InventoryError::TooManyTones(tone_values.len()));
+        }
+
+        let mut tones = Vec::new();
+        for (i, value) in tone_values.iter().enumerate() {
+            let number = i + 1;
+            let contour = levels(value.get("contour"))
+                .ok_or_else(|| InventoryError::BadContour(format!("tone {}", number)))?;
+            let tolerance = match value.get("tolerance") {
+                None => DEFAULT_TONE_TOLERANCE,
+                Some(t) => t
+                    .as_f64()
+                    .map(|t| t as f32)
+                    .filter(|&t| t > 0.0 && t.is_finite())
+                    .ok_or(InventoryError::BadTolerance(number))?,
+            };
+            let label = value
+                .get("label")
+                .and_then(JsonValue::as_str)
+                .map_or_else(|| number.to_string(), str::to_string);
+            tones.push(ToneDefinition {
+                label,
+                contour,
+                tolerance,
+            });
+        }
+
+        let tone_number = |value: Option<&JsonValue>| -> Result<u8, InventoryError> {
+            let n = value
+                .and_then(JsonValue::as_f64)
+                .ok_or(InventoryError::Missing("sandhi[].tone"))?;
+            if n.fract() != 0.0 || n < 1.0 || n as usize > tones.len() {
+                return Err(InventoryError::UnknownTone(n.max(0.0) as usize));
+            }
+            Ok(n as u8)
+        };
+        let mut sandhi = Vec::new();
+        let rules = match root.get("sandhi") {
+            None => &[][..],
+            Some(rules) => rules.as_array().ok_or(InventoryError::Missing("sandhi"))?,
+        };
+        for (i, rule) in rules.iter().enumerate() {
+            let tone = tone_number(rule.get("tone"))?;
+            let before = match rule.get("before") {
+                None => Following::Any,
+                Some(JsonValue::String(s)) if s == "end" => Following::End,
+                Some(value) => Following::Tone(tone_number(Some(value))?),
+            };
+            let contour = levels(rule.get("contour"))
+                .ok_or_else(|| InventoryError::BadContour(format!("sandhi rule {}", i + 1)))?;
+            sandhi.push(SandhiRule {
+                tone,
+                before,
+                contour,
+            });
+        }
+
+        Ok(ToneInventory {
+            name,
+            tones,
+            sandhi,
+        })
+    }
+}
+
+/// Chao levels of a JSON array, None unless it is a non-empty array of
+/// numbers within 1-5
+fn levels(value: Option<&JsonValue>) -> Option<Vec<f32>> {
+    let items = value?.as_array()?;
+    let levels: Option<Vec<f32>> = items.iter().map(|v| v.as_f64().map(|l| l as f32)).collect();
+    levels.filter(|l| !l.is_empty() && l.iter().all(|&x| (1.0..=5.0).contains(&x)))
+}
+
+fn push_levels(out: &mut String, levels: &[f32]) {
+    out.push('[');
+    for (i, &level) in levels.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        push_number(out, level);
+    }
+    out.push(']');
+}
+
+thread_local! {
+    /// Inventories registered from JS, in registration order
+    static INVENTORIES: RefCell<Vec<ToneInventory>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Register the inventory described by `json` (see `ToneInventory`),
+/// replacing one of the same name; returns its name. Registering
+/// "mandarin" overrides the built-in one.
+#[wasm_bindgen]
+pub fn register_tone_inventory(json: &str) -> Result<String, JsError> {
+    let inventory = ToneInventory::from_json(json)?;
+    let name = inventory.name.clone();
+    INVENTORIES.with(|inventories| {
+        let mut inventories = inventories.borrow_mut();
+        match inventories.iter_mut().find(|i| i.name == name) {
+            Some(stored) => *stored = inventory,
+            None => inventories.push(inventory),
+        }
+    });
+    Ok(name)
+}
+
+/// Copy of the inventory called `name`, including the built-in "mandarin"
+#[wasm_bindgen]
+pub fn tone_inventory(name: &str) -> Option<ToneInventory> {
+    INVENTORIES
+        .with(|inventories| {
+            inventories
+                .borrow()
+                .iter()
+                .find(|i| i.name == name)
+                .cloned()
+        })
+        .or_else(|| (name == MANDARIN).then(ToneInventory::mandarin))
+}
+
+/// Forget a registered inventory; returns whether there was one. The
+/// built-in "mandarin" can't be removed, only overridden.
+#[wasm_bindgen]
+pub fn remove_tone_inventory(name: &str) -> bool {
+    INVENTORIES.with(|inventories| {
+        let mut inventories = inventories.borrow_mut();
+        let before = inventories.len();
+        inventories.retain(|i| i.name != name);
+        inventories.len() < before
+    })
+}
+
+/// Names of the available inventories: "mandarin" first, then the
+/// registered ones in registration order
+#[wasm_bindgen]
+pub fn tone_inventory_names() -> Vec<String> {
+    let mut names = vec![MANDARIN.to_string()];
+    INVENTORIES.with(|inventories| {
+        for inventory in inventories.borrow().iter() {
+            if inventory.name != MANDARIN {
+                names.push(inventory.name.clone());
+            }
+        }
+    });
+    names
+}
+
+/// `ToneInventory::score` with the inventory called `inventory`; throws
+/// when there is none
+#[wasm_bindgen]
+pub fn score_with_inventory(
+    pitch: &[f32],
+    tones: &[u8],
+    inventory: &str,
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Result<Vec<f32>, JsError> {
+    let inventory = tone_inventory(inventory)
+        .ok_or_else(|| JsError::new(&format!("no tone inventory \"{}\"", inventory)))?;
+    Ok(inventory.score(pitch, tones, auto_crop, rubric))
+}
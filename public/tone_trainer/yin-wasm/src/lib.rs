@@ -1,5 +1,84 @@
 use wasm_bindgen::prelude::*;
 
+use logging::{log_debug, log_trace, log_warn};
+
+pub mod agc;
+pub mod analysis;
+pub mod archive;
+pub mod aubio;
+pub mod bench;
+mod binary;
+pub mod cache;
+pub mod classify;
+pub mod config;
+pub mod contour;
+pub mod cross_validation;
+pub mod dataset;
+pub mod drift;
+pub mod dtw;
+pub mod echo;
+pub mod evaluate;
+pub mod feedback;
+mod fft;
+pub mod fingerprint;
+pub mod fit;
+pub mod fixtures;
+pub mod game;
+pub mod gate;
+pub mod governor;
+pub mod hash;
+pub mod hpss;
+pub mod input;
+pub mod intonation;
+pub mod inventory;
+mod json;
+pub mod logging;
+pub mod ltas;
+pub mod metrics;
+pub mod migrate;
+pub mod mix;
+pub mod morph;
+#[cfg(feature = "native")]
+pub mod native;
+pub mod noise;
+pub mod pager;
+pub mod pairs;
+pub mod panic;
+pub mod pca;
+pub mod pinyin;
+pub mod postprocess;
+pub mod praat;
+pub mod profile;
+pub mod progressive;
+pub mod prominence;
+pub mod pyin;
+pub mod quantize;
+pub mod rate;
+pub mod render;
+pub mod replay;
+pub mod retention;
+pub mod rubric;
+pub mod scale;
+pub mod segments;
+pub mod session;
+pub mod shadowing;
+pub mod simd;
+pub mod spectral;
+pub mod spectrogram;
+pub mod stream;
+pub mod summary;
+pub mod syllables;
+pub mod templates;
+pub mod threads;
+pub mod tones;
+pub mod trace;
+pub mod twm;
+pub mod typed;
+pub mod voicing;
+pub mod warnings;
+pub mod wav;
+pub mod window;
+
 #[wasm_bindgen]
 pub struct YinResult {
     pitch: f64,
@@ -25,9 +104,17 @@ impl YinResult {
     }
 }
 
-/// Compute the YIN difference function
+/// Frames shorter than this use the direct difference function; below it
+/// the FFT setup costs more than it saves
+const FFT_DIFFERENCE_MIN_SIZE: usize = 512;
+
+/// Compute the YIN difference function directly, O(n²) per frame, with
+/// `simd128` when the build has it (see `simd`)
 #[inline]
-fn yin_difference_function(buffer: &[f32]) -> Vec<f32> {
+fn yin_difference_function_naive(buffer: &[f32]) -> Vec<f32> {
+    if let Some(difference_function) = simd::difference_function(buffer) {
+        return difference_function;
+    }
     let buffer_size = buffer.len();
     let half_size = buffer_size / 2;
     let mut difference_function = vec![0.0; half_size];
@@ -45,27 +132,124 @@ fn yin_difference_function(buffer: &[f32]) -> Vec<f32> {
     difference_function
 }
 
+/// Compute the YIN difference function in O(n log n) by expanding
+/// d_t(τ) = Σx_j² + Σx_{j+τ}² - 2Σx_j·x_{j+τ}: the energies come from
+/// prefix sums and the cross term is a correlation computed with the FFT
+fn yin_difference_function_fft(buffer: &[f32]) -> Vec<f32> {
+    let half_size = buffer.len() / 2;
+    let n_fft = buffer.len().next_power_of_two();
+
+    // Correlation of the first half with the whole frame. Lags stay below
+    // half_size, so j + τ < buffer.len() <= n_fft and nothing wraps around.
+    let mut a_re = vec![0.0; n_fft];
+    let mut a_im = vec![0.0; n_fft];
+    let mut b_re = vec![0.0; n_fft];
+    let mut b_im = vec![0.0; n_fft];
+    a_re[..half_size].copy_from_slice(&buffer[..half_size]);
+    b_re[..buffer.len()].copy_from_slice(buffer);
+    fft::fft_in_place(&mut a_re, &mut a_im);
+    fft::fft_in_place(&mut b_re, &mut b_im);
+    for k in 0..n_fft {
+        // conj(A) * B
+        let (ar, ai, br, bi) = (a_re[k], a_im[k], b_re[k], b_im[k]);
+        b_re[k] = ar * br + ai * bi;
+        b_im[k] = ar * bi - ai * br;
+    }
+    fft::ifft_in_place(&mut b_re, &mut b_im);
+
+    let mut energy = vec![0.0f64; buffer.len() + 1];
+    for (i, &x) in buffer.iter().enumerate() {
+        energy[i + 1] = energy[i] + x as f64 * x as f64;
+    }
+    let head = energy[half_size];
+    (0..half_size)
+        .map(|tau| {
+            let window = energy[tau + half_size] - energy[tau];
+            ((head + window) as f32 - 2.0 * b_re[tau]).max(0.0)
+        })
+        .collect()
+}
+
+/// Compute the YIN difference function
+#[inline]
+fn yin_difference_function(buffer: &[f32]) -> Vec<f32> {
+    if buffer.len() < FFT_DIFFERENCE_MIN_SIZE {
+        yin_difference_function_naive(buffer)
+    } else {
+        yin_difference_function_fft(buffer)
+    }
+}
+
+/// How the running sum of the CMNDF is accumulated
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CmndfSummation {
+    /// Plain f32 sum; fastest, loses precision over very long frames
+    #[default]
+    Plain = 0,
+    /// Kahan-compensated f32 sum, accurate to about one rounding error
+    /// regardless of frame length
+    Compensated = 1,
+}
+
+impl CmndfSummation {
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(CmndfSummation::Plain),
+            1 => Some(CmndfSummation::Compensated),
+            _ => None,
+        }
+    }
+}
+
 /// Compute cumulative mean normalized difference
 #[inline]
 fn yin_cumulative_mean_normalized_difference(difference_function: &[f32]) -> Vec<f32> {
+    yin_cmndf_with(difference_function, CmndfSummation::Plain)
+}
+
+/// Compute cumulative mean normalized difference with the running sum
+/// accumulated as `summation` says
+fn yin_cmndf_with(difference_function: &[f32], summation: CmndfSummation) -> Vec<f32> {
     let len = difference_function.len();
     let mut cmndf = vec![0.0; len];
     cmndf[0] = 1.0;
 
     let mut running_sum = 0.0;
+    // Low-order bits lost by the last addition (Kahan summation)
+    let mut compensation = 0.0;
     for tau in 1..len {
-        running_sum += difference_function[tau];
+        match summation {
+            CmndfSummation::Plain => running_sum += difference_function[tau],
+            CmndfSummation::Compensated => {
+                let y = difference_function[tau] - compensation;
+                let t = running_sum + y;
+                compensation = (t - running_sum) - y;
+                running_sum = t;
+            }
+        }
         cmndf[tau] = difference_function[tau] / (running_sum / tau as f32);
     }
 
     cmndf
 }
 
+/// First lag the threshold search looks at: the period of `max_freq` (at
+/// least 2), so lags too short to be a plausible pitch are never picked
+#[wasm_bindgen]
+pub fn tau_search_start(sample_rate: f32, max_freq: f32) -> usize {
+    if sample_rate > 0.0 && max_freq > 0.0 {
+        ((sample_rate / max_freq).floor() as usize).max(2)
+    } else {
+        2
+    }
+}
+
 /// Find the absolute threshold
 #[inline]
-fn yin_absolute_threshold(cmndf: &[f32], threshold: f32) -> i32 {
+fn yin_absolute_threshold(cmndf: &[f32], threshold: f32, tau_start: usize) -> i32 {
     // Step 3: Absolute threshold - find first minimum below threshold
-    let mut tau = 2;
+    let mut tau = tau_start.max(2);
     while tau < cmndf.len() {
         if cmndf[tau] < threshold {
             // Check if this is a local minimum
@@ -79,6 +263,14 @@ fn yin_absolute_threshold(cmndf: &[f32], threshold: f32) -> i32 {
     -1 // No period found
 }
 
+/// Local minima of the CMNDF within [tau_min, tau_max], in increasing tau order
+fn yin_local_minima(cmndf: &[f32], tau_min: usize, tau_max: usize) -> Vec<usize> {
+    let last = tau_max.min(cmndf.len().saturating_sub(2));
+    (tau_min.max(1)..=last)
+        .filter(|&tau| cmndf[tau] <= cmndf[tau - 1] && cmndf[tau] < cmndf[tau + 1])
+        .collect()
+}
+
 /// Parabolic interpolation for better accuracy
 #[inline]
 fn yin_parabolic_interpolation(cmndf: &[f32], tau_estimate: i32) -> f32 {
@@ -95,9 +287,143 @@ fn yin_parabolic_interpolation(cmndf: &[f32], tau_estimate: i32) -> f32 {
     tau_estimate as f32 + (s2 - s0) / (2.0 * (2.0 * s1 - s2 - s0))
 }
 
+/// Per-call parameters for analyzing a single frame
+#[derive(Clone, Copy, Debug)]
+struct YinParams {
+    sample_rate: f32,
+    threshold: f32,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+    /// First lag of the threshold search
+    tau_start: usize,
+    summation: CmndfSummation,
+    window: window::WindowFunction,
+}
+
+impl YinParams {
+    /// Params with the threshold search starting at the period of `max_freq`
+    fn new(
+        sample_rate: f32,
+        threshold: f32,
+        min_freq: f32,
+        max_freq: f32,
+        interpolation: bool,
+    ) -> Self {
+        YinParams {
+            sample_rate,
+            threshold,
+            min_freq,
+            max_freq,
+            interpolation,
+            tau_start: tau_search_start(sample_rate, max_freq),
+            summation: CmndfSummation::Plain,
+            window: window::WindowFunction::Rectangular,
+        }
+    }
+}
+
+/// Run the YIN steps on one frame, returning (pitch, confidence, tau).
+/// When `trace` is given, the decisions taken along the way are recorded.
+fn yin_analyze_frame(
+    frame: &[f32],
+    params: &YinParams,
+    mut trace: Option<&mut trace::FrameTrace>,
+) -> (f32, f32, i32) {
+    let frame = window::apply_window(params.window, frame);
+
+    // Step 1: Difference function
+    let difference_function = yin_difference_function(&frame);
+
+    // Step 2: Cumulative mean normalized difference function
+    let cmndf = yin_cmndf_with(&difference_function, params.summation);
+
+    // Step 3: Absolute threshold
+    let tau_estimate = yin_absolute_threshold(&cmndf, params.threshold, params.tau_start);
+
+    if let Some(trace) = trace.as_deref_mut() {
+        trace.record_candidates(
+            &cmndf,
+            params.threshold,
+            params.sample_rate,
+            params.tau_start,
+        );
+    }
+
+    let (pitch, confidence) = if tau_estimate > 0 {
+        // Step 4: Parabolic interpolation (if enabled)
+        let better_tau = if params.interpolation {
+            yin_parabolic_interpolation(&cmndf, tau_estimate)
+        } else {
+            tau_estimate as f32
+        };
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.chosen_tau = Some(tau_estimate as usize);
+            if better_tau != tau_estimate as f32 {
+                trace.add_correction("parabolic_interpolation", better_tau - tau_estimate as f32);
+            }
+        }
+
+        // Convert tau to frequency
+        let freq = params.sample_rate / better_tau;
+
+        // Confidence is inverse of CMNDF value at the estimated tau
+        let conf = 1.0 - cmndf[tau_estimate as usize];
+
+        // Filter out unrealistic pitches
+        if freq >= params.min_freq && freq <= params.max_freq {
+            (freq, conf)
+        } else {
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.add_filter(if freq < params.min_freq {
+                    "below_min_freq"
+                } else {
+                    "above_max_freq"
+                });
+            }
+            (0.0, 0.0)
+        }
+    } else {
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.add_filter("no_threshold_crossing");
+        }
+        (0.0, 0.0)
+    };
+
+    if let Some(trace) = trace {
+        trace.pitch = pitch;
+        trace.confidence = confidence;
+    }
+
+    (pitch, confidence, tau_estimate)
+}
+
 /// Perform YIN analysis on audio buffer
 /// Returns a flat array of results: [pitch1, confidence1, tau1, pitch2, confidence2, tau2, ...]
+/// (see `YinAnalysis` for typed accessors over the same data)
+///
+/// ```
+/// use yin_wasm::fixtures::fixture;
+/// use yin_wasm::perform_yin_analysis;
+///
+/// let voice = fixture("voice_110").unwrap();
+/// let config = voice.config();
+/// let results = perform_yin_analysis(
+///     &voice.audio(),
+///     config.sample_rate,
+///     config.frame_size,
+///     config.hop_size,
+///     config.threshold,
+///     config.min_freq,
+///     config.max_freq,
+///     config.interpolation,
+/// );
+/// let frames = (voice.audio().len() - config.frame_size) / config.hop_size + 1;
+/// assert_eq!(results.len(), frames * 3);
+/// assert!((results[0] - 110.0).abs() < 1.0);
+/// ```
 #[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
 pub fn perform_yin_analysis(
     audio_data: &[f32],
     sample_rate: f32,
@@ -107,63 +433,128 @@ pub fn perform_yin_analysis(
     min_freq: f32,
     max_freq: f32,
     interpolation: bool,
+) -> Vec<f32> {
+    let params = YinParams::new(sample_rate, threshold, min_freq, max_freq, interpolation);
+    analyze_with_params(audio_data, frame_size, hop_size, &params)
+}
+
+/// `perform_yin_analysis` with all per-frame settings in `params`
+fn analyze_with_params(
+    audio_data: &[f32],
+    frame_size: usize,
+    hop_size: usize,
+    params: &YinParams,
 ) -> Vec<f32> {
     let mut results = Vec::new();
 
     let audio_len = audio_data.len();
     if audio_len < frame_size {
+        log_warn!(
+            "audio shorter than one frame ({} < {} samples)",
+            audio_len,
+            frame_size
+        );
+        return results;
+    }
+    if let Some(err) = config::framing_error(frame_size, hop_size) {
+        log_warn!("{}", err);
+        return results;
+    }
+    if let Some(err) = config::workload_error(audio_len, params.sample_rate, frame_size, hop_size) {
+        log_warn!("{}", err);
         return results;
     }
 
     let num_frames = (audio_len - frame_size) / hop_size + 1;
     results.reserve(num_frames * 3); // pitch, confidence, tau for each frame
+    log_debug!(
+        "analyzing {} frames (frame {}, hop {}, {} Hz)",
+        num_frames,
+        frame_size,
+        hop_size,
+        params.sample_rate
+    );
 
-    let mut i = 0;
-    while i + frame_size <= audio_len {
-        let frame = &audio_data[i..i + frame_size];
+    panic::set_context(panic::AnalysisContext {
+        sample_rate: params.sample_rate,
+        frame_size,
+        hop_size,
+        threshold: params.threshold,
+        min_freq: params.min_freq,
+        max_freq: params.max_freq,
+        audio_len,
+    });
 
-        // Step 1: Difference function
-        let difference_function = yin_difference_function(frame);
-
-        // Step 2: Cumulative mean normalized difference function
-        let cmndf = yin_cumulative_mean_normalized_difference(&difference_function);
-
-        // Step 3: Absolute threshold
-        let tau_estimate = yin_absolute_threshold(&cmndf, threshold);
-
-        let (pitch, confidence) = if tau_estimate > 0 {
-            // Step 4: Parabolic interpolation (if enabled)
-            let better_tau = if interpolation {
-                yin_parabolic_interpolation(&cmndf, tau_estimate)
-            } else {
-                tau_estimate as f32
-            };
+    if let Some(frames) =
+        threads::analyze_frames(audio_data, num_frames, frame_size, hop_size, params)
+    {
+        for (pitch, confidence, tau_estimate) in frames {
+            results.extend_from_slice(&[pitch, confidence, tau_estimate as f32]);
+        }
+    } else {
+        for (k, frame) in audio_frames(audio_data, frame_size, hop_size).enumerate() {
+            panic::set_frame(k);
+            let (pitch, confidence, tau_estimate) = yin_analyze_frame(frame, params, None);
+            log_trace!(
+                "frame {}: pitch {:.1} Hz, confidence {:.3}, tau {}",
+                k,
+                pitch,
+                confidence,
+                tau_estimate
+            );
 
-            // Convert tau to frequency
-            let freq = sample_rate / better_tau;
+            // Store results as flat array: pitch, confidence, tau
+            results.push(pitch);
+            results.push(confidence);
+            results.push(tau_estimate as f32);
+        }
+    }
+    panic::clear_context();
 
-            // Confidence is inverse of CMNDF value at the estimated tau
-            let conf = 1.0 - cmndf[tau_estimate as usize];
+    let frames = results.len() / 3;
+    let voiced = results.chunks(3).filter(|f| f[0] > 0.0).count();
+    metrics::record_analysis(frames, voiced);
 
-            // Filter out unrealistic pitches
-            if freq >= min_freq && freq <= max_freq {
-                (freq, conf)
-            } else {
-                (0.0, 0.0)
-            }
-        } else {
-            (0.0, 0.0)
-        };
-
-        // Store results as flat array: pitch, confidence, tau
-        results.push(pitch);
-        results.push(confidence);
-        results.push(tau_estimate as f32);
+    results
+}
 
-        i += hop_size;
-    }
+/// Perform YIN analysis on a Float64Array buffer (e.g. from OfflineAudioContext
+/// processing or scientific tooling), converting to f32 internally.
+/// Returns the same flat [pitch, confidence, tau, ...] layout as `perform_yin_analysis`.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn perform_yin_analysis_f64(
+    audio_data: &[f64],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    threshold: f32,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+) -> Vec<f32> {
+    let samples: Vec<f32> = audio_data.iter().map(|&x| x as f32).collect();
+    perform_yin_analysis(
+        &samples,
+        sample_rate,
+        frame_size,
+        hop_size,
+        threshold,
+        min_freq,
+        max_freq,
+        interpolation,
+    )
+}
 
-    results
+/// The frames `get_frame_count` counts: `frame_size` samples every
+/// `hop_size` (which must not be 0)
+pub(crate) fn audio_frames(
+    audio_data: &[f32],
+    frame_size: usize,
+    hop_size: usize,
+) -> impl Iterator<Item = &[f32]> {
+    (0..get_frame_count(audio_data.len(), frame_size, hop_size))
+        .map(move |k| &audio_data[k * hop_size..k * hop_size + frame_size])
 }
 
 /// Get the number of frames that will be analyzed
@@ -174,3 +565,45 @@ pub fn get_frame_count(audio_len: usize, frame_size: usize, hop_size: usize) ->
     }
     (audio_len - frame_size) / hop_size + 1
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Largest relative deviation of the f32 CMNDF from one accumulated in
+    /// f64 over the same difference function
+    fn cmndf_error(difference_function: &[f32], summation: CmndfSummation) -> f64 {
+        let mut running_sum = 0.0f64;
+        yin_cmndf_with(difference_function, summation)
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(tau, &value)| {
+                running_sum += difference_function[tau] as f64;
+                let exact = difference_function[tau] as f64 / (running_sum / tau as f64);
+                (value as f64 - exact).abs() / exact.abs().max(f64::EPSILON)
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn compensated_cmndf_sum_is_more_accurate_on_long_frames() {
+        let mut seed = 0x2545_f491_u32;
+        let frame: Vec<f32> = (0..16384)
+            .map(|i| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let noise = seed as f32 / u32::MAX as f32 - 0.5;
+                let phase = i as f32 * 2.0 * std::f32::consts::PI * 220.0 / 44100.0;
+                phase.sin() + 0.5 * (2.0 * phase).sin() + 0.05 * noise
+            })
+            .collect();
+        let difference_function = yin_difference_function(&frame);
+
+        let plain = cmndf_error(&difference_function, CmndfSummation::Plain);
+        let compensated = cmndf_error(&difference_function, CmndfSummation::Compensated);
+        assert!(compensated < plain, "{compensated} >= {plain}");
+        assert!(compensated < 1e-6, "{compensated}");
+    }
+}
@@ -0,0 +1,101 @@
+//! Leveled logging to the browser console, controllable at runtime.
+//!
+//! With the `logging` feature the crate's `log` calls are routed to
+//! `console.warn` / `console.log` / `console.debug`; without it the macros
+//! compile to nothing and `set_log_level` only validates its argument.
+
+use wasm_bindgen::prelude::*;
+
+/// Log at warn level (no-op without the `logging` feature)
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "logging")]
+        log::warn!($($arg)*);
+    };
+}
+
+/// Log at debug level (no-op without the `logging` feature)
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "logging")]
+        log::debug!($($arg)*);
+    };
+}
+
+/// Log at trace level (no-op without the `logging` feature)
+macro_rules! log_trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "logging")]
+        log::trace!($($arg)*);
+    };
+}
+
+pub(crate) use {log_debug, log_trace, log_warn};
+
+#[cfg(all(feature = "logging", target_arch = "wasm32"))]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn console_warn(msg: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = log)]
+    fn console_log(msg: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = debug)]
+    fn console_debug(msg: &str);
+}
+
+#[cfg(feature = "logging")]
+struct ConsoleLogger;
+
+#[cfg(feature = "logging")]
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = format!("[yin-wasm {}] {}", record.level(), record.args());
+
+        #[cfg(target_arch = "wasm32")]
+        match record.level() {
+            log::Level::Error | log::Level::Warn => console_warn(&message),
+            log::Level::Info => console_log(&message),
+            log::Level::Debug | log::Level::Trace => console_debug(&message),
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        eprintln!("{}", message);
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "logging")]
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+/// Set the log level: "off", "warn", "debug" or "trace".
+/// Installs the console logger on first use.
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<(), JsError> {
+    #[cfg(feature = "logging")]
+    let filter = match level {
+        "off" => log::LevelFilter::Off,
+        "warn" => log::LevelFilter::Warn,
+        "debug" => log::LevelFilter::Debug,
+        "trace" => log::LevelFilter::Trace,
+        _ => return Err(JsError::new(&format!("unknown log level: {}", level))),
+    };
+    #[cfg(not(feature = "logging"))]
+    if !matches!(level, "off" | "warn" | "debug" | "trace") {
+        return Err(JsError::new(&format!("unknown log level: {}", level)));
+    }
+
+    #[cfg(feature = "logging")]
+    {
+        // Fails harmlessly if the logger is already installed
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(filter);
+    }
+    Ok(())
+}
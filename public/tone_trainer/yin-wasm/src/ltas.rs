@@ -0,0 +1,192 @@
+//! Long-term average spectrum (LTAS) of a recording, for checking the voice
+//! setup: a muffled microphone shows up as a steep high-frequency tilt, a
+//! low-pass codec (e.g. a Bluetooth headset) as a hard bandwidth limit.
+
+use wasm_bindgen::prelude::*;
+
+use crate::fft::magnitude_spectrum;
+use crate::input::rms;
+use crate::noise::estimate_noise;
+
+/// Level above the noise floor (dB) a frame needs to count towards the LTAS
+const SPEECH_MARGIN_DB: f32 = 6.0;
+
+/// Lowest power considered when computing decibels
+const POWER_FLOOR: f32 = 1e-20;
+
+/// Bandwidth is the highest frequency whose level is within this many dB
+/// of the spectrum's peak
+const BANDWIDTH_DROP_DB: f32 = 50.0;
+
+/// Band (Hz) taken as the speech fundamental and first formant region
+const LOW_BAND_HZ: (f32, f32) = (100.0, 1000.0);
+
+/// Band (Hz) that carries consonant and voice-quality detail
+const HIGH_BAND_HZ: (f32, f32) = (2000.0, 4000.0);
+
+/// High band this far (dB) below the low band sounds muffled
+const MUFFLED_TILT_DB: f32 = -35.0;
+
+/// Range (Hz) of the bandwidth left by narrowband Bluetooth HFP (CVSD at
+/// 8 kHz sample rate)
+const NARROWBAND_HZ: (f32, f32) = (3200.0, 4200.0);
+
+/// Range (Hz) of the bandwidth left by wideband Bluetooth HFP (mSBC at
+/// 16 kHz sample rate)
+const WIDEBAND_HZ: (f32, f32) = (6800.0, 8200.0);
+
+/// A codec cutoff drops at least this much (dB) from just below to just
+/// above the bandwidth; microphones and rooms roll off gradually
+const CUTOFF_DROP_DB: f32 = 25.0;
+
+/// Width (Hz) of the bands compared on either side of the cutoff
+const CUTOFF_BAND_HZ: f32 = 500.0;
+
+/// Average power spectrum of the speech frames of a recording in dB relative
+/// to its strongest bin, or empty when the audio is shorter than one frame.
+/// Bins are `sample_rate / frame_size.next_power_of_two()` Hz apart.
+#[wasm_bindgen]
+pub fn long_term_average_spectrum(
+    audio_data: &[f32],
+    frame_size: usize,
+    hop_size: usize,
+) -> Vec<f32> {
+    if frame_size == 0 || hop_size == 0 || audio_data.len() < frame_size {
+        return Vec::new();
+    }
+    let noise = estimate_noise(audio_data, frame_size, hop_size);
+    let gate = noise.noise_rms * 10f32.powf(SPEECH_MARGIN_DB / 20.0);
+    let frames: Vec<&[f32]> = (0..=(audio_data.len() - frame_size) / hop_size)
+        .map(|i| &audio_data[i * hop_size..i * hop_size + frame_size])
+        .collect();
+    let speech: Vec<&[f32]> = frames.iter().copied().filter(|f| rms(f) > gate).collect();
+    let frames = if speech.is_empty() { frames } else { speech };
+
+    let mut power = Vec::new();
+    for frame in &frames {
+        let spectrum = magnitude_spectrum(frame);
+        power.resize(spectrum.len(), 0.0f32);
+        for (p, m) in power.iter_mut().zip(&spectrum) {
+            *p += m * m;
+        }
+    }
+    let peak = power.iter().copied().fold(POWER_FLOOR, f32::max);
+    power
+        .iter()
+        .map(|&p| 10.0 * (p.max(POWER_FLOOR) / peak).log10())
+        .collect()
+}
+
+/// Hard bandwidth limit found in a recording, the signature of a telephony
+/// codec such as Bluetooth HFP upsampled to the browser's rate
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BandLimit {
+    /// No codec cutoff below the Nyquist frequency
+    None = 0,
+    /// Cut off around 8 kHz (16 kHz wideband speech, e.g. mSBC)
+    Wideband = 1,
+    /// Cut off around 4 kHz (8 kHz narrowband speech, e.g. CVSD)
+    Narrowband = 2,
+}
+
+/// What the LTAS says about the recording chain
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpectrumSummary {
+    /// Highest frequency (Hz) within `BANDWIDTH_DROP_DB` of the peak
+    pub bandwidth_hz: f32,
+    /// Mean level of 2-4 kHz minus mean level of 100-1000 Hz, in dB
+    pub tilt_db: f32,
+    /// The high band is so weak the voice sounds muffled
+    pub muffled: bool,
+    /// Codec cutoff, if any; pitch and scores from band-limited recordings
+    /// are systematically worse
+    pub band_limit: BandLimit,
+}
+
+#[wasm_bindgen]
+impl SpectrumSummary {
+    /// The recording most likely went through a Bluetooth headset's
+    /// telephony codec
+    #[wasm_bindgen(getter)]
+    pub fn bluetooth_suspected(&self) -> bool {
+        self.band_limit != BandLimit::None
+    }
+}
+
+/// Mean of the dB levels between `lo` and `hi` Hz, or None when the range
+/// holds no bins
+fn band_level(ltas: &[f32], bin_hz: f32, (lo, hi): (f32, f32)) -> Option<f32> {
+    let first = (lo / bin_hz).ceil() as usize;
+    let last = ((hi / bin_hz).floor() as usize).min(ltas.len().saturating_sub(1));
+    (first <= last).then(|| ltas[first..=last].iter().sum::<f32>() / (last - first + 1) as f32)
+}
+
+/// Codec cutoff at `bandwidth_hz`: it falls in a telephony band well below
+/// the Nyquist frequency and the spectrum drops off a cliff right after it
+fn band_limit(ltas: &[f32], bin_hz: f32, bandwidth_hz: f32, sample_rate: f32) -> BandLimit {
+    let in_range = |(lo, hi): (f32, f32)| (lo..=hi).contains(&bandwidth_hz);
+    let kind = if in_range(NARROWBAND_HZ) {
+        BandLimit::Narrowband
+    } else if in_range(WIDEBAND_HZ) {
+        BandLimit::Wideband
+    } else {
+        return BandLimit::None;
+    };
+    if bandwidth_hz + 2.0 * CUTOFF_BAND_HZ >= sample_rate / 2.0 {
+        return BandLimit::None;
+    }
+    let below = band_level(ltas, bin_hz, (bandwidth_hz - CUTOFF_BAND_HZ, bandwidth_hz));
+    let above = band_level(ltas, bin_hz, (bandwidth_hz, bandwidth_hz + CUTOFF_BAND_HZ));
+    match (below, above) {
+        (Some(below), Some(above)) if below - above >= CUTOFF_DROP_DB => kind,
+        _ => BandLimit::None,
+    }
+}
+
+/// Bandwidth, spectral tilt and codec cutoff of an LTAS from
+/// `long_term_average_spectrum`
+pub fn summarize_spectrum(ltas: &[f32], sample_rate: f32) -> SpectrumSummary {
+    if ltas.len() < 2 {
+        return SpectrumSummary {
+            bandwidth_hz: 0.0,
+            tilt_db: 0.0,
+            muffled: false,
+            band_limit: BandLimit::None,
+        };
+    }
+    let bin_hz = sample_rate / (2 * (ltas.len() - 1)) as f32;
+    let bandwidth_hz = ltas
+        .iter()
+        .rposition(|&db| db >= -BANDWIDTH_DROP_DB)
+        .map_or(0.0, |k| k as f32 * bin_hz);
+    let tilt_db = match (
+        band_level(ltas, bin_hz, LOW_BAND_HZ),
+        band_level(ltas, bin_hz, HIGH_BAND_HZ),
+    ) {
+        (Some(low), Some(high)) => high - low,
+        _ => 0.0,
+    };
+    SpectrumSummary {
+        bandwidth_hz,
+        tilt_db,
+        muffled: tilt_db < MUFFLED_TILT_DB,
+        band_limit: band_limit(ltas, bin_hz, bandwidth_hz, sample_rate),
+    }
+}
+
+/// Bandwidth, tilt and codec cutoff of a recording's long-term average
+/// spectrum
+#[wasm_bindgen]
+pub fn analyze_spectrum(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> SpectrumSummary {
+    summarize_spectrum(
+        &long_term_average_spectrum(audio_data, frame_size, hop_size),
+        sample_rate,
+    )
+}
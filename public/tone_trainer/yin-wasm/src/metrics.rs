@@ -0,0 +1,104 @@
+//! Lightweight process-wide counters so the app can report aggregate health
+//! without instrumenting every call site
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use wasm_bindgen::prelude::*;
+
+static ANALYSES: AtomicU64 = AtomicU64::new(0);
+static FRAMES_PROCESSED: AtomicU64 = AtomicU64::new(0);
+static VOICED_FRAMES: AtomicU64 = AtomicU64::new(0);
+static CORRECTIONS_APPLIED: AtomicU64 = AtomicU64::new(0);
+static DECODE_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Count one finished analysis pass
+pub fn record_analysis(frames: usize, voiced: usize) {
+    ANALYSES.fetch_add(1, Ordering::Relaxed);
+    FRAMES_PROCESSED.fetch_add(frames as u64, Ordering::Relaxed);
+    VOICED_FRAMES.fetch_add(voiced as u64, Ordering::Relaxed);
+}
+
+/// Count frames whose estimate was changed by a post-processing stage
+pub fn record_corrections(count: usize) {
+    CORRECTIONS_APPLIED.fetch_add(count as u64, Ordering::Relaxed);
+}
+
+/// Count an input that could not be decoded
+pub fn record_decode_error() {
+    DECODE_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Snapshot of the counters
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct Metrics {
+    analyses: u64,
+    frames_processed: u64,
+    voiced_frames: u64,
+    corrections_applied: u64,
+    decode_errors: u64,
+}
+
+#[wasm_bindgen]
+impl Metrics {
+    #[wasm_bindgen(getter)]
+    pub fn analyses(&self) -> f64 {
+        self.analyses as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn frames_processed(&self) -> f64 {
+        self.frames_processed as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn voiced_frames(&self) -> f64 {
+        self.voiced_frames as f64
+    }
+
+    /// Fraction of processed frames that came out voiced
+    #[wasm_bindgen(getter)]
+    pub fn voiced_fraction(&self) -> f64 {
+        if self.frames_processed == 0 {
+            0.0
+        } else {
+            self.voiced_frames as f64 / self.frames_processed as f64
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn corrections_applied(&self) -> f64 {
+        self.corrections_applied as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn decode_errors(&self) -> f64 {
+        self.decode_errors as f64
+    }
+}
+
+/// Current values of the telemetry counters
+#[wasm_bindgen]
+pub fn get_metrics() -> Metrics {
+    Metrics {
+        analyses: ANALYSES.load(Ordering::Relaxed),
+        frames_processed: FRAMES_PROCESSED.load(Ordering::Relaxed),
+        voiced_frames: VOICED_FRAMES.load(Ordering::Relaxed),
+        corrections_applied: CORRECTIONS_APPLIED.load(Ordering::Relaxed),
+        decode_errors: DECODE_ERRORS.load(Ordering::Relaxed),
+    }
+}
+
+/// Reset all counters to zero
+#[wasm_bindgen]
+pub fn reset_metrics() {
+    for counter in [
+        &ANALYSES,
+        &FRAMES_PROCESSED,
+        &VOICED_FRAMES,
+        &CORRECTIONS_APPLIED,
+        &DECODE_ERRORS,
+    ] {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
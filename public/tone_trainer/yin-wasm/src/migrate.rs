@@ -0,0 +1,217 @@
+//! Re-analysis of cache entries written by older versions of the analyzer.
+//! When the algorithm or the encoding changes, stored reference analyses
+//! are recomputed from their audio with the settings they were made with,
+//! and the report says how much the pitch track and the scores moved, so
+//! an upgrade doesn't silently change what learners see.
+
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{BinaryError, ByteReader};
+use crate::cache::{cache_put, CACHE_MAGIC, CACHE_VERSION};
+use crate::config::YinConfig;
+use crate::hash::hash_samples;
+use crate::rubric::ScoringRubric;
+use crate::scale::PitchScale;
+use crate::segments::pitch_column;
+use crate::tones::score_tones;
+use crate::window::WindowFunction;
+use crate::CmndfSummation;
+
+/// Oldest cache version whose audio hash can still be checked; version 1
+/// used a different hash function
+const FIRST_XXH64_VERSION: u8 = 2;
+
+/// Settings written by `YinConfig::write_to` at cache version `version`;
+/// fields added in later versions take the current defaults
+pub(crate) fn read_config(input: &mut ByteReader, version: u8) -> Result<YinConfig, BinaryError> {
+    let defaults = YinConfig::new();
+    Ok(YinConfig {
+        sample_rate: input.f32()?,
+        frame_size: input.usize()?,
+        hop_size: input.usize()?,
+        threshold: input.f32()?,
+        min_freq: input.f32()?,
+        max_freq: input.f32()?,
+        interpolation: input.bool()?,
+        tau_start: if version >= 3 {
+            input.usize()?
+        } else {
+            defaults.tau_start
+        },
+        summation: if version >= 4 {
+            CmndfSummation::from_u8(input.u8()?).ok_or(BinaryError::Invalid("summation"))?
+        } else {
+            defaults.summation
+        },
+        window: if version >= 5 {
+            WindowFunction::from_u8(input.u8()?).ok_or(BinaryError::Invalid("window"))?
+        } else {
+            defaults.window
+        },
+        noise_robust: if version >= 6 {
+            input.bool()?
+        } else {
+            defaults.noise_robust
+        },
+        scale: if version >= 7 {
+            PitchScale::from_u8(input.u8()?).ok_or(BinaryError::Invalid("scale"))?
+        } else {
+            defaults.scale
+        },
+    })
+}
+
+/// Settings and results of a cache entry of any version
+fn decode_legacy(bytes: &[u8], audio: &[f32]) -> Result<(u8, YinConfig, Vec<f32>), BinaryError> {
+    let (mut input, version) = ByteReader::with_header(bytes, CACHE_MAGIC)?;
+    if version == 0 || version > CACHE_VERSION {
+        return Err(BinaryError::UnsupportedVersion(version));
+    }
+    input.u64()?; // config hash; the layout it was computed from changed
+    let audio_hash = input.u64()?;
+    if version >= FIRST_XXH64_VERSION && audio_hash != hash_samples(audio) {
+        return Err(BinaryError::Invalid("audio for this entry"));
+    }
+
+    let config = read_config(&mut input, version)?;
+    let results = input.f32s()?;
+    input.finish()?;
+    Ok((version, config, results))
+}
+
+/// Outcome of `migrate_cache_entry`
+#[wasm_bindgen]
+pub struct MigrationReport {
+    from_version: u8,
+    results: Vec<f32>,
+    entry: Vec<u8>,
+    frames_compared: usize,
+    mean_pitch_change: f32,
+    voicing_changes: usize,
+    old_scores: Vec<f32>,
+    new_scores: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl MigrationReport {
+    /// Cache version the entry was written with
+    #[wasm_bindgen(getter)]
+    pub fn from_version(&self) -> u8 {
+        self.from_version
+    }
+
+    /// Re-analyzed flat [pitch, confidence, tau, ...] array
+    #[wasm_bindgen(getter)]
+    pub fn results(&self) -> Vec<f32> {
+        self.results.clone()
+    }
+
+    /// Current-version cache entry to store in place of the old one
+    #[wasm_bindgen(getter)]
+    pub fn entry(&self) -> Vec<u8> {
+        self.entry.clone()
+    }
+
+    /// Frames present in both the old and new results
+    #[wasm_bindgen(getter)]
+    pub fn frames_compared(&self) -> usize {
+        self.frames_compared
+    }
+
+    /// Mean absolute pitch change (semitones) over frames voiced in both
+    #[wasm_bindgen(getter)]
+    pub fn mean_pitch_change(&self) -> f32 {
+        self.mean_pitch_change
+    }
+
+    /// Frames that went from voiced to unvoiced or back
+    #[wasm_bindgen(getter)]
+    pub fn voicing_changes(&self) -> usize {
+        self.voicing_changes
+    }
+
+    /// Per-syllable scores of the old results, same layout as
+    /// `score_expected_contour` (empty without tones)
+    #[wasm_bindgen(getter)]
+    pub fn old_scores(&self) -> Vec<f32> {
+        self.old_scores.clone()
+    }
+
+    /// Per-syllable scores of the new results
+    #[wasm_bindgen(getter)]
+    pub fn new_scores(&self) -> Vec<f32> {
+        self.new_scores.clone()
+    }
+
+    /// Largest per-syllable score change
+    #[wasm_bindgen(getter)]
+    pub fn max_score_change(&self) -> f32 {
+        self.old_scores
+            .chunks_exact(2)
+            .zip(self.new_scores.chunks_exact(2))
+            .map(|(old, new)| (new[0] - old[0]).abs())
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Whether a cache entry was written by an older version and should go
+/// through `migrate_cache_entry`
+#[wasm_bindgen]
+pub fn needs_migration(entry: &[u8]) -> bool {
+    matches!(ByteReader::with_header(entry, CACHE_MAGIC), Ok((_, version)) if version < CACHE_VERSION)
+}
+
+/// Re-analyze the audio of a cache entry of any version with the settings
+/// stored in it and compare with the stored results. `tones` is the
+/// expected tone sequence for the score comparison; pass an empty array to
+/// compare pitch only. Throws when the entry is unreadable or was made from
+/// other audio.
+#[wasm_bindgen]
+pub fn migrate_cache_entry(
+    entry: &[u8],
+    audio: &[f32],
+    tones: &[u8],
+) -> Result<MigrationReport, JsError> {
+    let (from_version, config, old_results) =
+        decode_legacy(entry, audio).map_err(|err| JsError::new(&err.to_string()))?;
+    let results = config.analyze(audio);
+
+    let old_pitch = pitch_column(&old_results);
+    let new_pitch = pitch_column(&results);
+    let frames_compared = old_pitch.len().min(new_pitch.len());
+    let mut voicing_changes = 0;
+    let (mut change_sum, mut both_voiced) = (0.0, 0);
+    for (&old, &new) in old_pitch.iter().zip(&new_pitch) {
+        match (old > 0.0, new > 0.0) {
+            (true, true) => {
+                change_sum += (12.0 * (new / old).log2()).abs();
+                both_voiced += 1;
+            }
+            (false, false) => {}
+            _ => voicing_changes += 1,
+        }
+    }
+
+    let rubric = ScoringRubric::default();
+    let score = |pitch: &[f32]| {
+        if tones.is_empty() {
+            Vec::new()
+        } else {
+            score_tones(pitch, tones, &[], &[], true, &rubric)
+        }
+    };
+    Ok(MigrationReport {
+        from_version,
+        entry: cache_put(audio, &config, &results),
+        frames_compared,
+        mean_pitch_change: if both_voiced > 0 {
+            change_sum / both_voiced as f32
+        } else {
+            0.0
+        },
+        voicing_changes,
+        old_scores: score(&old_pitch),
+        new_scores: score(&new_pitch),
+        results,
+    })
+}
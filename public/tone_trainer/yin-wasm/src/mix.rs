@@ -0,0 +1,124 @@
+//! Stereo mix of a learner's attempt and the reference for simultaneous
+//! A/B playback. Both recordings are brought to the same speech level, so
+//! neither drowns the other however far the learner sat from the
+//! microphone, and the learner's audio is moved onto the reference's
+//! timeline with its `ReplayAlignment`.
+
+use std::f32::consts::FRAC_PI_4;
+
+use wasm_bindgen::prelude::*;
+
+use crate::input::frame_levels;
+
+/// Frame length (seconds) over which speech level is measured
+const LEVEL_FRAME_SECONDS: f32 = 0.02;
+
+/// Frames within this many dB of the loudest frame count as speech when
+/// measuring a recording's level; pauses and breaths are left out
+const SPEECH_RANGE_DB: f32 = 20.0;
+
+/// Speech RMS level both recordings are normalized to (-20 dBFS)
+const TARGET_SPEECH_RMS: f32 = 0.1;
+
+/// Highest peak of the mix; louder mixes are scaled down as a whole rather
+/// than clipped
+const MAX_PEAK: f32 = 0.98;
+
+/// RMS level of the speech in `audio`: the RMS of the frames within
+/// `SPEECH_RANGE_DB` of the loudest one (0 for silence)
+pub fn speech_level(audio: &[f32], sample_rate: f32) -> f32 {
+    let frame = ((LEVEL_FRAME_SECONDS * sample_rate) as usize).max(1);
+    let levels = frame_levels(audio, frame, frame);
+    let loudest = levels.iter().copied().fold(0.0, f32::max);
+    if loudest <= 0.0 {
+        return 0.0;
+    }
+    let floor = loudest * 10f32.powf(-SPEECH_RANGE_DB / 20.0);
+    let speech: Vec<f32> = levels.into_iter().filter(|&l| l >= floor).collect();
+    (speech.iter().map(|l| l * l).sum::<f32>() / speech.len() as f32).sqrt()
+}
+
+/// Gain bringing `audio` to `TARGET_SPEECH_RMS` (1 for silence)
+fn normalizing_gain(audio: &[f32], sample_rate: f32) -> f32 {
+    let level = speech_level(audio, sample_rate);
+    if level > 0.0 {
+        TARGET_SPEECH_RMS / level
+    } else {
+        1.0
+    }
+}
+
+/// Sample of `audio` at fractional position `position`, linearly
+/// interpolated; 0 outside the recording
+fn sample_at(audio: &[f32], position: f32) -> f32 {
+    if position < 0.0 {
+        return 0.0;
+    }
+    let index = position as usize;
+    let frac = position - index as f32;
+    match (audio.get(index), audio.get(index + 1)) {
+        (Some(&a), Some(&b)) => a + (b - a) * frac,
+        (Some(&a), None) => a * (1.0 - frac),
+        _ => 0.0,
+    }
+}
+
+/// Interleaved stereo [left, right, ...] mix of `learner` and `reference`
+/// (mono, both at `sample_rate`) on the reference's timeline, each
+/// normalized to the same speech level.
+///
+/// `offset` and `stretch` map reference time to learner time as in
+/// `ReplayAlignment` (0 and 1 play both from the start). Stretching
+/// resamples the learner's audio, which also shifts its pitch by
+/// `-12 * log2(stretch)` semitones; pass `stretch` 1 to only line up the
+/// starts when the pitch comparison matters more than matched timing.
+///
+/// `pan` (0-1) separates the voices: 0 plays both in both ears, 1 puts the
+/// learner fully left and the reference fully right, with equal-power
+/// panning in between.
+#[wasm_bindgen]
+pub fn mix_for_comparison(
+    learner: &[f32],
+    reference: &[f32],
+    sample_rate: f32,
+    offset: f32,
+    stretch: f32,
+    pan: f32,
+) -> Vec<f32> {
+    if sample_rate <= 0.0 {
+        return Vec::new();
+    }
+    let stretch = if stretch > 0.0 { stretch } else { 1.0 };
+    let learner_gain = normalizing_gain(learner, sample_rate);
+    let reference_gain = normalizing_gain(reference, sample_rate);
+
+    // Reference-time sample where the learner's recording starts and ends
+    let learner_start = -offset * sample_rate / stretch;
+    let learner_end = learner_start + learner.len() as f32 / stretch;
+    let start = learner_start.min(0.0).floor() as isize;
+    let end = (learner_end.ceil() as isize).max(reference.len() as isize);
+
+    // Equal-power pan: angle 0 is fully left, FRAC_PI_2 fully right
+    let spread = FRAC_PI_4 * pan.clamp(0.0, 1.0);
+    let (learner_left, learner_right) = ((FRAC_PI_4 - spread).cos(), (FRAC_PI_4 - spread).sin());
+    let (reference_left, reference_right) = (learner_right, learner_left);
+
+    let mut out = Vec::with_capacity(2 * (end - start).max(0) as usize);
+    for n in start..end {
+        let l = learner_gain * sample_at(learner, n as f32 * stretch + offset * sample_rate);
+        let r = if n >= 0 {
+            reference_gain * reference.get(n as usize).copied().unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        out.push(l * learner_left + r * reference_left);
+        out.push(l * learner_right + r * reference_right);
+    }
+
+    let peak = out.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+    if peak > MAX_PEAK {
+        let scale = MAX_PEAK / peak;
+        out.iter_mut().for_each(|x| *x *= scale);
+    }
+    out
+}
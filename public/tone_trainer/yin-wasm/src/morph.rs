@@ -0,0 +1,133 @@
+//! Intermediate contours between a learner's attempt and the target, for an
+//! animation of "how to get from yours to correct". Both tracks are put in
+//! semitones around their own voiced median and aligned with DTW, as in
+//! `compare_contours`; every aligned pair of frames then moves from the
+//! learner's position (time and pitch) to the target's, so the morph fixes
+//! timing as well as pitch.
+
+use wasm_bindgen::prelude::*;
+
+use crate::dtw::dtw;
+use crate::tones::voiced_median;
+
+/// Voiced frames of a Hz track in semitones around its voiced median
+fn voiced_semitones(track: &[f32]) -> Vec<f32> {
+    let median = voiced_median(track);
+    track
+        .iter()
+        .filter(|&&hz| hz > 0.0)
+        .map(|&hz| 12.0 * (hz / median).log2())
+        .collect()
+}
+
+/// Aligned learner and target contours, ready to be morphed
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct ContourMorph {
+    /// Per step of the warping path: normalized time (0-1) and semitones of
+    /// the learner's frame, then of the target's
+    steps: Vec<[f32; 4]>,
+}
+
+#[wasm_bindgen]
+impl ContourMorph {
+    /// Align a learner's Hz pitch track with the target's (0 for unvoiced
+    /// frames; unvoiced frames are dropped)
+    #[wasm_bindgen(constructor)]
+    pub fn new(user: &[f32], reference: &[f32]) -> ContourMorph {
+        let user = voiced_semitones(user);
+        let reference = voiced_semitones(reference);
+        let position = |i: usize, len: usize| {
+            if len > 1 {
+                i as f32 / (len - 1) as f32
+            } else {
+                0.0
+            }
+        };
+        let steps = dtw(&user, &reference)
+            .1
+            .into_iter()
+            .map(|(i, j)| {
+                [
+                    position(i, user.len()),
+                    user[i],
+                    position(j, reference.len()),
+                    reference[j],
+                ]
+            })
+            .collect();
+        ContourMorph { steps }
+    }
+
+    /// False when either track had no voiced frame, so there is nothing to
+    /// morph
+    pub fn is_valid(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    /// The contour `t` of the way from the learner's (0) to the target's
+    /// (1), as `n_points` semitone values evenly spaced in time; empty when
+    /// not `is_valid`
+    pub fn at(&self, t: f32, n_points: usize) -> Vec<f32> {
+        if self.steps.is_empty() || n_points == 0 {
+            return Vec::new();
+        }
+        let t = t.clamp(0.0, 1.0);
+        // Morphed points in time order; points landing on the same time are
+        // averaged
+        let mut points: Vec<(f32, f32, usize)> = Vec::new();
+        for &[user_time, user_st, reference_time, reference_st] in &self.steps {
+            let time = user_time + t * (reference_time - user_time);
+            let value = user_st + t * (reference_st - user_st);
+            match points.last_mut() {
+                Some((last, sum, count)) if (time - *last).abs() < 1e-6 => {
+                    *sum += value;
+                    *count += 1;
+                }
+                _ => points.push((time, value, 1)),
+            }
+        }
+        let points: Vec<(f32, f32)> = points
+            .into_iter()
+            .map(|(time, sum, count)| (time, sum / count as f32))
+            .collect();
+
+        let mut segment = 0;
+        (0..n_points)
+            .map(|k| {
+                let time = if n_points > 1 {
+                    k as f32 / (n_points - 1) as f32
+                } else {
+                    0.5
+                };
+                while segment + 2 < points.len() && points[segment + 1].0 < time {
+                    segment += 1;
+                }
+                let (t0, v0) = points[segment];
+                let Some(&(t1, v1)) = points.get(segment + 1) else {
+                    return v0;
+                };
+                let fraction = ((time - t0) / (t1 - t0)).clamp(0.0, 1.0);
+                v0 + fraction * (v1 - v0)
+            })
+            .collect()
+    }
+
+    /// `frames + 1` contours from the learner's to the target's in equal
+    /// steps, as one flat array of `(frames + 1) * n_points` semitone values
+    /// for the animation
+    pub fn animation(&self, frames: usize, n_points: usize) -> Vec<f32> {
+        let frames = frames.max(1);
+        (0..=frames)
+            .flat_map(|f| self.at(f as f32 / frames as f32, n_points))
+            .collect()
+    }
+}
+
+/// The contour `t` (0-1) of the way from a learner's Hz pitch track to the
+/// target's, as `n_points` semitone values around the speaker's median;
+/// see `ContourMorph` to compute several steps from one alignment
+#[wasm_bindgen]
+pub fn morph_contour(user: &[f32], reference: &[f32], t: f32, n_points: usize) -> Vec<f32> {
+    ContourMorph::new(user, reference).at(t, n_points)
+}
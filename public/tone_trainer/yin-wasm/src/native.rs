@@ -0,0 +1,213 @@
+//! Plain Rust API over the YIN core for native tools, e.g. precomputing the
+//! reference contours of the whole deck offline. Nothing here takes or
+//! returns wasm-bindgen types, so it can be called from a binary, an
+//! example or a benchmark; see `examples/contours.rs`. Only built with the
+//! `native` feature.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::config::{ConfigError, YinConfig};
+use crate::input::analyze_at_rate;
+use crate::json::{push_key, push_number};
+use crate::scale::PitchScale;
+use crate::typed::push_frames;
+use crate::wav::{decode_wav, WavError};
+use crate::yin_analyze_frame;
+
+/// Analysis settings, the plain counterpart of `YinConfig`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    /// Rate (Hz) the audio is analyzed at; WAV files at other rates are
+    /// resampled to it
+    pub sample_rate: f32,
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub threshold: f32,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub interpolation: bool,
+}
+
+impl Default for Settings {
+    /// The defaults of `YinConfig::new`
+    fn default() -> Self {
+        let config = YinConfig::new();
+        Settings {
+            sample_rate: config.sample_rate,
+            frame_size: config.frame_size,
+            hop_size: config.hop_size,
+            threshold: config.threshold,
+            min_freq: config.min_freq,
+            max_freq: config.max_freq,
+            interpolation: config.interpolation,
+        }
+    }
+}
+
+impl Settings {
+    fn config(&self) -> YinConfig {
+        YinConfig::new()
+            .with_sample_rate(self.sample_rate)
+            .with_frame_size(self.frame_size)
+            .with_hop_size(self.hop_size)
+            .with_threshold(self.threshold)
+            .with_freq_range(self.min_freq, self.max_freq)
+            .with_interpolation(self.interpolation)
+    }
+}
+
+/// Why a native analysis failed
+#[derive(Debug)]
+pub enum Error {
+    Config(ConfigError),
+    Wav(WavError),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(err) => write!(f, "{}", err),
+            Error::Wav(err) => write!(f, "{}", err),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(err: ConfigError) -> Self {
+        Error::Config(err)
+    }
+}
+
+impl From<WavError> for Error {
+    fn from(err: WavError) -> Self {
+        Error::Wav(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// One analyzed frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    /// Start of the frame (seconds)
+    pub time: f32,
+    /// Pitch (Hz), 0 when unvoiced
+    pub pitch: f32,
+    pub confidence: f32,
+    /// Period (samples) before interpolation, -1 when none was found
+    pub tau: i32,
+}
+
+/// A pitch contour with the settings it was analyzed with
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contour {
+    pub settings: Settings,
+    /// Flat [pitch, confidence, tau, ...] array, as `perform_yin_analysis`
+    /// returns it
+    pub results: Vec<f32>,
+}
+
+impl Contour {
+    /// Seconds between frames
+    pub fn frame_period(&self) -> f32 {
+        self.settings.hop_size as f32 / self.settings.sample_rate
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = Frame> + '_ {
+        let period = self.frame_period();
+        self.results
+            .chunks_exact(3)
+            .enumerate()
+            .map(move |(i, f)| Frame {
+                time: i as f32 * period,
+                pitch: f[0],
+                confidence: f[1],
+                tau: f[2] as i32,
+            })
+    }
+
+    /// The contour as JSON, with the same frame objects as `to_json`
+    /// exports: `{"sample_rate":..,"frame_size":..,"hop_size":..,"frames":[
+    /// {"time":..,"pitch":..,"confidence":..,"tau":..}, ...]}`
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        push_key(&mut out, "sample_rate", true);
+        push_number(&mut out, self.settings.sample_rate);
+        push_key(&mut out, "frame_size", false);
+        push_number(&mut out, self.settings.frame_size as f32);
+        push_key(&mut out, "hop_size", false);
+        push_number(&mut out, self.settings.hop_size as f32);
+        push_key(&mut out, "frames", false);
+        push_frames(
+            &mut out,
+            &self.results,
+            self.frame_period(),
+            0.0,
+            PitchScale::Hz,
+        );
+        out.push('}');
+        out
+    }
+}
+
+/// Analyze mono audio at `settings.sample_rate`
+pub fn analyze(audio: &[f32], settings: &Settings) -> Result<Contour, Error> {
+    let results = settings.config().analyze_checked(audio)?;
+    Ok(Contour {
+        settings: *settings,
+        results,
+    })
+}
+
+/// Analyze a WAV file's bytes: channels are averaged to mono and the audio
+/// is resampled to `settings.sample_rate`
+pub fn analyze_wav(bytes: &[u8], settings: &Settings) -> Result<Contour, Error> {
+    let config = settings.config();
+    config.check()?;
+    let (mono, format) = decode_wav(bytes)?;
+    Ok(Contour {
+        settings: *settings,
+        results: analyze_at_rate(mono, format.sample_rate as f32, &config),
+    })
+}
+
+/// `analyze_wav` on the file at `path`
+pub fn analyze_wav_file(path: impl AsRef<Path>, settings: &Settings) -> Result<Contour, Error> {
+    analyze_wav(&std::fs::read(path)?, settings)
+}
+
+/// Analyze a single frame of `settings.frame_size` samples (the frame
+/// length is taken from `frame` itself)
+pub fn analyze_frame(frame: &[f32], settings: &Settings) -> Frame {
+    let (pitch, confidence, tau) = yin_analyze_frame(frame, &settings.config().params(), None);
+    Frame {
+        time: 0.0,
+        pitch,
+        confidence,
+        tau,
+    }
+}
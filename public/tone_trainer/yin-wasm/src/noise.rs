@@ -0,0 +1,279 @@
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::fft::{fft_in_place, ifft_in_place};
+use crate::input::{frame_levels, rms};
+use crate::{audio_frames, metrics, perform_yin_analysis, yin_analyze_frame, YinParams};
+
+/// Percentile of frame levels taken as the noise floor
+const NOISE_PERCENTILE: f32 = 0.1;
+
+/// Percentile of frame levels taken as the speech level
+const SIGNAL_PERCENTILE: f32 = 0.9;
+
+/// SNR (dB) at or above which the strictest threshold is used
+const CLEAN_SNR_DB: f32 = 35.0;
+
+/// SNR (dB) at or below which the most lenient threshold is used
+const NOISY_SNR_DB: f32 = 10.0;
+
+/// Threshold for clean recordings: deep CMNDF dips, so stay strict
+const CLEAN_THRESHOLD: f32 = 0.08;
+
+/// Threshold for noisy recordings: noise makes dips shallower
+const NOISY_THRESHOLD: f32 = 0.25;
+
+/// Lowest level considered when computing decibels
+const LEVEL_FLOOR: f32 = 1e-9;
+
+/// Leading stretch (seconds) taken as background noise, before the learner
+/// starts speaking
+const LEAD_IN_SECONDS: f32 = 0.3;
+
+/// High-pass cutoff as a fraction of `min_freq`: mains hum and fan rumble
+/// below the pitch range go, the fundamental stays
+const HIGH_PASS_RATIO: f32 = 0.75;
+
+/// FFT length and hop of the spectral subtraction
+const SUBTRACTION_FFT_SIZE: usize = 1024;
+const SUBTRACTION_HOP: usize = SUBTRACTION_FFT_SIZE / 2;
+
+/// Frames within this much (dB) of the noise floor make up the noise
+/// spectrum
+const NOISE_FRAME_MARGIN_DB: f32 = 3.0;
+
+/// Multiple of the noise spectrum subtracted, and the fraction of the
+/// original magnitude always kept so no bin is zeroed ("musical noise")
+const OVER_SUBTRACTION: f32 = 1.5;
+const SPECTRAL_FLOOR: f32 = 0.05;
+
+/// Noise floor, speech level and their ratio for one utterance
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseEstimate {
+    pub noise_rms: f32,
+    pub signal_rms: f32,
+    pub snr_db: f32,
+}
+
+/// Value at `fraction` (0..1) of the sorted values
+fn percentile(sorted: &[f32], fraction: f32) -> f32 {
+    let idx = ((sorted.len() - 1) as f32 * fraction).round() as usize;
+    sorted[idx]
+}
+
+/// Estimate the noise floor and SNR from per-frame RMS levels: the quietest
+/// frames are taken as background noise, the loudest as speech
+pub fn estimate_noise(audio_data: &[f32], frame_size: usize, hop_size: usize) -> NoiseEstimate {
+    let mut levels = frame_levels(audio_data, frame_size, hop_size);
+    if levels.is_empty() {
+        levels.push(rms(audio_data));
+    }
+    levels.sort_by(|a, b| a.total_cmp(b));
+
+    let noise_rms = percentile(&levels, NOISE_PERCENTILE).max(LEVEL_FLOOR);
+    let signal_rms = percentile(&levels, SIGNAL_PERCENTILE).max(LEVEL_FLOOR);
+    NoiseEstimate {
+        noise_rms,
+        signal_rms,
+        snr_db: 20.0 * (signal_rms / noise_rms).log10(),
+    }
+}
+
+/// Map an SNR to a YIN threshold, interpolating linearly between the clean
+/// and noisy settings
+pub fn threshold_for_snr(snr_db: f32) -> f32 {
+    let t = ((CLEAN_SNR_DB - snr_db) / (CLEAN_SNR_DB - NOISY_SNR_DB)).clamp(0.0, 1.0);
+    CLEAN_THRESHOLD + t * (NOISY_THRESHOLD - CLEAN_THRESHOLD)
+}
+
+/// Measured SNR of an utterance in dB
+#[wasm_bindgen]
+pub fn estimate_snr_db(audio_data: &[f32], frame_size: usize, hop_size: usize) -> f32 {
+    estimate_noise(audio_data, frame_size, hop_size).snr_db
+}
+
+/// YIN threshold chosen for this utterance from its measured noise floor
+#[wasm_bindgen]
+pub fn select_adaptive_threshold(audio_data: &[f32], frame_size: usize, hop_size: usize) -> f32 {
+    threshold_for_snr(estimate_noise(audio_data, frame_size, hop_size).snr_db)
+}
+
+/// `perform_yin_analysis` with the threshold chosen per utterance from the
+/// measured SNR instead of a fixed value: stricter in quiet rooms, more
+/// lenient in noisy ones
+#[wasm_bindgen]
+pub fn perform_yin_analysis_adaptive(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+) -> Vec<f32> {
+    perform_yin_analysis(
+        audio_data,
+        sample_rate,
+        frame_size,
+        hop_size,
+        select_adaptive_threshold(audio_data, frame_size, hop_size),
+        min_freq,
+        max_freq,
+        interpolation,
+    )
+}
+
+/// Noise floor (RMS) for the noise-robust mode: the level of the first
+/// `LEAD_IN_SECONDS`, or of the quietest frames when the lead-in already
+/// holds speech
+pub fn noise_floor_rms(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> f32 {
+    let lead_in = ((LEAD_IN_SECONDS * sample_rate) as usize).min(audio_data.len());
+    let quietest = estimate_noise(audio_data, frame_size, hop_size).noise_rms;
+    if lead_in == 0 {
+        return quietest;
+    }
+    rms(&audio_data[..lead_in]).clamp(LEVEL_FLOOR, quietest)
+}
+
+/// Second-order Butterworth high-pass at `cutoff` Hz
+fn high_pass(audio_data: &[f32], sample_rate: f32, cutoff: f32) -> Vec<f32> {
+    if !(cutoff > 0.0 && cutoff < sample_rate / 2.0) {
+        return audio_data.to_vec();
+    }
+    let w0 = 2.0 * PI * cutoff / sample_rate;
+    let alpha = w0.sin() / 2.0f32.sqrt();
+    let a0 = 1.0 + alpha;
+    let (b0, b1, b2) = (
+        (1.0 + w0.cos()) / 2.0,
+        -(1.0 + w0.cos()),
+        (1.0 + w0.cos()) / 2.0,
+    );
+    let (a1, a2) = (-2.0 * w0.cos(), 1.0 - alpha);
+
+    let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+    audio_data
+        .iter()
+        .map(|&x| {
+            let y = (b0 * x + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2) / a0;
+            (x2, x1, y2, y1) = (x1, x, y1, y);
+            y
+        })
+        .collect()
+}
+
+/// Subtract the magnitude spectrum of the frames near `noise_rms` from
+/// every frame (Hann STFT with 50% overlap, so overlap-add restores the
+/// signal where nothing is subtracted). Unchanged when no frame is quiet
+/// enough to measure the noise on.
+fn spectral_subtract(audio_data: &[f32], noise_rms: f32) -> Vec<f32> {
+    let n = SUBTRACTION_FFT_SIZE;
+    // Periodic Hann, whose copies a half-frame apart sum to exactly 1
+    let window: Vec<f32> = (0..n)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / n as f32).cos())
+        .collect();
+    let frames = audio_data.len().div_ceil(SUBTRACTION_HOP) + 1;
+    let mut padded = vec![0.0; SUBTRACTION_HOP];
+    padded.extend_from_slice(audio_data);
+    padded.resize((frames + 1) * SUBTRACTION_HOP, 0.0);
+
+    let mut spectra = Vec::with_capacity(frames);
+    let mut noise = vec![0.0; n];
+    let mut noise_frames = 0;
+    let margin = noise_rms * 10f32.powf(NOISE_FRAME_MARGIN_DB / 20.0);
+    for frame in audio_frames(&padded, n, SUBTRACTION_HOP) {
+        let mut re: Vec<f32> = frame.iter().zip(&window).map(|(x, w)| x * w).collect();
+        let mut im = vec![0.0; n];
+        fft_in_place(&mut re, &mut im);
+        if rms(frame) <= margin {
+            for (k, bin) in noise.iter_mut().enumerate() {
+                *bin += re[k].hypot(im[k]);
+            }
+            noise_frames += 1;
+        }
+        spectra.push((re, im));
+    }
+    if noise_frames == 0 {
+        return audio_data.to_vec();
+    }
+    noise.iter_mut().for_each(|bin| *bin /= noise_frames as f32);
+
+    let mut out = vec![0.0; padded.len()];
+    for (i, (mut re, mut im)) in spectra.into_iter().enumerate() {
+        for k in 0..n {
+            let magnitude = re[k].hypot(im[k]);
+            if magnitude > 0.0 {
+                let kept = (magnitude - OVER_SUBTRACTION * noise[k])
+                    .max(SPECTRAL_FLOOR * magnitude)
+                    / magnitude;
+                re[k] *= kept;
+                im[k] *= kept;
+            }
+        }
+        ifft_in_place(&mut re, &mut im);
+        for (sample, value) in out[i * SUBTRACTION_HOP..].iter_mut().zip(&re) {
+            *sample += value;
+        }
+    }
+    out.drain(..SUBTRACTION_HOP);
+    out.truncate(audio_data.len());
+    out
+}
+
+/// The preprocessing of the noise-robust mode: a high-pass below the pitch
+/// range, then spectral subtraction of the noise measured from the lead-in
+/// (or the quietest frames)
+#[wasm_bindgen]
+pub fn reduce_noise(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    min_freq: f32,
+) -> Vec<f32> {
+    let noise_rms = noise_floor_rms(audio_data, sample_rate, frame_size, hop_size);
+    let filtered = high_pass(audio_data, sample_rate, min_freq * HIGH_PASS_RATIO);
+    spectral_subtract(&filtered, noise_rms)
+}
+
+/// Analysis in the noise-robust mode (`YinConfig::noise_robust`): the audio
+/// goes through `reduce_noise`, and every frame's threshold is relaxed
+/// from `params.threshold` towards `NOISY_THRESHOLD` as its SNR against the
+/// noise floor drops
+pub(crate) fn analyze_noise_robust(
+    audio_data: &[f32],
+    frame_size: usize,
+    hop_size: usize,
+    params: &YinParams,
+) -> Vec<f32> {
+    let sample_rate = params.sample_rate;
+    let noise_rms = noise_floor_rms(audio_data, sample_rate, frame_size, hop_size);
+    let levels = frame_levels(audio_data, frame_size, hop_size);
+    let cleaned = reduce_noise(
+        audio_data,
+        sample_rate,
+        frame_size,
+        hop_size,
+        params.min_freq,
+    );
+
+    let mut results = Vec::with_capacity(levels.len() * 3);
+    for (frame, &level) in audio_frames(&cleaned, frame_size, hop_size).zip(&levels) {
+        let snr_db = 20.0 * (level.max(LEVEL_FLOOR) / noise_rms).log10();
+        let frame_params = YinParams {
+            threshold: threshold_for_snr(snr_db).max(params.threshold),
+            ..*params
+        };
+        let (pitch, confidence, tau) = yin_analyze_frame(frame, &frame_params, None);
+        results.extend_from_slice(&[pitch, confidence, tau as f32]);
+    }
+
+    let voiced = results.chunks(3).filter(|f| f[0] > 0.0).count();
+    metrics::record_analysis(results.len() / 3, voiced);
+    results
+}
@@ -0,0 +1,196 @@
+use wasm_bindgen::prelude::*;
+
+use crate::config::YinConfig;
+use crate::yin_analyze_frame;
+use crate::{metrics, panic};
+
+/// Page-at-a-time analysis of very long recordings (e.g. hour-long immersion
+/// audio): results are produced on demand with `next_frames`, so only one
+/// page of results exists at a time instead of one giant array.
+#[wasm_bindgen]
+pub struct YinFramePager {
+    audio: Vec<f32>,
+    config: YinConfig,
+    next_frame: usize,
+    total_frames: usize,
+}
+
+#[wasm_bindgen]
+impl YinFramePager {
+    /// Takes ownership of `audio`; analysis happens in `next_frames`
+    #[wasm_bindgen(constructor)]
+    pub fn new(audio: Vec<f32>, config: &YinConfig) -> YinFramePager {
+        let hop_size = config.hop_size.max(1);
+        let total_frames = if config.frame_size == 0 || audio.len() < config.frame_size {
+            0
+        } else {
+            (audio.len() - config.frame_size) / hop_size + 1
+        };
+        YinFramePager {
+            audio,
+            config: YinConfig {
+                hop_size,
+                ..*config
+            },
+            next_frame: 0,
+            total_frames,
+        }
+    }
+
+    /// Analyze up to `n` more frames. Returns them as a flat
+    /// [pitch, confidence, tau, ...] array; empty once everything is done.
+    pub fn next_frames(&mut self, n: usize) -> Vec<f32> {
+        let end = (self.next_frame + n).min(self.total_frames);
+        let mut results = Vec::with_capacity((end - self.next_frame) * 3);
+        let config = &self.config;
+        let params = config.params();
+        panic::set_context(panic::AnalysisContext {
+            sample_rate: config.sample_rate,
+            frame_size: config.frame_size,
+            hop_size: config.hop_size,
+            threshold: config.threshold,
+            min_freq: config.min_freq,
+            max_freq: config.max_freq,
+            audio_len: self.audio.len(),
+        });
+
+        let mut voiced = 0;
+        for index in self.next_frame..end {
+            panic::set_frame(index);
+            let start = index * config.hop_size;
+            let frame = &self.audio[start..start + config.frame_size];
+            let (pitch, confidence, tau) = yin_analyze_frame(frame, &params, None);
+            results.push(pitch);
+            results.push(confidence);
+            results.push(tau as f32);
+            if pitch > 0.0 {
+                voiced += 1;
+            }
+        }
+        panic::clear_context();
+
+        metrics::record_analysis(end - self.next_frame, voiced);
+        self.next_frame = end;
+        results
+    }
+
+    /// Index of the first frame the next page will contain
+    #[wasm_bindgen(getter)]
+    pub fn position(&self) -> usize {
+        self.next_frame
+    }
+
+    /// Number of frames in the whole recording
+    #[wasm_bindgen(getter)]
+    pub fn total_frames(&self) -> usize {
+        self.total_frames
+    }
+
+    /// True once every frame has been returned
+    #[wasm_bindgen(getter)]
+    pub fn done(&self) -> bool {
+        self.next_frame >= self.total_frames
+    }
+
+    /// Start again from the first frame
+    pub fn rewind(&mut self) {
+        self.next_frame = 0;
+    }
+}
+
+/// State of a `YinJob`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    /// Frames remain; call `process_next` again
+    Running = 0,
+    /// Every frame has been analyzed
+    Done = 1,
+    /// `cancel` was called; no more frames will be analyzed
+    Cancelled = 2,
+}
+
+/// Whole-recording analysis in bounded steps, for long recordings in a
+/// worker: each `process_next` call analyzes at most a given number of
+/// frames and returns, so the worker can post progress (and notice a
+/// cancel message) between steps. The results are the same as
+/// `perform_yin_analysis` on the whole recording.
+#[wasm_bindgen]
+pub struct YinJob {
+    pager: YinFramePager,
+    results: Vec<f32>,
+    cancelled: bool,
+}
+
+#[wasm_bindgen]
+impl YinJob {
+    /// Takes ownership of `audio`. Throws when the settings are invalid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(audio: Vec<f32>, config: &YinConfig) -> Result<YinJob, JsError> {
+        config.validate()?;
+        let pager = YinFramePager::new(audio, config);
+        Ok(YinJob {
+            results: Vec::with_capacity(pager.total_frames() * 3),
+            pager,
+            cancelled: false,
+        })
+    }
+
+    /// Analyze up to `max_frames` more frames
+    pub fn process_next(&mut self, max_frames: usize) -> JobStatus {
+        if self.status() == JobStatus::Running {
+            let page = self.pager.next_frames(max_frames);
+            self.results.extend_from_slice(&page);
+        }
+        self.status()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> JobStatus {
+        if self.cancelled {
+            JobStatus::Cancelled
+        } else if self.pager.done() {
+            JobStatus::Done
+        } else {
+            JobStatus::Running
+        }
+    }
+
+    /// Percent of the frames analyzed so far (0-100)
+    #[wasm_bindgen(getter)]
+    pub fn progress(&self) -> f32 {
+        match self.pager.total_frames() {
+            0 => 100.0,
+            total => 100.0 * self.pager.position() as f32 / total as f32,
+        }
+    }
+
+    /// Frames analyzed so far
+    #[wasm_bindgen(getter)]
+    pub fn frames_done(&self) -> usize {
+        self.pager.position()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn total_frames(&self) -> usize {
+        self.pager.total_frames()
+    }
+
+    /// Stop the job; later `process_next` calls do nothing. The frames
+    /// analyzed so far stay available.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    /// Flat [pitch, confidence, tau, ...] array of the frames analyzed so
+    /// far; the complete analysis once the status is `Done`
+    pub fn results(&self) -> Vec<f32> {
+        self.results.clone()
+    }
+
+    /// `results`, handing the buffer over instead of copying it; the job
+    /// is empty afterwards
+    pub fn take_results(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.results)
+    }
+}
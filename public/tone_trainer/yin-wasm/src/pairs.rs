@@ -0,0 +1,131 @@
+//! Built-in expected contours and scoring for two-syllable tone pairs, the
+//! core drill format of the deck: tones 1-4 followed by tones 1-4 or the
+//! neutral tone, 20 combinations in all.
+
+use wasm_bindgen::prelude::*;
+
+use crate::pinyin::NEUTRAL_TONE;
+use crate::rubric::ScoringRubric;
+use crate::segments::voiced_core;
+use crate::tones::{
+    coarticulate, expand_template, hz_to_chao, resample_syllable, syllable_score, tone_template,
+    voiced_median, weighted_syllable_ranges, SYLLABLE_POINTS,
+};
+
+/// Share of the voiced frames a neutral-tone syllable takes relative to a
+/// full one; neutral syllables are short
+const NEUTRAL_LENGTH_WEIGHT: f32 = 0.6;
+
+/// Chao targets of each syllable of a tone pair as spoken in connected
+/// speech: 3+3 becomes 2+3 (sandhi), 3 before another tone is a half third,
+/// 4+4 starts with a shortened fall, and a neutral tone takes its pitch from
+/// the tone before it. None unless `first` is 1-4 and `second` is 1-5.
+pub fn pair_templates(first: u8, second: u8) -> Option<(&'static [f32], &'static [f32])> {
+    if !(1..=4).contains(&first) || !(1..=NEUTRAL_TONE).contains(&second) {
+        return None;
+    }
+    let first_template: &'static [f32] = match (first, second) {
+        (3, 3) => &[3.0, 5.0],
+        (3, _) => &[2.0, 1.0, 1.0],
+        (4, 4) => &[5.0, 3.0],
+        _ => tone_template(first),
+    };
+    let second_template: &'static [f32] = match (first, second) {
+        (1, NEUTRAL_TONE) => &[2.0, 2.0],
+        (2, NEUTRAL_TONE) => &[3.0, 3.0],
+        (3, NEUTRAL_TONE) => &[4.0, 4.0],
+        (4, NEUTRAL_TONE) => &[2.0, 1.0],
+        _ => tone_template(second),
+    };
+    Some((first_template, second_template))
+}
+
+/// `pair_templates` expanded to `n1` and `n2` points, with the two
+/// syllables bent toward each other across the boundary (see `coarticulate`)
+fn pair_contours(first: u8, second: u8, n1: usize, n2: usize) -> Option<(Vec<f32>, Vec<f32>)> {
+    let (first_template, second_template) = pair_templates(first, second)?;
+    let mut first_contour = expand_template(first_template, n1);
+    let mut second_contour = expand_template(second_template, n2);
+    let boundary = (first_template[first_template.len() - 1], second_template[0]);
+    coarticulate(&mut first_contour, None, Some(boundary.1));
+    coarticulate(&mut second_contour, Some(boundary.0), None);
+    Some((first_contour, second_contour))
+}
+
+/// Relative lengths of the two syllables of a pair
+fn pair_weights(second: u8) -> [f32; 2] {
+    if second == NEUTRAL_TONE {
+        [1.0, NEUTRAL_LENGTH_WEIGHT]
+    } else {
+        [1.0, 1.0]
+    }
+}
+
+/// Expected Chao contour of a tone pair, `SYLLABLE_POINTS` per full syllable
+/// (fewer for a neutral second syllable), for drawing the target curve.
+/// Empty for an unknown pair.
+#[wasm_bindgen]
+pub fn expected_pair_contour(first: u8, second: u8) -> Vec<f32> {
+    let [w1, w2] = pair_weights(second);
+    let points = |w: f32| ((SYLLABLE_POINTS as f32 * w).round() as usize).max(2);
+    let Some((mut contour, second_contour)) = pair_contours(first, second, points(w1), points(w2))
+    else {
+        return Vec::new();
+    };
+    contour.extend(second_contour);
+    contour
+}
+
+/// Score a Hz pitch track as the tone pair (`first`, `second`), against the
+/// connected-speech contours of `pair_templates` with coarticulation across
+/// the boundary. Voiced frames are split between the syllables (a neutral
+/// syllable gets a shorter share); with `auto_crop`, the track is cropped
+/// to its voiced core first.
+/// Returns [score1, score2, overall] where overall is the mean, or an error
+/// for an unknown pair.
+#[wasm_bindgen]
+pub fn score_tone_pair(
+    pitch: &[f32],
+    first: u8,
+    second: u8,
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Result<Vec<f32>, JsError> {
+    let (first_contour, second_contour) =
+        pair_contours(first, second, SYLLABLE_POINTS, SYLLABLE_POINTS)
+            .ok_or_else(|| JsError::new(&format!("unknown tone pair {}+{}", first, second)))?;
+
+    let pitch = if auto_crop { voiced_core(pitch) } else { pitch };
+    let voicing = rubric.voicing_score(pitch);
+    let chao = hz_to_chao(pitch, voiced_median(pitch));
+    let ranges = weighted_syllable_ranges(chao.len(), &pair_weights(second));
+
+    let syllables: Vec<(u8, Vec<f32>, Vec<f32>)> =
+        [(first, first_contour), (second, second_contour)]
+            .into_iter()
+            .zip(&ranges)
+            .filter(|(_, &(start, end))| start < end)
+            .map(|((tone, expected), &(start, end))| {
+                (tone, resample_syllable(&chao[start..end]), expected)
+            })
+            .collect();
+    if syllables.len() < 2 {
+        return Ok(vec![0.0; 3]);
+    }
+
+    // Register is judged between the two syllables, not against the
+    // speaker's median, which a pair like 1+5 doesn't sit on
+    let observed_sum: f32 = syllables.iter().flat_map(|s| &s.1).sum();
+    let expected_sum: f32 = syllables.iter().flat_map(|s| &s.2).sum();
+    let offset = (observed_sum - expected_sum) / (2 * SYLLABLE_POINTS) as f32;
+
+    let mut scores: Vec<f32> = syllables
+        .iter()
+        .map(|(tone, observed, expected)| {
+            let observed: Vec<f32> = observed.iter().map(|c| c - offset).collect();
+            syllable_score(&observed, expected, *tone, rubric, voicing)
+        })
+        .collect();
+    scores.push((scores[0] + scores[1]) / 2.0);
+    Ok(scores)
+}
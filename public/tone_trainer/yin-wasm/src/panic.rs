@@ -0,0 +1,184 @@
+use std::cell::{Cell, RefCell};
+use std::sync::Once;
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::YinConfig;
+use crate::json::{push_key, push_number, push_string};
+use crate::perform_yin_analysis;
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(msg: &str);
+}
+
+/// Configuration of the analysis currently running, captured for crash reports
+#[derive(Clone, Copy, Debug)]
+pub struct AnalysisContext {
+    pub sample_rate: f32,
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub threshold: f32,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub audio_len: usize,
+}
+
+thread_local! {
+    static CONTEXT: Cell<Option<AnalysisContext>> = const { Cell::new(None) };
+    static FRAME_INDEX: Cell<Option<usize>> = const { Cell::new(None) };
+    static LAST_REPORT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static INSTALL: Once = Once::new();
+
+/// Remember the configuration of the analysis that is starting
+pub fn set_context(context: AnalysisContext) {
+    CONTEXT.with(|c| c.set(Some(context)));
+    FRAME_INDEX.with(|f| f.set(None));
+}
+
+/// Remember which frame is being analyzed
+#[inline]
+pub fn set_frame(index: usize) {
+    FRAME_INDEX.with(|f| f.set(Some(index)));
+}
+
+/// Forget the context once an analysis finished normally
+pub fn clear_context() {
+    CONTEXT.with(|c| c.set(None));
+    FRAME_INDEX.with(|f| f.set(None));
+}
+
+/// Build the JSON crash report for a panic message
+fn build_report(message: &str, location: Option<String>) -> String {
+    let mut out = String::from("{");
+    push_key(&mut out, "message", true);
+    push_string(&mut out, message);
+    push_key(&mut out, "location", false);
+    match location {
+        Some(location) => push_string(&mut out, &location),
+        None => out.push_str("null"),
+    }
+    push_key(&mut out, "frame_index", false);
+    push_number(
+        &mut out,
+        FRAME_INDEX.with(|f| f.get()).map_or(f32::NAN, |i| i as f32),
+    );
+    push_key(&mut out, "config", false);
+    match CONTEXT.with(|c| c.get()) {
+        Some(ctx) => {
+            out.push('{');
+            let fields = [
+                ("sample_rate", ctx.sample_rate),
+                ("frame_size", ctx.frame_size as f32),
+                ("hop_size", ctx.hop_size as f32),
+                ("threshold", ctx.threshold),
+                ("min_freq", ctx.min_freq),
+                ("max_freq", ctx.max_freq),
+                ("audio_len", ctx.audio_len as f32),
+            ];
+            for (i, (key, value)) in fields.iter().enumerate() {
+                push_key(&mut out, key, i == 0);
+                push_number(&mut out, *value);
+            }
+            out.push('}');
+        }
+        None => out.push_str("null"),
+    }
+    out.push('}');
+    out
+}
+
+/// Install a panic hook that logs a structured report (message, location,
+/// failing frame index and analysis config) to `console.error` and keeps it
+/// for `last_panic_report()`. Safe to call more than once.
+///
+/// On wasm a panic aborts the module call, which JS sees as a
+/// `RuntimeError: unreachable`; the report is how that error becomes
+/// actionable.
+#[wasm_bindgen]
+pub fn install_panic_hook() {
+    INSTALL.call_once(|| {
+        std::panic::set_hook(Box::new(|info| {
+            let message = info
+                .payload()
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| info.payload().downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic".to_string());
+            let location = info
+                .location()
+                .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+            let report = build_report(&message, location);
+
+            #[cfg(target_arch = "wasm32")]
+            console_error(&report);
+            #[cfg(not(target_arch = "wasm32"))]
+            eprintln!("{}", report);
+
+            LAST_REPORT.with(|r| *r.borrow_mut() = Some(report));
+        }));
+    });
+}
+
+/// JSON report of the most recent panic, if any
+#[wasm_bindgen]
+pub fn last_panic_report() -> Option<String> {
+    LAST_REPORT.with(|r| r.borrow().clone())
+}
+
+/// Validated variant of `perform_yin_analysis`: configuration problems that
+/// would otherwise panic or silently return nothing (see `YinConfig::check`)
+/// are returned as a `JsError` whose message is a JSON report carrying the
+/// config.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn try_perform_yin_analysis(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    threshold: f32,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+) -> Result<Vec<f32>, JsError> {
+    let config = YinConfig {
+        sample_rate,
+        frame_size,
+        hop_size,
+        threshold,
+        min_freq,
+        max_freq,
+        interpolation,
+        ..YinConfig::new()
+    };
+    if let Err(problem) = config.check() {
+        set_context(AnalysisContext {
+            sample_rate,
+            frame_size,
+            hop_size,
+            threshold,
+            min_freq,
+            max_freq,
+            audio_len: audio_data.len(),
+        });
+        let report = build_report(&problem.to_string(), None);
+        clear_context();
+        return Err(JsError::new(&report));
+    }
+
+    Ok(perform_yin_analysis(
+        audio_data,
+        sample_rate,
+        frame_size,
+        hop_size,
+        threshold,
+        min_freq,
+        max_freq,
+        interpolation,
+    ))
+}
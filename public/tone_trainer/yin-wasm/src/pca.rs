@@ -0,0 +1,205 @@
+use wasm_bindgen::prelude::*;
+
+use crate::fit::{FitQuality, MAX_FIT_RMS_SEMITONES};
+
+/// Power-iteration steps per component
+const POWER_ITERATIONS: usize = 200;
+
+/// Convergence tolerance for power iteration
+const POWER_TOLERANCE: f64 = 1e-10;
+
+/// Principal components of a set of equal-length contours
+#[wasm_bindgen]
+pub struct ContourPca {
+    mean: Vec<f32>,
+    components: Vec<f32>,
+    explained_variance: Vec<f32>,
+    projections: Vec<f32>,
+    n_points: usize,
+    n_components: usize,
+}
+
+#[wasm_bindgen]
+impl ContourPca {
+    /// Mean contour
+    #[wasm_bindgen(getter)]
+    pub fn mean(&self) -> Vec<f32> {
+        self.mean.clone()
+    }
+
+    /// Components as a flat array, `n_components` rows of `n_points` values
+    #[wasm_bindgen(getter)]
+    pub fn components(&self) -> Vec<f32> {
+        self.components.clone()
+    }
+
+    /// Variance captured by each component
+    #[wasm_bindgen(getter)]
+    pub fn explained_variance(&self) -> Vec<f32> {
+        self.explained_variance.clone()
+    }
+
+    /// Per-contour scores as a flat array, one row of `n_components` values
+    /// per input contour
+    #[wasm_bindgen(getter)]
+    pub fn projections(&self) -> Vec<f32> {
+        self.projections.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn n_points(&self) -> usize {
+        self.n_points
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn n_components(&self) -> usize {
+        self.n_components
+    }
+
+    /// Project a new contour of `n_points` values onto the components
+    pub fn project(&self, contour: &[f32]) -> Vec<f32> {
+        if contour.len() != self.n_points {
+            return Vec::new();
+        }
+        self.components
+            .chunks(self.n_points)
+            .map(|component| {
+                contour
+                    .iter()
+                    .zip(&self.mean)
+                    .zip(component)
+                    .map(|((x, m), c)| (x - m) * c)
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// How well the components reconstruct a contour of `n_points`
+    /// (semitone) values: residuals of the mean plus its projection. A
+    /// contour the components don't describe gets an unreliable fit, so
+    /// its projection can be ignored.
+    pub fn fit_quality(&self, contour: &[f32]) -> FitQuality {
+        let projection = self.project(contour);
+        if projection.is_empty() {
+            return FitQuality::from_residuals(&[], &[], 0.0, MAX_FIT_RMS_SEMITONES);
+        }
+        let mut reconstruction = self.mean.clone();
+        for (score, component) in projection.iter().zip(self.components.chunks(self.n_points)) {
+            for (value, c) in reconstruction.iter_mut().zip(component) {
+                *value += score * c;
+            }
+        }
+        FitQuality::from_residuals(contour, &reconstruction, 1.0, MAX_FIT_RMS_SEMITONES)
+    }
+}
+
+impl ContourPca {
+    /// Fit PCA to `contours` (all of length `n_points`), keeping at most
+    /// `n_components` components. Uses power iteration with deflation on the
+    /// covariance matrix, which is plenty for the ~100-point contours we store.
+    pub fn fit(contours: &[&[f32]], n_points: usize, n_components: usize) -> Option<Self> {
+        if contours.is_empty() || n_points == 0 || contours.iter().any(|c| c.len() != n_points) {
+            return None;
+        }
+        let n_components = n_components.min(n_points);
+        let count = contours.len() as f64;
+
+        let mut mean = vec![0.0f64; n_points];
+        for contour in contours {
+            for (m, &x) in mean.iter_mut().zip(contour.iter()) {
+                *m += x as f64 / count;
+            }
+        }
+
+        let mut cov = vec![0.0f64; n_points * n_points];
+        for contour in contours {
+            for i in 0..n_points {
+                let di = contour[i] as f64 - mean[i];
+                for j in i..n_points {
+                    let v = di * (contour[j] as f64 - mean[j]) / count;
+                    cov[i * n_points + j] += v;
+                    if i != j {
+                        cov[j * n_points + i] += v;
+                    }
+                }
+            }
+        }
+
+        let mut components = Vec::with_capacity(n_components * n_points);
+        let mut explained_variance = Vec::with_capacity(n_components);
+        for k in 0..n_components {
+            let (eigenvalue, vector) = power_iteration(&cov, n_points, k);
+            if eigenvalue <= 0.0 {
+                break;
+            }
+            // Deflate so the next iteration finds the next component
+            for i in 0..n_points {
+                for j in 0..n_points {
+                    cov[i * n_points + j] -= eigenvalue * vector[i] * vector[j];
+                }
+            }
+            explained_variance.push(eigenvalue as f32);
+            components.extend(vector.iter().map(|&v| v as f32));
+        }
+
+        let mut pca = ContourPca {
+            mean: mean.iter().map(|&m| m as f32).collect(),
+            n_components: explained_variance.len(),
+            components,
+            explained_variance,
+            projections: Vec::new(),
+            n_points,
+        };
+        pca.projections = contours.iter().flat_map(|c| pca.project(c)).collect();
+        Some(pca)
+    }
+}
+
+/// Dominant eigenpair of a symmetric matrix. `seed` varies the start vector
+/// so successive deflated calls don't begin orthogonal to their target.
+fn power_iteration(matrix: &[f64], n: usize, seed: usize) -> (f64, Vec<f64>) {
+    let mut vector: Vec<f64> = (0..n)
+        .map(|i| 1.0 + ((i + seed) % 7) as f64 * 0.1)
+        .collect();
+    normalize(&mut vector);
+
+    let mut eigenvalue = 0.0;
+    for _ in 0..POWER_ITERATIONS {
+        let mut next: Vec<f64> = (0..n)
+            .map(|i| (0..n).map(|j| matrix[i * n + j] * vector[j]).sum())
+            .collect();
+        let norm = normalize(&mut next);
+        if norm == 0.0 {
+            return (0.0, vector);
+        }
+        let delta: f64 = next.iter().zip(&vector).map(|(a, b)| (a - b).abs()).sum();
+        vector = next;
+        eigenvalue = norm;
+        if delta < POWER_TOLERANCE {
+            break;
+        }
+    }
+
+    (eigenvalue, vector)
+}
+
+/// Scale to unit length, returning the original length
+fn normalize(vector: &mut [f64]) -> f64 {
+    let norm = vector.iter().map(|v| v * v).sum::<f64>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    norm
+}
+
+/// Functional PCA over stored contours. `contours` is a flat array of
+/// equal-length contours (`n_points` values each, e.g. resampled and
+/// speaker-normalized attempts). Returns `None` if the lengths don't match.
+#[wasm_bindgen]
+pub fn contour_pca(contours: &[f32], n_points: usize, n_components: usize) -> Option<ContourPca> {
+    if n_points == 0 || !contours.len().is_multiple_of(n_points) {
+        return None;
+    }
+    let rows: Vec<&[f32]> = contours.chunks(n_points).collect();
+    ContourPca::fit(&rows, n_points, n_components)
+}
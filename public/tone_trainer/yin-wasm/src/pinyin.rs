@@ -0,0 +1,359 @@
+//! Pinyin parsing into syllables with tone numbers.
+//!
+//! Accepts numbered ("lv4", "lu:4") and diacritic ("lǜ") spellings in any
+//! case. ü is handled internally as "v" so syllables can be sliced by byte.
+
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+/// Tone number used for neutral (unmarked) syllables
+pub const NEUTRAL_TONE: u8 = 5;
+
+/// Initials, longest first so "zh" is tried before "z"
+const INITIALS: &[&str] = &[
+    "zh", "ch", "sh", "b", "p", "m", "f", "d", "t", "n", "l", "g", "k", "h", "j", "q", "x", "r",
+    "z", "c", "s", "y", "w",
+];
+
+/// Aspirated initials, after which the voice starts a tone raised (see
+/// `ASPIRATION_ONSET_RAISE` in tones.rs)
+const ASPIRATED_INITIALS: &[&str] = &["p", "t", "k", "q", "c", "ch"];
+
+/// Finals, longest first ("v" stands for ü)
+const FINALS: &[&str] = &[
+    "iang", "iong", "uang", "ueng", "ang", "eng", "ing", "ong", "ian", "iao", "uai", "uan", "van",
+    "ai", "ei", "ao", "ou", "an", "en", "er", "ia", "ie", "iu", "in", "ua", "uo", "ui", "un", "ue",
+    "ve", "vn", "a", "o", "e", "i", "u", "v",
+];
+
+/// Finals spelled after the "y" initial (yi, ya, you, yuan, ...)
+const Y_FINALS: &[&str] = &[
+    "i", "a", "e", "ao", "ou", "an", "in", "ang", "ing", "ong", "u", "ue", "uan", "un", "v", "ve",
+    "van", "vn",
+];
+
+/// Finals spelled after the "w" initial (wu, wa, wei, weng, ...)
+const W_FINALS: &[&str] = &["u", "a", "o", "ai", "ei", "an", "en", "ang", "eng"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PinyinError {
+    /// Text that can't be split into pinyin syllables
+    UnknownSyllable(String),
+    /// A tone digit outside 0-5
+    BadTone(char),
+    /// A character that isn't part of pinyin
+    UnexpectedChar(char),
+}
+
+impl fmt::Display for PinyinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PinyinError::UnknownSyllable(text) => write!(f, "not a pinyin syllable: {:?}", text),
+            PinyinError::BadTone(digit) => write!(f, "tone number must be 0-5, got {}", digit),
+            PinyinError::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+        }
+    }
+}
+
+/// One parsed syllable, spelled canonically: lowercase, without tone
+/// marks, with ü written out after n/l ("lüe") and as u after j/q/x/y ("xue")
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinyinSyllable {
+    initial: String,
+    final_: String,
+    tone: u8,
+    marked: bool,
+    erhua: bool,
+}
+
+#[wasm_bindgen]
+impl PinyinSyllable {
+    /// Toneless syllable, e.g. "hao" or "dianr"
+    #[wasm_bindgen(getter)]
+    pub fn syllable(&self) -> String {
+        let r = if self.erhua { "r" } else { "" };
+        format!("{}{}{}", self.initial, self.final_, r)
+    }
+
+    /// Spelled initial, e.g. "zh"; "y" and "w" count as initials and
+    /// vowel-initial syllables have ""
+    #[wasm_bindgen(getter)]
+    pub fn initial(&self) -> String {
+        self.initial.clone()
+    }
+
+    /// Spelled final, everything after the initial
+    #[wasm_bindgen(getter = final)]
+    pub fn final_(&self) -> String {
+        self.final_.clone()
+    }
+
+    /// Tone number 1-4, or 5 for the neutral tone
+    #[wasm_bindgen(getter)]
+    pub fn tone(&self) -> u8 {
+        self.tone
+    }
+
+    /// Whether the tone was written (mark, digit or neutral dot) rather than
+    /// defaulted to neutral
+    #[wasm_bindgen(getter)]
+    pub fn marked(&self) -> bool {
+        self.marked
+    }
+
+    /// Whether the final is rhotacized (儿化, e.g. "dianr"). The 儿 is merged
+    /// into the syllable and carries its tone rather than counting as one.
+    #[wasm_bindgen(getter)]
+    pub fn erhua(&self) -> bool {
+        self.erhua
+    }
+
+    /// Whether the syllable has the neutral tone
+    #[wasm_bindgen(getter)]
+    pub fn neutral(&self) -> bool {
+        self.tone == NEUTRAL_TONE
+    }
+
+    /// Whether the initial is aspirated (p, t, k, q, c, ch)
+    #[wasm_bindgen(getter)]
+    pub fn aspirated(&self) -> bool {
+        ASPIRATED_INITIALS.contains(&self.initial.as_str())
+    }
+}
+
+/// Strip a tone mark from a vowel, returning (base, tone)
+fn strip_tone_mark(c: char) -> Option<(char, u8)> {
+    let (base, tone) = match c {
+        'ā' => ('a', 1),
+        'á' => ('a', 2),
+        'ǎ' => ('a', 3),
+        'à' => ('a', 4),
+        'ē' => ('e', 1),
+        'é' => ('e', 2),
+        'ě' => ('e', 3),
+        'è' => ('e', 4),
+        'ī' => ('i', 1),
+        'í' => ('i', 2),
+        'ǐ' => ('i', 3),
+        'ì' => ('i', 4),
+        'ō' => ('o', 1),
+        'ó' => ('o', 2),
+        'ǒ' => ('o', 3),
+        'ò' => ('o', 4),
+        'ū' => ('u', 1),
+        'ú' => ('u', 2),
+        'ǔ' => ('u', 3),
+        'ù' => ('u', 4),
+        'ǖ' => ('v', 1),
+        'ǘ' => ('v', 2),
+        'ǚ' => ('v', 3),
+        'ǜ' => ('v', 4),
+        _ => return None,
+    };
+    Some((base, tone))
+}
+
+/// Letters that start a final ("v" stands for ü)
+const VOWELS: [char; 6] = ['a', 'o', 'e', 'i', 'u', 'v'];
+
+fn valid_syllable(initial: &str, final_: &str) -> bool {
+    match initial {
+        "" => final_.starts_with(['a', 'o', 'e']),
+        "y" => Y_FINALS.contains(&final_),
+        "w" => W_FINALS.contains(&final_),
+        "j" | "q" | "x" => final_.starts_with(['i', 'u', 'v']) && final_ != "ueng",
+        "n" | "l" => final_ != "er" && final_ != "ueng",
+        _ => final_ != "er" && final_ != "ueng" && !final_.starts_with('v'),
+    }
+}
+
+/// Split toneless letters into (length, erhua) syllables, where the length
+/// excludes an erhua "r". A syllable starting with a vowel never directly
+/// follows another one (pinyin writes an apostrophe there), which resolves
+/// "xian" as one syllable and "dangan" as dan-gan. An "r" that can't start
+/// the next syllable rhotacizes the previous one ("yidianr", "huarmen").
+fn segment(letters: &str) -> Option<Vec<(usize, bool)>> {
+    if letters.is_empty() {
+        return Some(Vec::new());
+    }
+    for initial in INITIALS.iter().copied().chain(std::iter::once("")) {
+        let Some(rest) = letters.strip_prefix(initial) else {
+            continue;
+        };
+        for final_ in FINALS {
+            let Some(tail) = rest.strip_prefix(final_) else {
+                continue;
+            };
+            if !valid_syllable(initial, final_) || tail.starts_with(['a', 'o', 'e']) {
+                continue;
+            }
+            let len = initial.len() + final_.len();
+            if let Some(mut lengths) = segment(tail) {
+                lengths.insert(0, (len, false));
+                return Some(lengths);
+            }
+            let erhua_tail = tail
+                .strip_prefix('r')
+                .filter(|rest| *final_ != "er" && !rest.starts_with(VOWELS));
+            if let Some(mut lengths) = erhua_tail.and_then(segment) {
+                lengths.insert(0, (len, true));
+                return Some(lengths);
+            }
+        }
+    }
+    None
+}
+
+/// Canonical spelling of a final: "v" becomes ü after n/l (where "ue" is
+/// also read as üe) and u after j/q/x/y, which write ü without the dots
+fn canonical_final(initial: &str, final_: &str) -> String {
+    match (initial, final_) {
+        ("n" | "l", "ue") => "üe".to_string(),
+        ("n" | "l", _) => final_.replace('v', "ü"),
+        _ => final_.replace('v', "u"),
+    }
+}
+
+/// Length of the initial at the start of a segmented syllable
+fn initial_len(syllable: &str) -> usize {
+    INITIALS
+        .iter()
+        .find(|initial| {
+            syllable
+                .strip_prefix(**initial)
+                .is_some_and(|rest| FINALS.contains(&rest) && valid_syllable(initial, rest))
+        })
+        .map_or(0, |initial| initial.len())
+}
+
+/// Parse one run of letters (no separators or digits) into syllables.
+/// `marks` holds the tone mark of each letter; `digit` is a tone number
+/// written right after the run and applies to its last syllable. A leading
+/// neutral dot ("·de") makes the first syllable neutral. A lone "r" after
+/// a tone digit or space ("dian3r") rhotacizes the previous syllable.
+fn parse_run(
+    letters: &str,
+    marks: &[Option<u8>],
+    neutral_dot: bool,
+    digit: Option<u8>,
+    out: &mut Vec<PinyinSyllable>,
+) -> Result<(), PinyinError> {
+    if letters == "r" {
+        if let Some(last) = out.last_mut().filter(|s| !s.erhua && s.final_ != "er") {
+            last.erhua = true;
+            return Ok(());
+        }
+    }
+
+    let lengths = segment(letters).ok_or_else(|| PinyinError::UnknownSyllable(letters.into()))?;
+    let mut start = 0;
+    for (i, &(len, erhua)) in lengths.iter().enumerate() {
+        let end = start + len;
+        let mark = marks[start..end].iter().flatten().next().copied();
+        let dot = (neutral_dot && i == 0).then_some(NEUTRAL_TONE);
+        let written = if i + 1 == lengths.len() { digit } else { None };
+        let tone = mark.or(dot).or(written);
+
+        let syllable = &letters[start..end];
+        let (initial, final_) = syllable.split_at(initial_len(syllable));
+        out.push(PinyinSyllable {
+            initial: initial.to_string(),
+            final_: canonical_final(initial, final_),
+            tone: tone.unwrap_or(NEUTRAL_TONE),
+            marked: tone.is_some(),
+            erhua,
+        });
+        start = end + erhua as usize;
+    }
+    Ok(())
+}
+
+/// Whether `c` separates syllables: whitespace, hyphens, apostrophes
+/// (including typographic ones) and punctuation
+fn is_separator(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(c, '’' | '‘' | 'ʼ' | '，' | '。' | '？' | '！' | '、')
+        || (c.is_ascii_punctuation() && c != ':')
+}
+
+/// Parse pinyin in numbered ("ni3hao3") or diacritic ("nǐhǎo") form.
+/// Syllables may be run together or separated by spaces, hyphens,
+/// apostrophes or punctuation; case is ignored. ü may be written as "ü",
+/// "v" or "u:". The neutral tone may be written as 5, 0, a leading middle
+/// dot ("·de") or left unmarked.
+pub fn parse(text: &str) -> Result<Vec<PinyinSyllable>, PinyinError> {
+    let mut out = Vec::new();
+    let mut letters = String::new();
+    let mut marks = Vec::new();
+    let mut neutral_dot = false;
+
+    let mut chars = text.chars().flat_map(char::to_lowercase).peekable();
+    while let Some(c) = chars.next() {
+        let digit = match c {
+            '1'..='5' => Some(c as u8 - b'0'),
+            '0' => Some(NEUTRAL_TONE),
+            '6'..='9' => return Err(PinyinError::BadTone(c)),
+            _ => None,
+        };
+        if digit.is_some() || is_separator(c) || c == '·' {
+            if !letters.is_empty() {
+                parse_run(&letters, &marks, neutral_dot, digit, &mut out)?;
+                letters.clear();
+                marks.clear();
+            }
+            neutral_dot = c == '·';
+            continue;
+        }
+
+        let (base, mark) = match strip_tone_mark(c) {
+            Some((base, tone)) => (base, Some(tone)),
+            None if c == 'ü' => ('v', None),
+            None if c == 'u' && chars.peek() == Some(&':') => {
+                chars.next();
+                ('v', None)
+            }
+            None if c.is_ascii_lowercase() => (c, None),
+            None => return Err(PinyinError::UnexpectedChar(c)),
+        };
+        letters.push(base);
+        marks.push(mark);
+    }
+    if !letters.is_empty() {
+        parse_run(&letters, &marks, neutral_dot, None, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Per-syllable facts the scorer needs, one entry per syllable
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToneSequence {
+    pub tones: Vec<u8>,
+    pub erhua: Vec<bool>,
+    pub aspirated: Vec<bool>,
+}
+
+/// Tone numbers, erhua flags and aspirated-initial flags of a pinyin string
+pub fn parse_tones(text: &str) -> Result<ToneSequence, PinyinError> {
+    let syllables = parse(text)?;
+    Ok(ToneSequence {
+        tones: syllables.iter().map(|s| s.tone).collect(),
+        erhua: syllables.iter().map(|s| s.erhua).collect(),
+        aspirated: syllables.iter().map(PinyinSyllable::aspirated).collect(),
+    })
+}
+
+/// Parse pinyin (see `parse`) into syllables with tone numbers
+#[wasm_bindgen]
+pub fn parse_pinyin(text: &str) -> Result<Vec<PinyinSyllable>, JsError> {
+    parse(text).map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// Tone numbers of a pinyin string, in the form `score_expected_contour`
+/// and `diagnose_expected_contour` take
+#[wasm_bindgen]
+pub fn pinyin_tones(text: &str) -> Result<Vec<u8>, JsError> {
+    let sequence = parse_tones(text).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(sequence.tones)
+}
@@ -0,0 +1,171 @@
+//! Clean-up of raw YIN pitch tracks: octave-error correction, median
+//! filtering and interpolation across short unvoiced gaps
+
+use wasm_bindgen::prelude::*;
+
+use crate::tones::voiced_median;
+
+/// Half-width (frames) of the neighbourhood an octave jump is judged against
+const OCTAVE_CONTEXT_FRAMES: usize = 15;
+
+/// Largest distance (octaves) from an exact doubling or halving that still
+/// counts as an octave error
+const OCTAVE_TOLERANCE: f32 = 0.25;
+
+/// Settings for `smooth_pitch_contour`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct SmoothingSettings {
+    /// Median filter length in frames (rounded up to odd; 1 disables it)
+    pub median_window: usize,
+    /// Fold frames that jump an octave away from their neighbourhood back
+    pub correct_octaves: bool,
+    /// Interpolate unvoiced gaps up to this many frames (0 disables it)
+    pub max_gap_frames: usize,
+}
+
+#[wasm_bindgen]
+impl SmoothingSettings {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SmoothingSettings {
+        SmoothingSettings {
+            median_window: 5,
+            correct_octaves: true,
+            max_gap_frames: 5,
+        }
+    }
+}
+
+impl Default for SmoothingSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Raw and cleaned pitch tracks side by side
+#[wasm_bindgen]
+pub struct SmoothedContour {
+    raw: Vec<f32>,
+    cleaned: Vec<f32>,
+    octave_corrections: usize,
+    interpolated_frames: usize,
+}
+
+#[wasm_bindgen]
+impl SmoothedContour {
+    /// Input pitch track, unchanged
+    #[wasm_bindgen(getter)]
+    pub fn raw(&self) -> Vec<f32> {
+        self.raw.clone()
+    }
+
+    /// Cleaned pitch track, same length as `raw`
+    #[wasm_bindgen(getter)]
+    pub fn cleaned(&self) -> Vec<f32> {
+        self.cleaned.clone()
+    }
+
+    /// Frames moved by an octave
+    #[wasm_bindgen(getter)]
+    pub fn octave_corrections(&self) -> usize {
+        self.octave_corrections
+    }
+
+    /// Unvoiced frames filled in by gap interpolation
+    #[wasm_bindgen(getter)]
+    pub fn interpolated_frames(&self) -> usize {
+        self.interpolated_frames
+    }
+}
+
+/// Fold voiced frames that are about an octave above or below the median of
+/// their neighbourhood. Returns the number of frames changed.
+pub fn correct_octave_errors(pitch: &mut [f32]) -> usize {
+    let original = pitch.to_vec();
+    let mut corrected = 0;
+    for (i, p) in pitch.iter_mut().enumerate() {
+        if *p <= 0.0 {
+            continue;
+        }
+        let start = i.saturating_sub(OCTAVE_CONTEXT_FRAMES);
+        let end = (i + OCTAVE_CONTEXT_FRAMES + 1).min(original.len());
+        let reference = voiced_median(&original[start..end]);
+        if reference <= 0.0 {
+            continue;
+        }
+        let octaves = (*p / reference).log2();
+        if (octaves - 1.0).abs() < OCTAVE_TOLERANCE {
+            *p /= 2.0;
+            corrected += 1;
+        } else if (octaves + 1.0).abs() < OCTAVE_TOLERANCE {
+            *p *= 2.0;
+            corrected += 1;
+        }
+    }
+    corrected
+}
+
+/// Median filter over the voiced frames; unvoiced frames stay unvoiced and
+/// don't pull voiced ones towards 0
+pub fn median_filter_voiced(pitch: &[f32], window: usize) -> Vec<f32> {
+    let radius = window / 2;
+    if radius == 0 {
+        return pitch.to_vec();
+    }
+    (0..pitch.len())
+        .map(|i| {
+            if pitch[i] <= 0.0 {
+                return pitch[i];
+            }
+            let start = i.saturating_sub(radius);
+            let end = (i + radius + 1).min(pitch.len());
+            voiced_median(&pitch[start..end])
+        })
+        .collect()
+}
+
+/// Fill unvoiced gaps of at most `max_gap` frames between two voiced frames,
+/// interpolating linearly in semitones. Returns the number of frames filled.
+pub fn interpolate_gaps(pitch: &mut [f32], max_gap: usize) -> usize {
+    let mut filled = 0;
+    let mut last_voiced: Option<usize> = None;
+    for i in 0..pitch.len() {
+        if pitch[i] <= 0.0 {
+            continue;
+        }
+        if let Some(prev) = last_voiced {
+            let gap = i - prev - 1;
+            if gap > 0 && gap <= max_gap {
+                let (a, b) = (pitch[prev].log2(), pitch[i].log2());
+                for k in 1..=gap {
+                    let t = k as f32 / (gap + 1) as f32;
+                    pitch[prev + k] = (a + (b - a) * t).exp2();
+                }
+                filled += gap;
+            }
+        }
+        last_voiced = Some(i);
+    }
+    filled
+}
+
+/// Clean a raw pitch track (Hz, <= 0 for unvoiced): octave errors are
+/// corrected first so the median filter isn't skewed by them, then the
+/// median filter removes jitter and finally short gaps are interpolated.
+#[wasm_bindgen]
+pub fn smooth_pitch_contour(pitch: &[f32], settings: &SmoothingSettings) -> SmoothedContour {
+    let mut cleaned = pitch.to_vec();
+    let octave_corrections = if settings.correct_octaves {
+        correct_octave_errors(&mut cleaned)
+    } else {
+        0
+    };
+    let mut cleaned = median_filter_voiced(&cleaned, settings.median_window);
+    let interpolated_frames = interpolate_gaps(&mut cleaned, settings.max_gap_frames);
+    SmoothedContour {
+        raw: pitch.to_vec(),
+        cleaned,
+        octave_corrections,
+        interpolated_frames,
+    }
+}
@@ -0,0 +1,159 @@
+use wasm_bindgen::prelude::*;
+
+use crate::config::{frame_count_error, ConfigError, MAX_FRAME_SIZE};
+use crate::logging::log_warn;
+use crate::{
+    yin_cumulative_mean_normalized_difference, yin_difference_function, yin_local_minima,
+    yin_parabolic_interpolation,
+};
+
+/// Pitch settings mirroring Praat's "To Pitch (ac)..." defaults, so results can
+/// be compared with what teachers see in Praat
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct PraatSettings {
+    /// Seconds between frames; 0 selects Praat's automatic 0.75 / pitch_floor
+    pub time_step: f32,
+    /// Lowest pitch considered (Hz); also sets the window to 3 periods of it
+    pub pitch_floor: f32,
+    /// Highest pitch considered (Hz)
+    pub pitch_ceiling: f32,
+    /// Bonus per octave for higher-frequency candidates
+    pub octave_cost: f32,
+    /// Minimum periodicity strength for a frame to count as voiced
+    pub voicing_threshold: f32,
+    /// Frames quieter than this fraction of the global peak are unvoiced
+    pub silence_threshold: f32,
+}
+
+#[wasm_bindgen]
+impl PraatSettings {
+    /// Praat's default settings
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PraatSettings {
+        PraatSettings {
+            time_step: 0.0,
+            pitch_floor: 75.0,
+            pitch_ceiling: 600.0,
+            octave_cost: 0.01,
+            voicing_threshold: 0.45,
+            silence_threshold: 0.03,
+        }
+    }
+
+    /// Effective time step in seconds
+    pub fn effective_time_step(&self) -> f32 {
+        if self.time_step > 0.0 {
+            self.time_step
+        } else {
+            0.75 / self.pitch_floor
+        }
+    }
+}
+
+impl Default for PraatSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Analyze audio with Praat-style framing and candidate selection.
+///
+/// Frames use a window of 3 periods of the pitch floor and are centred in the
+/// signal the way Praat places them. Among the CMNDF minima in range, the
+/// candidate with the highest `strength - octave_cost * log2(floor / f)` wins,
+/// where strength is `1 - CMNDF`. Praat's cross-frame path finder is not
+/// emulated, so octave-jump and voiced/unvoiced costs have no effect.
+///
+/// Returns a flat array: [time1, pitch1, strength1, time2, ...] with pitch 0
+/// for unvoiced frames.
+#[wasm_bindgen]
+pub fn perform_praat_analysis(
+    audio_data: &[f32],
+    sample_rate: f32,
+    settings: &PraatSettings,
+) -> Vec<f32> {
+    let mut results = Vec::new();
+    if !(sample_rate.is_finite() && sample_rate > 0.0)
+        || settings.pitch_floor <= 0.0
+        || settings.pitch_ceiling <= settings.pitch_floor
+    {
+        return results;
+    }
+
+    let dt = settings.effective_time_step();
+    // The YIN difference function uses the first half of the buffer as its
+    // window, so the buffer spans twice Praat's 3-period window
+    let window = (3.0 / settings.pitch_floor * sample_rate).round() as usize;
+    let frame_size = window.saturating_mul(2);
+    if frame_size > MAX_FRAME_SIZE {
+        log_warn!(
+            "{}",
+            ConfigError::FrameTooLong {
+                frame_size,
+                max: MAX_FRAME_SIZE
+            }
+        );
+        return results;
+    }
+    let duration = audio_data.len() as f32 / sample_rate;
+    let window_duration = frame_size as f32 / sample_rate;
+    if duration < window_duration {
+        return results;
+    }
+
+    // The time step plays the hop's part in the frame limit, so a tiny step
+    // is refused like a tiny hop (the cast saturates)
+    let num_frames = (((duration - window_duration) / dt).floor() as usize).saturating_add(1);
+    if let Some(err) = frame_count_error(audio_data.len(), sample_rate, num_frames) {
+        log_warn!("{}", err);
+        return results;
+    }
+    let first_centre = 0.5 * (duration - (num_frames - 1) as f32 * dt);
+    let global_peak = audio_data.iter().fold(0.0f32, |m, &x| m.max(x.abs()));
+
+    let tau_min = ((sample_rate / settings.pitch_ceiling).floor() as usize).max(2);
+    let tau_max = (sample_rate / settings.pitch_floor).ceil() as usize;
+
+    results.reserve(num_frames * 3);
+    for i in 0..num_frames {
+        let centre = first_centre + i as f32 * dt;
+        let start = ((centre * sample_rate).round() as usize).saturating_sub(frame_size / 2);
+        let end = (start + frame_size).min(audio_data.len());
+        let frame = &audio_data[end - frame_size..end];
+
+        let local_peak = frame.iter().fold(0.0f32, |m, &x| m.max(x.abs()));
+        let mut pitch = 0.0;
+        let mut strength = 0.0;
+
+        if local_peak >= settings.silence_threshold * global_peak {
+            let cmndf = yin_cumulative_mean_normalized_difference(&yin_difference_function(frame));
+            let best = yin_local_minima(&cmndf, tau_min, tau_max)
+                .into_iter()
+                .map(|tau| {
+                    let refined = yin_parabolic_interpolation(&cmndf, tau as i32);
+                    let freq = sample_rate / refined;
+                    let s = (1.0 - cmndf[tau]).max(0.0);
+                    let score = s - settings.octave_cost * (settings.pitch_floor / freq).log2();
+                    (freq, s, score)
+                })
+                .filter(|&(freq, _, _)| {
+                    freq >= settings.pitch_floor && freq <= settings.pitch_ceiling
+                })
+                .max_by(|a, b| a.2.total_cmp(&b.2));
+
+            if let Some((freq, s, _)) = best {
+                if s >= settings.voicing_threshold {
+                    pitch = freq;
+                    strength = s;
+                }
+            }
+        }
+
+        results.push(centre);
+        results.push(pitch);
+        results.push(strength);
+    }
+
+    results
+}
@@ -0,0 +1,300 @@
+//! The learner's voice: median pitch and usual range, learned from their
+//! attempts. Every attempt is blended in with older data slowly forgotten,
+//! so the profile keeps improving (and follows a changing voice) without
+//! explicit recalibration sessions.
+//!
+//! Several named profiles can live in one module instance (family members
+//! sharing a deck); the selected one narrows the range of `global_defaults`
+//! and is the reference of `normalize_contour`.
+
+use std::cell::RefCell;
+
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{BinaryError, ByteReader, ByteWriter};
+use crate::config::YinConfig;
+use crate::scale::{normalize_contour_to, ContourUnit, NormalizedContour};
+
+const PROFILE_MAGIC: &[u8; 4] = b"YINP";
+const PROFILE_VERSION: u8 = 1;
+
+/// Percentiles of the voiced pitch taken as the bottom and top of the range
+const FLOOR_PERCENTILE: f32 = 0.05;
+const CEILING_PERCENTILE: f32 = 0.95;
+
+/// Factor the accumulated weight is multiplied by at every update, so old
+/// attempts fade out
+const FORGETTING: f32 = 0.95;
+
+/// Cap on the accumulated weight (voiced frames, about half a minute of
+/// speech) so new attempts always move the profile
+const MAX_WEIGHT: f32 = 3000.0;
+
+/// Voiced frames an attempt needs to be blended in
+const MIN_VOICED_FRAMES: usize = 10;
+
+/// Margin (semitones) around the range when choosing the analysis range
+const RANGE_MARGIN_SEMITONES: f32 = 4.0;
+
+/// Lowest and highest analysis range `apply_to` picks (Hz)
+const MIN_ANALYSIS_HZ: f32 = 50.0;
+const MAX_ANALYSIS_HZ: f32 = 1000.0;
+
+/// Value at `fraction` (0..1) of the sorted values
+fn percentile(sorted: &[f32], fraction: f32) -> f32 {
+    sorted[((sorted.len() - 1) as f32 * fraction).round() as usize]
+}
+
+/// A learner's pitch statistics; all levels are kept as log2(Hz) so
+/// blending averages ratios rather than Hz
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpeakerProfile {
+    median: f32,
+    floor: f32,
+    ceiling: f32,
+    /// Voiced frames behind the statistics, after forgetting
+    weight: f32,
+    updates: usize,
+}
+
+#[wasm_bindgen]
+impl SpeakerProfile {
+    /// An empty profile; it is calibrated by the first `update_with`
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SpeakerProfile {
+        SpeakerProfile::default()
+    }
+
+    /// Blend an attempt's Hz pitch track (0 for unvoiced frames) into the
+    /// profile, weighted by its voiced frames against the forgotten weight
+    /// of earlier attempts. Returns false (and leaves the profile alone)
+    /// when the attempt has too little voicing.
+    pub fn update_with(&mut self, track: &[f32]) -> bool {
+        let mut voiced: Vec<f32> = track
+            .iter()
+            .filter(|&&p| p > 0.0)
+            .map(|p| p.log2())
+            .collect();
+        if voiced.len() < MIN_VOICED_FRAMES {
+            return false;
+        }
+        voiced.sort_by(f32::total_cmp);
+
+        let old = self.weight * FORGETTING;
+        let new = voiced.len() as f32;
+        let blend = |current: f32, value: f32| (current * old + value * new) / (old + new);
+        self.median = blend(self.median, percentile(&voiced, 0.5));
+        self.floor = blend(self.floor, percentile(&voiced, FLOOR_PERCENTILE));
+        self.ceiling = blend(self.ceiling, percentile(&voiced, CEILING_PERCENTILE));
+        self.weight = (old + new).min(MAX_WEIGHT);
+        self.updates += 1;
+        true
+    }
+
+    /// Whether any attempt has been blended in
+    #[wasm_bindgen(getter)]
+    pub fn is_calibrated(&self) -> bool {
+        self.updates > 0
+    }
+
+    /// Attempts blended in so far
+    #[wasm_bindgen(getter)]
+    pub fn updates(&self) -> usize {
+        self.updates
+    }
+
+    /// Median pitch (Hz); 0 until calibrated
+    #[wasm_bindgen(getter)]
+    pub fn median_hz(&self) -> f32 {
+        self.hz(self.median)
+    }
+
+    /// Bottom of the usual range (Hz); 0 until calibrated
+    #[wasm_bindgen(getter)]
+    pub fn floor_hz(&self) -> f32 {
+        self.hz(self.floor)
+    }
+
+    /// Top of the usual range (Hz); 0 until calibrated
+    #[wasm_bindgen(getter)]
+    pub fn ceiling_hz(&self) -> f32 {
+        self.hz(self.ceiling)
+    }
+
+    /// Width of the usual range in semitones
+    #[wasm_bindgen(getter)]
+    pub fn range_semitones(&self) -> f32 {
+        12.0 * (self.ceiling - self.floor)
+    }
+
+    /// `config` with the frequency range narrowed to this voice plus a
+    /// margin, so octave errors outside it are never picked; unchanged
+    /// until calibrated
+    pub fn apply_to(&self, config: &YinConfig) -> YinConfig {
+        if !self.is_calibrated() {
+            return *config;
+        }
+        let margin = RANGE_MARGIN_SEMITONES / 12.0;
+        let min_freq = 2f32.powf(self.floor - margin).max(MIN_ANALYSIS_HZ);
+        let max_freq = 2f32
+            .powf(self.ceiling + margin)
+            .min(MAX_ANALYSIS_HZ)
+            .min(config.sample_rate / 2.0 * 0.95);
+        config.with_freq_range(min_freq, max_freq.max(min_freq * 2.0))
+    }
+
+    /// A Hz pitch track expressed in `unit` relative to this voice's median
+    pub fn normalize(&self, pitch: &[f32], unit: ContourUnit) -> NormalizedContour {
+        normalize_contour_to(pitch, self.median_hz(), unit)
+    }
+
+    /// Versioned binary form, to keep the profile between sessions
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = ByteWriter::with_header(PROFILE_MAGIC, PROFILE_VERSION);
+        self.write_to(&mut out);
+        out.bytes
+    }
+
+    /// Inverse of `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<SpeakerProfile, JsError> {
+        Self::decode(bytes).map_err(|err| JsError::new(&err.to_string()))
+    }
+}
+
+impl SpeakerProfile {
+    fn hz(&self, level: f32) -> f32 {
+        if self.is_calibrated() {
+            2f32.powf(level)
+        } else {
+            0.0
+        }
+    }
+
+    pub(crate) fn write_to(&self, out: &mut ByteWriter) {
+        out.f32(self.median);
+        out.f32(self.floor);
+        out.f32(self.ceiling);
+        out.f32(self.weight);
+        out.usize(self.updates);
+    }
+
+    pub(crate) fn read_from(input: &mut ByteReader) -> Result<Self, BinaryError> {
+        Ok(SpeakerProfile {
+            median: input.f32()?,
+            floor: input.f32()?,
+            ceiling: input.f32()?,
+            weight: input.f32()?,
+            updates: input.usize()?,
+        })
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let (mut input, version) = ByteReader::with_header(bytes, PROFILE_MAGIC)?;
+        if version != PROFILE_VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+        let profile = Self::read_from(&mut input)?;
+        input.finish()?;
+        Ok(profile)
+    }
+}
+
+thread_local! {
+    /// Named profiles in insertion order, and the selected profile's id
+    static PROFILES: RefCell<Vec<(String, SpeakerProfile)>> = const { RefCell::new(Vec::new()) };
+    static SELECTED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Store `profile` under `id`, replacing any profile with that id
+#[wasm_bindgen]
+pub fn set_profile(id: &str, profile: &SpeakerProfile) {
+    PROFILES.with(|profiles| {
+        let mut profiles = profiles.borrow_mut();
+        match profiles.iter_mut().find(|(key, _)| key == id) {
+            Some((_, stored)) => *stored = profile.clone(),
+            None => profiles.push((id.to_string(), profile.clone())),
+        }
+    });
+}
+
+/// Copy of the profile stored under `id`
+#[wasm_bindgen]
+pub fn get_profile(id: &str) -> Option<SpeakerProfile> {
+    PROFILES.with(|profiles| {
+        profiles
+            .borrow()
+            .iter()
+            .find(|(key, _)| key == id)
+            .map(|(_, profile)| profile.clone())
+    })
+}
+
+/// Delete the profile stored under `id` (deselecting it if selected);
+/// returns whether there was one
+#[wasm_bindgen]
+pub fn remove_profile(id: &str) -> bool {
+    SELECTED.with(|selected| {
+        let mut selected = selected.borrow_mut();
+        if selected.as_deref() == Some(id) {
+            *selected = None;
+        }
+    });
+    PROFILES.with(|profiles| {
+        let mut profiles = profiles.borrow_mut();
+        let before = profiles.len();
+        profiles.retain(|(key, _)| key != id);
+        profiles.len() < before
+    })
+}
+
+/// Ids of the stored profiles, in the order they were added
+#[wasm_bindgen]
+pub fn profile_ids() -> Vec<String> {
+    PROFILES.with(|profiles| profiles.borrow().iter().map(|(id, _)| id.clone()).collect())
+}
+
+/// Make the profile stored under `id` the one used by subsequent calls
+/// (range selection in `global_defaults`, `normalize_contour`); throws when
+/// there is no such profile
+#[wasm_bindgen]
+pub fn select_profile(id: &str) -> Result<(), JsError> {
+    if get_profile(id).is_none() {
+        return Err(JsError::new(&format!("no speaker profile \"{}\"", id)));
+    }
+    SELECTED.with(|selected| *selected.borrow_mut() = Some(id.to_string()));
+    Ok(())
+}
+
+/// Go back to no profile: full default range, per-track normalization
+#[wasm_bindgen]
+pub fn clear_profile_selection() {
+    SELECTED.with(|selected| *selected.borrow_mut() = None);
+}
+
+/// Id of the selected profile
+#[wasm_bindgen]
+pub fn selected_profile_id() -> Option<String> {
+    SELECTED.with(|selected| selected.borrow().clone())
+}
+
+/// Blend `track` into the selected profile (see `update_with`); false when
+/// nothing is selected or the track was too short
+#[wasm_bindgen]
+pub fn update_selected_profile(track: &[f32]) -> bool {
+    let Some(id) = selected_profile_id() else {
+        return false;
+    };
+    PROFILES.with(|profiles| {
+        profiles
+            .borrow_mut()
+            .iter_mut()
+            .find(|(key, _)| *key == id)
+            .is_some_and(|(_, profile)| profile.update_with(track))
+    })
+}
+
+/// The selected profile, if any
+pub(crate) fn selected_profile() -> Option<SpeakerProfile> {
+    selected_profile_id().and_then(|id| get_profile(&id))
+}
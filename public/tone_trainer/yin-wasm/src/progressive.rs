@@ -0,0 +1,154 @@
+//! Two-phase grading for review sessions: an approximate score is ready as
+//! soon as the recording is, from a decimated analysis, and the accurate
+//! pass runs afterwards in bounded steps (like `YinJob`) so the card can
+//! show the quick score at once and update it when the refined one lands.
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::YinConfig;
+use crate::dtw::compare_contours;
+use crate::pager::{JobStatus, YinJob};
+use crate::rubric::ScoringRubric;
+use crate::segments::pitch_column;
+use crate::stream::FrameStream;
+use crate::tones::score_tones;
+
+/// Only every this many frames is analyzed in the quick pass; the frames in
+/// between are interpolated
+const QUICK_STRIDE: usize = 4;
+
+/// Which score a `ProgressiveGrader` has so far
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GradePhase {
+    /// The quick score from the decimated analysis
+    Quick = 0,
+    /// The refined score from the full analysis
+    Refined = 1,
+}
+
+/// Grades one recording in two phases. The constructor runs the quick pass:
+/// a `QUICK_STRIDE`-decimated analysis scored per syllable, without
+/// reference alignment. `refine` then analyzes every frame, a bounded
+/// number per call; when it reports `Done` the scores are recomputed from
+/// the full analysis and, if a reference was given, the contour is compared
+/// with it by DTW.
+#[wasm_bindgen]
+pub struct ProgressiveGrader {
+    job: YinJob,
+    tones: Vec<u8>,
+    rubric: ScoringRubric,
+    reference: Vec<f32>,
+    quick_scores: Vec<f32>,
+    refined_scores: Vec<f32>,
+    similarity: Option<f32>,
+    refined: bool,
+}
+
+#[wasm_bindgen]
+impl ProgressiveGrader {
+    /// Takes ownership of `audio` and computes the quick score. Throws when
+    /// the settings are invalid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        audio: Vec<f32>,
+        config: &YinConfig,
+        tones: Vec<u8>,
+        rubric: &ScoringRubric,
+    ) -> Result<ProgressiveGrader, JsError> {
+        config.validate()?;
+        // Like the refined pass, frame-by-frame analysis without the
+        // whole-recording noise-robust preprocessing
+        let quick = YinConfig {
+            noise_robust: false,
+            ..*config
+        };
+        let mut stream =
+            FrameStream::from_config(&quick).map_err(|err| JsError::new(&err.to_string()))?;
+        stream.set_stride(QUICK_STRIDE);
+        let mut results = Vec::new();
+        stream.push(&audio, &mut results);
+        stream.flush(&mut results);
+        let quick_scores = score_tones(&pitch_column(&results), &tones, &[], &[], true, rubric);
+
+        Ok(ProgressiveGrader {
+            job: YinJob::new(audio, config)?,
+            tones,
+            rubric: rubric.clone(),
+            reference: Vec::new(),
+            quick_scores,
+            refined_scores: Vec::new(),
+            similarity: None,
+            refined: false,
+        })
+    }
+
+    /// Reference Hz pitch track to compare the refined contour with (same
+    /// frame rate); only used by the refined pass
+    pub fn set_reference(&mut self, reference_pitch: &[f32]) {
+        self.reference = reference_pitch.to_vec();
+    }
+
+    /// Analyze up to `max_frames` more frames of the accurate pass. When the
+    /// last frame is done the refined scores are computed and the phase
+    /// becomes `Refined`.
+    pub fn refine(&mut self, max_frames: usize) -> JobStatus {
+        if self.refined {
+            return JobStatus::Done;
+        }
+        let status = self.job.process_next(max_frames);
+        if status == JobStatus::Done {
+            let pitch = pitch_column(&self.job.take_results());
+            self.refined_scores = score_tones(&pitch, &self.tones, &[], &[], true, &self.rubric);
+            if !self.reference.is_empty() {
+                self.similarity = Some(compare_contours(&pitch, &self.reference).similarity());
+            }
+            self.refined = true;
+        }
+        status
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn phase(&self) -> GradePhase {
+        if self.refined {
+            GradePhase::Refined
+        } else {
+            GradePhase::Quick
+        }
+    }
+
+    /// Percent of the accurate pass done (0-100)
+    #[wasm_bindgen(getter)]
+    pub fn progress(&self) -> f32 {
+        self.job.progress()
+    }
+
+    /// The best scores so far, same layout as `score_expected_contour`:
+    /// refined once available, quick before
+    #[wasm_bindgen(getter)]
+    pub fn scores(&self) -> Vec<f32> {
+        match self.phase() {
+            GradePhase::Quick => self.quick_scores.clone(),
+            GradePhase::Refined => self.refined_scores.clone(),
+        }
+    }
+
+    /// Scores of the quick pass
+    #[wasm_bindgen(getter)]
+    pub fn quick_scores(&self) -> Vec<f32> {
+        self.quick_scores.clone()
+    }
+
+    /// Scores of the refined pass, empty until it completes
+    #[wasm_bindgen(getter)]
+    pub fn refined_scores(&self) -> Vec<f32> {
+        self.refined_scores.clone()
+    }
+
+    /// 0-100 similarity to the reference (see `compare_contours`), once the
+    /// refined pass completed with a reference set
+    #[wasm_bindgen(getter)]
+    pub fn similarity(&self) -> Option<f32> {
+        self.similarity
+    }
+}
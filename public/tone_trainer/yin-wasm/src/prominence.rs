@@ -0,0 +1,153 @@
+//! Which syllable of a phrase is stressed: prominence combines pitch range,
+//! loudness and duration, and the learner's most prominent syllable is
+//! compared with the reference's to catch stress on the wrong word.
+
+use wasm_bindgen::prelude::*;
+
+use crate::segments::{voiced_core_range, voiced_runs};
+use crate::tones::{syllable_ranges, voiced_median};
+
+/// Unvoiced gap (frames) still treated as part of one syllable
+const SYLLABLE_GAP_FRAMES: usize = 2;
+
+/// Shortest voiced run (frames) counted as a syllable
+const MIN_SYLLABLE_FRAMES: usize = 3;
+
+/// Smallest pitch range (semitones) credited to a syllable, so a level tone
+/// isn't rated as having no prominence at all
+const MIN_RANGE_SEMITONES: f32 = 1.0;
+
+/// Frame ranges of `n_syllables` syllables: the voiced runs when there are
+/// exactly that many, otherwise an even split of the voiced core
+fn syllable_spans(pitch: &[f32], n_syllables: usize) -> Vec<(usize, usize)> {
+    let runs = voiced_runs(pitch, SYLLABLE_GAP_FRAMES, MIN_SYLLABLE_FRAMES);
+    if runs.len() == n_syllables {
+        return runs;
+    }
+    let (start, end) = voiced_core_range(pitch);
+    syllable_ranges(end - start, n_syllables)
+        .into_iter()
+        .map(|(s, e)| (start + s, start + e))
+        .collect()
+}
+
+/// Relative prominence of each syllable (summing to 1): the product of its
+/// pitch range (semitones), mean level relative to the phrase and length
+/// relative to the average syllable. `levels` holds per-frame RMS levels
+/// (see `frame_levels`); pass an empty array to ignore loudness.
+#[wasm_bindgen]
+pub fn syllable_prominence(pitch: &[f32], levels: &[f32], n_syllables: usize) -> Vec<f32> {
+    let spans = syllable_spans(pitch, n_syllables);
+    let median = voiced_median(pitch);
+    if spans.is_empty() || median <= 0.0 {
+        return vec![0.0; n_syllables];
+    }
+
+    let level_at = |i: usize| levels.get(i).copied().unwrap_or(1.0);
+    let mean_level = {
+        let (sum, n) = spans
+            .iter()
+            .flat_map(|&(s, e)| s..e)
+            .fold((0.0, 0), |(sum, n), i| (sum + level_at(i), n + 1));
+        if n > 0 && sum > 0.0 {
+            sum / n as f32
+        } else {
+            1.0
+        }
+    };
+    let mean_length = spans.iter().map(|&(s, e)| e - s).sum::<usize>() as f32 / spans.len() as f32;
+
+    let raw: Vec<f32> = spans
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                return 0.0;
+            }
+            let semitones: Vec<f32> = pitch[start..end]
+                .iter()
+                .filter(|&&p| p > 0.0)
+                .map(|&p| 12.0 * (p / median).log2())
+                .collect();
+            let min = semitones.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = semitones.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let range = if semitones.is_empty() {
+                0.0
+            } else {
+                (max - min).max(MIN_RANGE_SEMITONES)
+            };
+            let level = (start..end).map(level_at).sum::<f32>() / (end - start) as f32;
+            range * (level / mean_level) * ((end - start) as f32 / mean_length.max(1.0))
+        })
+        .collect();
+
+    let total: f32 = raw.iter().sum();
+    raw.iter()
+        .map(|r| if total > 0.0 { r / total } else { 0.0 })
+        .collect()
+}
+
+/// Learner and reference prominence of the same phrase
+#[wasm_bindgen]
+pub struct ProminenceComparison {
+    learner: Vec<f32>,
+    reference: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl ProminenceComparison {
+    /// Learner's prominence per syllable (see `syllable_prominence`)
+    #[wasm_bindgen(getter)]
+    pub fn learner(&self) -> Vec<f32> {
+        self.learner.clone()
+    }
+
+    /// Reference's prominence per syllable
+    #[wasm_bindgen(getter)]
+    pub fn reference(&self) -> Vec<f32> {
+        self.reference.clone()
+    }
+
+    /// Learner's most prominent syllable
+    #[wasm_bindgen(getter)]
+    pub fn learner_peak(&self) -> usize {
+        Self::peak(&self.learner)
+    }
+
+    /// Reference's most prominent syllable
+    #[wasm_bindgen(getter)]
+    pub fn reference_peak(&self) -> usize {
+        Self::peak(&self.reference)
+    }
+
+    /// Whether the learner stressed the same syllable as the reference
+    #[wasm_bindgen(getter)]
+    pub fn matches(&self) -> bool {
+        self.learner_peak() == self.reference_peak()
+    }
+}
+
+impl ProminenceComparison {
+    fn peak(prominence: &[f32]) -> usize {
+        prominence
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+/// Compare the prominence pattern of a learner's phrase with the reference
+/// recording of the same `n_syllables`
+#[wasm_bindgen]
+pub fn compare_prominence(
+    learner_pitch: &[f32],
+    learner_levels: &[f32],
+    reference_pitch: &[f32],
+    reference_levels: &[f32],
+    n_syllables: usize,
+) -> ProminenceComparison {
+    ProminenceComparison {
+        learner: syllable_prominence(learner_pitch, learner_levels, n_syllables),
+        reference: syllable_prominence(reference_pitch, reference_levels, n_syllables),
+    }
+}
@@ -0,0 +1,218 @@
+//! Probabilistic YIN: instead of taking the first CMNDF dip below a fixed
+//! threshold in every frame, keep several candidates per frame and pick the
+//! path through them with a Viterbi decode, so the track doesn't flip
+//! between octaves or drop out on breathy, low-energy speech.
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::{framing_error, workload_error};
+use crate::logging::log_warn;
+use crate::metrics;
+use crate::{
+    tau_search_start, yin_cumulative_mean_normalized_difference, yin_difference_function,
+    yin_local_minima, yin_parabolic_interpolation,
+};
+
+/// Settings of the candidate collection and the Viterbi decode
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct PyinSettings {
+    /// Most candidates kept per frame (cheapest first)
+    pub max_candidates: usize,
+    /// CMNDF minima above this value are not candidates
+    pub candidate_threshold: f32,
+    /// Cost of the unvoiced state of a frame; a voiced candidate costs its
+    /// CMNDF value (plus the octave penalty), so frames whose best dip is
+    /// above this lean unvoiced
+    pub unvoiced_cost: f32,
+    /// Extra cost per octave below the highest candidate pitch, so that
+    /// the period dip wins over the equally deep dips at its multiples
+    pub octave_cost: f32,
+    /// Cost per semitone of pitch change between consecutive frames
+    pub jump_cost: f32,
+    /// Cost of switching between voiced and unvoiced
+    pub voicing_switch_cost: f32,
+}
+
+#[wasm_bindgen]
+impl PyinSettings {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PyinSettings {
+        PyinSettings {
+            max_candidates: 5,
+            candidate_threshold: 0.6,
+            unvoiced_cost: 0.5,
+            octave_cost: 0.1,
+            jump_cost: 0.05,
+            voicing_switch_cost: 0.2,
+        }
+    }
+}
+
+impl Default for PyinSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pitch candidate of one frame
+#[derive(Clone, Copy, Debug)]
+struct Candidate {
+    freq: f32,
+    cmndf: f32,
+    tau: usize,
+    /// CMNDF value plus the octave penalty
+    cost: f32,
+}
+
+/// Candidates of one frame within [tau_min, tau_max], cheapest first
+fn frame_candidates(
+    frame: &[f32],
+    sample_rate: f32,
+    tau_min: usize,
+    tau_max: usize,
+    interpolation: bool,
+    settings: &PyinSettings,
+) -> Vec<Candidate> {
+    let max_freq = sample_rate / tau_min as f32;
+    let cmndf = yin_cumulative_mean_normalized_difference(&yin_difference_function(frame));
+    let mut candidates: Vec<Candidate> = yin_local_minima(&cmndf, tau_min, tau_max)
+        .into_iter()
+        .filter(|&tau| cmndf[tau] < settings.candidate_threshold)
+        .map(|tau| {
+            let refined = if interpolation {
+                yin_parabolic_interpolation(&cmndf, tau as i32)
+            } else {
+                tau as f32
+            };
+            let freq = sample_rate / refined;
+            Candidate {
+                freq,
+                cmndf: cmndf[tau],
+                tau,
+                cost: cmndf[tau] + settings.octave_cost * (max_freq / freq).log2().max(0.0),
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.cost.total_cmp(&b.cost));
+    candidates.truncate(settings.max_candidates);
+    candidates
+}
+
+/// Cost of moving from `from` to `to` (None is the unvoiced state)
+fn transition_cost(
+    from: Option<&Candidate>,
+    to: Option<&Candidate>,
+    settings: &PyinSettings,
+) -> f32 {
+    match (from, to) {
+        (Some(a), Some(b)) => settings.jump_cost * 12.0 * (b.freq / a.freq).log2().abs(),
+        (None, None) => 0.0,
+        _ => settings.voicing_switch_cost,
+    }
+}
+
+/// Viterbi decode over the candidates of every frame. Returns, per frame,
+/// the index of the chosen candidate or None for unvoiced.
+fn viterbi(frames: &[Vec<Candidate>], settings: &PyinSettings) -> Vec<Option<usize>> {
+    // State s of frame t: candidates 0..n, then the unvoiced state n
+    let local = |candidates: &[Candidate], s: usize| {
+        candidates.get(s).map_or(settings.unvoiced_cost, |c| c.cost)
+    };
+
+    let Some(first) = frames.first() else {
+        return Vec::new();
+    };
+    let mut cost: Vec<f32> = (0..=first.len()).map(|s| local(first, s)).collect();
+    let mut back: Vec<Vec<usize>> = Vec::with_capacity(frames.len());
+    back.push(vec![0; first.len() + 1]);
+
+    for t in 1..frames.len() {
+        let (prev, cur) = (&frames[t - 1], &frames[t]);
+        let mut next_cost = Vec::with_capacity(cur.len() + 1);
+        let mut pointers = Vec::with_capacity(cur.len() + 1);
+        for s in 0..=cur.len() {
+            let (best, total) = (0..=prev.len())
+                .map(|p| {
+                    let c = cost[p] + transition_cost(prev.get(p), cur.get(s), settings);
+                    (p, c)
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 0.0));
+            next_cost.push(total + local(cur, s));
+            pointers.push(best);
+        }
+        cost = next_cost;
+        back.push(pointers);
+    }
+
+    let mut s = (0..cost.len())
+        .min_by(|&a, &b| cost[a].total_cmp(&cost[b]))
+        .unwrap_or(0);
+    let mut path = vec![None; frames.len()];
+    for t in (0..frames.len()).rev() {
+        path[t] = (s < frames[t].len()).then_some(s);
+        s = back[t][s];
+    }
+    path
+}
+
+/// Perform probabilistic YIN analysis: several CMNDF minima per frame are
+/// kept as candidates and the pitch track is decoded with Viterbi, trading
+/// each candidate's CMNDF value against pitch jumps and voicing switches.
+/// Returns the same flat [pitch, confidence, tau, ...] layout as
+/// `perform_yin_analysis`, with pitch 0 and tau -1 for unvoiced frames.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn perform_pyin_analysis(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+    settings: &PyinSettings,
+) -> Vec<f32> {
+    if audio_data.len() < frame_size || hop_size == 0 || min_freq <= 0.0 || max_freq <= min_freq {
+        return Vec::new();
+    }
+    if let Some(err) = framing_error(frame_size, hop_size)
+        .or_else(|| workload_error(audio_data.len(), sample_rate, frame_size, hop_size))
+    {
+        log_warn!("{}", err);
+        return Vec::new();
+    }
+    let tau_min = tau_search_start(sample_rate, max_freq);
+    let tau_max = (sample_rate / min_freq).ceil() as usize;
+
+    let frames: Vec<Vec<Candidate>> = (0..=(audio_data.len() - frame_size) / hop_size)
+        .map(|i| {
+            let frame = &audio_data[i * hop_size..i * hop_size + frame_size];
+            frame_candidates(
+                frame,
+                sample_rate,
+                tau_min,
+                tau_max,
+                interpolation,
+                settings,
+            )
+            .into_iter()
+            .filter(|c| c.freq >= min_freq && c.freq <= max_freq)
+            .collect()
+        })
+        .collect();
+
+    let path = viterbi(&frames, settings);
+    let mut results = Vec::with_capacity(frames.len() * 3);
+    for (candidates, choice) in frames.iter().zip(&path) {
+        match choice.map(|s| candidates[s]) {
+            Some(c) => results.extend([c.freq, 1.0 - c.cmndf, c.tau as f32]),
+            None => results.extend([0.0, 0.0, -1.0]),
+        }
+    }
+
+    let voiced = path.iter().filter(|s| s.is_some()).count();
+    metrics::record_analysis(path.len(), voiced);
+    results
+}
@@ -0,0 +1,158 @@
+//! Quantized contour storage for attempt history. Every frame becomes one
+//! u16: its pitch in 0.1-semitone steps relative to the speaker's median,
+//! with 0 reserved for unvoiced frames. That's 2 bytes per frame instead of
+//! 12 for the raw [pitch, confidence, tau] triple, and well below what a
+//! learner can hear.
+
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{BinaryError, ByteReader, ByteWriter};
+use crate::segments::pitch_column;
+use crate::tones::voiced_median;
+
+const QUANTIZED_MAGIC: &[u8; 4] = b"YINQ";
+const QUANTIZED_VERSION: u8 = 1;
+
+/// Quantization step (semitones)
+const STEP_SEMITONES: f32 = 0.1;
+
+/// Code of the median itself; codes above are higher, below lower
+const ZERO_CODE: u16 = 32768;
+
+/// Code of an unvoiced frame
+const UNVOICED_CODE: u16 = 0;
+
+/// Code of one pitch (Hz) relative to `median_hz`, saturating at the ends of
+/// the range (±3276 semitones, far outside any voice)
+fn quantize(hz: f32, median_hz: f32) -> u16 {
+    if !(hz > 0.0 && median_hz > 0.0) {
+        return UNVOICED_CODE;
+    }
+    let steps = (12.0 * (hz / median_hz).log2() / STEP_SEMITONES).round();
+    (ZERO_CODE as f32 + steps).clamp(1.0, u16::MAX as f32) as u16
+}
+
+/// Semitones above `median_hz` of a code; NaN when unvoiced
+fn code_semitones(code: u16) -> f32 {
+    if code == UNVOICED_CODE {
+        f32::NAN
+    } else {
+        (code as f32 - ZERO_CODE as f32) * STEP_SEMITONES
+    }
+}
+
+/// Quantize a Hz pitch track (0 for unvoiced) to one code per frame,
+/// relative to `median_hz`
+#[wasm_bindgen]
+pub fn quantize_contour(pitch: &[f32], median_hz: f32) -> Vec<u16> {
+    pitch.iter().map(|&hz| quantize(hz, median_hz)).collect()
+}
+
+/// Inverse of `quantize_contour`: Hz per frame, 0 for unvoiced frames
+#[wasm_bindgen]
+pub fn dequantize_contour(codes: &[u16], median_hz: f32) -> Vec<f32> {
+    codes
+        .iter()
+        .map(|&code| {
+            if code == UNVOICED_CODE {
+                0.0
+            } else {
+                median_hz * 2f32.powf(code_semitones(code) / 12.0)
+            }
+        })
+        .collect()
+}
+
+/// A quantized contour with the median and frame period needed to restore it
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuantizedContour {
+    median_hz: f32,
+    frame_period: f32,
+    codes: Vec<u16>,
+}
+
+#[wasm_bindgen]
+impl QuantizedContour {
+    /// Quantize a Hz pitch track relative to `median_hz` (the speaker's
+    /// profile median), or to the track's own voiced median when that is 0.
+    /// `frame_period` is the hop between frames in seconds.
+    #[wasm_bindgen(constructor)]
+    pub fn new(pitch: &[f32], median_hz: f32, frame_period: f32) -> QuantizedContour {
+        let median_hz = if median_hz > 0.0 {
+            median_hz
+        } else {
+            voiced_median(pitch)
+        };
+        QuantizedContour {
+            median_hz,
+            frame_period,
+            codes: quantize_contour(pitch, median_hz),
+        }
+    }
+
+    /// `new` from a flat [pitch, confidence, tau, ...] analysis
+    pub fn from_results(results: &[f32], median_hz: f32, frame_period: f32) -> QuantizedContour {
+        Self::new(&pitch_column(results), median_hz, frame_period)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn median_hz(&self) -> f32 {
+        self.median_hz
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn frame_period(&self) -> f32 {
+        self.frame_period
+    }
+
+    /// One code per frame: 0 when unvoiced, otherwise 32768 plus the pitch
+    /// in 0.1-semitone steps above the median
+    #[wasm_bindgen(getter)]
+    pub fn codes(&self) -> Vec<u16> {
+        self.codes.clone()
+    }
+
+    /// Restored pitch (Hz) per frame, 0 for unvoiced frames
+    pub fn pitch(&self) -> Vec<f32> {
+        dequantize_contour(&self.codes, self.median_hz)
+    }
+
+    /// Semitones above the median per frame, NaN for unvoiced frames
+    pub fn semitones(&self) -> Vec<f32> {
+        self.codes
+            .iter()
+            .map(|&code| code_semitones(code))
+            .collect()
+    }
+
+    /// Versioned binary form: 21 bytes of header plus 2 per frame
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = ByteWriter::with_header(QUANTIZED_MAGIC, QUANTIZED_VERSION);
+        out.f32(self.median_hz);
+        out.f32(self.frame_period);
+        out.u16s(&self.codes);
+        out.bytes
+    }
+
+    /// Inverse of `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<QuantizedContour, JsError> {
+        Self::decode(bytes).map_err(|err| JsError::new(&err.to_string()))
+    }
+}
+
+impl QuantizedContour {
+    fn decode(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let (mut input, version) = ByteReader::with_header(bytes, QUANTIZED_MAGIC)?;
+        if version != QUANTIZED_VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+        let contour = QuantizedContour {
+            median_hz: input.f32()?,
+            frame_period: input.f32()?,
+            codes: input.u16s()?,
+        };
+        input.finish()?;
+        Ok(contour)
+    }
+}
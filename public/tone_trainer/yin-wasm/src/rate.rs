@@ -0,0 +1,93 @@
+//! Results at a fixed output frame rate. The analysis frame rate follows
+//! the hop size and the device's sample rate (512 samples is 10.7 ms at
+//! 48 kHz but 11.6 ms at 44.1 kHz), so contours stored from different
+//! devices don't line up frame for frame; resampling them to e.g. exactly
+//! 100 frames per second makes them comparable.
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::YinConfig;
+
+/// [pitch, confidence, tau] at fraction `t` between two frames: linear
+/// between voiced neighbours, otherwise the nearer neighbour's values, as in
+/// low-power mode
+fn interpolate(prev: &[f32], next: &[f32], t: f32) -> [f32; 3] {
+    if prev[0] > 0.0 && next[0] > 0.0 {
+        [0, 1, 2].map(|i| prev[i] + (next[i] - prev[i]) * t)
+    } else if t < 0.5 {
+        [prev[0], prev[1], prev[2]]
+    } else {
+        [next[0], next[1], next[2]]
+    }
+}
+
+/// Resample a flat [pitch, confidence, tau, ...] array whose frame `i`
+/// describes time `offset + i * frame_period` to `output_rate` frames per
+/// second, frame `k` describing time `k / output_rate`. Times before the
+/// first frame repeat it; the output ends at the last frame's time.
+pub fn resample_frames(
+    results: &[f32],
+    frame_period: f32,
+    offset: f32,
+    output_rate: f32,
+) -> Vec<f32> {
+    let frames: Vec<&[f32]> = results.chunks_exact(3).collect();
+    let Some(last) = frames.len().checked_sub(1) else {
+        return Vec::new();
+    };
+    if !(frame_period > 0.0 && output_rate > 0.0) {
+        return results.to_vec();
+    }
+
+    let end_time = offset + last as f32 * frame_period;
+    let count = (end_time * output_rate).floor().max(0.0) as usize + 1;
+    let mut out = Vec::with_capacity(count * 3);
+    for k in 0..count {
+        let position = ((k as f32 / output_rate - offset) / frame_period).clamp(0.0, last as f32);
+        let i = (position.floor() as usize).min(last);
+        let next = (i + 1).min(last);
+        out.extend_from_slice(&interpolate(frames[i], frames[next], position - i as f32));
+    }
+    out
+}
+
+/// Resample `perform_yin_analysis` results, analyzed with `frame_size` and
+/// `hop_size` at `sample_rate`, to `output_rate` frames per second. Frame
+/// times are frame centres; output frame `k` describes time
+/// `k / output_rate`.
+#[wasm_bindgen]
+pub fn resample_results(
+    results: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    output_rate: f32,
+) -> Vec<f32> {
+    resample_frames(
+        results,
+        hop_size as f32 / sample_rate,
+        frame_size as f32 / 2.0 / sample_rate,
+        output_rate,
+    )
+}
+
+/// `perform_yin_analysis_with_config` delivering `output_rate` frames per
+/// second (e.g. 100) whatever the hop size and sample rate; frame `k`
+/// describes time `k / output_rate`
+#[wasm_bindgen]
+pub fn perform_yin_analysis_at_rate(
+    audio_data: &[f32],
+    config: &YinConfig,
+    output_rate: f32,
+) -> Result<Vec<f32>, JsError> {
+    let results = config
+        .analyze_checked(audio_data)
+        .map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(resample_results(
+        &results,
+        config.sample_rate,
+        config.frame_size,
+        config.hop_size,
+        output_rate,
+    ))
+}
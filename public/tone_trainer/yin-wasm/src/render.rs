@@ -0,0 +1,176 @@
+//! Geometry for the canvas renderer and card templates, so drawing code
+//! only has to fill polygons or paste a path
+
+use std::fmt::Write;
+
+use wasm_bindgen::prelude::*;
+
+use crate::contour::{smooth_weighted, ConfidenceWeighting};
+use crate::segments::voiced_runs;
+
+/// Filled band between an aligned learner and reference contour, in canvas
+/// pixels. Points where the curves cross are inserted so every run has a
+/// single sign and can be filled in one color.
+#[wasm_bindgen]
+pub struct DiffBand {
+    x: Vec<f32>,
+    upper: Vec<f32>,
+    lower: Vec<f32>,
+    runs: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl DiffBand {
+    #[wasm_bindgen(getter)]
+    pub fn x(&self) -> Vec<f32> {
+        self.x.clone()
+    }
+
+    /// Top edge of the band (smaller canvas y) at each x
+    #[wasm_bindgen(getter)]
+    pub fn upper(&self) -> Vec<f32> {
+        self.upper.clone()
+    }
+
+    /// Bottom edge of the band (larger canvas y) at each x
+    #[wasm_bindgen(getter)]
+    pub fn lower(&self) -> Vec<f32> {
+        self.lower.clone()
+    }
+
+    /// Flat [start, end, sign, ...] point ranges [start, end) to fill as one
+    /// polygon each: along `upper` forwards, then `lower` backwards. Sign is
+    /// 1 where the learner is above the reference, -1 where below.
+    #[wasm_bindgen(getter)]
+    pub fn runs(&self) -> Vec<f32> {
+        self.runs.clone()
+    }
+}
+
+impl DiffBand {
+    /// End the current run at the last point, dropping runs of a single point
+    fn close_run(&mut self, run: &mut Option<(usize, f32)>) {
+        if let Some((start, sign)) = run.take() {
+            if self.x.len() - start > 1 {
+                self.runs.extend([start as f32, self.x.len() as f32, sign]);
+            }
+        }
+    }
+}
+
+/// Band between `learner` and `reference`, two contours of equal length that
+/// are already aligned (e.g. by `align_to`). Values in [min_value,
+/// max_value] map to canvas y from `height` down to 0 and points are spread
+/// evenly over `width`. NaN marks unvoiced points; the band breaks there.
+#[wasm_bindgen]
+pub fn contour_diff_band(
+    learner: &[f32],
+    reference: &[f32],
+    width: f32,
+    height: f32,
+    min_value: f32,
+    max_value: f32,
+) -> DiffBand {
+    let n = learner.len().min(reference.len());
+    let range = (max_value - min_value).max(f32::EPSILON);
+    let to_x = |i: f32| {
+        if n > 1 {
+            i * width / (n - 1) as f32
+        } else {
+            0.0
+        }
+    };
+    let to_y = |v: f32| height * (1.0 - (v - min_value) / range);
+
+    let mut band = DiffBand {
+        x: Vec::with_capacity(n),
+        upper: Vec::with_capacity(n),
+        lower: Vec::with_capacity(n),
+        runs: Vec::new(),
+    };
+    let mut run: Option<(usize, f32)> = None;
+    let push = |band: &mut DiffBand, x: f32, learner: f32, reference: f32| {
+        let (a, b) = (to_y(learner), to_y(reference));
+        band.x.push(x);
+        band.upper.push(a.min(b));
+        band.lower.push(a.max(b));
+    };
+
+    for i in 0..n {
+        let (l, r) = (learner[i], reference[i]);
+        if l.is_nan() || r.is_nan() {
+            band.close_run(&mut run);
+            continue;
+        }
+        let sign = if l >= r { 1.0 } else { -1.0 };
+        match run {
+            Some((_, run_sign)) if run_sign != sign => {
+                // Close at the interpolated crossing and start the next run there
+                let (pl, pr) = (learner[i - 1], reference[i - 1]);
+                let t = (pl - pr) / ((pl - pr) - (l - r));
+                let y = pl + (l - pl) * t;
+                push(&mut band, to_x(i as f32 - 1.0 + t), y, y);
+                band.close_run(&mut run);
+                run = Some((band.x.len() - 1, sign));
+            }
+            Some(_) => {}
+            None => run = Some((band.x.len(), sign)),
+        }
+        push(&mut band, to_x(i as f32), l, r);
+    }
+    band.close_run(&mut run);
+    band
+}
+
+/// SVG path data ("M x y L x y ...") for a Hz pitch track, e.g. for contour
+/// thumbnails embedded in Anki card templates. Frames spread evenly over
+/// `width`; pitch maps from [min_hz, max_hz] to y from `height` up to 0
+/// (clamped), on a semitone scale when `log_scale` is set. Each voiced run
+/// becomes its own subpath, smoothed with a moving average of
+/// `2 * smoothing_radius + 1` frames (0 disables smoothing). Coordinates are
+/// rounded to 0.1 to keep the string short.
+#[wasm_bindgen]
+pub fn pitch_svg_path(
+    pitch: &[f32],
+    width: f32,
+    height: f32,
+    min_hz: f32,
+    max_hz: f32,
+    smoothing_radius: usize,
+    log_scale: bool,
+) -> String {
+    let scale = |hz: f32| {
+        if log_scale {
+            hz.max(f32::EPSILON).log2()
+        } else {
+            hz
+        }
+    };
+    let (low, high) = (scale(min_hz), scale(max_hz));
+    let range = (high - low).max(f32::EPSILON);
+    let step = if pitch.len() > 1 {
+        width / (pitch.len() - 1) as f32
+    } else {
+        0.0
+    };
+
+    let mut path = String::new();
+    for (start, end) in voiced_runs(pitch, 0, 1) {
+        let run = smooth_weighted(
+            &pitch[start..end],
+            &[],
+            smoothing_radius,
+            &ConfidenceWeighting::default(),
+        );
+        for (i, &hz) in run.iter().enumerate() {
+            let x = (start + i) as f32 * step;
+            let y = (height * (1.0 - (scale(hz) - low) / range)).clamp(0.0, height);
+            let command = if i == 0 { 'M' } else { 'L' };
+            if !path.is_empty() {
+                path.push(' ');
+            }
+            let _ = write!(path, "{}{:.1} {:.1}", command, x, y);
+        }
+    }
+    path
+}
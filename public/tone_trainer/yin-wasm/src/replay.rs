@@ -0,0 +1,138 @@
+//! Timing of an attempt relative to its reference recording, stored with
+//! the attempt so A/B playback can start and stretch the learner's audio to
+//! line up with the reference instead of playing both from zero.
+
+use wasm_bindgen::prelude::*;
+
+use crate::dtw::dtw;
+use crate::intonation::line;
+use crate::segments::voiced_core_range;
+use crate::tones::voiced_median;
+
+/// Plausible range of the stretch factor; fits outside it come from a
+/// failed alignment and fall back to matching the voiced cores' ends
+const MIN_STRETCH: f32 = 0.25;
+const MAX_STRETCH: f32 = 4.0;
+
+/// Mapping from reference time to learner time (seconds from the start of
+/// each recording): `learner = reference * stretch + offset`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayAlignment {
+    offset: f32,
+    stretch: f32,
+    aligned: bool,
+}
+
+#[wasm_bindgen]
+impl ReplayAlignment {
+    /// Learner time (seconds) at reference time 0
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> f32 {
+        self.offset
+    }
+
+    /// Learner seconds per reference second; play the learner's recording
+    /// at `1 / stretch` speed to match the reference's timing
+    #[wasm_bindgen(getter)]
+    pub fn stretch(&self) -> f32 {
+        self.stretch
+    }
+
+    /// Whether both recordings had voicing to align; when not, the mapping
+    /// is the identity
+    #[wasm_bindgen(getter)]
+    pub fn aligned(&self) -> bool {
+        self.aligned
+    }
+
+    /// Learner time matching `reference_time`
+    pub fn learner_time(&self, reference_time: f32) -> f32 {
+        reference_time * self.stretch + self.offset
+    }
+
+    /// Reference time matching `learner_time`
+    pub fn reference_time(&self, learner_time: f32) -> f32 {
+        (learner_time - self.offset) / self.stretch
+    }
+}
+
+impl ReplayAlignment {
+    pub(crate) fn new(offset: f32, stretch: f32, aligned: bool) -> Self {
+        ReplayAlignment {
+            offset,
+            stretch,
+            aligned,
+        }
+    }
+
+    fn identity() -> Self {
+        ReplayAlignment::new(0.0, 1.0, false)
+    }
+}
+
+/// Semitones of the frames [start, end) of a Hz pitch track against its
+/// voiced median, holding the last voiced value across unvoiced frames so
+/// every frame keeps its place on the time axis
+fn held_semitones(pitch: &[f32], start: usize, end: usize) -> Vec<f32> {
+    let core = &pitch[start..end];
+    let median = voiced_median(core);
+    let mut last = 0.0;
+    core.iter()
+        .map(|&p| {
+            if p > 0.0 {
+                last = 12.0 * (p / median).log2();
+            }
+            last
+        })
+        .collect()
+}
+
+/// Alignment of a learner's Hz pitch track (frames `frame_period` seconds
+/// apart) to a reference track (`reference_frame_period` apart). The voiced
+/// cores are matched by DTW on their median-relative semitone contours, so
+/// speakers at different pitches align by melody, and a line through the
+/// warping path gives the offset and stretch.
+#[wasm_bindgen]
+pub fn replay_alignment(
+    pitch: &[f32],
+    frame_period: f32,
+    reference: &[f32],
+    reference_frame_period: f32,
+) -> ReplayAlignment {
+    let voiced = |track: &[f32]| track.iter().any(|&p| p > 0.0);
+    if !voiced(pitch) || !voiced(reference) {
+        return ReplayAlignment::identity();
+    }
+    let (start, end) = voiced_core_range(pitch);
+    let (ref_start, ref_end) = voiced_core_range(reference);
+    let (_, path) = dtw(
+        &held_semitones(pitch, start, end),
+        &held_semitones(reference, ref_start, ref_end),
+    );
+
+    let (reference_times, learner_times): (Vec<f32>, Vec<f32>) = path
+        .iter()
+        .map(|&(i, j)| {
+            (
+                (ref_start + j) as f32 * reference_frame_period,
+                (start + i) as f32 * frame_period,
+            )
+        })
+        .unzip();
+    let (stretch, offset) = line(&reference_times, &learner_times);
+    if (MIN_STRETCH..=MAX_STRETCH).contains(&stretch) {
+        return ReplayAlignment::new(offset, stretch, true);
+    }
+
+    // Degenerate path (e.g. a one-frame core): match the cores' ends
+    let ref_duration = (ref_end - ref_start) as f32 * reference_frame_period;
+    let duration = (end - start) as f32 * frame_period;
+    let stretch = if ref_duration > 0.0 {
+        (duration / ref_duration).clamp(MIN_STRETCH, MAX_STRETCH)
+    } else {
+        1.0
+    };
+    let offset = start as f32 * frame_period - ref_start as f32 * reference_frame_period * stretch;
+    ReplayAlignment::new(offset, stretch, true)
+}
@@ -0,0 +1,135 @@
+//! Frame-level quality gating before storage. Live grading can work with
+//! shaky frames, but cached reference analyses and history fingerprints are
+//! kept for a long time and compared against later, so only frames that are
+//! voiced, confident, within the pitch range and part of a voiced run are
+//! worth storing.
+
+use wasm_bindgen::prelude::*;
+
+use crate::cache::cache_put;
+use crate::config::YinConfig;
+use crate::fingerprint::attempt_fingerprint;
+use crate::segments::pitch_column;
+
+/// Which frames of an analysis are trustworthy enough to store
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct StoragePolicy {
+    /// Frames less confident than this are dropped
+    pub min_confidence: f32,
+    /// Pitch range (Hz) a stored frame must fall in
+    pub min_freq: f32,
+    pub max_freq: f32,
+    /// Voiced runs shorter than this many frames are dropped as blips
+    pub min_run_frames: usize,
+}
+
+#[wasm_bindgen]
+impl StoragePolicy {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> StoragePolicy {
+        StoragePolicy {
+            min_confidence: 0.7,
+            min_freq: 60.0,
+            max_freq: 600.0,
+            min_run_frames: 3,
+        }
+    }
+}
+
+impl Default for StoragePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether each frame of a flat [pitch, confidence, tau, ...] array passes
+/// `policy`
+pub fn store_worthy(results: &[f32], policy: &StoragePolicy) -> Vec<bool> {
+    let mut keep: Vec<bool> = results
+        .chunks_exact(3)
+        .map(|f| {
+            f[0] > 0.0
+                && f[1] >= policy.min_confidence
+                && f[0] >= policy.min_freq
+                && f[0] <= policy.max_freq
+        })
+        .collect();
+
+    let mut i = 0;
+    while i < keep.len() {
+        if !keep[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < keep.len() && keep[i] {
+            i += 1;
+        }
+        if i - start < policy.min_run_frames {
+            keep[start..i].iter_mut().for_each(|k| *k = false);
+        }
+    }
+    keep
+}
+
+/// 1 for every frame `policy` would store, 0 otherwise
+#[wasm_bindgen]
+pub fn storage_mask(results: &[f32], policy: &StoragePolicy) -> Vec<u8> {
+    store_worthy(results, policy)
+        .into_iter()
+        .map(u8::from)
+        .collect()
+}
+
+/// `results` with every frame `policy` rejects turned unvoiced (pitch 0,
+/// confidence 0, tau -1), so frame indices stay aligned with the audio
+#[wasm_bindgen]
+pub fn filter_for_storage(results: &[f32], policy: &StoragePolicy) -> Vec<f32> {
+    let mut filtered = results.to_vec();
+    for (frame, keep) in filtered
+        .chunks_exact_mut(3)
+        .zip(store_worthy(results, policy))
+    {
+        if !keep {
+            frame.copy_from_slice(&[0.0, 0.0, -1.0]);
+        }
+    }
+    filtered
+}
+
+/// Pitch track of the frames `policy` would store (0 elsewhere)
+#[wasm_bindgen]
+pub fn storable_pitch_track(results: &[f32], policy: &StoragePolicy) -> Vec<f32> {
+    pitch_column(&filter_for_storage(results, policy))
+}
+
+/// `cache_put` with only the frames `policy` accepts. The policy isn't part
+/// of the cache key, so keep it fixed for a cache.
+#[wasm_bindgen]
+pub fn cache_put_filtered(
+    audio: &[f32],
+    config: &YinConfig,
+    results: &[f32],
+    policy: &StoragePolicy,
+) -> Vec<u8> {
+    cache_put(audio, config, &filter_for_storage(results, policy))
+}
+
+/// `attempt_fingerprint` of the frames `policy` accepts, from a flat
+/// [pitch, confidence, tau, ...] array
+#[wasm_bindgen]
+pub fn attempt_fingerprint_filtered(
+    results: &[f32],
+    tone: u8,
+    score: f32,
+    frame_period: f32,
+    policy: &StoragePolicy,
+) -> Vec<u8> {
+    attempt_fingerprint(
+        &storable_pitch_track(results, policy),
+        tone,
+        score,
+        frame_period,
+    )
+}
@@ -0,0 +1,151 @@
+use wasm_bindgen::prelude::*;
+
+/// Tolerance (Chao steps RMS) at which a tone's shape or register scores zero
+pub(crate) const DEFAULT_TONE_TOLERANCE: f32 = 2.0;
+
+/// How strictly recordings are scored, tunable per deck without code changes.
+///
+/// The final score is the weighted mean of the component scores (each 0-100).
+/// Components an entry point cannot measure (e.g. duration without a
+/// reference) are left out and the remaining weights renormalized.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct ScoringRubric {
+    /// Weight of the contour shape (movement around the syllable's own level)
+    pub shape_weight: f32,
+    /// Weight of the register (overall level relative to the target)
+    pub register_weight: f32,
+    /// Weight of the voiced duration relative to the reference
+    pub duration_weight: f32,
+    /// Weight of the fraction of frames that are voiced
+    pub voicing_weight: f32,
+    /// Voiced fraction at or above which voicing scores 100
+    pub full_voicing_coverage: f32,
+    /// Duration ratio (either way) at which duration scores zero
+    pub max_duration_ratio: f32,
+    /// Duration ratio a phrase-final syllable may run over its reference
+    /// without penalty: the last syllable of a phrase is naturally longer
+    /// than the same syllable said in isolation
+    pub phrase_final_lengthening: f32,
+    /// Semitones outside the template band at which a template score is zero
+    pub template_tolerance_semitones: f32,
+    /// Per-tone tolerance in Chao steps, indexed by tone (0 and 5 = neutral)
+    tone_tolerances: [f32; 6],
+}
+
+impl Default for ScoringRubric {
+    fn default() -> Self {
+        ScoringRubric {
+            shape_weight: 0.6,
+            register_weight: 0.2,
+            duration_weight: 0.1,
+            voicing_weight: 0.1,
+            full_voicing_coverage: 0.8,
+            max_duration_ratio: 2.0,
+            phrase_final_lengthening: 1.5,
+            template_tolerance_semitones: 4.0,
+            tone_tolerances: [DEFAULT_TONE_TOLERANCE; 6],
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl ScoringRubric {
+    /// Rubric with the default weights and tolerances
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ScoringRubric {
+        ScoringRubric::default()
+    }
+
+    /// Tolerance (Chao steps) for `tone`; unknown tones use the neutral tone's
+    pub fn tone_tolerance(&self, tone: u8) -> f32 {
+        self.tone_tolerances[Self::tone_index(tone)]
+    }
+
+    /// Set the tolerance (Chao steps) for `tone`, e.g. be lenient on tone 3
+    pub fn set_tone_tolerance(&mut self, tone: u8, tolerance: f32) {
+        self.tone_tolerances[Self::tone_index(tone)] = tolerance;
+    }
+}
+
+impl ScoringRubric {
+    fn tone_index(tone: u8) -> usize {
+        match tone {
+            1..=4 => tone as usize,
+            _ => 0,
+        }
+    }
+
+    /// Weighted mean of the measured components
+    pub fn combine(
+        &self,
+        shape: f32,
+        register: Option<f32>,
+        duration: Option<f32>,
+        voicing: Option<f32>,
+    ) -> f32 {
+        let components = [
+            (self.shape_weight, Some(shape)),
+            (self.register_weight, register),
+            (self.duration_weight, duration),
+            (self.voicing_weight, voicing),
+        ];
+        let (sum, total_weight) = components
+            .iter()
+            .filter_map(|&(w, score)| score.map(|s| (w.max(0.0), s)))
+            .fold((0.0, 0.0), |(sum, tw), (w, s)| (sum + w * s, tw + w));
+        if total_weight > 0.0 {
+            sum / total_weight
+        } else {
+            shape
+        }
+    }
+
+    /// Score (0-100) for a deviation, reaching zero at `tolerance`
+    pub fn deviation_score(deviation: f32, tolerance: f32) -> f32 {
+        if tolerance <= 0.0 {
+            return if deviation <= 0.0 { 100.0 } else { 0.0 };
+        }
+        100.0 * (1.0 - deviation / tolerance).clamp(0.0, 1.0)
+    }
+
+    /// Score (0-100) for the fraction of voiced frames in a Hz pitch track
+    pub fn voicing_score(&self, pitch: &[f32]) -> Option<f32> {
+        if pitch.is_empty() {
+            return None;
+        }
+        let coverage = pitch.iter().filter(|&&p| p > 0.0).count() as f32 / pitch.len() as f32;
+        let full = self.full_voicing_coverage.max(f32::EPSILON);
+        Some(100.0 * (coverage / full).min(1.0))
+    }
+
+    /// Score (0-100) for a duration against the expected one, symmetric in
+    /// the ratio so that half as long and twice as long are equally wrong
+    pub fn duration_score(&self, actual: f32, expected: f32) -> Option<f32> {
+        if actual <= 0.0 || expected <= 0.0 {
+            return None;
+        }
+        let deviation = (actual / expected).ln().abs();
+        Some(Self::deviation_score(
+            deviation,
+            self.max_duration_ratio.max(1.0).ln(),
+        ))
+    }
+
+    /// `duration_score` for a syllable at the end of a phrase when
+    /// `phrase_final`: running up to `phrase_final_lengthening` times the
+    /// expected duration is free, and only the excess beyond that counts
+    pub fn phrase_duration_score(
+        &self,
+        actual: f32,
+        expected: f32,
+        phrase_final: bool,
+    ) -> Option<f32> {
+        let expected = if phrase_final && actual > expected {
+            (expected * self.phrase_final_lengthening.max(1.0)).min(actual)
+        } else {
+            expected
+        };
+        self.duration_score(actual, expected)
+    }
+}
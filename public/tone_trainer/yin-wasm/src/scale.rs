@@ -0,0 +1,215 @@
+use wasm_bindgen::prelude::*;
+
+use crate::dtw::dtw;
+use crate::perform_yin_analysis;
+use crate::profile::selected_profile;
+use crate::segments::voiced_core;
+use crate::tones::{voiced_median, SEMITONES_PER_CHAO_STEP};
+
+/// Reference frequency for the semitone scale (same as Praat's "semitones re 100 Hz")
+pub const SEMITONE_REFERENCE_HZ: f32 = 100.0;
+
+/// Unit in which pitch values are reported
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PitchScale {
+    /// Linear frequency
+    #[default]
+    Hz = 0,
+    /// Octaves (log2 of Hz)
+    Log2 = 1,
+    /// Mel scale (O'Shaughnessy: 2595 * log10(1 + f / 700))
+    Mel = 2,
+    /// ERB-rate scale (Glasberg & Moore: 21.4 * log10(1 + 0.00437 f))
+    Erb = 3,
+    /// Semitones relative to `SEMITONE_REFERENCE_HZ`
+    Semitones = 4,
+}
+
+impl PitchScale {
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PitchScale::Hz),
+            1 => Some(PitchScale::Log2),
+            2 => Some(PitchScale::Mel),
+            3 => Some(PitchScale::Erb),
+            4 => Some(PitchScale::Semitones),
+            _ => None,
+        }
+    }
+
+    /// Convert a frequency in Hz to this scale. Unvoiced values (<= 0) become NaN.
+    pub fn from_hz(self, hz: f32) -> f32 {
+        if hz <= 0.0 {
+            return f32::NAN;
+        }
+        match self {
+            PitchScale::Hz => hz,
+            PitchScale::Log2 => hz.log2(),
+            PitchScale::Mel => 2595.0 * (1.0 + hz / 700.0).log10(),
+            PitchScale::Erb => 21.4 * (1.0 + 0.00437 * hz).log10(),
+            PitchScale::Semitones => 12.0 * (hz / SEMITONE_REFERENCE_HZ).log2(),
+        }
+    }
+
+    /// Distance of `hz` from `reference_hz` on this scale, e.g. semitones
+    /// above the speaker's median. Unvoiced values (<= 0) become NaN.
+    pub fn relative(self, hz: f32, reference_hz: f32) -> f32 {
+        self.from_hz(hz) - self.from_hz(reference_hz)
+    }
+
+    /// Convert a value on this scale back to Hz. NaN maps to 0 (unvoiced).
+    pub fn to_hz(self, value: f32) -> f32 {
+        if value.is_nan() {
+            return 0.0;
+        }
+        match self {
+            PitchScale::Hz => value,
+            PitchScale::Log2 => value.exp2(),
+            PitchScale::Mel => 700.0 * (10f32.powf(value / 2595.0) - 1.0),
+            PitchScale::Erb => (10f32.powf(value / 21.4) - 1.0) / 0.00437,
+            PitchScale::Semitones => SEMITONE_REFERENCE_HZ * (value / 12.0).exp2(),
+        }
+    }
+}
+
+/// Convert a Hz pitch track to `scale`; unvoiced frames (pitch <= 0) become NaN
+#[wasm_bindgen]
+pub fn convert_pitch_track(pitch: &[f32], scale: PitchScale) -> Vec<f32> {
+    pitch.iter().map(|&hz| scale.from_hz(hz)).collect()
+}
+
+/// Convert values on `scale` back to Hz; NaN becomes 0 (unvoiced)
+#[wasm_bindgen]
+pub fn pitch_track_to_hz(values: &[f32], scale: PitchScale) -> Vec<f32> {
+    values.iter().map(|&v| scale.to_hz(v)).collect()
+}
+
+/// Same as `perform_yin_analysis`, but the pitch of each frame is reported
+/// on `scale` (NaN for unvoiced frames). Layout: [pitch, confidence, tau, ...]
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn perform_yin_analysis_scaled(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    threshold: f32,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+    scale: PitchScale,
+) -> Vec<f32> {
+    let mut results = perform_yin_analysis(
+        audio_data,
+        sample_rate,
+        frame_size,
+        hop_size,
+        threshold,
+        min_freq,
+        max_freq,
+        interpolation,
+    );
+    for frame in results.chunks_mut(3) {
+        frame[0] = scale.from_hz(frame[0]);
+    }
+    results
+}
+
+/// DTW distance between two Hz pitch tracks measured on `scale`.
+/// Unvoiced frames are skipped in both tracks. With `auto_crop`, both tracks
+/// are first cropped to their voiced cores so stray noises outside the word
+/// don't enter the alignment.
+#[wasm_bindgen]
+pub fn compare_pitch_tracks(a: &[f32], b: &[f32], scale: PitchScale, auto_crop: bool) -> f32 {
+    let voiced = |track: &[f32]| -> Vec<f32> {
+        let track = if auto_crop { voiced_core(track) } else { track };
+        track
+            .iter()
+            .filter(|&&hz| hz > 0.0)
+            .map(|&hz| scale.from_hz(hz))
+            .collect()
+    };
+    dtw(&voiced(a), &voiced(b)).0
+}
+
+/// Unit of a speaker-normalized contour
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContourUnit {
+    /// Semitones relative to the speaker's median
+    Semitones = 0,
+    /// Cents (1/100 semitone) relative to the speaker's median
+    Cents = 1,
+    /// Chao tone letters, level 3 at the speaker's median and one level per
+    /// `SEMITONES_PER_CHAO_STEP` semitones
+    Chao = 2,
+}
+
+/// Pitch contour re-expressed relative to the speaker, so a low male voice
+/// and a high female reference land on the same scale
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct NormalizedContour {
+    values: Vec<f32>,
+    median_hz: f32,
+    unit: ContourUnit,
+}
+
+#[wasm_bindgen]
+impl NormalizedContour {
+    /// One value per input frame; NaN for unvoiced frames
+    #[wasm_bindgen(getter)]
+    pub fn values(&self) -> Vec<f32> {
+        self.values.clone()
+    }
+
+    /// Speaker median F0 (Hz) the contour is relative to; 0 when nothing
+    /// was voiced
+    #[wasm_bindgen(getter)]
+    pub fn median_hz(&self) -> f32 {
+        self.median_hz
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn unit(&self) -> ContourUnit {
+        self.unit
+    }
+}
+
+/// Express a Hz pitch track in `unit` relative to `median_hz`, keeping one
+/// value per frame (NaN for unvoiced frames)
+#[wasm_bindgen]
+pub fn normalize_contour_to(pitch: &[f32], median_hz: f32, unit: ContourUnit) -> NormalizedContour {
+    let values = pitch
+        .iter()
+        .map(|&hz| {
+            if hz <= 0.0 || median_hz <= 0.0 {
+                return f32::NAN;
+            }
+            let semitones = 12.0 * (hz / median_hz).log2();
+            match unit {
+                ContourUnit::Semitones => semitones,
+                ContourUnit::Cents => 100.0 * semitones,
+                ContourUnit::Chao => 3.0 + semitones / SEMITONES_PER_CHAO_STEP,
+            }
+        })
+        .collect();
+    NormalizedContour {
+        values,
+        median_hz,
+        unit,
+    }
+}
+
+/// Express a Hz pitch track in `unit` relative to the speaker's median F0:
+/// the selected speaker profile's when it is calibrated, otherwise
+/// estimated over the track's voiced frames
+#[wasm_bindgen]
+pub fn normalize_contour(pitch: &[f32], unit: ContourUnit) -> NormalizedContour {
+    let median_hz = match selected_profile() {
+        Some(profile) if profile.is_calibrated() => profile.median_hz(),
+        _ => voiced_median(pitch),
+    };
+    normalize_contour_to(pitch, median_hz, unit)
+}
@@ -0,0 +1,174 @@
+use wasm_bindgen::prelude::*;
+
+/// Voiced runs of a pitch track as half-open frame ranges [start, end).
+/// Runs separated by fewer than `min_gap_frames` unvoiced frames are merged,
+/// then runs shorter than `min_len_frames` are dropped.
+pub fn voiced_runs(
+    pitch: &[f32],
+    min_gap_frames: usize,
+    min_len_frames: usize,
+) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut start = None;
+    for (i, &p) in pitch.iter().chain(std::iter::once(&0.0)).enumerate() {
+        match (p > 0.0, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                match runs.last_mut() {
+                    Some(last) if s - last.1 < min_gap_frames => last.1 = i,
+                    _ => runs.push((s, i)),
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs.retain(|&(s, e)| e - s >= min_len_frames);
+    runs
+}
+
+/// Pitch column of a flat [pitch, confidence, tau, ...] result array
+pub fn pitch_column(results: &[f32]) -> Vec<f32> {
+    results.chunks_exact(3).map(|f| f[0]).collect()
+}
+
+/// Convert a duration in milliseconds to a whole number of hops (rounded up)
+pub fn ms_to_frames(ms: f32, sample_rate: f32, hop_size: usize) -> usize {
+    (ms / 1000.0 * sample_rate / hop_size as f32)
+        .ceil()
+        .max(0.0) as usize
+}
+
+/// Clean voiced segments from `perform_yin_analysis` results for syllable
+/// highlighting. Segments separated by gaps shorter than `min_gap_ms` are
+/// merged and blips shorter than `min_len_ms` are dropped.
+/// Frame times are frame centres. Returns a flat array of [start_s, end_s, ...].
+#[wasm_bindgen]
+pub fn voiced_segments(
+    results: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    min_gap_ms: f32,
+    min_len_ms: f32,
+) -> Vec<f32> {
+    let pitch = pitch_column(results);
+    let runs = voiced_runs(
+        &pitch,
+        ms_to_frames(min_gap_ms, sample_rate, hop_size),
+        ms_to_frames(min_len_ms, sample_rate, hop_size),
+    );
+
+    let centre = |frame: usize| (frame * hop_size + frame_size / 2) as f32 / sample_rate;
+    runs.iter()
+        .flat_map(|&(start, end)| [centre(start), centre(end - 1)])
+        .collect()
+}
+
+/// A single voiced segment with its pitch contour
+#[wasm_bindgen]
+pub struct VoicedSegment {
+    start_frame: usize,
+    end_frame: usize,
+    start_time: f32,
+    end_time: f32,
+    contour: Vec<f32>,
+    confidence: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl VoicedSegment {
+    /// First frame of the segment
+    #[wasm_bindgen(getter)]
+    pub fn start_frame(&self) -> usize {
+        self.start_frame
+    }
+
+    /// One past the last frame of the segment
+    #[wasm_bindgen(getter)]
+    pub fn end_frame(&self) -> usize {
+        self.end_frame
+    }
+
+    /// Centre of the first frame (seconds)
+    #[wasm_bindgen(getter)]
+    pub fn start_time(&self) -> f32 {
+        self.start_time
+    }
+
+    /// Centre of the last frame (seconds)
+    #[wasm_bindgen(getter)]
+    pub fn end_time(&self) -> f32 {
+        self.end_time
+    }
+
+    /// Pitch (Hz) of each frame in the segment; merged gaps stay 0
+    #[wasm_bindgen(getter)]
+    pub fn contour(&self) -> Vec<f32> {
+        self.contour.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn confidence(&self) -> Vec<f32> {
+        self.confidence.clone()
+    }
+}
+
+/// The longest voiced segment of `perform_yin_analysis` results, after
+/// merging gaps shorter than `min_gap_ms`. Single-syllable cards use it to
+/// ignore stray noises before and after the word.
+#[wasm_bindgen]
+pub fn longest_voiced_segment(
+    results: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    min_gap_ms: f32,
+) -> Option<VoicedSegment> {
+    let pitch = pitch_column(results);
+    let (start, end) = voiced_runs(&pitch, ms_to_frames(min_gap_ms, sample_rate, hop_size), 1)
+        .into_iter()
+        .max_by_key(|&(s, e)| e - s)?;
+
+    let centre = |frame: usize| (frame * hop_size + frame_size / 2) as f32 / sample_rate;
+    Some(VoicedSegment {
+        start_frame: start,
+        end_frame: end,
+        start_time: centre(start),
+        end_time: centre(end - 1),
+        contour: pitch[start..end].to_vec(),
+        confidence: results[start * 3..end * 3]
+            .chunks_exact(3)
+            .map(|f| f[1])
+            .collect(),
+    })
+}
+
+/// Unvoiced gap (frames) bridged when locating the voiced core
+pub const CORE_MIN_GAP_FRAMES: usize = 5;
+
+/// Shortest voiced run (frames) kept when locating the voiced core
+pub const CORE_MIN_LEN_FRAMES: usize = 3;
+
+/// Frame range [start, end) of the voiced core (see `voiced_core`)
+pub fn voiced_core_range(pitch: &[f32]) -> (usize, usize) {
+    voiced_runs(pitch, CORE_MIN_GAP_FRAMES, CORE_MIN_LEN_FRAMES)
+        .into_iter()
+        .max_by_key(|&(s, e)| e - s)
+        .unwrap_or((0, pitch.len()))
+}
+
+/// The voiced core of a pitch track: the longest voiced run after bridging
+/// short gaps and ignoring blips. Leading/trailing silence and stray noises
+/// are cut so they can't influence alignment. Falls back to the whole track
+/// when nothing qualifies.
+pub fn voiced_core(pitch: &[f32]) -> &[f32] {
+    let (start, end) = voiced_core_range(pitch);
+    &pitch[start..end]
+}
+
+/// Crop a pitch track to its voiced core (see `voiced_core`)
+#[wasm_bindgen]
+pub fn crop_to_voiced_core(pitch: &[f32]) -> Vec<f32> {
+    voiced_core(pitch).to_vec()
+}
@@ -0,0 +1,603 @@
+use wasm_bindgen::prelude::*;
+
+use crate::config::{framing_error, global_defaults, ConfigError, YinConfig};
+use crate::feedback::{diagnose, issues_to_flat};
+use crate::fit::MIN_SCORE_RELIABILITY;
+use crate::input::rms;
+use crate::json::{push_key, push_number};
+use crate::ltas::{analyze_spectrum, BandLimit, SpectrumSummary};
+use crate::noise::{estimate_noise, threshold_for_snr};
+use crate::pinyin::{parse_tones, ToneSequence};
+use crate::rubric::ScoringRubric;
+use crate::scale::PitchScale;
+use crate::segments::pitch_column;
+use crate::stream::FrameStream;
+use crate::tones::{score_tones, tone_fits, Realization};
+use crate::typed::{push_frames, to_js};
+use crate::voicing::{attempt_status, AttemptStatus};
+use crate::warnings::{
+    push_warnings, recording_warnings, tail_warning, AnalysisWarning, CLIP_LEVEL,
+};
+
+/// Level above the noise floor (dB) a frame needs to survive trimming
+const TRIM_MARGIN_DB: f32 = 6.0;
+
+/// Settings for `RecordingSession::finalize`
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct SessionConfig {
+    /// Analysis settings; the threshold is ignored when
+    /// `adaptive_threshold` is set
+    analysis: YinConfig,
+    /// Pick the threshold from the recording's SNR (see `noise`)
+    pub adaptive_threshold: bool,
+    /// Cut leading and trailing silence before analysis
+    pub trim: bool,
+    /// Crop the pitch track to its voiced core before scoring
+    pub auto_crop: bool,
+    /// Analyze only every Nth frame while live (1 = every frame), see
+    /// `FrameStream::set_stride`
+    pub live_frame_stride: usize,
+    tones: Vec<u8>,
+    /// Erhua flag per syllable, set from pinyin
+    erhua: Vec<bool>,
+    /// Aspirated-initial flag per syllable, set from pinyin
+    aspirated: Vec<bool>,
+    rubric: ScoringRubric,
+}
+
+impl SessionConfig {
+    /// Frame stream analyzing with these settings and `threshold`, if the
+    /// framing is usable (see `FrameStream::from_config`). Streams can't
+    /// apply `noise_robust`, so it is left out.
+    fn frame_stream(&self, threshold: f32) -> Result<FrameStream, ConfigError> {
+        FrameStream::from_config(&YinConfig {
+            threshold,
+            noise_robust: false,
+            ..self.analysis
+        })
+    }
+
+    /// True if both configs produce the same frames for the same audio
+    fn same_analysis(&self, other: &SessionConfig) -> bool {
+        self.analysis == other.analysis
+    }
+}
+
+#[wasm_bindgen]
+impl SessionConfig {
+    /// Analysis settings start from the global defaults (see
+    /// `set_global_defaults`), with the recording's own `sample_rate`
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_rate: f32) -> SessionConfig {
+        SessionConfig {
+            analysis: YinConfig {
+                sample_rate,
+                ..global_defaults()
+            },
+            adaptive_threshold: true,
+            trim: true,
+            auto_crop: true,
+            live_frame_stride: 1,
+            tones: Vec::new(),
+            erhua: Vec::new(),
+            aspirated: Vec::new(),
+            rubric: ScoringRubric::default(),
+        }
+    }
+
+    /// Analysis settings (a copy; set them back with `analysis = ...`)
+    #[wasm_bindgen(getter)]
+    pub fn analysis(&self) -> YinConfig {
+        self.analysis
+    }
+
+    /// Replace every analysis setting, the sample rate included
+    #[wasm_bindgen(setter)]
+    pub fn set_analysis(&mut self, analysis: &YinConfig) {
+        self.analysis = *analysis;
+    }
+
+    /// Expected tone sequence; scoring and feedback are skipped when empty
+    #[wasm_bindgen(getter)]
+    pub fn tones(&self) -> Vec<u8> {
+        self.tones.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_tones(&mut self, tones: Vec<u8>) {
+        self.tones = tones;
+        self.erhua.clear();
+        self.aspirated.clear();
+    }
+
+    /// Set the expected tones from pinyin (see `parse_pinyin`), keeping
+    /// track of erhua syllables and aspirated initials so they're scored
+    /// like `score_expected_pinyin`
+    pub fn set_pinyin(&mut self, pinyin: &str) -> Result<(), JsError> {
+        let ToneSequence {
+            tones,
+            erhua,
+            aspirated,
+        } = parse_tones(pinyin).map_err(|err| JsError::new(&err.to_string()))?;
+        self.tones = tones;
+        self.erhua = erhua;
+        self.aspirated = aspirated;
+        Ok(())
+    }
+
+    pub fn set_rubric(&mut self, rubric: &ScoringRubric) {
+        self.rubric = rubric.clone();
+    }
+}
+
+/// Everything the app needs after a recording, from one `finalize` call
+#[wasm_bindgen]
+pub struct SessionReport {
+    status: AttemptStatus,
+    trim_start: f32,
+    trim_end: f32,
+    snr_db: f32,
+    peak: f32,
+    clipped_fraction: f32,
+    spectrum: SpectrumSummary,
+    threshold: f32,
+    frame_period: f32,
+    /// Unit of the pitch in `to_object`'s frames
+    scale: PitchScale,
+    results: Vec<f32>,
+    scores: Vec<f32>,
+    reliability: Vec<f32>,
+    issues: Vec<f32>,
+    warnings: Vec<AnalysisWarning>,
+}
+
+#[wasm_bindgen]
+impl SessionReport {
+    /// Whether the attempt was voiced; scores and feedback are only
+    /// produced for voiced attempts
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> AttemptStatus {
+        self.status
+    }
+
+    /// Start of the analyzed region (seconds into the recording)
+    #[wasm_bindgen(getter)]
+    pub fn trim_start(&self) -> f32 {
+        self.trim_start
+    }
+
+    /// End of the analyzed region (seconds into the recording)
+    #[wasm_bindgen(getter)]
+    pub fn trim_end(&self) -> f32 {
+        self.trim_end
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn snr_db(&self) -> f32 {
+        self.snr_db
+    }
+
+    /// Largest absolute sample value
+    #[wasm_bindgen(getter)]
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    /// Fraction of samples at or above the clipping level
+    #[wasm_bindgen(getter)]
+    pub fn clipped_fraction(&self) -> f32 {
+        self.clipped_fraction
+    }
+
+    /// Highest frequency the recording carries (see `SpectrumSummary`);
+    /// far below half the sample rate points to a low-pass codec
+    #[wasm_bindgen(getter)]
+    pub fn bandwidth_hz(&self) -> f32 {
+        self.spectrum.bandwidth_hz
+    }
+
+    /// High-band minus low-band level of the long-term spectrum in dB
+    #[wasm_bindgen(getter)]
+    pub fn spectral_tilt_db(&self) -> f32 {
+        self.spectrum.tilt_db
+    }
+
+    /// The microphone sounds muffled
+    #[wasm_bindgen(getter)]
+    pub fn muffled(&self) -> bool {
+        self.spectrum.muffled
+    }
+
+    /// Codec cutoff found in the recording
+    #[wasm_bindgen(getter)]
+    pub fn band_limit(&self) -> BandLimit {
+        self.spectrum.band_limit
+    }
+
+    /// The recording looks like it went through a Bluetooth headset's
+    /// telephony codec; worth a warning since it grades systematically worse
+    #[wasm_bindgen(getter)]
+    pub fn bluetooth_suspected(&self) -> bool {
+        self.spectrum.bluetooth_suspected()
+    }
+
+    /// YIN threshold the analysis ran with
+    #[wasm_bindgen(getter)]
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Analysis of the trimmed region as a flat [pitch, confidence, tau, ...] array
+    #[wasm_bindgen(getter)]
+    pub fn results(&self) -> Vec<f32> {
+        self.results.clone()
+    }
+
+    /// Per-syllable scores, same layout as `score_expected_contour`
+    #[wasm_bindgen(getter)]
+    pub fn scores(&self) -> Vec<f32> {
+        self.scores.clone()
+    }
+
+    /// Confidence (0-1) in each syllable's score, see
+    /// `FitQuality::reliability`
+    #[wasm_bindgen(getter)]
+    pub fn reliability(&self) -> Vec<f32> {
+        self.reliability.clone()
+    }
+
+    /// Whether the attempt was scored and every syllable's score is
+    /// reliable enough to update mastery statistics
+    #[wasm_bindgen(getter)]
+    pub fn reliable(&self) -> bool {
+        !self.reliability.is_empty() && self.reliability.iter().all(|&r| r >= MIN_SCORE_RELIABILITY)
+    }
+
+    /// Feedback tuples, same layout as `diagnose_expected_contour`, with
+    /// times relative to the start of the recording
+    #[wasm_bindgen(getter)]
+    pub fn issues(&self) -> Vec<f32> {
+        self.issues.clone()
+    }
+
+    /// Caveats about the recording (clipping, noise, dropped tail)
+    #[wasm_bindgen(getter)]
+    pub fn warnings(&self) -> Vec<AnalysisWarning> {
+        self.warnings.clone()
+    }
+
+    /// The whole report as a plain object, frame times relative to the
+    /// start of the recording and pitch in the config's scale
+    #[wasm_bindgen(unchecked_return_type = "SessionReportObject")]
+    pub fn to_object(&self) -> JsValue {
+        to_js(&self.to_json())
+    }
+}
+
+impl SessionReport {
+    /// The report as a `SessionReportObject` in JSON
+    pub(crate) fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        for (i, (key, value)) in [
+            ("status", self.status as u8 as f32),
+            ("trimStart", self.trim_start),
+            ("trimEnd", self.trim_end),
+            ("snrDb", self.snr_db),
+            ("peak", self.peak),
+            ("clippedFraction", self.clipped_fraction),
+            ("bandwidthHz", self.spectrum.bandwidth_hz),
+            ("spectralTiltDb", self.spectrum.tilt_db),
+            ("bandLimit", self.spectrum.band_limit as u8 as f32),
+            ("threshold", self.threshold),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            push_key(&mut out, key, i == 0);
+            push_number(&mut out, value);
+        }
+        push_key(&mut out, "muffled", false);
+        out.push_str(if self.spectrum.muffled {
+            "true"
+        } else {
+            "false"
+        });
+        push_key(&mut out, "bluetoothSuspected", false);
+        out.push_str(if self.bluetooth_suspected() {
+            "true"
+        } else {
+            "false"
+        });
+
+        push_key(&mut out, "frames", false);
+        push_frames(
+            &mut out,
+            &self.results,
+            self.frame_period,
+            self.trim_start,
+            self.scale,
+        );
+
+        push_key(&mut out, "scores", false);
+        out.push('[');
+        for (i, score) in self.scores.chunks_exact(2).enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('{');
+            push_key(&mut out, "score", true);
+            push_number(&mut out, score[0]);
+            push_key(&mut out, "realization", false);
+            push_number(&mut out, score[1]);
+            push_key(&mut out, "halfThird", false);
+            let half_third = score[1] == Realization::HalfThird as u8 as f32;
+            out.push_str(if half_third { "true" } else { "false" });
+            push_key(&mut out, "reliability", false);
+            push_number(&mut out, self.reliability.get(i).copied().unwrap_or(0.0));
+            out.push('}');
+        }
+        out.push(']');
+        push_key(&mut out, "reliable", false);
+        out.push_str(if self.reliable() { "true" } else { "false" });
+
+        push_key(&mut out, "issues", false);
+        out.push('[');
+        for (i, issue) in self.issues.chunks_exact(6).enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('{');
+            for (j, key) in ["code", "severity", "magnitude", "syllable", "start", "end"]
+                .into_iter()
+                .enumerate()
+            {
+                push_key(&mut out, key, j == 0);
+                if key == "syllable" && issue[j] < 0.0 {
+                    out.push_str("null");
+                } else {
+                    push_number(&mut out, issue[j]);
+                }
+            }
+            out.push('}');
+        }
+        out.push(']');
+
+        push_key(&mut out, "warnings", false);
+        push_warnings(&mut out, &self.warnings);
+        out.push('}');
+        out
+    }
+}
+
+/// Sample range [start, end) between the first and last frame that rises
+/// `TRIM_MARGIN_DB` above the noise floor, or the whole input if none does
+pub fn trim_range(audio_data: &[f32], frame_size: usize, hop_size: usize) -> (usize, usize) {
+    if frame_size == 0 || hop_size == 0 || audio_data.len() < frame_size {
+        return (0, audio_data.len());
+    }
+    let noise = estimate_noise(audio_data, frame_size, hop_size);
+    let gate = noise.noise_rms * 10f32.powf(TRIM_MARGIN_DB / 20.0);
+    let loud: Vec<usize> = (0..=(audio_data.len() - frame_size) / hop_size)
+        .filter(|&i| rms(&audio_data[i * hop_size..i * hop_size + frame_size]) > gate)
+        .collect();
+    match (loud.first(), loud.last()) {
+        (Some(&first), Some(&last)) => (first * hop_size, last * hop_size + frame_size),
+        _ => (0, audio_data.len()),
+    }
+}
+
+/// Accumulates a recording during capture, then trims, checks, analyzes and
+/// scores it in one pass. With `start_live`, chunks are also analyzed as they
+/// arrive; the provisional frames come from the same `FrameStream` that
+/// `finalize` uses, so the live view matches the final grading.
+#[wasm_bindgen]
+pub struct RecordingSession {
+    samples: Vec<f32>,
+    /// Config and stream for analyze-as-you-go
+    live: Option<(SessionConfig, FrameStream)>,
+    provisional: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl RecordingSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> RecordingSession {
+        RecordingSession {
+            samples: Vec::new(),
+            live: None,
+            provisional: Vec::new(),
+        }
+    }
+
+    /// Analyze chunks as they arrive, with `config`'s fixed threshold and
+    /// without `noise_robust` (both need the whole recording). Audio
+    /// captured so far is analyzed immediately. Throws when the settings
+    /// are invalid.
+    pub fn start_live(&mut self, config: &SessionConfig) -> Result<(), JsError> {
+        let mut stream = config
+            .frame_stream(config.analysis.threshold)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        stream.set_stride(config.live_frame_stride);
+        self.provisional.clear();
+        stream.push(&self.samples, &mut self.provisional);
+        self.live = Some((config.clone(), stream));
+        Ok(())
+    }
+
+    /// Add the next captured chunk of mono samples. While live, returns the
+    /// provisional frames it completed as a flat [pitch, confidence, tau, ...]
+    /// array; otherwise an empty array.
+    pub fn append(&mut self, chunk: &[f32]) -> Vec<f32> {
+        self.samples.extend_from_slice(chunk);
+        let mut frames = Vec::new();
+        if let Some((_, stream)) = self.live.as_mut() {
+            stream.push(chunk, &mut frames);
+            self.provisional.extend_from_slice(&frames);
+        }
+        frames
+    }
+
+    /// All provisional frames of the recording so far
+    #[wasm_bindgen(getter)]
+    pub fn provisional_results(&self) -> Vec<f32> {
+        self.provisional.clone()
+    }
+
+    /// Number of samples captured so far
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Drop the captured audio to start a new take (live analysis stays on)
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.provisional.clear();
+        if let Some((_, stream)) = self.live.as_mut() {
+            stream.reset();
+        }
+    }
+
+    /// Run trimming, quality checks, analysis, scoring and feedback over the
+    /// captured audio. The session keeps its samples, so it can be
+    /// finalized again with a different config. Throws when the settings
+    /// are invalid, like `start_live`.
+    pub fn finalize(&self, config: &SessionConfig) -> Result<SessionReport, JsError> {
+        self.report(config)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+}
+
+impl RecordingSession {
+    /// `finalize`, if the framing of `config` is usable (see
+    /// `FrameStream::from_config`)
+    pub(crate) fn report(&self, config: &SessionConfig) -> Result<SessionReport, ConfigError> {
+        let analysis = &config.analysis;
+        if let Some(err) = framing_error(analysis.frame_size, analysis.hop_size) {
+            return Err(err);
+        }
+        let audio = &self.samples;
+        let sr = analysis.sample_rate;
+        let (start, end) = if config.trim {
+            trim_range(audio, analysis.frame_size, analysis.hop_size)
+        } else {
+            (0, audio.len())
+        };
+        let trimmed = &audio[start..end];
+
+        let noise = estimate_noise(audio, analysis.frame_size, analysis.hop_size);
+        let peak = audio.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        let clipped = audio.iter().filter(|x| x.abs() >= CLIP_LEVEL).count();
+        let spectrum = analyze_spectrum(audio, sr, analysis.frame_size, analysis.hop_size);
+        let threshold = if config.adaptive_threshold {
+            threshold_for_snr(noise.snr_db)
+        } else {
+            analysis.threshold
+        };
+
+        // The provisional frames are already the final ones when nothing
+        // about the analysis changed since capture
+        let reuse_live = !config.trim
+            && !config.adaptive_threshold
+            && !analysis.noise_robust
+            && matches!(&self.live, Some((live, _))
+                if live.same_analysis(config) && live.live_frame_stride <= 1);
+        let results = if reuse_live {
+            self.provisional.clone()
+        } else if analysis.noise_robust {
+            YinConfig {
+                threshold,
+                ..*analysis
+            }
+            .analyze(trimmed)
+        } else {
+            let mut results = Vec::new();
+            config.frame_stream(threshold)?.push(trimmed, &mut results);
+            results
+        };
+
+        let status = attempt_status(
+            trimmed,
+            audio,
+            &results,
+            sr,
+            analysis.frame_size,
+            analysis.hop_size,
+        );
+        let (scores, reliability, issues) =
+            if config.tones.is_empty() || status != AttemptStatus::Voiced {
+                (Vec::new(), Vec::new(), Vec::new())
+            } else {
+                let pitch = pitch_column(&results);
+                let frame_period = analysis.hop_size as f32 / sr;
+                let issues = diagnose(
+                    &pitch,
+                    &config.tones,
+                    &config.erhua,
+                    &config.aspirated,
+                    config.auto_crop,
+                    &config.rubric,
+                    frame_period,
+                );
+                (
+                    score_tones(
+                        &pitch,
+                        &config.tones,
+                        &config.erhua,
+                        &config.aspirated,
+                        config.auto_crop,
+                        &config.rubric,
+                    ),
+                    tone_fits(
+                        &pitch,
+                        &config.tones,
+                        &config.erhua,
+                        &config.aspirated,
+                        config.auto_crop,
+                        &config.rubric,
+                    )
+                    .iter()
+                    .map(|fit| fit.reliability(noise.snr_db))
+                    .collect(),
+                    issues_to_flat(&issues, frame_period, start as f32 / sr),
+                )
+            };
+
+        let mut warnings = recording_warnings(audio, noise.snr_db);
+        warnings.extend(tail_warning(
+            trimmed.len(),
+            sr,
+            analysis.frame_size,
+            analysis.hop_size,
+        ));
+
+        Ok(SessionReport {
+            status,
+            trim_start: start as f32 / sr,
+            trim_end: end as f32 / sr,
+            snr_db: noise.snr_db,
+            peak,
+            clipped_fraction: if audio.is_empty() {
+                0.0
+            } else {
+                clipped as f32 / audio.len() as f32
+            },
+            spectrum,
+            threshold,
+            frame_period: analysis.hop_size as f32 / sr,
+            scale: analysis.scale,
+            results,
+            scores,
+            reliability,
+            issues,
+            warnings,
+        })
+    }
+}
+
+impl Default for RecordingSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,145 @@
+//! Alignment of a long shadowing attempt (several sentences) against the
+//! reference passage. A coarse DTW over loudness envelopes finds where each
+//! reference sentence lies in the learner's recording, then each sentence
+//! is scored on its own with the contour DTW of the template scorer.
+
+use wasm_bindgen::prelude::*;
+
+use crate::dtw::dtw;
+use crate::rubric::ScoringRubric;
+use crate::templates::ReferenceTemplate;
+use crate::tones::{hz_to_semitones, voiced_median};
+
+/// Longest envelope fed to the coarse DTW; longer recordings are averaged
+/// down in blocks so the cost matrix stays small
+const COARSE_MAX_POINTS: usize = 800;
+
+/// Level floor (RMS) before converting to dB, so silence doesn't go to -inf
+const LEVEL_FLOOR: f32 = 1e-4;
+
+/// Per-sentence result of `align_shadowing`
+#[wasm_bindgen]
+pub struct ShadowingResult {
+    scores: Vec<f32>,
+    spans: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl ShadowingResult {
+    /// Score (0-100) of each reference sentence
+    #[wasm_bindgen(getter)]
+    pub fn scores(&self) -> Vec<f32> {
+        self.scores.clone()
+    }
+
+    /// Where each sentence was found in the learner's recording, as a flat
+    /// [start_s, end_s, ...] array
+    #[wasm_bindgen(getter)]
+    pub fn spans(&self) -> Vec<f32> {
+        self.spans.clone()
+    }
+
+    /// Mean of the sentence scores
+    #[wasm_bindgen(getter)]
+    pub fn overall(&self) -> f32 {
+        if self.scores.is_empty() {
+            0.0
+        } else {
+            self.scores.iter().sum::<f32>() / self.scores.len() as f32
+        }
+    }
+}
+
+/// Loudness envelope in dB averaged over blocks of `block` frames and
+/// normalized to zero mean and unit variance, so recording gain and
+/// microphone distance don't matter
+fn envelope(levels: &[f32], block: usize) -> Vec<f32> {
+    let db: Vec<f32> = levels
+        .chunks(block)
+        .map(|c| {
+            let mean = c.iter().sum::<f32>() / c.len() as f32;
+            20.0 * mean.max(LEVEL_FLOOR).log10()
+        })
+        .collect();
+    let n = db.len().max(1) as f32;
+    let mean = db.iter().sum::<f32>() / n;
+    let std = (db.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / n).sqrt();
+    db.iter()
+        .map(|v| if std > 0.0 { (v - mean) / std } else { 0.0 })
+        .collect()
+}
+
+/// Score a learner segment against a reference segment (Hz pitch tracks)
+fn segment_score(learner: &[f32], reference: &[f32], rubric: &ScoringRubric) -> f32 {
+    let reference = hz_to_semitones(reference, voiced_median(reference));
+    let contour = hz_to_semitones(learner, voiced_median(learner));
+    match ReferenceTemplate::from_contours(&[&reference]) {
+        Some(template) => template.score_contour(&contour, rubric, rubric.voicing_score(learner)),
+        None => 0.0,
+    }
+}
+
+/// Align a learner's shadowing recording with the reference passage and
+/// score every sentence. Pitch tracks are in Hz and `*_levels` are per-frame
+/// RMS levels of the same frames (see `frame_levels`); both recordings must
+/// use the same `frame_period` (seconds). `sentences` gives the reference
+/// sentences as a flat [start_s, end_s, ...] array.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn align_shadowing(
+    learner_pitch: &[f32],
+    learner_levels: &[f32],
+    reference_pitch: &[f32],
+    reference_levels: &[f32],
+    sentences: &[f32],
+    frame_period: f32,
+    rubric: &ScoringRubric,
+) -> ShadowingResult {
+    let frame_period = frame_period.max(f32::EPSILON);
+    let learner_len = learner_pitch.len().min(learner_levels.len());
+    let reference_len = reference_pitch.len().min(reference_levels.len());
+    let block = learner_len
+        .max(reference_len)
+        .div_ceil(COARSE_MAX_POINTS)
+        .max(1);
+
+    let reference_env = envelope(&reference_levels[..reference_len], block);
+    let learner_env = envelope(&learner_levels[..learner_len], block);
+    // Learner blocks aligned to each reference block, as (first, last)
+    let mut mapping = vec![(usize::MAX, 0); reference_env.len()];
+    if !reference_env.is_empty() && !learner_env.is_empty() {
+        for (i, j) in dtw(&reference_env, &learner_env).1 {
+            mapping[i] = (mapping[i].0.min(j), mapping[i].1.max(j));
+        }
+    }
+
+    let mut scores = Vec::with_capacity(sentences.len() / 2);
+    let mut spans = Vec::with_capacity(sentences.len());
+    for sentence in sentences.chunks_exact(2) {
+        let to_frame = |t: f32| ((t / frame_period).round().max(0.0) as usize).min(reference_len);
+        let (ref_start, ref_end) = (to_frame(sentence[0]), to_frame(sentence[1]));
+        if ref_start >= ref_end || mapping.is_empty() || learner_env.is_empty() {
+            scores.push(0.0);
+            spans.extend([0.0, 0.0]);
+            continue;
+        }
+        let first_block = mapping[ref_start / block].0;
+        let last_block = mapping[(ref_end - 1) / block].1;
+        let learner_start = (first_block * block).min(learner_len);
+        let learner_end = ((last_block + 1) * block)
+            .min(learner_len)
+            .max(learner_start);
+
+        scores.push(segment_score(
+            &learner_pitch[learner_start..learner_end],
+            &reference_pitch[ref_start..ref_end],
+            rubric,
+        ));
+        spans.extend([
+            learner_start as f32 * frame_period,
+            learner_end as f32 * frame_period,
+        ]);
+    }
+
+    ShadowingResult { scores, spans }
+}
@@ -0,0 +1,94 @@
+//! `simd128` version of the direct difference function. It is compiled in
+//! only with the `simd` feature on a wasm build with
+//! `RUSTFLAGS="-C target-feature=+simd128"`; every other build keeps the
+//! scalar code. Engines without SIMD reject such a module at compile time,
+//! so the app checks support (e.g. with `wasm-feature-detect`) and loads the
+//! scalar build instead; `simd_supported` tells which build is running.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use wasm_bindgen::prelude::*;
+
+static SIMD_ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+mod imp {
+    use core::arch::wasm32::*;
+
+    /// d_t(τ) = Σ(x_j - x_{j+τ})², four lags of j per instruction
+    pub fn difference_function(buffer: &[f32]) -> Vec<f32> {
+        let half_size = buffer.len() / 2;
+        let lanes = half_size / 4 * 4;
+        let ptr = buffer.as_ptr();
+        let mut difference_function = vec![0.0; half_size];
+
+        for (tau, out) in difference_function.iter_mut().enumerate() {
+            let mut acc = f32x4_splat(0.0);
+            for j in (0..lanes).step_by(4) {
+                // SAFETY: j + 3 < half_size and j + τ + 3 < 2 * half_size <=
+                // buffer.len(); v128_load allows unaligned addresses
+                let (a, b) = unsafe {
+                    (
+                        v128_load(ptr.add(j) as *const v128),
+                        v128_load(ptr.add(j + tau) as *const v128),
+                    )
+                };
+                let delta = f32x4_sub(a, b);
+                acc = f32x4_add(acc, f32x4_mul(delta, delta));
+            }
+            let mut sum = f32x4_extract_lane::<0>(acc)
+                + f32x4_extract_lane::<1>(acc)
+                + f32x4_extract_lane::<2>(acc)
+                + f32x4_extract_lane::<3>(acc);
+            for j in lanes..half_size {
+                let delta = buffer[j] - buffer[j + tau];
+                sum += delta * delta;
+            }
+            *out = sum;
+        }
+
+        difference_function
+    }
+}
+
+/// Direct difference function with `simd128`, or None when this build has
+/// no SIMD path or it was switched off with `set_simd_enabled`
+#[inline]
+pub(crate) fn difference_function(buffer: &[f32]) -> Option<Vec<f32>> {
+    #[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+    {
+        if simd_active() {
+            return Some(imp::difference_function(buffer));
+        }
+    }
+    let _ = buffer;
+    None
+}
+
+/// Whether this build contains the `simd128` code path
+#[wasm_bindgen]
+pub fn simd_supported() -> bool {
+    cfg!(all(
+        feature = "simd",
+        target_arch = "wasm32",
+        target_feature = "simd128"
+    ))
+}
+
+/// Whether analysis currently uses the `simd128` code path
+#[wasm_bindgen]
+pub fn simd_active() -> bool {
+    simd_supported() && simd_enabled()
+}
+
+/// Last value passed to `set_simd_enabled` (true by default)
+pub(crate) fn simd_enabled() -> bool {
+    SIMD_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Switch the `simd128` code path on or off at runtime, e.g. to compare
+/// against the scalar code on a device. No effect in builds without it.
+#[wasm_bindgen]
+pub fn set_simd_enabled(enabled: bool) {
+    SIMD_ENABLED.store(enabled, Ordering::Relaxed);
+}
@@ -0,0 +1,225 @@
+use wasm_bindgen::prelude::*;
+
+use crate::fft::magnitude_spectrum;
+use crate::metrics;
+use crate::{yin_cumulative_mean_normalized_difference, yin_difference_function};
+
+/// Harmonics summed when judging how well a candidate explains the spectrum
+const SALIENCE_HARMONICS: usize = 5;
+
+/// Weight decay per harmonic in the salience sum
+const SALIENCE_DECAY: f32 = 0.8;
+
+/// Relative tolerance when deciding a pitch/peak ratio is an octave
+const OCTAVE_RATIO_TOLERANCE: f32 = 0.1;
+
+/// Octave candidate must beat YIN's salience by this factor to be preferred
+const SALIENCE_MARGIN: f32 = 1.2;
+
+/// Magnitude at `freq`, taking the largest of the nearest bin and its neighbours
+fn magnitude_at(spectrum: &[f32], freq: f32, bin_hz: f32) -> f32 {
+    let bin = (freq / bin_hz).round() as usize;
+    if bin >= spectrum.len() {
+        return 0.0;
+    }
+    let lo = bin.saturating_sub(1);
+    let hi = (bin + 1).min(spectrum.len() - 1);
+    spectrum[lo..=hi].iter().copied().fold(0.0, f32::max)
+}
+
+/// Weighted sum of the magnitudes at the first harmonics of `f0`
+pub fn harmonic_salience(spectrum: &[f32], f0: f32, bin_hz: f32) -> f32 {
+    (1..=SALIENCE_HARMONICS)
+        .map(|k| SALIENCE_DECAY.powi(k as i32 - 1) * magnitude_at(spectrum, f0 * k as f32, bin_hz))
+        .sum()
+}
+
+/// Frequency of the strongest spectral peak within [lo_hz, hi_hz]
+pub fn dominant_peak(spectrum: &[f32], bin_hz: f32, lo_hz: f32, hi_hz: f32) -> Option<f32> {
+    let lo = ((lo_hz / bin_hz).floor() as usize).max(1);
+    let hi = ((hi_hz / bin_hz).ceil() as usize).min(spectrum.len().saturating_sub(2));
+    (lo..=hi)
+        .filter(|&k| spectrum[k] >= spectrum[k - 1] && spectrum[k] >= spectrum[k + 1])
+        .max_by(|&a, &b| spectrum[a].total_cmp(&spectrum[b]))
+        .map(|k| {
+            // Parabolic refinement of the peak position
+            let (a, b, c) = (spectrum[k - 1], spectrum[k], spectrum[k + 1]);
+            let denom = a - 2.0 * b + c;
+            let offset = if denom.abs() > f32::EPSILON {
+                0.5 * (a - c) / denom
+            } else {
+                0.0
+            };
+            (k as f32 + offset) * bin_hz
+        })
+}
+
+/// How a YIN estimate relates to the spectrum of its frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OctaveCheck {
+    Consistent,
+    /// YIN is an octave below the spectral evidence
+    Halved,
+    /// YIN is an octave above the spectral evidence
+    Doubled,
+}
+
+/// Cross-check one frame's YIN pitch against its spectrum. Returns the
+/// verdict and the octave-shifted pitch the spectrum supports, if any.
+pub fn check_frame(
+    frame: &[f32],
+    sample_rate: f32,
+    pitch: f32,
+    min_freq: f32,
+    max_freq: f32,
+) -> (OctaveCheck, f32) {
+    if pitch <= 0.0 {
+        return (OctaveCheck::Consistent, pitch);
+    }
+
+    let spectrum = magnitude_spectrum(frame);
+    let n_fft = (spectrum.len() - 1) * 2;
+    let bin_hz = sample_rate / n_fft as f32;
+    let Some(peak) = dominant_peak(&spectrum, bin_hz, min_freq, max_freq * 2.0) else {
+        return (OctaveCheck::Consistent, pitch);
+    };
+
+    let ratio = pitch / peak;
+    let near = |target: f32| (ratio / target - 1.0).abs() < OCTAVE_RATIO_TOLERANCE;
+    let (verdict, alternative) = if near(0.5) {
+        (OctaveCheck::Halved, pitch * 2.0)
+    } else if near(2.0) {
+        (OctaveCheck::Doubled, pitch * 0.5)
+    } else {
+        return (OctaveCheck::Consistent, pitch);
+    };
+    if alternative < min_freq || alternative > max_freq {
+        return (OctaveCheck::Consistent, pitch);
+    }
+
+    // The peak alone can be the 2nd harmonic of a weak fundamental, so only
+    // report a mismatch when the octave candidate explains the harmonic
+    // structure clearly better than YIN's estimate
+    let yin_salience = harmonic_salience(&spectrum, pitch, bin_hz);
+    let alt_salience = harmonic_salience(&spectrum, alternative, bin_hz);
+    if alt_salience > yin_salience * SALIENCE_MARGIN {
+        (verdict, alternative)
+    } else {
+        (OctaveCheck::Consistent, pitch)
+    }
+}
+
+/// Cross-check every voiced frame of `perform_yin_analysis` results against
+/// the frame's dominant low-frequency spectral peak and harmonic structure.
+///
+/// `results` must come from the same audio, frame and hop size. Returns the
+/// results with an extra per-frame flag appended, i.e. a flat
+/// [pitch, confidence, tau, flag, ...] array where flag is 0 (consistent),
+/// -1 (YIN halved) or 1 (YIN doubled). With `fix`, flagged frames get the
+/// corrected pitch.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn spectral_octave_check(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    results: &[f32],
+    min_freq: f32,
+    max_freq: f32,
+    fix: bool,
+) -> Vec<f32> {
+    let mut checked = Vec::with_capacity(results.len() / 3 * 4);
+    let mut corrections = 0;
+
+    for (i, frame_result) in results.chunks_exact(3).enumerate() {
+        let start = i * hop_size;
+        let mut pitch = frame_result[0];
+        let mut flag = 0.0;
+
+        if pitch > 0.0 && start + frame_size <= audio_data.len() {
+            let frame = &audio_data[start..start + frame_size];
+            let (verdict, alternative) = check_frame(frame, sample_rate, pitch, min_freq, max_freq);
+            flag = match verdict {
+                OctaveCheck::Consistent => 0.0,
+                OctaveCheck::Halved => -1.0,
+                OctaveCheck::Doubled => 1.0,
+            };
+            if fix && verdict != OctaveCheck::Consistent {
+                pitch = alternative;
+                corrections += 1;
+            }
+        }
+
+        checked.extend_from_slice(&[pitch, frame_result[1], frame_result[2], flag]);
+    }
+
+    metrics::record_corrections(corrections);
+    checked
+}
+
+/// CMNDF gap over which a competing octave dip stops looking plausible
+const OCTAVE_DIP_MARGIN: f32 = 0.2;
+
+/// Share of the octave-error probability given to the CMNDF candidates;
+/// the rest comes from the spectral cross-check
+const CANDIDATE_WEIGHT: f32 = 0.5;
+
+/// Deepest CMNDF value within one lag of `tau`
+fn dip_depth(cmndf: &[f32], tau: usize) -> Option<f32> {
+    if tau < 1 || tau + 1 >= cmndf.len() {
+        return None;
+    }
+    Some(cmndf[tau - 1].min(cmndf[tau]).min(cmndf[tau + 1]))
+}
+
+/// How plausible an octave error is (0-1) from the CMNDF alone: a dip at
+/// half the chosen period nearly as deep as the chosen one means the pitch
+/// may be an octave too low, a clearly deeper dip at twice the period that
+/// it may be an octave too high
+fn candidate_octave_evidence(cmndf: &[f32], tau: usize) -> f32 {
+    let chosen = cmndf[tau];
+    let halved =
+        dip_depth(cmndf, tau / 2).map_or(0.0, |half| 1.0 - (half - chosen) / OCTAVE_DIP_MARGIN);
+    let doubled =
+        dip_depth(cmndf, 2 * tau).map_or(0.0, |double| (chosen - double) / OCTAVE_DIP_MARGIN);
+    halved.max(doubled).clamp(0.0, 1.0)
+}
+
+/// Per-frame probability (0-1) that the pitch of `perform_yin_analysis`
+/// results is an octave off, combining competing CMNDF dips at half and
+/// twice the chosen period with the spectral cross-check of
+/// `spectral_octave_check`. Unvoiced frames get 0. `results` must come from
+/// the same audio, frame and hop size.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn octave_error_probability(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    results: &[f32],
+    min_freq: f32,
+    max_freq: f32,
+) -> Vec<f32> {
+    results
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, frame_result)| {
+            let (pitch, tau) = (frame_result[0], frame_result[2]);
+            let start = i * hop_size;
+            if pitch <= 0.0 || tau < 1.0 || start + frame_size > audio_data.len() {
+                return 0.0;
+            }
+            let frame = &audio_data[start..start + frame_size];
+            let cmndf = yin_cumulative_mean_normalized_difference(&yin_difference_function(frame));
+            let tau = (tau as usize).min(cmndf.len() - 1);
+            let candidates = candidate_octave_evidence(&cmndf, tau);
+            let spectral = match check_frame(frame, sample_rate, pitch, min_freq, max_freq).0 {
+                OctaveCheck::Consistent => 0.0,
+                OctaveCheck::Halved | OctaveCheck::Doubled => 1.0,
+            };
+            CANDIDATE_WEIGHT * candidates + (1.0 - CANDIDATE_WEIGHT) * spectral
+        })
+        .collect()
+}
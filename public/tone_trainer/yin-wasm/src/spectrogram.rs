@@ -0,0 +1,99 @@
+//! Short-time Fourier transform for drawing a spectrogram behind the pitch
+//! curve. Frames are cut and windowed exactly as for pitch analysis, so
+//! spectrogram column `i` lines up with pitch frame `i`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::fft::fft_in_place;
+use crate::window::{apply_window, window_coefficients, WindowFunction};
+use crate::{audio_frames, get_frame_count};
+
+/// Level (dBFS) reported for empty bins
+const FLOOR_DB: f32 = -120.0;
+
+/// Magnitudes of an STFT in dB relative to a full-scale sine
+#[wasm_bindgen]
+pub struct Spectrogram {
+    frames: usize,
+    bins: usize,
+    fft_size: usize,
+    data: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl Spectrogram {
+    /// Number of frames (columns)
+    #[wasm_bindgen(getter)]
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Bins per frame, `fft_size / 2 + 1`, from 0 Hz to Nyquist
+    #[wasm_bindgen(getter)]
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+
+    /// FFT length; bin `k` is at `k * sample_rate / fft_size` Hz
+    #[wasm_bindgen(getter)]
+    pub fn fft_size(&self) -> usize {
+        self.fft_size
+    }
+
+    /// Frame-major flat array of `frames * bins` levels (dBFS); the level
+    /// of bin `k` of frame `i` is at `i * bins + k`
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<f32> {
+        self.data.clone()
+    }
+}
+
+/// STFT of `audio_data` with the framing of `perform_yin_analysis` and the
+/// given window, zero-padded to the next power of two. Empty when the audio
+/// is shorter than one frame.
+#[wasm_bindgen]
+pub fn compute_spectrogram(
+    audio_data: &[f32],
+    frame_size: usize,
+    hop_size: usize,
+    window: WindowFunction,
+) -> Spectrogram {
+    let fft_size = frame_size.next_power_of_two().max(2);
+    let bins = fft_size / 2 + 1;
+    if frame_size == 0 || hop_size == 0 {
+        return Spectrogram {
+            frames: 0,
+            bins,
+            fft_size,
+            data: Vec::new(),
+        };
+    }
+
+    // A full-scale sine peaks at half the window sum
+    let gain: f32 = window_coefficients(window, frame_size).iter().sum::<f32>() / 2.0;
+    let frames = get_frame_count(audio_data.len(), frame_size, hop_size);
+    let mut data = Vec::with_capacity(frames * bins);
+    let mut re = vec![0.0; fft_size];
+    let mut im = vec![0.0; fft_size];
+    for frame in audio_frames(audio_data, frame_size, hop_size) {
+        re[..frame_size].copy_from_slice(&apply_window(window, frame));
+        re[frame_size..].fill(0.0);
+        im.fill(0.0);
+        fft_in_place(&mut re, &mut im);
+        data.extend((0..bins).map(|k| {
+            let magnitude = (re[k] * re[k] + im[k] * im[k]).sqrt() / gain;
+            if magnitude > 0.0 {
+                (20.0 * magnitude.log10()).max(FLOOR_DB)
+            } else {
+                FLOOR_DB
+            }
+        }));
+    }
+
+    Spectrogram {
+        frames,
+        bins,
+        fft_size,
+        data,
+    }
+}
@@ -0,0 +1,900 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{BinaryError, ByteReader, ByteWriter};
+use crate::config::{framing_error, ConfigError, YinConfig, MAX_FRAME_SIZE};
+use crate::gate::{NoiseFloorTracker, VoicingGate};
+use crate::input::{rms, LinearResampler};
+use crate::logging::log_warn;
+use crate::metrics;
+use crate::wav::WavStreamDecoder;
+use crate::window::WindowFunction;
+use crate::{yin_analyze_frame, CmndfSummation, YinParams};
+
+/// Incremental framing for YIN: keeps only the samples the next frame still
+/// needs, so memory stays bounded by the frame size plus one input chunk.
+/// Produces exactly the frames `perform_yin_analysis` would for the
+/// concatenated input.
+#[derive(Clone, Debug)]
+pub struct FrameStream {
+    params: YinParams,
+    frame_size: usize,
+    hop_size: usize,
+    /// Samples starting at the next frame's first sample
+    buffer: Vec<f32>,
+    /// Samples still to discard when the hop is larger than the buffer
+    skip: usize,
+    frames_emitted: usize,
+    /// Low-power mode: only every `stride`-th frame is analyzed
+    stride: usize,
+    /// Frames framed so far, analyzed or not
+    frames_seen: usize,
+    /// Skipped frames waiting for the next analyzed frame to interpolate to
+    pending: usize,
+    /// Last analyzed [pitch, confidence, tau]
+    last: Option<[f32; 3]>,
+}
+
+impl FrameStream {
+    /// Stream with the given settings; see `from_config` for what is rejected
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sample_rate: f32,
+        frame_size: usize,
+        hop_size: usize,
+        threshold: f32,
+        min_freq: f32,
+        max_freq: f32,
+        interpolation: bool,
+    ) -> Result<Self, ConfigError> {
+        Self::from_config(&YinConfig {
+            sample_rate,
+            frame_size,
+            hop_size,
+            threshold,
+            min_freq,
+            max_freq,
+            interpolation,
+            ..YinConfig::new()
+        })
+    }
+
+    /// Stream with the settings of `config`. Framing that
+    /// `perform_yin_analysis` also refuses (a zero hop, a frame outside
+    /// 2..=`MAX_FRAME_SIZE`) is rejected, as is `noise_robust`, which needs
+    /// the whole recording. Frames shorter than `YinConfig::check` asks for
+    /// work as in the flat API, searching lags up to half the frame.
+    pub fn from_config(config: &YinConfig) -> Result<Self, ConfigError> {
+        if let Some(err) = framing_error(config.frame_size, config.hop_size) {
+            return Err(err);
+        }
+        if config.noise_robust {
+            return Err(ConfigError::NoiseRobustStreaming);
+        }
+        Ok(FrameStream {
+            params: config.params(),
+            frame_size: config.frame_size,
+            hop_size: config.hop_size,
+            buffer: Vec::with_capacity(config.frame_size * 2),
+            skip: 0,
+            frames_emitted: 0,
+            stride: 1,
+            frames_seen: 0,
+            pending: 0,
+            last: None,
+        })
+    }
+
+    /// Low-power mode: analyze only every `stride`-th frame and interpolate
+    /// the frames in between (1 = analyze every frame). Interpolated frames
+    /// are emitted once the next analyzed frame is known, so output lags by
+    /// up to `stride - 1` frames; see `flush`.
+    pub fn set_stride(&mut self, stride: usize) {
+        self.stride = stride.max(1);
+    }
+
+    /// Values for the `k`-th of `n` skipped frames between two analyzed ones:
+    /// linear between voiced neighbours, otherwise the nearer neighbour's
+    fn interpolate(prev: [f32; 3], next: [f32; 3], k: usize, n: usize) -> [f32; 3] {
+        let t = k as f32 / (n + 1) as f32;
+        if prev[0] > 0.0 && next[0] > 0.0 {
+            [0, 1, 2].map(|i| prev[i] + (next[i] - prev[i]) * t)
+        } else if t < 0.5 {
+            prev
+        } else {
+            next
+        }
+    }
+
+    /// Emit the pending skipped frames leading up to `next`
+    fn emit_pending(&mut self, next: [f32; 3], out: &mut Vec<f32>) {
+        let prev = self.last.unwrap_or(next);
+        for k in 1..=self.pending {
+            out.extend_from_slice(&Self::interpolate(prev, next, k, self.pending));
+        }
+        self.frames_emitted += self.pending;
+        self.pending = 0;
+    }
+
+    /// Emit frames still held back by low-power mode, repeating the last
+    /// analyzed frame (call when the input ends)
+    pub fn flush(&mut self, out: &mut Vec<f32>) {
+        if let Some(last) = self.last {
+            for _ in 0..self.pending {
+                out.extend_from_slice(&last);
+            }
+            self.frames_emitted += self.pending;
+            self.pending = 0;
+        }
+    }
+
+    /// Number of frames produced so far
+    pub fn frames_emitted(&self) -> usize {
+        self.frames_emitted
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.params.sample_rate
+    }
+
+    /// Append the full state (settings, buffered samples, position)
+    pub(crate) fn write_to(&self, out: &mut ByteWriter) {
+        out.f32(self.params.sample_rate);
+        out.f32(self.params.threshold);
+        out.f32(self.params.min_freq);
+        out.f32(self.params.max_freq);
+        out.bool(self.params.interpolation);
+        out.usize(self.params.tau_start);
+        out.u8(self.params.summation as u8);
+        out.u8(self.params.window as u8);
+        out.usize(self.frame_size);
+        out.usize(self.hop_size);
+        out.f32s(&self.buffer);
+        out.usize(self.skip);
+        out.usize(self.frames_emitted);
+        out.usize(self.stride);
+        out.usize(self.frames_seen);
+        out.usize(self.pending);
+        out.bool(self.last.is_some());
+        out.f32s(&self.last.unwrap_or_default());
+    }
+
+    /// Inverse of `write_to` for a state written at `version`; states before
+    /// version 3 predate the tau start, summation and window fields and get
+    /// the defaults `YinParams::new` picks, and version 1 states predate
+    /// low-power mode and analyzed every frame (stride 1)
+    pub(crate) fn read_from(input: &mut ByteReader, version: u8) -> Result<Self, BinaryError> {
+        let mut params = YinParams::new(
+            input.f32()?,
+            input.f32()?,
+            input.f32()?,
+            input.f32()?,
+            input.bool()?,
+        );
+        if version >= 3 {
+            params.tau_start = input.usize()?;
+            params.summation =
+                CmndfSummation::from_u8(input.u8()?).ok_or(BinaryError::Invalid("summation"))?;
+            params.window =
+                WindowFunction::from_u8(input.u8()?).ok_or(BinaryError::Invalid("window"))?;
+        }
+        let frame_size = input.usize()?;
+        if !(2..=MAX_FRAME_SIZE).contains(&frame_size) {
+            return Err(BinaryError::Invalid("frame size"));
+        }
+        let hop_size = input.usize()?;
+        if hop_size == 0 {
+            return Err(BinaryError::Invalid("hop size"));
+        }
+        let buffer = input.f32s()?;
+        let skip = input.usize()?;
+        let frames_emitted = input.usize()?;
+        if version < 2 {
+            return Ok(FrameStream {
+                params,
+                frame_size,
+                hop_size,
+                buffer,
+                skip,
+                frames_emitted,
+                stride: 1,
+                frames_seen: frames_emitted,
+                pending: 0,
+                last: None,
+            });
+        }
+        let stride = input.usize()?.max(1);
+        let frames_seen = input.usize()?;
+        let pending = input.usize()?;
+        let has_last = input.bool()?;
+        let last =
+            <[f32; 3]>::try_from(input.f32s()?).map_err(|_| BinaryError::Invalid("last frame"))?;
+        Ok(FrameStream {
+            params,
+            frame_size,
+            hop_size,
+            buffer,
+            skip,
+            frames_emitted,
+            stride,
+            frames_seen,
+            pending,
+            last: has_last.then_some(last),
+        })
+    }
+
+    /// Forget buffered samples and start again from frame 0
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.skip = 0;
+        self.frames_emitted = 0;
+        self.frames_seen = 0;
+        self.pending = 0;
+        self.last = None;
+    }
+
+    /// Feed samples, appending [pitch, confidence, tau] for every frame that
+    /// became complete
+    pub fn push(&mut self, samples: &[f32], out: &mut Vec<f32>) {
+        self.push_with_levels(samples, out, None);
+    }
+
+    /// `push`, also appending the RMS level of every frame framed (analyzed
+    /// or skipped by low-power mode) to `levels`
+    pub fn push_with_levels(
+        &mut self,
+        mut samples: &[f32],
+        out: &mut Vec<f32>,
+        mut levels: Option<&mut Vec<f32>>,
+    ) {
+        if self.skip > 0 {
+            let n = self.skip.min(samples.len());
+            samples = &samples[n..];
+            self.skip -= n;
+        }
+        self.buffer.extend_from_slice(samples);
+
+        let mut start = 0;
+        let mut voiced = 0;
+        let mut frames = 0;
+        while start + self.frame_size <= self.buffer.len() {
+            let index = self.frames_seen;
+            self.frames_seen += 1;
+            if let Some(levels) = levels.as_deref_mut() {
+                levels.push(rms(&self.buffer[start..start + self.frame_size]));
+            }
+            if !index.is_multiple_of(self.stride) {
+                self.pending += 1;
+                start += self.hop_size;
+                continue;
+            }
+
+            let frame = &self.buffer[start..start + self.frame_size];
+            let (pitch, confidence, tau) = yin_analyze_frame(frame, &self.params, None);
+            let current = [pitch, confidence, tau as f32];
+            self.emit_pending(current, out);
+            out.extend_from_slice(&current);
+            self.last = Some(current);
+            frames += 1;
+            if pitch > 0.0 {
+                voiced += 1;
+            }
+            start += self.hop_size;
+        }
+
+        if start >= self.buffer.len() {
+            self.skip = start - self.buffer.len();
+            self.buffer.clear();
+        } else {
+            self.buffer.drain(..start);
+        }
+
+        if frames > 0 {
+            self.frames_emitted += frames;
+            metrics::record_analysis(frames, voiced);
+        }
+    }
+}
+
+/// `perform_yin_analysis` in low-power mode: only every `stride`-th frame is
+/// analyzed and the frames in between are interpolated, cutting CPU use by
+/// about `stride` times where full temporal resolution isn't needed.
+/// Returns the same flat [pitch, confidence, tau, ...] layout and frame count.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn perform_yin_analysis_low_power(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    threshold: f32,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+    stride: usize,
+) -> Vec<f32> {
+    let mut results = Vec::new();
+    let mut stream = match FrameStream::new(
+        sample_rate,
+        frame_size,
+        hop_size,
+        threshold,
+        min_freq,
+        max_freq,
+        interpolation,
+    ) {
+        Ok(stream) => stream,
+        Err(err) => {
+            log_warn!("{}", err);
+            return results;
+        }
+    };
+    stream.set_stride(stride);
+    stream.push(audio_data, &mut results);
+    stream.flush(&mut results);
+    results
+}
+
+/// Stateful analyzer for live microphone input: feed audio chunks as they
+/// arrive (e.g. from an AudioWorklet) and draw the pitch curve while the
+/// learner is still speaking. Samples that a later frame still needs are
+/// carried over between calls, so chunk boundaries don't change the result:
+/// the frames match `perform_yin_analysis` on the whole recording.
+#[wasm_bindgen]
+pub struct YinStreamAnalyzer {
+    stream: FrameStream,
+    /// Voicing gate against the tracked noise floor, when enabled
+    gate: Option<VoicingGate>,
+    noise_floor: NoiseFloorTracker,
+    /// Levels of frames framed but not emitted yet (low-power mode)
+    levels: VecDeque<f32>,
+    /// From the device's rate to the analysis rate
+    resampler: LinearResampler,
+    rate_changes: usize,
+}
+
+#[wasm_bindgen]
+impl YinStreamAnalyzer {
+    /// Throws on framing `perform_yin_analysis` can't use either (see
+    /// `FrameStream::from_config`: a zero hop, or a frame outside
+    /// 2..=`MAX_FRAME_SIZE`)
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sample_rate: f32,
+        frame_size: usize,
+        hop_size: usize,
+        threshold: f32,
+        min_freq: f32,
+        max_freq: f32,
+        interpolation: bool,
+    ) -> Result<YinStreamAnalyzer, JsError> {
+        let stream = FrameStream::new(
+            sample_rate,
+            frame_size,
+            hop_size,
+            threshold,
+            min_freq,
+            max_freq,
+            interpolation,
+        )
+        .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(Self::with_stream(stream))
+    }
+
+    /// Analyzer with all the settings of `config` (window, summation and
+    /// tau start included); throws on unusable framing (see `new`) or when
+    /// `noise_robust` is set
+    pub fn from_config(config: &YinConfig) -> Result<YinStreamAnalyzer, JsError> {
+        let stream =
+            FrameStream::from_config(config).map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(Self::with_stream(stream))
+    }
+
+    /// Feed the next chunk of samples, recorded at the current `input_rate`.
+    /// Returns the frames it completed as a flat [pitch, confidence, tau,
+    /// ...] array (possibly empty).
+    pub fn push_samples(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.push_samples_at_rate(samples, self.resampler.from_rate())
+    }
+
+    /// `push_samples` for a chunk recorded at `input_rate`, the device's
+    /// rate at the time. When it differs from the previous chunk's (a
+    /// headset was plugged in or out), the change is counted in
+    /// `rate_changes` and input is resampled to the analysis rate from then
+    /// on, so frames keep their spacing and the frame index continues
+    /// where it left off instead of the track being stretched.
+    pub fn push_samples_at_rate(&mut self, samples: &[f32], input_rate: f32) -> Vec<f32> {
+        if input_rate > 0.0 && input_rate != self.resampler.from_rate() {
+            self.resampler.set_from_rate(input_rate);
+            self.rate_changes += 1;
+        }
+        let mut resampled = Vec::with_capacity(samples.len());
+        self.resampler.process(samples, &mut resampled);
+        self.push_resampled(&resampled)
+    }
+
+    /// Rate (Hz) the last chunk was recorded at; initially the analysis rate
+    #[wasm_bindgen(getter)]
+    pub fn input_rate(&self) -> f32 {
+        self.resampler.from_rate()
+    }
+
+    /// Input rate changes seen since the analyzer was created
+    #[wasm_bindgen(getter)]
+    pub fn rate_changes(&self) -> usize {
+        self.rate_changes
+    }
+
+    /// Gate frames against a noise floor tracked through the recording:
+    /// frames whose voicing probability (see `frame_voicing`) is below
+    /// `gate.min_probability` report pitch 0. The floor follows the level
+    /// of unvoiced stretches, so background noise that starts mid-session
+    /// doesn't turn into voiced frames.
+    pub fn enable_voicing_gate(&mut self, gate: &VoicingGate) {
+        self.gate = Some(*gate);
+    }
+
+    /// Stop gating; frames are reported as YIN found them
+    pub fn disable_voicing_gate(&mut self) {
+        self.gate = None;
+    }
+
+    /// Tracked noise floor in dBFS (RMS), or -Infinity before any gated
+    /// frame
+    #[wasm_bindgen(getter)]
+    pub fn noise_floor_db(&self) -> f32 {
+        20.0 * self.noise_floor.floor_rms().log10()
+    }
+
+    /// Frames produced so far; the next frame pushed is this frame index
+    #[wasm_bindgen(getter)]
+    pub fn frames_emitted(&self) -> usize {
+        self.stream.frames_emitted()
+    }
+
+    /// Start a new recording with the same settings
+    pub fn reset(&mut self) {
+        self.stream.reset();
+        self.noise_floor.reset();
+        self.levels.clear();
+        self.resampler.reset();
+    }
+}
+
+impl YinStreamAnalyzer {
+    /// Analyzer around a stream whose settings were already checked
+    pub(crate) fn with_stream(stream: FrameStream) -> YinStreamAnalyzer {
+        let sample_rate = stream.sample_rate();
+        YinStreamAnalyzer {
+            noise_floor: NoiseFloorTracker::new(stream.hop_size as f32 / sample_rate),
+            stream,
+            gate: None,
+            levels: VecDeque::new(),
+            resampler: LinearResampler::new(sample_rate, sample_rate),
+            rate_changes: 0,
+        }
+    }
+
+    /// Frame and analyze samples already at the analysis rate
+    fn push_resampled(&mut self, samples: &[f32]) -> Vec<f32> {
+        let mut results = Vec::new();
+        let Some(gate) = self.gate else {
+            self.stream.push(samples, &mut results);
+            return results;
+        };
+
+        let mut levels = Vec::new();
+        self.stream
+            .push_with_levels(samples, &mut results, Some(&mut levels));
+        self.levels.extend(levels);
+        for frame in results.chunks_exact_mut(3) {
+            let level = self.levels.pop_front().unwrap_or(0.0);
+            self.noise_floor.gate_frame(frame, level, &gate);
+        }
+        results
+    }
+}
+
+/// Header of a serialized `AnalysisState`
+const STATE_MAGIC: &[u8; 4] = b"YINS";
+
+/// Current `AnalysisState` encoding version
+const STATE_VERSION: u8 = 3;
+
+/// Oldest `AnalysisState` encoding version that can still be read
+const OLDEST_STATE_VERSION: u8 = 1;
+
+/// Saved state of a YIN analysis that can be continued when more audio
+/// arrives, without re-analyzing the prefix that was already processed
+/// (e.g. the learner keeps talking after a provisional stop).
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct AnalysisState {
+    stream: FrameStream,
+    samples_consumed: usize,
+}
+
+#[wasm_bindgen]
+impl AnalysisState {
+    /// Throws on framing `perform_yin_analysis` can't use either (a zero
+    /// hop, or a frame outside 2..=`MAX_FRAME_SIZE`)
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sample_rate: f32,
+        frame_size: usize,
+        hop_size: usize,
+        threshold: f32,
+        min_freq: f32,
+        max_freq: f32,
+        interpolation: bool,
+    ) -> Result<AnalysisState, JsError> {
+        let stream = FrameStream::new(
+            sample_rate,
+            frame_size,
+            hop_size,
+            threshold,
+            min_freq,
+            max_freq,
+            interpolation,
+        )
+        .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(AnalysisState {
+            stream,
+            samples_consumed: 0,
+        })
+    }
+
+    /// Analyze `audio`, the samples following everything seen so far.
+    /// Returns only the frames completed by this call as a flat
+    /// [pitch, confidence, tau, ...] array, whose first frame is frame
+    /// `frames_analyzed` (as read before the call) of the whole recording.
+    pub fn continue_analysis(&mut self, audio: &[f32]) -> Vec<f32> {
+        let mut results = Vec::new();
+        self.stream.push(audio, &mut results);
+        self.samples_consumed += audio.len();
+        results
+    }
+
+    /// Serialize the state so the analysis can survive a page reload or be
+    /// handed to another worker and resumed with `from_bytes`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = ByteWriter::with_header(STATE_MAGIC, STATE_VERSION);
+        self.stream.write_to(&mut out);
+        out.usize(self.samples_consumed);
+        out.bytes
+    }
+
+    /// Restore a state saved with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<AnalysisState, JsError> {
+        Self::decode(bytes).map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// Low-power mode, see `FrameStream::set_stride`
+    pub fn set_frame_stride(&mut self, stride: usize) {
+        self.stream.set_stride(stride);
+    }
+
+    /// Emit the frames low-power mode is still holding back
+    pub fn flush(&mut self) -> Vec<f32> {
+        let mut results = Vec::new();
+        self.stream.flush(&mut results);
+        results
+    }
+
+    /// Copy of the current state, to continue from this point later
+    pub fn snapshot(&self) -> AnalysisState {
+        self.clone()
+    }
+
+    /// Frames produced so far
+    #[wasm_bindgen(getter)]
+    pub fn frames_analyzed(&self) -> usize {
+        self.stream.frames_emitted()
+    }
+
+    /// Samples fed so far
+    #[wasm_bindgen(getter)]
+    pub fn samples_consumed(&self) -> usize {
+        self.samples_consumed
+    }
+}
+
+impl AnalysisState {
+    pub(crate) fn decode(bytes: &[u8]) -> Result<AnalysisState, BinaryError> {
+        let (mut input, version) = ByteReader::with_header(bytes, STATE_MAGIC)?;
+        if !(OLDEST_STATE_VERSION..=STATE_VERSION).contains(&version) {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+        let stream = FrameStream::read_from(&mut input, version)?;
+        let samples_consumed = input.usize()?;
+        input.finish()?;
+        Ok(AnalysisState {
+            stream,
+            samples_consumed,
+        })
+    }
+}
+
+/// Streaming decode + analysis of a WAV file that never holds the whole
+/// waveform: feed the file in chunks with `push_bytes` (e.g. straight from a
+/// ReadableStream reader), pitch frames are emitted as soon as they are
+/// complete, and `finish` ends the stream. Compressed containers are
+/// recognized and rejected with a descriptive error.
+#[wasm_bindgen]
+pub struct StreamingWavAnalyzer {
+    decoder: WavStreamDecoder,
+    bytes_consumed: usize,
+    frames: Option<FrameStream>,
+    samples: Vec<f32>,
+    frame_size: usize,
+    hop_size: usize,
+    threshold: f32,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+}
+
+#[wasm_bindgen]
+impl StreamingWavAnalyzer {
+    /// Throws on framing `perform_yin_analysis` can't use either (a zero
+    /// hop, or a frame outside 2..=`MAX_FRAME_SIZE`)
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        frame_size: usize,
+        hop_size: usize,
+        threshold: f32,
+        min_freq: f32,
+        max_freq: f32,
+        interpolation: bool,
+    ) -> Result<StreamingWavAnalyzer, JsError> {
+        if let Some(err) = framing_error(frame_size, hop_size) {
+            return Err(JsError::new(&err.to_string()));
+        }
+        Ok(StreamingWavAnalyzer {
+            decoder: WavStreamDecoder::new(),
+            bytes_consumed: 0,
+            frames: None,
+            samples: Vec::new(),
+            frame_size,
+            hop_size,
+            threshold,
+            min_freq,
+            max_freq,
+            interpolation,
+        })
+    }
+
+    /// Feed the next chunk of file bytes. Returns the frames completed by this
+    /// chunk as a flat [pitch, confidence, tau, ...] array.
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<Vec<f32>, JsError> {
+        self.bytes_consumed += bytes.len();
+        self.samples.clear();
+        if let Err(err) = self.decoder.push(bytes, &mut self.samples) {
+            metrics::record_decode_error();
+            return Err(JsError::new(&err.to_string()));
+        }
+
+        let mut results = Vec::new();
+        if self.frames.is_none() {
+            if let Some(format) = self.decoder.format() {
+                let frames = FrameStream::new(
+                    format.sample_rate as f32,
+                    self.frame_size,
+                    self.hop_size,
+                    self.threshold,
+                    self.min_freq,
+                    self.max_freq,
+                    self.interpolation,
+                )
+                .map_err(|err| JsError::new(&err.to_string()))?;
+                self.frames = Some(frames);
+            }
+        }
+        if let Some(frames) = self.frames.as_mut() {
+            frames.push(&self.samples, &mut results);
+        }
+        Ok(results)
+    }
+
+    /// Sample rate from the WAV header, or 0 before it has been read
+    #[wasm_bindgen(getter)]
+    pub fn sample_rate(&self) -> f32 {
+        self.decoder
+            .format()
+            .map_or(0.0, |format| format.sample_rate as f32)
+    }
+
+    /// Number of frames emitted so far
+    #[wasm_bindgen(getter)]
+    pub fn frames_emitted(&self) -> usize {
+        self.frames.as_ref().map_or(0, FrameStream::frames_emitted)
+    }
+
+    /// Call once the byte stream has ended (e.g. the ReadableStream reader
+    /// reported `done`). Returns any frames still held back, or an error if
+    /// the stream ended before the audio data.
+    pub fn finish(&mut self) -> Result<Vec<f32>, JsError> {
+        if let Err(err) = self.decoder.finish() {
+            metrics::record_decode_error();
+            return Err(JsError::new(&err.to_string()));
+        }
+        let mut results = Vec::new();
+        if let Some(frames) = self.frames.as_mut() {
+            frames.flush(&mut results);
+        }
+        Ok(results)
+    }
+
+    /// Total file bytes fed so far
+    #[wasm_bindgen(getter)]
+    pub fn bytes_consumed(&self) -> usize {
+        self.bytes_consumed
+    }
+
+    /// True once the end of the WAV data chunk has been reached
+    #[wasm_bindgen(getter)]
+    pub fn finished(&self) -> bool {
+        self.decoder.is_done()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tau_search_start;
+
+    #[test]
+    fn degenerate_frame_sizes_are_rejected() {
+        for frame_size in [0, 1] {
+            let stream = FrameStream::new(44100.0, frame_size, 1, 0.15, 80.0, 500.0, true);
+            assert!(matches!(stream, Err(ConfigError::FrameTooShort { .. })));
+        }
+    }
+
+    #[test]
+    fn stream_uses_all_config_params() {
+        let config = YinConfig {
+            tau_start: 40,
+            summation: CmndfSummation::Compensated,
+            window: WindowFunction::Hann,
+            ..YinConfig::new()
+        };
+        let audio: Vec<f32> = (0..8192)
+            .map(|i| (i as f32 * 2.0 * std::f32::consts::PI * 180.0 / 44100.0).sin())
+            .collect();
+        let mut results = Vec::new();
+        FrameStream::from_config(&config)
+            .unwrap()
+            .push(&audio, &mut results);
+        assert_eq!(results, config.analyze(&audio));
+
+        let noise_robust = YinConfig {
+            noise_robust: true,
+            ..config
+        };
+        assert_eq!(
+            FrameStream::from_config(&noise_robust).err(),
+            Some(ConfigError::NoiseRobustStreaming)
+        );
+    }
+
+    #[test]
+    fn stream_accepts_what_the_flat_api_accepts() {
+        // A frame too short for min_freq by `check`, as in the app's defaults
+        let config = YinConfig {
+            min_freq: 30.0,
+            ..YinConfig::new()
+        };
+        assert!(config.check().is_err());
+        let audio: Vec<f32> = (0..8192)
+            .map(|i| (i as f32 * 2.0 * std::f32::consts::PI * 180.0 / 44100.0).sin())
+            .collect();
+        let mut results = Vec::new();
+        FrameStream::from_config(&config)
+            .unwrap()
+            .push(&audio, &mut results);
+        assert_eq!(results, config.analyze(&audio));
+
+        let zero_hop = YinConfig {
+            hop_size: 0,
+            ..config
+        };
+        assert_eq!(
+            FrameStream::from_config(&zero_hop).err(),
+            Some(ConfigError::ZeroHopSize)
+        );
+    }
+
+    #[test]
+    fn state_with_bad_frame_size_is_rejected() {
+        let stream = FrameStream::from_config(&YinConfig::new()).unwrap();
+        for frame_size in [0, 1, MAX_FRAME_SIZE + 1] {
+            let mut out = ByteWriter::with_header(STATE_MAGIC, STATE_VERSION);
+            FrameStream {
+                frame_size,
+                ..stream.clone()
+            }
+            .write_to(&mut out);
+            let (mut input, version) = ByteReader::with_header(&out.bytes, STATE_MAGIC).unwrap();
+            assert!(matches!(
+                FrameStream::read_from(&mut input, version),
+                Err(BinaryError::Invalid("frame size"))
+            ));
+        }
+    }
+
+    #[test]
+    fn state_keeps_window_and_summation() {
+        let config = YinConfig {
+            tau_start: 40,
+            summation: CmndfSummation::Compensated,
+            window: WindowFunction::Blackman,
+            ..YinConfig::new()
+        };
+        let state = AnalysisState {
+            stream: FrameStream::from_config(&config).unwrap(),
+            samples_consumed: 0,
+        };
+        let restored = AnalysisState::decode(&state.to_bytes()).unwrap();
+        assert_eq!(restored.stream.params.tau_start, 40);
+        assert_eq!(
+            restored.stream.params.summation,
+            CmndfSummation::Compensated
+        );
+        assert_eq!(restored.stream.params.window, WindowFunction::Blackman);
+    }
+
+    #[test]
+    fn version_2_state_gets_default_params() {
+        let mut out = ByteWriter::with_header(STATE_MAGIC, 2);
+        out.f32(44100.0);
+        out.f32(0.15);
+        out.f32(80.0);
+        out.f32(500.0);
+        out.bool(true);
+        out.usize(2048);
+        out.usize(512);
+        out.f32s(&[]);
+        for value in [0, 3, 1, 3, 0] {
+            out.usize(value);
+        }
+        out.bool(false);
+        out.f32s(&[0.0; 3]);
+        out.usize(2048 + 2 * 512);
+        let state = AnalysisState::decode(&out.bytes).unwrap();
+        let params = &state.stream.params;
+        assert_eq!(params.tau_start, tau_search_start(44100.0, 500.0));
+        assert_eq!(params.summation, CmndfSummation::Plain);
+        assert_eq!(params.window, WindowFunction::Rectangular);
+        assert_eq!(state.frames_analyzed(), 3);
+    }
+
+    #[test]
+    fn version_1_state_analyzes_every_frame() {
+        let audio: Vec<f32> = (0..8192)
+            .map(|i| (i as f32 * 2.0 * std::f32::consts::PI * 180.0 / 44100.0).sin())
+            .collect();
+        let mut out = ByteWriter::with_header(STATE_MAGIC, 1);
+        out.f32(44100.0);
+        out.f32(0.15);
+        out.f32(80.0);
+        out.f32(500.0);
+        out.bool(true);
+        out.usize(2048);
+        out.usize(512);
+        out.f32s(&audio[..2048]);
+        out.usize(0);
+        out.usize(0);
+        out.usize(2048);
+        let mut state = AnalysisState::decode(&out.bytes).unwrap();
+        assert_eq!(state.stream.stride, 1);
+        assert_eq!(state.frames_analyzed(), 0);
+
+        let results = state.continue_analysis(&audio[2048..]);
+        assert_eq!(results, YinConfig::new().analyze(&audio));
+        assert!(state.flush().is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+//! Coarse summaries of long recordings (e.g. an hour of immersion audio):
+//! instead of a frame every few milliseconds, one row of pitch statistics
+//! per window of several seconds, small enough to chart the whole recording.
+//! Audio is analyzed as it streams in and only the frames of the current
+//! window are kept.
+
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::{ConfigError, YinConfig};
+use crate::logging::log_warn;
+use crate::stream::{FrameStream, YinStreamAnalyzer};
+
+/// Window length (seconds) used when none is given
+pub const DEFAULT_SUMMARY_SECONDS: f32 = 10.0;
+
+/// Values per summary row: [start, voiced ratio, median, p10, p90]
+pub const SUMMARY_STRIDE: usize = 5;
+
+/// Value at `fraction` (0..1) of the sorted values
+fn percentile(sorted: &[f32], fraction: f32) -> f32 {
+    sorted[((sorted.len() - 1) as f32 * fraction).round() as usize]
+}
+
+/// Summarizes a recording as it is pushed, one row per window. Windows are
+/// `window_seconds` long and start every `step_seconds`, so a step shorter
+/// than the window gives overlapping (sliding) windows.
+#[wasm_bindgen]
+pub struct RecordingSummarizer {
+    analyzer: YinStreamAnalyzer,
+    frame_period: f32,
+    window_frames: usize,
+    step_frames: usize,
+    /// Pitch of the frames of the current window
+    window: VecDeque<f32>,
+    /// Index of the window's first frame
+    window_start: usize,
+    /// Frames still to skip before the next window starts, when the step
+    /// is longer than the window
+    skip: usize,
+    windows: usize,
+}
+
+#[wasm_bindgen]
+impl RecordingSummarizer {
+    /// Summarizer analyzing with `config`; `window_seconds` and
+    /// `step_seconds` of 0 or less use `DEFAULT_SUMMARY_SECONDS` and
+    /// back-to-back windows. Throws on unusable framing or `noise_robust`
+    /// (see `FrameStream::from_config`).
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        config: &YinConfig,
+        window_seconds: f32,
+        step_seconds: f32,
+    ) -> Result<RecordingSummarizer, JsError> {
+        Self::build(config, window_seconds, step_seconds)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// Feed the next chunk of samples (at the config's sample rate).
+    /// Returns the windows it completed as flat [start, voiced ratio,
+    /// median, p10, p90, ...] rows: start in seconds, the share of voiced
+    /// frames, and the median, 10th and 90th percentile of the voiced pitch
+    /// in Hz (0 when nothing is voiced).
+    pub fn push_samples(&mut self, samples: &[f32]) -> Vec<f32> {
+        let frames = self.analyzer.push_samples(samples);
+        let mut out = Vec::new();
+        for frame in frames.chunks_exact(3) {
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            self.window.push_back(frame[0]);
+            if self.window.len() == self.window_frames {
+                self.summarize(&mut out);
+                self.advance();
+            }
+        }
+        out
+    }
+
+    /// Summarize the last, partial window (the recording ended); same
+    /// layout as `push_samples`
+    pub fn finish(&mut self) -> Vec<f32> {
+        let mut out = Vec::new();
+        // Frames already reported by an overlapping window don't need a row
+        // of their own
+        let reported = if self.windows > 0 {
+            self.window_frames - self.step_frames.min(self.window_frames)
+        } else {
+            0
+        };
+        if self.window.len() > reported {
+            self.summarize(&mut out);
+        }
+        self.window.clear();
+        out
+    }
+
+    /// Windows summarized so far
+    #[wasm_bindgen(getter)]
+    pub fn windows(&self) -> usize {
+        self.windows
+    }
+
+    /// Start over on a new recording
+    pub fn reset(&mut self) {
+        self.analyzer.reset();
+        self.window.clear();
+        self.window_start = 0;
+        self.skip = 0;
+        self.windows = 0;
+    }
+}
+
+impl RecordingSummarizer {
+    /// `new`, if `config` is usable for streaming (see
+    /// `FrameStream::from_config`)
+    fn build(
+        config: &YinConfig,
+        window_seconds: f32,
+        step_seconds: f32,
+    ) -> Result<RecordingSummarizer, ConfigError> {
+        let stream = FrameStream::from_config(config)?;
+        let frame_period = config.hop_size as f32 / config.sample_rate;
+        let window_seconds = if window_seconds > 0.0 {
+            window_seconds
+        } else {
+            DEFAULT_SUMMARY_SECONDS
+        };
+        let step_seconds = if step_seconds > 0.0 {
+            step_seconds
+        } else {
+            window_seconds
+        };
+        let frames = |seconds: f32| ((seconds / frame_period).round() as usize).max(1);
+        Ok(RecordingSummarizer {
+            analyzer: YinStreamAnalyzer::with_stream(stream),
+            frame_period,
+            window_frames: frames(window_seconds),
+            step_frames: frames(step_seconds),
+            window: VecDeque::new(),
+            window_start: 0,
+            skip: 0,
+            windows: 0,
+        })
+    }
+
+    fn summarize(&mut self, out: &mut Vec<f32>) {
+        let mut voiced: Vec<f32> = self.window.iter().copied().filter(|&p| p > 0.0).collect();
+        voiced.sort_by(|a, b| a.total_cmp(b));
+        let ratio = voiced.len() as f32 / self.window.len().max(1) as f32;
+        let stats = if voiced.is_empty() {
+            [0.0; 3]
+        } else {
+            [0.5, 0.1, 0.9].map(|fraction| percentile(&voiced, fraction))
+        };
+        out.push(self.window_start as f32 * self.frame_period);
+        out.push(ratio);
+        out.extend(stats);
+        self.windows += 1;
+    }
+
+    /// Move the window on by one step
+    fn advance(&mut self) {
+        let drop = self.step_frames.min(self.window.len());
+        self.window.drain(..drop);
+        self.window_start += self.step_frames;
+        // A step longer than the window skips frames between windows
+        self.skip = self.step_frames - drop;
+    }
+}
+
+/// Summarize a whole recording, see `RecordingSummarizer`
+#[wasm_bindgen]
+pub fn summarize_recording(
+    audio_data: &[f32],
+    config: &YinConfig,
+    window_seconds: f32,
+    step_seconds: f32,
+) -> Vec<f32> {
+    let mut summarizer = match RecordingSummarizer::build(config, window_seconds, step_seconds) {
+        Ok(summarizer) => summarizer,
+        Err(err) => {
+            log_warn!("{}", err);
+            return Vec::new();
+        }
+    };
+    let mut rows = summarizer.push_samples(audio_data);
+    rows.extend(summarizer.finish());
+    rows
+}
@@ -0,0 +1,247 @@
+//! Syllable segmentation of multi-syllable prompts. Voiced stretches come
+//! from the pitch track and the energy above the noise floor; a stretch is
+//! then split where the energy envelope dips between two syllables or where
+//! the pitch jumps instead of gliding, since connected speech often runs
+//! syllables together without an unvoiced gap.
+
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::agc::detrend_levels;
+use crate::classify::{classify_ranges, ToneGuess};
+use crate::config::YinConfig;
+use crate::input::frame_levels;
+use crate::noise::estimate_noise;
+use crate::segments::{ms_to_frames, pitch_column, voiced_runs};
+
+/// Level above the noise floor (dB) a frame needs to count as voiced
+const VOICED_ABOVE_NOISE_DB: f32 = 6.0;
+
+/// Unvoiced gaps shorter than this stay inside a syllable
+const MAX_GAP_MS: f32 = 30.0;
+
+/// Shortest syllable; shorter voiced stretches are dropped and no split
+/// leaves a piece shorter than this
+const MIN_SYLLABLE_MS: f32 = 60.0;
+
+/// Depth (dB) an energy dip needs below the peaks on both sides to separate
+/// two syllables
+const MIN_DIP_DB: f32 = 6.0;
+
+/// Pitch change (semitones) between consecutive frames that breaks pitch
+/// continuity and starts a new syllable
+const MAX_GLIDE_SEMITONES: f32 = 3.0;
+
+/// Lowest level considered when computing decibels
+const LEVEL_FLOOR: f32 = 1e-9;
+
+/// Syllables found by `segment_syllables`, with the analysis they came from
+#[wasm_bindgen]
+pub struct SyllableSegmentation {
+    /// Frame ranges [start, end)
+    ranges: Vec<(usize, usize)>,
+    results: Vec<f32>,
+    frame_size: usize,
+    hop_size: usize,
+    sample_rate: f32,
+    audio_len: usize,
+}
+
+#[wasm_bindgen]
+impl SyllableSegmentation {
+    /// Number of syllables found
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Syllables as a flat [start_sample, end_sample, ...] array (end
+    /// exclusive), covering the frames of each syllable
+    #[wasm_bindgen(getter)]
+    pub fn boundaries(&self) -> Vec<usize> {
+        self.ranges
+            .iter()
+            .flat_map(|&(start, end)| {
+                [
+                    start * self.hop_size,
+                    ((end - 1) * self.hop_size + self.frame_size).min(self.audio_len),
+                ]
+            })
+            .collect()
+    }
+
+    /// Syllables as a flat [start_frame, end_frame, ...] array of frame
+    /// indices into `results` (end exclusive)
+    #[wasm_bindgen(getter)]
+    pub fn frame_ranges(&self) -> Vec<usize> {
+        self.ranges.iter().flat_map(|&(s, e)| [s, e]).collect()
+    }
+
+    /// The flat [pitch, confidence, tau, ...] analysis that was segmented
+    #[wasm_bindgen(getter)]
+    pub fn results(&self) -> Vec<f32> {
+        self.results.clone()
+    }
+
+    /// Audio of syllable `index` cut from `audio_data` (the recording that
+    /// was segmented) with `fade_ms` fades at both ends, for replaying one
+    /// syllable on its own; empty when there is no such syllable
+    pub fn syllable_audio(&self, audio_data: &[f32], index: usize, fade_ms: f32) -> Vec<f32> {
+        let Some(&(start, end)) = self.ranges.get(index) else {
+            return Vec::new();
+        };
+        let end = ((end - 1) * self.hop_size + self.frame_size).min(audio_data.len());
+        extract_segment(
+            audio_data,
+            start * self.hop_size,
+            end,
+            self.sample_rate,
+            fade_ms,
+        )
+    }
+
+    /// Tone of every syllable, as `classify_tones` would judge it given
+    /// these boundaries
+    pub fn classify(&self) -> Vec<ToneGuess> {
+        let pitch = pitch_column(&self.results);
+        let period = self.hop_size as f32 / self.sample_rate;
+        let centre = self.frame_size as f32 / 2.0 / self.sample_rate;
+        let timestamps: Vec<f32> = (0..pitch.len())
+            .map(|i| centre + i as f32 * period)
+            .collect();
+        classify_ranges(&pitch, &timestamps, &self.ranges)
+    }
+}
+
+/// Samples [start, end) of `audio_data` with raised-cosine fades of
+/// `fade_ms` (at most half the segment each) so playback starts and stops
+/// without clicks
+#[wasm_bindgen]
+pub fn extract_segment(
+    audio_data: &[f32],
+    start: usize,
+    end: usize,
+    sample_rate: f32,
+    fade_ms: f32,
+) -> Vec<f32> {
+    let end = end.min(audio_data.len());
+    let mut segment = audio_data[start.min(end)..end].to_vec();
+    let len = segment.len();
+    let fade = ((fade_ms.max(0.0) / 1000.0 * sample_rate) as usize).min(len / 2);
+    for i in 0..fade {
+        let gain = 0.5 - 0.5 * (PI * (i as f32 + 0.5) / fade as f32).cos();
+        segment[i] *= gain;
+        segment[len - 1 - i] *= gain;
+    }
+    segment
+}
+
+/// Split a voiced stretch [start, end) at its deepest energy dip, if that
+/// dip is deep enough and leaves both pieces at least `min_len` long
+fn split_at_dips(
+    levels_db: &[f32],
+    (start, end): (usize, usize),
+    min_len: usize,
+    out: &mut Vec<(usize, usize)>,
+) {
+    let dip = (start + min_len..end.saturating_sub(min_len))
+        .filter(|&i| levels_db[i] <= levels_db[i - 1] && levels_db[i] <= levels_db[i + 1])
+        .filter(|&i| {
+            let before = levels_db[start..i].iter().copied().fold(f32::MIN, f32::max);
+            let after = levels_db[i..end].iter().copied().fold(f32::MIN, f32::max);
+            before.min(after) - levels_db[i] >= MIN_DIP_DB
+        })
+        .min_by(|&a, &b| levels_db[a].total_cmp(&levels_db[b]));
+    match dip {
+        Some(i) => {
+            split_at_dips(levels_db, (start, i), min_len, out);
+            split_at_dips(levels_db, (i, end), min_len, out);
+        }
+        None => out.push((start, end)),
+    }
+}
+
+/// Split [start, end) where consecutive voiced frames jump by more than
+/// `MAX_GLIDE_SEMITONES`, keeping pieces at least `min_len` long
+fn split_at_jumps(
+    pitch: &[f32],
+    (start, end): (usize, usize),
+    min_len: usize,
+    out: &mut Vec<(usize, usize)>,
+) {
+    let mut piece_start = start;
+    let mut previous: Option<f32> = None;
+    for (i, &p) in pitch.iter().enumerate().take(end).skip(start) {
+        if p <= 0.0 {
+            continue;
+        }
+        if let Some(prev) = previous {
+            let jump = (12.0 * (p / prev).log2()).abs();
+            if jump > MAX_GLIDE_SEMITONES && i - piece_start >= min_len && end - i >= min_len {
+                out.push((piece_start, i));
+                piece_start = i;
+            }
+        }
+        previous = Some(p);
+    }
+    out.push((piece_start, end));
+}
+
+/// Split `audio_data` into syllables using the energy envelope, voicing and
+/// pitch continuity. Throws when the settings are invalid or the audio is
+/// shorter than one frame.
+#[wasm_bindgen]
+pub fn segment_syllables(
+    audio_data: &[f32],
+    config: &YinConfig,
+) -> Result<SyllableSegmentation, JsError> {
+    let results = config
+        .analyze_checked(audio_data)
+        .map_err(|err| JsError::new(&err.to_string()))?;
+    let (frame_size, hop_size) = (config.frame_size, config.hop_size);
+    let levels = frame_levels(audio_data, frame_size, hop_size);
+    let noise = estimate_noise(audio_data, frame_size, hop_size);
+    let gate = noise.noise_rms * 10f32.powf(VOICED_ABOVE_NOISE_DB / 20.0);
+
+    // Voiced: YIN found a pitch and the frame stands out of the noise
+    let pitch: Vec<f32> = pitch_column(&results)
+        .into_iter()
+        .zip(&levels)
+        .map(|(p, &level)| if level > gate { p } else { 0.0 })
+        .collect();
+    // Dips are judged with AGC gain changes removed, so a gain ramp inside
+    // a syllable isn't taken for a boundary
+    let levels_db: Vec<f32> = detrend_levels(
+        &levels,
+        noise.noise_rms,
+        hop_size as f32 / config.sample_rate,
+    )
+    .iter()
+    .map(|&l| 20.0 * l.max(LEVEL_FLOOR).log10())
+    .collect();
+
+    let min_len = ms_to_frames(MIN_SYLLABLE_MS, config.sample_rate, hop_size).max(1);
+    let runs = voiced_runs(
+        &pitch,
+        ms_to_frames(MAX_GAP_MS, config.sample_rate, hop_size),
+        min_len,
+    );
+    let mut by_energy = Vec::new();
+    for &run in &runs {
+        split_at_dips(&levels_db, run, min_len, &mut by_energy);
+    }
+    let mut ranges = Vec::new();
+    for &piece in &by_energy {
+        split_at_jumps(&pitch, piece, min_len, &mut ranges);
+    }
+
+    Ok(SyllableSegmentation {
+        ranges,
+        results,
+        frame_size,
+        hop_size,
+        sample_rate: config.sample_rate,
+        audio_len: audio_data.len(),
+    })
+}
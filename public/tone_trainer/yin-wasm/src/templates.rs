@@ -0,0 +1,346 @@
+use wasm_bindgen::prelude::*;
+
+use crate::dtw::{align_to, dba, dtw, split_flat};
+use crate::fit::{voiced_coverage, FitQuality, MAX_FIT_RMS_SEMITONES};
+use crate::rubric::ScoringRubric;
+use crate::segments::voiced_core;
+use crate::tones::{hz_to_semitones, voiced_median};
+
+/// Smallest tolerance band (semitones) so that points where all recordings
+/// happen to agree don't demand an exact match
+const MIN_BAND_SEMITONES: f32 = 0.5;
+
+/// DBA refinement passes when building a template
+const DBA_ITERATIONS: usize = 10;
+
+/// Two-sided 80% Student t quantiles for 1..=10 degrees of freedom, and the
+/// normal quantile beyond. With few recordings the sample spread is itself
+/// uncertain, so the band widens.
+const T_80: [f32; 10] = [
+    3.078, 1.886, 1.638, 1.533, 1.476, 1.440, 1.415, 1.397, 1.383, 1.372,
+];
+const Z_80: f32 = 1.282;
+
+/// Canonical contour averaged from several native recordings of one item.
+/// Values are semitones relative to each speaker's median pitch.
+#[wasm_bindgen]
+pub struct ReferenceTemplate {
+    mean: Vec<f32>,
+    spread: Vec<f32>,
+    count: usize,
+}
+
+#[wasm_bindgen]
+impl ReferenceTemplate {
+    /// Averaged contour
+    #[wasm_bindgen(getter)]
+    pub fn mean(&self) -> Vec<f32> {
+        self.mean.clone()
+    }
+
+    /// Per-point standard deviation across the recordings
+    #[wasm_bindgen(getter)]
+    pub fn spread(&self) -> Vec<f32> {
+        self.spread.clone()
+    }
+
+    /// Number of recordings the template was built from
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Per-point half-width (semitones) of the tolerance band: the 80%
+    /// prediction interval of another native recording, from the sample
+    /// spread and a Student t quantile for the number of recordings, never
+    /// narrower than `MIN_BAND_SEMITONES`. The scorer only penalizes
+    /// deviations beyond it.
+    #[wasm_bindgen(getter)]
+    pub fn band(&self) -> Vec<f32> {
+        (0..self.mean.len()).map(|i| self.band_at(i)).collect()
+    }
+
+    /// Lower edge of the tolerance band, `mean - band`
+    #[wasm_bindgen(getter)]
+    pub fn lower_band(&self) -> Vec<f32> {
+        self.mean
+            .iter()
+            .zip(self.band())
+            .map(|(m, b)| m - b)
+            .collect()
+    }
+
+    /// Upper edge of the tolerance band, `mean + band`
+    #[wasm_bindgen(getter)]
+    pub fn upper_band(&self) -> Vec<f32> {
+        self.mean
+            .iter()
+            .zip(self.band())
+            .map(|(m, b)| m + b)
+            .collect()
+    }
+}
+
+impl ReferenceTemplate {
+    /// Band half-width at point `i` (see `band`). A single recording has no
+    /// spread to go by, so it gets the minimum band.
+    fn band_at(&self, i: usize) -> f32 {
+        if self.count < 2 {
+            return MIN_BAND_SEMITONES;
+        }
+        let n = self.count as f32;
+        let dof = self.count - 1;
+        let t = T_80.get(dof - 1).copied().unwrap_or(Z_80);
+        // `spread` divides by n; the sample standard deviation by n - 1
+        let sample_sd = self.spread[i] * (n / (n - 1.0)).sqrt();
+        (t * sample_sd * (1.0 + 1.0 / n).sqrt()).max(MIN_BAND_SEMITONES)
+    }
+
+    /// Build a template from contours that are already in semitones.
+    /// The canonical contour is the DTW barycenter average of the inputs; the
+    /// spread is measured after aligning every contour to that average.
+    pub fn from_contours(contours: &[&[f32]]) -> Option<Self> {
+        let contours: Vec<&[f32]> = contours.iter().copied().filter(|c| !c.is_empty()).collect();
+        let mean = dba(&contours, DBA_ITERATIONS);
+        if mean.is_empty() {
+            return None;
+        }
+
+        let aligned: Vec<Vec<f32>> = contours.iter().map(|c| align_to(&mean, c)).collect();
+        let n = aligned.len() as f32;
+        let spread = (0..mean.len())
+            .map(|i| {
+                let var = aligned
+                    .iter()
+                    .map(|a| (a[i] - mean[i]) * (a[i] - mean[i]))
+                    .sum::<f32>()
+                    / n;
+                var.sqrt()
+            })
+            .collect();
+
+        Some(ReferenceTemplate {
+            mean,
+            spread,
+            count: aligned.len(),
+        })
+    }
+
+    /// Residuals of a semitone contour against the template along their
+    /// DTW alignment; `coverage` is the voiced share of the track the
+    /// contour came from
+    pub fn fit_quality(&self, contour: &[f32], coverage: f32) -> FitQuality {
+        if contour.is_empty() || self.mean.is_empty() {
+            return FitQuality::from_residuals(&[], &[], coverage, MAX_FIT_RMS_SEMITONES);
+        }
+        let (_, path) = dtw(&self.mean, contour);
+        let (observed, fitted): (Vec<f32>, Vec<f32>) = path
+            .iter()
+            .map(|&(i, j)| (contour[j], self.mean[i]))
+            .unzip();
+        FitQuality::from_residuals(&observed, &fitted, coverage, MAX_FIT_RMS_SEMITONES)
+    }
+
+    /// Score (0-100) a semitone contour against the template. Deviations inside
+    /// the per-point tolerance band (see `band`) are free; only the excess
+    /// beyond it is penalized.
+    /// The shape score is combined with register (mean level difference) and
+    /// duration (length relative to the template) per `rubric`; the voicing
+    /// score is passed in since the contour no longer has unvoiced frames.
+    pub fn score_contour(
+        &self,
+        contour: &[f32],
+        rubric: &ScoringRubric,
+        voicing: Option<f32>,
+    ) -> f32 {
+        self.score_contour_in_phrase(contour, rubric, voicing, false)
+    }
+
+    /// `score_contour` for a syllable cut from a phrase; a `phrase_final`
+    /// one may run longer than the template (see
+    /// `ScoringRubric::phrase_duration_score`)
+    pub fn score_contour_in_phrase(
+        &self,
+        contour: &[f32],
+        rubric: &ScoringRubric,
+        voicing: Option<f32>,
+        phrase_final: bool,
+    ) -> f32 {
+        if contour.is_empty() || self.mean.is_empty() {
+            return 0.0;
+        }
+
+        let (_, path) = dtw(&self.mean, contour);
+        let sum_sq: f32 = path
+            .iter()
+            .map(|&(i, j)| {
+                let band = self.band_at(i);
+                let excess = ((contour[j] - self.mean[i]).abs() - band).max(0.0);
+                excess * excess
+            })
+            .sum();
+        let rms = (sum_sq / path.len() as f32).sqrt();
+        let tolerance = rubric.template_tolerance_semitones;
+
+        let mean = |c: &[f32]| c.iter().sum::<f32>() / c.len() as f32;
+        let register = (mean(contour) - mean(&self.mean)).abs();
+        rubric.combine(
+            ScoringRubric::deviation_score(rms, tolerance),
+            Some(ScoringRubric::deviation_score(register, tolerance)),
+            rubric.phrase_duration_score(
+                contour.len() as f32,
+                self.mean.len() as f32,
+                phrase_final,
+            ),
+            voicing,
+        )
+    }
+}
+
+/// Build a reference template from several native recordings of the same item.
+/// `pitches` is the concatenation of the recordings' Hz pitch tracks and
+/// `lengths` gives the number of frames in each one. With `auto_crop`, each
+/// recording is cropped to its voiced core before alignment.
+#[wasm_bindgen]
+pub fn build_reference_template(
+    pitches: &[f32],
+    lengths: &[u32],
+    auto_crop: bool,
+) -> Option<ReferenceTemplate> {
+    let contours: Vec<Vec<f32>> = split_flat(pitches, lengths)
+        .into_iter()
+        .map(|track| {
+            let track = if auto_crop { voiced_core(track) } else { track };
+            hz_to_semitones(track, voiced_median(track))
+        })
+        .collect();
+    let refs: Vec<&[f32]> = contours.iter().map(Vec::as_slice).collect();
+    ReferenceTemplate::from_contours(&refs)
+}
+
+/// Score a learner's Hz pitch track (0-100) against a reference template,
+/// using the template's variance bands as tolerance and `rubric` for the
+/// component weights. With `auto_crop`, the track is cropped to its voiced
+/// core first.
+#[wasm_bindgen]
+pub fn score_against_template(
+    pitch: &[f32],
+    template: &ReferenceTemplate,
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> f32 {
+    let pitch = if auto_crop { voiced_core(pitch) } else { pitch };
+    template.score_contour(
+        &hz_to_semitones(pitch, voiced_median(pitch)),
+        rubric,
+        rubric.voicing_score(pitch),
+    )
+}
+
+/// How well a reference template fits a learner's Hz pitch track, prepared
+/// as in `score_against_template`
+#[wasm_bindgen]
+pub fn template_fit_quality(
+    pitch: &[f32],
+    template: &ReferenceTemplate,
+    auto_crop: bool,
+) -> FitQuality {
+    let pitch = if auto_crop { voiced_core(pitch) } else { pitch };
+    template.fit_quality(
+        &hz_to_semitones(pitch, voiced_median(pitch)),
+        voiced_coverage(pitch),
+    )
+}
+
+/// `score_against_template` for one syllable of a phrase scored against an
+/// isolated-syllable template; when `phrase_final` (the last syllable of
+/// the phrase) its natural lengthening isn't penalized
+#[wasm_bindgen]
+pub fn score_syllable_against_template(
+    pitch: &[f32],
+    template: &ReferenceTemplate,
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+    phrase_final: bool,
+) -> f32 {
+    let pitch = if auto_crop { voiced_core(pitch) } else { pitch };
+    template.score_contour_in_phrase(
+        &hz_to_semitones(pitch, voiced_median(pitch)),
+        rubric,
+        rubric.voicing_score(pitch),
+        phrase_final,
+    )
+}
+
+/// Result of scoring against several references of the same item
+#[wasm_bindgen]
+pub struct ReferenceMatch {
+    score: f32,
+    reference: usize,
+    scores: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl ReferenceMatch {
+    /// Best score (0-100) over all references
+    #[wasm_bindgen(getter)]
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    /// Index of the reference that gave the best score
+    #[wasm_bindgen(getter)]
+    pub fn reference(&self) -> usize {
+        self.reference
+    }
+
+    /// Score against each reference, in input order (0 for empty ones)
+    #[wasm_bindgen(getter)]
+    pub fn scores(&self) -> Vec<f32> {
+        self.scores.clone()
+    }
+}
+
+/// Score a learner's Hz pitch track against several native references of
+/// the same item and keep the best match, so imitating any one speaker's
+/// natural variation scores well. `reference_pitches` and `lengths` are laid
+/// out as in `build_reference_template`. Each reference is aligned on its
+/// own with the minimum tolerance band. Returns None when no reference has
+/// voiced frames.
+#[wasm_bindgen]
+pub fn score_against_references(
+    pitch: &[f32],
+    reference_pitches: &[f32],
+    lengths: &[u32],
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Option<ReferenceMatch> {
+    let pitch = if auto_crop { voiced_core(pitch) } else { pitch };
+    let contour = hz_to_semitones(pitch, voiced_median(pitch));
+    let voicing = rubric.voicing_score(pitch);
+
+    let mut best: Option<(usize, f32)> = None;
+    let scores: Vec<f32> = split_flat(reference_pitches, lengths)
+        .into_iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let track = if auto_crop { voiced_core(track) } else { track };
+            let reference = hz_to_semitones(track, voiced_median(track));
+            let Some(template) = ReferenceTemplate::from_contours(&[&reference]) else {
+                return 0.0;
+            };
+            let score = template.score_contour(&contour, rubric, voicing);
+            if best.is_none_or(|(_, b)| score > b) {
+                best = Some((i, score));
+            }
+            score
+        })
+        .collect();
+
+    let (reference, score) = best?;
+    Some(ReferenceMatch {
+        score,
+        reference,
+        scores,
+    })
+}
@@ -0,0 +1,96 @@
+//! Parallel frame analysis. Frames are independent, so with the `threads`
+//! feature and an initialized pool they are spread over a rayon pool (web
+//! workers sharing memory in the browser). Without the feature, or until
+//! `init_thread_pool` is called, analysis stays on the calling thread.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wasm_bindgen::prelude::*;
+
+use crate::YinParams;
+
+/// Fewest frames worth splitting across threads; below this the hand-off
+/// costs more than it saves
+#[cfg(feature = "threads")]
+const PARALLEL_MIN_FRAMES: usize = 64;
+
+/// Threads the pool was started with; 1 while analysis is single-threaded
+static POOL_THREADS: AtomicUsize = AtomicUsize::new(1);
+
+/// Start a pool of `num_threads` workers for frame analysis. Returns a
+/// Promise to await before the next analysis; in builds without the
+/// `threads` feature it resolves immediately and analysis stays
+/// single-threaded. Only call it when `crossOriginIsolated` is true, i.e.
+/// SharedArrayBuffer is available.
+#[wasm_bindgen(unchecked_return_type = "Promise<void>")]
+pub fn init_thread_pool(num_threads: usize) -> JsValue {
+    #[cfg(all(feature = "threads", target_arch = "wasm32"))]
+    {
+        let promise = wasm_bindgen_rayon::init_thread_pool(num_threads);
+        POOL_THREADS.store(num_threads.max(1), Ordering::Relaxed);
+        promise.into()
+    }
+    #[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+    {
+        if rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build_global()
+            .is_ok()
+        {
+            POOL_THREADS.store(rayon::current_num_threads(), Ordering::Relaxed);
+        }
+        JsValue::UNDEFINED
+    }
+    #[cfg(not(feature = "threads"))]
+    {
+        let _ = num_threads;
+        JsValue::UNDEFINED
+    }
+}
+
+/// Number of threads analysis runs on (1 when single-threaded)
+#[wasm_bindgen]
+pub fn thread_count() -> usize {
+    POOL_THREADS.load(Ordering::Relaxed)
+}
+
+/// (pitch, confidence, tau) of the `num_frames` frames of `audio_data`,
+/// computed on the pool, or None when analysis should stay on this thread
+#[cfg(feature = "threads")]
+pub(crate) fn analyze_frames(
+    audio_data: &[f32],
+    num_frames: usize,
+    frame_size: usize,
+    hop_size: usize,
+    params: &YinParams,
+) -> Option<Vec<(f32, f32, i32)>> {
+    use rayon::prelude::*;
+
+    use crate::yin_analyze_frame;
+
+    if thread_count() < 2 || num_frames < PARALLEL_MIN_FRAMES {
+        return None;
+    }
+    Some(
+        (0..num_frames)
+            .into_par_iter()
+            .map(|k| {
+                let frame = &audio_data[k * hop_size..k * hop_size + frame_size];
+                yin_analyze_frame(frame, params, None)
+            })
+            .collect(),
+    )
+}
+
+/// Single-threaded build: always analyze on this thread
+#[cfg(not(feature = "threads"))]
+#[inline]
+pub(crate) fn analyze_frames(
+    _audio_data: &[f32],
+    _num_frames: usize,
+    _frame_size: usize,
+    _hop_size: usize,
+    _params: &YinParams,
+) -> Option<Vec<(f32, f32, i32)>> {
+    None
+}
@@ -0,0 +1,586 @@
+use wasm_bindgen::prelude::*;
+
+use crate::contour::{resample_weighted, ConfidenceWeighting};
+use crate::fit::{voiced_coverage, FitQuality, MAX_FIT_RMS_SEMITONES};
+use crate::pinyin::{parse_tones, ToneSequence};
+use crate::rubric::ScoringRubric;
+use crate::scale::PitchScale;
+use crate::segments::voiced_core;
+
+/// Semitones covered by one step of the five-level Chao tone-letter scale
+pub const SEMITONES_PER_CHAO_STEP: f32 = 2.0;
+
+/// Number of points each syllable is resampled to before scoring
+pub const SYLLABLE_POINTS: usize = 20;
+
+/// Share of the voiced frames an erhua syllable takes relative to a plain
+/// one: the rhotacized rhyme lengthens the syllable, and the tone contour
+/// is carried over it
+pub const ERHUA_LENGTH_WEIGHT: f32 = 1.4;
+
+/// Extra height (Chao steps) of the first expected point after an aspirated
+/// initial (p, t, k, q, c, ch): the voice starts a tone raised after the
+/// burst of air and settles onto the target shortly after
+pub const ASPIRATION_ONSET_RAISE: f32 = 0.5;
+
+/// Share of the syllable over which the onset raise fades out
+const ASPIRATION_FRACTION: f32 = 0.2;
+
+/// Share of the gap to the previous syllable's final level that the start
+/// of a syllable keeps in connected speech (carryover coarticulation: the
+/// voice can't jump instantly, e.g. tone 4 starts lower after a tone 3)
+const CARRYOVER_WEIGHT: f32 = 0.3;
+
+/// Share of the syllable over which the carryover fades out
+const CARRYOVER_FRACTION: f32 = 0.3;
+
+/// Share of the gap to the next syllable's initial level that the end of a
+/// syllable moves by (anticipatory coarticulation, weaker than carryover)
+const ANTICIPATORY_WEIGHT: f32 = 0.15;
+
+/// Share of the syllable over which the anticipation builds up
+const ANTICIPATORY_FRACTION: f32 = 0.2;
+
+/// Highest mean level (Chao scale) still considered a low tone
+const HALF_THIRD_MAX_LEVEL: f32 = 2.5;
+
+/// Largest rise from the dip to the end that still counts as "no final rise"
+const HALF_THIRD_MAX_RISE: f32 = 1.0;
+
+/// A way of pronouncing a tone that the scorer accepts. Besides the citation
+/// form, some contexts allow alternatives native speakers use routinely.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Realization {
+    /// Citation form, see `tone_template`
+    Citation = 0,
+    /// Tone 3 before a non-third tone as a low fall without the final rise (21)
+    HalfThird = 1,
+    /// Tone 3 before another tone 3, raised to a rise (35) by tone sandhi
+    ThirdSandhi = 2,
+    /// Tone 4 at the end of a phrase with the fall cut short (53)
+    TruncatedFall = 3,
+}
+
+impl Realization {
+    /// Chao targets of this realization of `tone`
+    pub fn template(self, tone: u8) -> &'static [f32] {
+        match self {
+            Realization::Citation => tone_template(tone),
+            Realization::HalfThird => &[2.0, 1.0, 1.0],
+            Realization::ThirdSandhi => &[3.0, 5.0],
+            Realization::TruncatedFall => &[5.0, 3.0],
+        }
+    }
+}
+
+/// Realizations accepted for `tone` when followed by `next_tone` (None at
+/// the end of the phrase), citation form first
+pub fn tone_realizations(tone: u8, next_tone: Option<u8>) -> Vec<Realization> {
+    let mut realizations = vec![Realization::Citation];
+    match (tone, next_tone) {
+        (3, Some(3)) => realizations.push(Realization::ThirdSandhi),
+        (3, Some(_)) => realizations.push(Realization::HalfThird),
+        (4, None) => realizations.push(Realization::TruncatedFall),
+        _ => {}
+    }
+    realizations
+}
+
+/// Chao tone-letter targets for a lexical tone, evenly spaced over the syllable.
+/// Tone 5 (and anything unknown) is treated as neutral.
+pub fn tone_template(tone: u8) -> &'static [f32] {
+    match tone {
+        1 => &[5.0, 5.0],
+        2 => &[3.0, 5.0],
+        3 => &[2.0, 1.0, 4.0],
+        4 => &[5.0, 1.0],
+        _ => &[3.0, 3.0],
+    }
+}
+
+/// Expand template targets into a piecewise-linear contour of `n_points`
+pub fn expand_template(template: &[f32], n_points: usize) -> Vec<f32> {
+    if template.len() == 1 || n_points < 2 {
+        return vec![template[0]; n_points];
+    }
+
+    let segments = (template.len() - 1) as f32;
+    (0..n_points)
+        .map(|i| {
+            let pos = i as f32 / (n_points - 1) as f32 * segments;
+            let idx = (pos.floor() as usize).min(template.len() - 2);
+            let frac = pos - idx as f32;
+            template[idx] + (template[idx + 1] - template[idx]) * frac
+        })
+        .collect()
+}
+
+/// Add `amount` to the first point of `contour`, fading linearly to nothing
+/// over `fraction` of it
+fn shift_start(contour: &mut [f32], amount: f32, fraction: f32) {
+    let span = (contour.len() as f32 * fraction).max(1.0);
+    for (i, value) in contour.iter_mut().enumerate() {
+        let fade = 1.0 - i as f32 / span;
+        if fade <= 0.0 {
+            break;
+        }
+        *value += amount * fade;
+    }
+}
+
+/// Raise the start of an expected contour as after an aspirated initial,
+/// fading linearly from `ASPIRATION_ONSET_RAISE` to nothing over
+/// `ASPIRATION_FRACTION` of it
+pub fn perturb_onset(expected: &mut [f32]) {
+    shift_start(expected, ASPIRATION_ONSET_RAISE, ASPIRATION_FRACTION);
+}
+
+/// Bend the edges of an expected contour (Chao scale) toward its
+/// neighbours: the start toward `previous_end`, the level the previous
+/// syllable ended on, and the end toward `next_start`, the level the next
+/// one starts from (None at phrase edges)
+pub fn coarticulate(expected: &mut [f32], previous_end: Option<f32>, next_start: Option<f32>) {
+    let (Some(&first), Some(&last)) = (expected.first(), expected.last()) else {
+        return;
+    };
+    if let Some(previous_end) = previous_end {
+        shift_start(
+            expected,
+            CARRYOVER_WEIGHT * (previous_end - first),
+            CARRYOVER_FRACTION,
+        );
+    }
+    if let Some(next_start) = next_start {
+        expected.reverse();
+        shift_start(
+            expected,
+            ANTICIPATORY_WEIGHT * (next_start - last),
+            ANTICIPATORY_FRACTION,
+        );
+        expected.reverse();
+    }
+}
+
+/// Level (Chao) a syllable of `tone` ends on before `next_tone` in
+/// connected speech, from its context realization (a half third ends low,
+/// a sandhi third high)
+pub fn connected_end_level(tone: u8, next_tone: Option<u8>) -> f32 {
+    let realization = *tone_realizations(tone, next_tone)
+        .last()
+        .expect("every tone has a citation form");
+    let template = realization.template(tone);
+    template[template.len() - 1]
+}
+
+/// Median of the voiced frames (pitch > 0), or 0.0 if nothing is voiced
+pub fn voiced_median(pitch: &[f32]) -> f32 {
+    let mut voiced: Vec<f32> = pitch.iter().copied().filter(|&p| p > 0.0).collect();
+    if voiced.is_empty() {
+        return 0.0;
+    }
+    voiced.sort_by(|a, b| a.total_cmp(b));
+    voiced[voiced.len() / 2]
+}
+
+/// Convert the voiced frames of a Hz pitch track to semitones relative to
+/// `median_hz`. Unvoiced frames (pitch <= 0) are dropped.
+pub fn hz_to_semitones(pitch: &[f32], median_hz: f32) -> Vec<f32> {
+    if median_hz <= 0.0 {
+        return Vec::new();
+    }
+    pitch
+        .iter()
+        .filter(|&&p| p > 0.0)
+        .map(|&p| PitchScale::Semitones.relative(p, median_hz))
+        .collect()
+}
+
+/// Convert the voiced frames of a Hz pitch track to the Chao scale, centred on
+/// `median_hz` so that level 3 corresponds to the speaker's median pitch.
+/// Unvoiced frames (pitch <= 0) are dropped.
+pub fn hz_to_chao(pitch: &[f32], median_hz: f32) -> Vec<f32> {
+    hz_to_semitones(pitch, median_hz)
+        .into_iter()
+        .map(|st| 3.0 + st / SEMITONES_PER_CHAO_STEP)
+        .collect()
+}
+
+/// Detect the half-third realization: a low level (or low fall) without the
+/// final rise of the citation-form tone 3
+pub fn is_half_third(chao: &[f32]) -> bool {
+    if chao.len() < 3 {
+        return false;
+    }
+
+    let mean = chao.iter().sum::<f32>() / chao.len() as f32;
+    let min = chao.iter().copied().fold(f32::INFINITY, f32::min);
+    let rise = chao[chao.len() - 1] - min;
+
+    mean <= HALF_THIRD_MAX_LEVEL && rise < HALF_THIRD_MAX_RISE
+}
+
+/// Split the deviation between two equal-length contours into shape (RMS
+/// after removing each contour's mean) and register (difference of means)
+fn shape_and_register(observed: &[f32], expected: &[f32]) -> (f32, f32) {
+    let mean = |c: &[f32]| c.iter().sum::<f32>() / c.len() as f32;
+    let offset = mean(observed) - mean(expected);
+    let sum_sq: f32 = observed
+        .iter()
+        .zip(expected)
+        .map(|(o, e)| (o - e - offset) * (o - e - offset))
+        .sum();
+    ((sum_sq / observed.len() as f32).sqrt(), offset.abs())
+}
+
+/// Resample a syllable's Chao contour to `SYLLABLE_POINTS`
+pub fn resample_syllable(chao: &[f32]) -> Vec<f32> {
+    resample_weighted(chao, &[], SYLLABLE_POINTS, &ConfidenceWeighting::default())
+}
+
+/// Rubric score of a resampled syllable against an expected contour
+pub fn syllable_score(
+    observed: &[f32],
+    expected: &[f32],
+    tone: u8,
+    rubric: &ScoringRubric,
+    voicing: Option<f32>,
+) -> f32 {
+    syllable_score_within(
+        observed,
+        expected,
+        rubric.tone_tolerance(tone),
+        rubric,
+        voicing,
+    )
+}
+
+/// `syllable_score` with an explicit tolerance (Chao steps) instead of the
+/// rubric's per-tone one
+pub fn syllable_score_within(
+    observed: &[f32],
+    expected: &[f32],
+    tolerance: f32,
+    rubric: &ScoringRubric,
+    voicing: Option<f32>,
+) -> f32 {
+    let (shape, register) = shape_and_register(observed, expected);
+    rubric.combine(
+        ScoringRubric::deviation_score(shape, tolerance),
+        Some(ScoringRubric::deviation_score(register, tolerance)),
+        None,
+        voicing,
+    )
+}
+
+/// Best-matching realization of `tone` (see `tone_realizations`) for a
+/// resampled syllable, bent toward the neighbouring tones (see
+/// `coarticulate`) and with the onset raised after an `aspirated` initial
+/// (see `perturb_onset`). Returns (expected contour, realization, score);
+/// ties go to the citation form.
+pub fn expected_syllable(
+    observed: &[f32],
+    tone: u8,
+    previous_tone: Option<u8>,
+    next_tone: Option<u8>,
+    aspirated: bool,
+    rubric: &ScoringRubric,
+    voicing: Option<f32>,
+) -> (Vec<f32>, Realization, f32) {
+    let previous_end = previous_tone.map(|previous| connected_end_level(previous, Some(tone)));
+    let next_start = next_tone.map(|next| tone_template(next)[0]);
+    let mut best: Option<(Vec<f32>, Realization, f32)> = None;
+    for realization in tone_realizations(tone, next_tone) {
+        let mut expected = expand_template(realization.template(tone), SYLLABLE_POINTS);
+        coarticulate(&mut expected, previous_end, next_start);
+        if aspirated {
+            perturb_onset(&mut expected);
+        }
+        let score = syllable_score(observed, &expected, tone, rubric, voicing);
+        if best.as_ref().is_none_or(|b| score > b.2) {
+            best = Some((expected, realization, score));
+        }
+    }
+    best.expect("every tone has a citation form")
+}
+
+/// Split `n_frames` voiced frames evenly between `n_syllables`; the last
+/// syllable takes the remainder. Returns [start, end) ranges.
+pub fn syllable_ranges(n_frames: usize, n_syllables: usize) -> Vec<(usize, usize)> {
+    if n_syllables == 0 {
+        return Vec::new();
+    }
+    let per_syllable = n_frames / n_syllables;
+    (0..n_syllables)
+        .map(|i| {
+            let start = i * per_syllable;
+            let end = if i + 1 == n_syllables {
+                n_frames
+            } else {
+                start + per_syllable
+            };
+            (start, end)
+        })
+        .collect()
+}
+
+/// Split `n_frames` voiced frames between syllables in proportion to
+/// `weights`. Returns [start, end) ranges.
+pub fn weighted_syllable_ranges(n_frames: usize, weights: &[f32]) -> Vec<(usize, usize)> {
+    let total: f32 = weights.iter().sum();
+    if weights.is_empty() || total <= 0.0 {
+        return syllable_ranges(n_frames, weights.len());
+    }
+    let mut cumulative = 0.0;
+    let mut start = 0;
+    weights
+        .iter()
+        .enumerate()
+        .map(|(i, &w)| {
+            cumulative += w;
+            let end = if i + 1 == weights.len() {
+                n_frames
+            } else {
+                ((n_frames as f32 * cumulative / total).round() as usize).clamp(start, n_frames)
+            };
+            let range = (start, end);
+            start = end;
+            range
+        })
+        .collect()
+}
+
+/// Syllable ranges for `n_syllables` where the syllables flagged in `erhua`
+/// (missing flags count as false) get `ERHUA_LENGTH_WEIGHT`. Without erhua
+/// this is the even split of `syllable_ranges`.
+pub fn erhua_syllable_ranges(
+    n_frames: usize,
+    n_syllables: usize,
+    erhua: &[bool],
+) -> Vec<(usize, usize)> {
+    if !erhua.iter().take(n_syllables).any(|&e| e) {
+        return syllable_ranges(n_frames, n_syllables);
+    }
+    let weights: Vec<f32> = (0..n_syllables)
+        .map(|i| {
+            if erhua.get(i).copied().unwrap_or(false) {
+                ERHUA_LENGTH_WEIGHT
+            } else {
+                1.0
+            }
+        })
+        .collect();
+    weighted_syllable_ranges(n_frames, &weights)
+}
+
+/// Score a single syllable (Chao scale) against every accepted realization
+/// of `tone` in context and keep the best match, so a tone 3 realized as a
+/// half third or a phrase-final truncated fall isn't penalized.
+/// Shape and register are scored against the rubric's tolerance for `tone`
+/// and combined with the `voicing` score, if any, using the rubric weights.
+/// The expected contour is bent toward `previous_tone` and `next_tone` (see
+/// `coarticulate`) and an `aspirated` initial raises its onset (see
+/// `perturb_onset`). Returns (score, realization).
+pub fn score_syllable(
+    chao: &[f32],
+    tone: u8,
+    previous_tone: Option<u8>,
+    next_tone: Option<u8>,
+    aspirated: bool,
+    rubric: &ScoringRubric,
+    voicing: Option<f32>,
+) -> (f32, Realization) {
+    if chao.is_empty() {
+        return (0.0, Realization::Citation);
+    }
+
+    let observed = resample_syllable(chao);
+    let (_, realization, score) = expected_syllable(
+        &observed,
+        tone,
+        previous_tone,
+        next_tone,
+        aspirated,
+        rubric,
+        voicing,
+    );
+    (score, realization)
+}
+
+/// Per-syllable scores for `score_expected_contour`, with the syllables
+/// flagged in `erhua` given a longer share of the track and those flagged
+/// in `aspirated` expected to start raised (missing flags count as false)
+pub fn score_tones(
+    pitch: &[f32],
+    tones: &[u8],
+    erhua: &[bool],
+    aspirated: &[bool],
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Vec<f32> {
+    let pitch = if auto_crop { voiced_core(pitch) } else { pitch };
+    let voicing = rubric.voicing_score(pitch);
+    let chao = hz_to_chao(pitch, voiced_median(pitch));
+    let mut results = Vec::with_capacity(tones.len() * 2);
+
+    let ranges = erhua_syllable_ranges(chao.len(), tones.len(), erhua);
+    for (i, (&tone, &(start, end))) in tones.iter().zip(&ranges).enumerate() {
+        let (score, realization) = score_syllable(
+            &chao[start..end],
+            tone,
+            i.checked_sub(1).map(|previous| tones[previous]),
+            tones.get(i + 1).copied(),
+            aspirated.get(i).copied().unwrap_or(false),
+            rubric,
+            voicing,
+        );
+        results.push(score);
+        results.push(realization as u8 as f32);
+    }
+
+    results
+}
+
+/// Score a pitch track against the expected contours of a tone sequence.
+/// Voiced frames are split evenly between the syllables. With `auto_crop`,
+/// the track is cropped to its voiced core first so noises before or after
+/// the word don't shift the syllable split. Every syllable's score includes
+/// the voicing coverage of the whole track, weighted per `rubric`.
+/// Returns a flat array: [score1, realization1, score2, realization2, ...]
+/// where realization is the `Realization` that matched best: 0.0 for the
+/// citation form, 1.0 for a half third, 2.0 for a third-tone sandhi rise and
+/// 3.0 for a phrase-final truncated fall.
+///
+/// ```
+/// use yin_wasm::fixtures::fixture;
+/// use yin_wasm::rubric::ScoringRubric;
+/// use yin_wasm::segments::pitch_column;
+/// use yin_wasm::tones::score_expected_contour;
+///
+/// let phrase = fixture("phrase_1234").unwrap();
+/// let pitch = pitch_column(&phrase.config().analyze(&phrase.audio()));
+/// let rubric = ScoringRubric::new();
+/// let right = score_expected_contour(&pitch, &phrase.tones(), true, &rubric);
+/// let wrong = score_expected_contour(&pitch, &[4, 3, 2, 1], true, &rubric);
+/// assert_eq!(right.len(), 2 * phrase.tones().len());
+/// let mean = |scores: &[f32]| scores.iter().step_by(2).sum::<f32>() / 4.0;
+/// assert!(mean(&right) > mean(&wrong));
+/// ```
+#[wasm_bindgen]
+pub fn score_expected_contour(
+    pitch: &[f32],
+    tones: &[u8],
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Vec<f32> {
+    score_tones(pitch, tones, &[], &[], auto_crop, rubric)
+}
+
+/// Score a pitch track against the tones of a pinyin string, e.g.
+/// "yi4dian3r". Erhua syllables count once, with their tone carried over
+/// the rhotacized rhyme, so 一点儿 is graded as two syllables. Syllables
+/// with an aspirated initial (p, t, k, q, c, ch) are expected to start a
+/// little raised, so a correct tone after one isn't heard as starting too
+/// high. Same output as `score_expected_contour`.
+#[wasm_bindgen]
+pub fn score_expected_pinyin(
+    pitch: &[f32],
+    pinyin: &str,
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Result<Vec<f32>, JsError> {
+    let ToneSequence {
+        tones,
+        erhua,
+        aspirated,
+    } = parse_tones(pinyin).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(score_tones(
+        pitch, &tones, &erhua, &aspirated, auto_crop, rubric,
+    ))
+}
+
+/// How well each syllable's best expected contour (as scored by
+/// `score_tones`) fits it, once the register is matched. Residuals are in
+/// Chao steps; coverage is that of the whole (cropped) track.
+pub fn tone_fits(
+    pitch: &[f32],
+    tones: &[u8],
+    erhua: &[bool],
+    aspirated: &[bool],
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Vec<FitQuality> {
+    let pitch = if auto_crop { voiced_core(pitch) } else { pitch };
+    let coverage = voiced_coverage(pitch);
+    let chao = hz_to_chao(pitch, voiced_median(pitch));
+    let max_rms = MAX_FIT_RMS_SEMITONES / SEMITONES_PER_CHAO_STEP;
+
+    let ranges = erhua_syllable_ranges(chao.len(), tones.len(), erhua);
+    tones
+        .iter()
+        .zip(&ranges)
+        .enumerate()
+        .map(|(i, (&tone, &(start, end)))| {
+            if start == end {
+                return FitQuality::from_residuals(&[], &[], coverage, max_rms);
+            }
+            let observed = resample_syllable(&chao[start..end]);
+            let (expected, _, _) = expected_syllable(
+                &observed,
+                tone,
+                i.checked_sub(1).map(|previous| tones[previous]),
+                tones.get(i + 1).copied(),
+                aspirated.get(i).copied().unwrap_or(false),
+                rubric,
+                None,
+            );
+            let mean = |c: &[f32]| c.iter().sum::<f32>() / c.len() as f32;
+            let offset = mean(&observed) - mean(&expected);
+            let fitted: Vec<f32> = expected.iter().map(|e| e + offset).collect();
+            FitQuality::from_residuals(&observed, &fitted, coverage, max_rms)
+        })
+        .collect()
+}
+
+/// Fit quality of every syllable of a pitch track scored with
+/// `score_expected_contour`, so scores of syllables the expected contours
+/// don't describe (fragmented or noisy pitch) can be set aside
+#[wasm_bindgen]
+pub fn expected_contour_fit(
+    pitch: &[f32],
+    tones: &[u8],
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Vec<FitQuality> {
+    tone_fits(pitch, tones, &[], &[], auto_crop, rubric)
+}
+
+/// `expected_contour_fit` for a track scored with `score_expected_pinyin`
+#[wasm_bindgen]
+pub fn expected_pinyin_fit(
+    pitch: &[f32],
+    pinyin: &str,
+    auto_crop: bool,
+    rubric: &ScoringRubric,
+) -> Result<Vec<FitQuality>, JsError> {
+    let ToneSequence {
+        tones,
+        erhua,
+        aspirated,
+    } = parse_tones(pinyin).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(tone_fits(
+        pitch, &tones, &erhua, &aspirated, auto_crop, rubric,
+    ))
+}
+
+/// Check whether a single-syllable pitch track is a half-third realization.
+/// The level is judged against `speaker_median_hz` (the speaker's typical
+/// pitch); a lone syllable cannot be judged low relative to itself.
+#[wasm_bindgen]
+pub fn detect_half_third(pitch: &[f32], speaker_median_hz: f32) -> bool {
+    let chao = hz_to_chao(pitch, speaker_median_hz);
+    if chao.is_empty() {
+        return false;
+    }
+    is_half_third(&resample_syllable(&chao))
+}
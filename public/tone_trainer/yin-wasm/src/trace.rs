@@ -0,0 +1,249 @@
+use wasm_bindgen::prelude::*;
+
+use crate::config::YinConfig;
+use crate::json::{push_key, push_number, push_string};
+use crate::window::apply_window;
+use crate::{
+    get_frame_count, yin_analyze_frame, yin_cmndf_with, yin_difference_function, yin_local_minima,
+    YinParams,
+};
+
+/// Maximum number of CMNDF minima recorded per frame
+const MAX_TRACED_CANDIDATES: usize = 5;
+
+/// Decisions taken while analyzing one frame
+#[derive(Clone, Debug, Default)]
+pub struct FrameTrace {
+    pub index: usize,
+    pub start_sample: usize,
+    /// First tau whose CMNDF dipped below the threshold
+    pub threshold_crossing: Option<usize>,
+    /// Tau after descending to the local minimum
+    pub chosen_tau: Option<usize>,
+    /// Deepest CMNDF minima as (tau, frequency, cmndf)
+    pub candidates: Vec<(usize, f32, f32)>,
+    /// Adjustments applied to the estimate as (name, amount)
+    pub corrections: Vec<(&'static str, f32)>,
+    /// Filters that rejected or altered the frame
+    pub filters: Vec<&'static str>,
+    pub pitch: f32,
+    pub confidence: f32,
+}
+
+impl FrameTrace {
+    pub fn new(index: usize, start_sample: usize) -> Self {
+        FrameTrace {
+            index,
+            start_sample,
+            ..Default::default()
+        }
+    }
+
+    /// Record the threshold crossing and the deepest CMNDF minima from
+    /// `tau_start` on
+    pub fn record_candidates(
+        &mut self,
+        cmndf: &[f32],
+        threshold: f32,
+        sample_rate: f32,
+        tau_start: usize,
+    ) {
+        let tau_start = tau_start.max(2);
+        self.threshold_crossing = (tau_start..cmndf.len()).find(|&tau| cmndf[tau] < threshold);
+
+        let mut minima: Vec<(usize, f32, f32)> = yin_local_minima(cmndf, tau_start, cmndf.len())
+            .into_iter()
+            .map(|tau| (tau, sample_rate / tau as f32, cmndf[tau]))
+            .collect();
+        minima.sort_by(|a, b| a.2.total_cmp(&b.2));
+        minima.truncate(MAX_TRACED_CANDIDATES);
+        self.candidates = minima;
+    }
+
+    pub fn add_correction(&mut self, name: &'static str, amount: f32) {
+        self.corrections.push((name, amount));
+    }
+
+    pub fn add_filter(&mut self, name: &'static str) {
+        self.filters.push(name);
+    }
+
+    /// Append this trace as a JSON object
+    pub fn write_json(&self, out: &mut String) {
+        out.push('{');
+        push_key(out, "index", true);
+        push_number(out, self.index as f32);
+        push_key(out, "start_sample", false);
+        push_number(out, self.start_sample as f32);
+        push_key(out, "threshold_crossing", false);
+        push_number(out, self.threshold_crossing.map_or(f32::NAN, |t| t as f32));
+        push_key(out, "chosen_tau", false);
+        push_number(out, self.chosen_tau.map_or(f32::NAN, |t| t as f32));
+
+        push_key(out, "candidates", false);
+        out.push('[');
+        for (i, &(tau, freq, value)) in self.candidates.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('{');
+            push_key(out, "tau", true);
+            push_number(out, tau as f32);
+            push_key(out, "frequency", false);
+            push_number(out, freq);
+            push_key(out, "cmndf", false);
+            push_number(out, value);
+            out.push('}');
+        }
+        out.push(']');
+
+        push_key(out, "corrections", false);
+        out.push('[');
+        for (i, &(name, amount)) in self.corrections.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('{');
+            push_key(out, "name", true);
+            push_string(out, name);
+            push_key(out, "amount", false);
+            push_number(out, amount);
+            out.push('}');
+        }
+        out.push(']');
+
+        push_key(out, "filters", false);
+        out.push('[');
+        for (i, name) in self.filters.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            push_string(out, name);
+        }
+        out.push(']');
+
+        push_key(out, "pitch", false);
+        push_number(out, self.pitch);
+        push_key(out, "confidence", false);
+        push_number(out, self.confidence);
+        out.push('}');
+    }
+}
+
+/// Run the same analysis as `perform_yin_analysis`, but return a JSON
+/// document with the configuration and a per-frame decision trace (threshold
+/// crossing, candidates considered, corrections applied, filters triggered)
+/// suitable for attaching to bug reports.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn perform_yin_analysis_trace(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    threshold: f32,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: bool,
+) -> String {
+    let params = YinParams::new(sample_rate, threshold, min_freq, max_freq, interpolation);
+
+    let mut out = String::from("{");
+    push_key(&mut out, "config", true);
+    out.push('{');
+    let config = [
+        ("sample_rate", sample_rate),
+        ("frame_size", frame_size as f32),
+        ("hop_size", hop_size as f32),
+        ("threshold", threshold),
+        ("min_freq", min_freq),
+        ("max_freq", max_freq),
+    ];
+    for (i, (key, value)) in config.iter().enumerate() {
+        push_key(&mut out, key, i == 0);
+        push_number(&mut out, *value);
+    }
+    push_key(&mut out, "interpolation", false);
+    out.push_str(if interpolation { "true" } else { "false" });
+    out.push('}');
+
+    push_key(&mut out, "frames", false);
+    out.push('[');
+    if hop_size > 0 {
+        let mut start = 0;
+        let mut index = 0;
+        while start + frame_size <= audio_data.len() {
+            let mut trace = FrameTrace::new(index, start);
+            yin_analyze_frame(
+                &audio_data[start..start + frame_size],
+                &params,
+                Some(&mut trace),
+            );
+            if index > 0 {
+                out.push(',');
+            }
+            trace.write_json(&mut out);
+            start += hop_size;
+            index += 1;
+        }
+    }
+    out.push_str("]}");
+    out
+}
+
+/// CMNDF of one frame, computed the way the analysis does (window and
+/// summation included)
+fn frame_cmndf_curve(frame: &[f32], params: &YinParams) -> Vec<f32> {
+    let frame = apply_window(params.window, frame);
+    yin_cmndf_with(&yin_difference_function(&frame), params.summation)
+}
+
+/// The `max_candidates` deepest CMNDF minima of every frame, between the
+/// start of the threshold search and the period of `min_freq`, for
+/// rendering a candidate overlay while tuning the threshold. Returns a flat
+/// array with `max_candidates` slots of [tau, frequency, cmndf] per frame,
+/// deepest first; unused slots are [-1, 0, 1].
+#[wasm_bindgen]
+pub fn frame_candidates(audio_data: &[f32], config: &YinConfig, max_candidates: usize) -> Vec<f32> {
+    if config.frame_size < 4 {
+        return Vec::new();
+    }
+    let params = config.params();
+    let tau_max = (config.sample_rate / config.min_freq).ceil() as usize;
+    let hop_size = config.hop_size.max(1);
+    let frames = get_frame_count(audio_data.len(), config.frame_size, hop_size);
+    let mut out = Vec::with_capacity(frames * max_candidates * 3);
+    for k in 0..frames {
+        let start = k * hop_size;
+        let cmndf = frame_cmndf_curve(&audio_data[start..start + config.frame_size], &params);
+        let mut minima: Vec<usize> = yin_local_minima(&cmndf, params.tau_start, tau_max);
+        minima.sort_by(|&a, &b| cmndf[a].total_cmp(&cmndf[b]));
+        for slot in 0..max_candidates {
+            match minima.get(slot) {
+                Some(&tau) => out.extend_from_slice(&[
+                    tau as f32,
+                    config.sample_rate / tau as f32,
+                    cmndf[tau],
+                ]),
+                None => out.extend_from_slice(&[-1.0, 0.0, 1.0]),
+            }
+        }
+    }
+    out
+}
+
+/// Full CMNDF curve (index = tau) of frame `frame_index`, or an empty array
+/// when the audio has no such frame
+#[wasm_bindgen]
+pub fn frame_cmndf(audio_data: &[f32], config: &YinConfig, frame_index: usize) -> Vec<f32> {
+    let hop_size = config.hop_size.max(1);
+    let frames = get_frame_count(audio_data.len(), config.frame_size, hop_size);
+    if config.frame_size < 4 || frame_index >= frames {
+        return Vec::new();
+    }
+    let start = frame_index * hop_size;
+    frame_cmndf_curve(
+        &audio_data[start..start + config.frame_size],
+        &config.params(),
+    )
+}
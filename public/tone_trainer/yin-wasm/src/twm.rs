@@ -0,0 +1,157 @@
+//! Two-way mismatch (Maher & Beauchamp 1994) refinement of pitch candidates
+
+use wasm_bindgen::prelude::*;
+
+use crate::fft::magnitude_spectrum;
+use crate::metrics;
+use crate::{
+    tau_search_start, yin_cumulative_mean_normalized_difference, yin_difference_function,
+    yin_local_minima,
+};
+
+/// Frequency-difference exponent
+const TWM_P: f32 = 0.5;
+/// Amplitude-weighted frequency-difference factor
+const TWM_Q: f32 = 1.4;
+/// Amplitude offset
+const TWM_R: f32 = 0.5;
+/// Weight of the measured-to-predicted error
+const TWM_RHO: f32 = 0.33;
+/// Harmonics predicted per candidate
+const TWM_HARMONICS: usize = 10;
+/// Spectral peaks considered per frame (strongest first)
+const TWM_MAX_PEAKS: usize = 12;
+/// Peaks above this frequency are ignored
+const TWM_MAX_PEAK_HZ: f32 = 5000.0;
+/// Only CMNDF minima below this value become extra candidates
+const TWM_CANDIDATE_CMNDF: f32 = 0.5;
+
+/// Strongest local maxima of a magnitude spectrum as (frequency, magnitude)
+pub fn spectral_peaks(
+    spectrum: &[f32],
+    bin_hz: f32,
+    max_hz: f32,
+    max_peaks: usize,
+) -> Vec<(f32, f32)> {
+    let last = ((max_hz / bin_hz) as usize).min(spectrum.len().saturating_sub(2));
+    let mut peaks: Vec<(f32, f32)> = (1..=last)
+        .filter(|&k| spectrum[k] > spectrum[k - 1] && spectrum[k] >= spectrum[k + 1])
+        .map(|k| {
+            let (a, b, c) = (spectrum[k - 1], spectrum[k], spectrum[k + 1]);
+            let denom = a - 2.0 * b + c;
+            let offset = if denom.abs() > f32::EPSILON {
+                0.5 * (a - c) / denom
+            } else {
+                0.0
+            };
+            ((k as f32 + offset) * bin_hz, b)
+        })
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    peaks.truncate(max_peaks);
+    peaks
+}
+
+/// Two-way mismatch error of `f0` against measured peaks (lower is better)
+pub fn twm_error(f0: f32, peaks: &[(f32, f32)]) -> f32 {
+    if f0 <= 0.0 || peaks.is_empty() {
+        return f32::INFINITY;
+    }
+    let a_max = peaks.iter().map(|p| p.1).fold(0.0, f32::max);
+    if a_max <= 0.0 {
+        return f32::INFINITY;
+    }
+    let max_peak_hz = peaks.iter().map(|p| p.0).fold(0.0, f32::max);
+    let harmonics = ((max_peak_hz / f0).ceil() as usize).clamp(1, TWM_HARMONICS);
+
+    // Predicted to measured: every harmonic should have a nearby peak
+    let mut err_pm = 0.0;
+    for n in 1..=harmonics {
+        let fn_ = f0 * n as f32;
+        let (df, amp) = peaks
+            .iter()
+            .map(|&(f, a)| ((f - fn_).abs(), a))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap_or((fn_, 0.0));
+        let term = df * fn_.powf(-TWM_P);
+        err_pm += term + (amp / a_max) * (TWM_Q * term - TWM_R);
+    }
+
+    // Measured to predicted: every peak should sit near a harmonic
+    let mut err_mp = 0.0;
+    for &(fk, ak) in peaks {
+        let nearest = (fk / f0).round().max(1.0) * f0;
+        let term = (fk - nearest).abs() * fk.powf(-TWM_P);
+        err_mp += term + (ak / a_max) * (TWM_Q * term - TWM_R);
+    }
+
+    err_pm / harmonics as f32 + TWM_RHO * err_mp / peaks.len() as f32
+}
+
+/// Pick the candidate with the lowest TWM error for one frame
+pub fn refine_frame(frame: &[f32], sample_rate: f32, candidates: &[f32]) -> Option<f32> {
+    let spectrum = magnitude_spectrum(frame);
+    let n_fft = (spectrum.len() - 1) * 2;
+    let bin_hz = sample_rate / n_fft as f32;
+    let peaks = spectral_peaks(&spectrum, bin_hz, TWM_MAX_PEAK_HZ, TWM_MAX_PEAKS);
+
+    candidates
+        .iter()
+        .map(|&f0| (f0, twm_error(f0, &peaks)))
+        .filter(|c| c.1.is_finite())
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|c| c.0)
+}
+
+/// Optional refinement of `perform_yin_analysis` results: for each voiced
+/// frame, score YIN's estimate, its octave neighbours and the other CMNDF
+/// minima with the two-way mismatch error over the frame spectrum, and keep
+/// the best. Helps most on harmonically rich (e.g. low male) voices.
+/// `results` must come from the same audio, frame and hop size; the returned
+/// array has the same [pitch, confidence, tau, ...] layout.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn twm_refine(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+    results: &[f32],
+    min_freq: f32,
+    max_freq: f32,
+) -> Vec<f32> {
+    let mut refined = results.to_vec();
+    let tau_min = tau_search_start(sample_rate, max_freq);
+    let tau_max = (sample_rate / min_freq).ceil() as usize;
+    let mut corrections = 0;
+
+    for (i, frame_result) in refined.chunks_exact_mut(3).enumerate() {
+        let pitch = frame_result[0];
+        let start = i * hop_size;
+        if pitch <= 0.0 || start + frame_size > audio_data.len() {
+            continue;
+        }
+        let frame = &audio_data[start..start + frame_size];
+
+        let cmndf = yin_cumulative_mean_normalized_difference(&yin_difference_function(frame));
+        let mut candidates = vec![pitch, pitch * 0.5, pitch * 2.0];
+        candidates.extend(
+            yin_local_minima(&cmndf, tau_min, tau_max)
+                .into_iter()
+                .filter(|&tau| cmndf[tau] < TWM_CANDIDATE_CMNDF)
+                .map(|tau| sample_rate / tau as f32),
+        );
+        candidates.retain(|&f| f >= min_freq && f <= max_freq);
+
+        if let Some(best) = refine_frame(frame, sample_rate, &candidates) {
+            if (best / pitch - 1.0).abs() > 0.05 {
+                corrections += 1;
+                frame_result[0] = best;
+                frame_result[2] = (sample_rate / best).round();
+            }
+        }
+    }
+
+    metrics::record_corrections(corrections);
+    refined
+}
@@ -0,0 +1,353 @@
+//! Plain-object views of results, reports and configs with TypeScript shapes,
+//! so the generated .d.ts describes proper objects instead of opaque classes
+//! and flat number arrays. Objects are built as JSON and parsed on the JS side.
+
+use wasm_bindgen::prelude::*;
+
+use crate::json::{push_key, push_number};
+use crate::scale::PitchScale;
+
+/// TypeScript declarations of the objects built here; natively only the
+/// tests read them
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+const TS_TYPES: &str = r#"
+/** One analysis frame */
+export interface YinFrame {
+    /** Seconds from the start of the recording to the frame start */
+    time: number;
+    /** Hz (0 when unvoiced), or on the config's `scale` (null when unvoiced) */
+    pitch: number | null;
+    confidence: number;
+    tau: number;
+    /** RMS level; only from gated analyses */
+    energy?: number;
+    /** Voicing probability (0-1); only from gated analyses */
+    voicing?: number;
+    /** False for frames the voicing gate zeroed; only from gated analyses */
+    voiced?: boolean;
+}
+
+/** Analysis settings; every field is optional where a partial config is accepted */
+export interface YinConfigObject {
+    sampleRate: number;
+    frameSize: number;
+    hopSize: number;
+    threshold: number;
+    minFreq: number;
+    maxFreq: number;
+    interpolation: boolean;
+    /** First lag of the threshold search in samples; 0 derives it from maxFreq */
+    tauStart: number;
+    summation: CmndfSummation;
+    window: WindowFunction;
+    /** High-pass, spectral subtraction and a per-frame threshold for noisy microphones */
+    noiseRobust: boolean;
+    /** Unit of `YinFrame.pitch` in frame exports */
+    scale: PitchScale;
+}
+
+/** Score of one syllable of the expected tone sequence */
+export interface SyllableScore {
+    score: number;
+    /** The accepted realization of the tone that matched best */
+    realization: Realization;
+    halfThird: boolean;
+    /** Confidence (0-1) in the score; see `FitQuality.reliability` */
+    reliability: number;
+}
+
+/** One feedback item; see `IssueCode` for the unit of `magnitude` */
+export interface FeedbackIssue {
+    code: IssueCode;
+    severity: Severity;
+    magnitude: number;
+    /** Syllable index, or null for the whole recording */
+    syllable: number | null;
+    start: number;
+    end: number;
+}
+
+/** A non-fatal caveat about an analysis */
+export interface AnalysisWarningObject {
+    code: WarningCode;
+    /** Clipped share, dropped seconds, input rate (Hz) or SNR (dB), by
+        code; null when not finite (e.g. the SNR of digital silence) */
+    value: number | null;
+    message: string;
+}
+
+/** Everything `RecordingSession.finalize` produced */
+export interface SessionReportObject {
+    /** Scores and issues are empty unless the attempt is voiced */
+    status: AttemptStatus;
+    trimStart: number;
+    trimEnd: number;
+    /** Null when not finite, e.g. over digital silence */
+    snrDb: number | null;
+    peak: number;
+    clippedFraction: number;
+    /** Highest frequency (Hz) the recording carries */
+    bandwidthHz: number;
+    /** High-band minus low-band level of the long-term spectrum, dB */
+    spectralTiltDb: number | null;
+    bandLimit: BandLimit;
+    muffled: boolean;
+    /** The recording went through a telephony codec, e.g. a Bluetooth headset */
+    bluetoothSuspected: boolean;
+    threshold: number;
+    frames: YinFrame[];
+    scores: SyllableScore[];
+    /** Every score is reliable enough to update mastery statistics */
+    reliable: boolean;
+    issues: FeedbackIssue[];
+    warnings: AnalysisWarningObject[];
+}
+"#;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES_SECTION: &'static str = TS_TYPES;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = JSON, js_name = parse)]
+    fn json_parse(text: &str) -> JsValue;
+
+    #[wasm_bindgen(catch, js_namespace = Reflect, js_name = get)]
+    fn reflect_get(target: &JsValue, key: &JsValue) -> Result<JsValue, JsValue>;
+}
+
+/// Turn JSON written by this crate into a JS value
+pub(crate) fn to_js(json: &str) -> JsValue {
+    json_parse(json)
+}
+
+/// Property `key` of a JS object, or None when missing or not an object
+fn property(object: &JsValue, key: &str) -> Option<JsValue> {
+    if !object.is_object() {
+        return None;
+    }
+    reflect_get(object, &JsValue::from_str(key))
+        .ok()
+        .filter(|value| !value.is_undefined() && !value.is_null())
+}
+
+/// Numeric property `key` of a JS object
+pub(crate) fn number_property(object: &JsValue, key: &str) -> Option<f64> {
+    property(object, key).and_then(|value| value.as_f64())
+}
+
+/// Boolean property `key` of a JS object
+pub(crate) fn bool_property(object: &JsValue, key: &str) -> Option<bool> {
+    property(object, key).and_then(|value| value.as_bool())
+}
+
+/// Append `[{"time":..,"pitch":..,"confidence":..,"tau":..}, ...]` for a flat
+/// [pitch, confidence, tau, ...] array, with times shifted by `time_offset`
+/// and the pitch converted from Hz to `scale`
+pub(crate) fn push_frames(
+    out: &mut String,
+    results: &[f32],
+    frame_period: f32,
+    time_offset: f32,
+    scale: PitchScale,
+) {
+    push_frames_with_voicing(out, results, &[], &[], frame_period, time_offset, scale);
+}
+
+/// `push_frames` plus `energy`, `voicing` and `voiced` for every frame when
+/// `voicing` isn't empty
+pub(crate) fn push_frames_with_voicing(
+    out: &mut String,
+    results: &[f32],
+    energy: &[f32],
+    voicing: &[f32],
+    frame_period: f32,
+    time_offset: f32,
+    scale: PitchScale,
+) {
+    out.push('[');
+    for (i, frame) in results.chunks_exact(3).enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push('{');
+        push_key(out, "time", true);
+        push_number(out, time_offset + i as f32 * frame_period);
+        push_key(out, "pitch", false);
+        // Hz keeps 0 for unvoiced frames, other scales have no such value
+        push_number(
+            out,
+            if scale == PitchScale::Hz {
+                frame[0]
+            } else {
+                scale.from_hz(frame[0])
+            },
+        );
+        push_key(out, "confidence", false);
+        push_number(out, frame[1]);
+        push_key(out, "tau", false);
+        push_number(out, frame[2]);
+        if let Some(&probability) = voicing.get(i) {
+            push_key(out, "energy", false);
+            push_number(out, energy.get(i).copied().unwrap_or(0.0));
+            push_key(out, "voicing", false);
+            push_number(out, probability);
+            push_key(out, "voiced", false);
+            out.push_str(if frame[0] > 0.0 { "true" } else { "false" });
+        }
+        out.push('}');
+    }
+    out.push(']');
+}
+
+/// YIN results as `YinFrame` objects
+#[wasm_bindgen(unchecked_return_type = "YinFrame[]")]
+pub fn results_to_frames(results: &[f32], sample_rate: f32, hop_size: usize) -> JsValue {
+    let mut out = String::new();
+    push_frames(
+        &mut out,
+        results,
+        hop_size as f32 / sample_rate,
+        0.0,
+        PitchScale::Hz,
+    );
+    to_js(&out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::YinConfig;
+    use crate::json::{parse_json, JsonValue};
+    use crate::session::{RecordingSession, SessionConfig};
+    use crate::warnings::{push_warnings, AnalysisWarning, WarningCode};
+
+    /// Interface name and its fields as (name, optional, type)
+    type Interface = (String, Vec<(String, bool, String)>);
+
+    /// Every interface in `TS_TYPES`
+    fn interfaces() -> Vec<Interface> {
+        let mut interfaces: Vec<Interface> = Vec::new();
+        for line in TS_TYPES.lines().map(str::trim) {
+            if let Some(rest) = line.strip_prefix("export interface ") {
+                interfaces.push((rest.trim_end_matches(" {").to_string(), Vec::new()));
+            } else if let Some((name, ty)) = line.strip_suffix(';').and_then(|l| l.split_once(": "))
+            {
+                let optional = name.ends_with('?');
+                let fields = &mut interfaces.last_mut().unwrap().1;
+                fields.push((
+                    name.trim_end_matches('?').to_string(),
+                    optional,
+                    ty.to_string(),
+                ));
+            }
+        }
+        interfaces
+    }
+
+    /// Why `value` doesn't match the TypeScript type `ty`, if it doesn't
+    fn mismatch(value: &JsonValue, ty: &str, path: &str) -> Option<String> {
+        let interfaces = interfaces();
+        let matches = |ty: &str| match (ty, value) {
+            ("null", JsonValue::Null)
+            | ("number", JsonValue::Number(_))
+            | ("boolean", JsonValue::Bool(_))
+            | ("string", JsonValue::String(_)) => None,
+            ("null" | "number" | "boolean" | "string", _) => {
+                Some(format!("{}: {:?} is not {}", path, value, ty))
+            }
+            (array, JsonValue::Array(items)) if array.ends_with("[]") => {
+                items.iter().enumerate().find_map(|(i, item)| {
+                    mismatch(item, &array[..array.len() - 2], &format!("{}[{}]", path, i))
+                })
+            }
+            (name, JsonValue::Object(members)) => {
+                let (_, fields) = interfaces.iter().find(|(n, _)| n == name)?;
+                members
+                    .iter()
+                    .find(|(key, _)| !fields.iter().any(|(field, _, _)| field == key))
+                    .map(|(key, _)| format!("{}.{} is not declared", path, key))
+                    .or_else(|| {
+                        fields.iter().find_map(|(field, optional, ty)| {
+                            let path = format!("{}.{}", path, field);
+                            match value.get(field) {
+                                Some(member) => mismatch(member, ty, &path),
+                                None if *optional => None,
+                                None => Some(format!("{} is missing", path)),
+                            }
+                        })
+                    })
+            }
+            // Enums are written as their numeric value
+            (name, JsonValue::Number(_)) if !interfaces.iter().any(|(n, _)| n == name) => None,
+            _ => Some(format!("{}: {:?} is not {}", path, value, ty)),
+        };
+        let errors: Vec<String> = ty.split(" | ").filter_map(matches).collect();
+        (errors.len() == ty.split(" | ").count()).then(|| errors.join("; "))
+    }
+
+    fn assert_conforms(json: &str, ty: &str) {
+        let value = parse_json(json).unwrap();
+        if let Some(error) = mismatch(&value, ty, ty) {
+            panic!("{}\n{}", error, json);
+        }
+    }
+
+    /// A 200 Hz tone after a second of digital silence, so the noise floor
+    /// (and with it the SNR) is degenerate
+    fn tone_after_silence() -> Vec<f32> {
+        let mut audio = vec![0.0; 44100];
+        audio.extend(
+            (0..44100)
+                .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 200.0 * i as f32 / 44100.0).sin()),
+        );
+        audio
+    }
+
+    #[test]
+    fn every_interface_matches_its_serializer() {
+        let declared: Vec<String> = interfaces().into_iter().map(|(name, _)| name).collect();
+        let mut covered = Vec::new();
+
+        let results = [200.0, 0.9, 220.0, 0.0, 0.1, 0.0];
+        for scale in [PitchScale::Hz, PitchScale::Semitones] {
+            let mut out = String::new();
+            push_frames_with_voicing(&mut out, &results, &[0.1; 2], &[0.9; 2], 0.01, 0.0, scale);
+            assert_conforms(&out, "YinFrame[]");
+        }
+        covered.push("YinFrame");
+
+        assert_conforms(&YinConfig::new().to_json(), "YinConfigObject");
+        covered.push("YinConfigObject");
+
+        let mut out = String::new();
+        push_warnings(
+            &mut out,
+            &[
+                AnalysisWarning::new(WarningCode::LowSnr, f32::NEG_INFINITY),
+                AnalysisWarning::new(WarningCode::Clipped, f32::NAN),
+            ],
+        );
+        assert_conforms(&out, "AnalysisWarningObject[]");
+        covered.push("AnalysisWarningObject");
+
+        for (audio, tones) in [
+            (tone_after_silence(), vec![1]),
+            (tone_after_silence(), vec![3, 4]),
+            (vec![0.0; 44100], vec![1]),
+            (Vec::new(), Vec::new()),
+        ] {
+            let mut session = RecordingSession::new();
+            session.append(&audio);
+            let mut config = SessionConfig::new(44100.0);
+            config.set_tones(tones);
+            let json = session.report(&config).unwrap().to_json();
+            assert_conforms(&json, "SessionReportObject");
+        }
+        covered.extend(["SessionReportObject", "SyllableScore", "FeedbackIssue"]);
+
+        for name in &declared {
+            assert!(covered.contains(&name.as_str()), "{} is not checked", name);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Whole-attempt voicing check: tells a whispered or otherwise unvoiced
+//! attempt (speech-like loudness, noisy high-frequency waveform, no
+//! periodicity) apart from silence and from normal voiced speech, so the UI
+//! can ask for a voiced attempt instead of showing a score of 0. Attempts
+//! that are mostly a cough, laughter or a bump on the microphone are flagged
+//! too, so they stay out of the attempt history and calibration.
+
+use wasm_bindgen::prelude::*;
+
+use crate::input::frame_levels;
+use crate::noise::estimate_noise;
+use crate::segments::pitch_column;
+
+/// Level (dB) above the noise floor the loudest frame needs for the
+/// attempt not to count as silence
+const MIN_PEAK_SNR_DB: f32 = 10.0;
+
+/// Level above the noise floor (dB) a frame needs to count as speech
+const SPEECH_MARGIN_DB: f32 = 6.0;
+
+/// Zero crossings per second above which speech frames sound like
+/// whispering or frication rather than voicing
+const WHISPER_ZCR_HZ: f32 = 1500.0;
+
+/// Share of the speech frames that may be voiced in a whispered attempt
+const MAX_WHISPER_VOICED_FRACTION: f32 = 0.2;
+
+/// Loud regions no longer than this (seconds, including one analysis
+/// window) are bumps or clicks, too short for a syllable
+const BUMP_MAX_SECONDS: f32 = 0.1;
+
+/// Longest loud region (seconds) of an attempt that may be a cough
+const COUGH_MAX_SECONDS: f32 = 0.6;
+
+/// A cough peaks within this leading fraction of its burst, then decays
+const COUGH_PEAK_FRACTION: f32 = 0.3;
+
+/// Share of a cough's frames that may be voiced
+const MAX_COUGH_VOICED_FRACTION: f32 = 0.5;
+
+/// Fewest bursts that can make up laughter
+const LAUGH_MIN_BURSTS: usize = 4;
+
+/// Longest single burst (seconds) of laughter
+const LAUGH_MAX_BURST_SECONDS: f32 = 0.35;
+
+/// Range of the mean spacing (seconds) between laughter bursts ("ha-ha-ha"
+/// runs at roughly 3-8 per second)
+const LAUGH_SPACING_SECONDS: (f32, f32) = (0.12, 0.4);
+
+/// Largest coefficient of variation of the burst spacing in laughter, which
+/// is much more regular than syllables
+const LAUGH_MAX_SPACING_VARIATION: f32 = 0.3;
+
+/// Overall verdict on an attempt before scoring
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttemptStatus {
+    /// Voiced speech; scores are meaningful
+    Voiced = 0,
+    /// Nothing rises clearly above the background noise
+    Silent = 1,
+    /// Speech-level energy without periodicity: whispered or unvoiced
+    Whispered = 2,
+    /// Mostly a cough, laughter or a bump (see `detect_non_speech`)
+    NonSpeech = 3,
+}
+
+/// Kind of non-speech sound an attempt is dominated by
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonSpeechKind {
+    /// Nothing suggests the attempt isn't speech
+    None = 0,
+    /// Only very short transients: a bump, tap or click
+    Bump = 1,
+    /// One or two short bursts with an explosive onset and little voicing
+    Cough = 2,
+    /// A regular run of short bursts
+    Laughter = 3,
+}
+
+/// Zero crossings per second of a frame
+pub fn zero_crossing_rate(frame: &[f32], sample_rate: f32) -> f32 {
+    if frame.len() < 2 {
+        return 0.0;
+    }
+    let crossings = frame
+        .windows(2)
+        .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+        .count();
+    crossings as f32 * sample_rate / (frame.len() - 1) as f32
+}
+
+/// Loud region of an attempt, in frames [start, end)
+struct Burst {
+    start: usize,
+    end: usize,
+    peak: usize,
+    voiced: usize,
+}
+
+/// Frames of an attempt that rise above the noise floor, with their levels
+struct SpeechFrames {
+    levels: Vec<f32>,
+    speech: Vec<usize>,
+}
+
+impl SpeechFrames {
+    /// None when the whole attempt is too quiet to hold speech
+    fn measure(
+        audio_data: &[f32],
+        noise_audio: &[f32],
+        frame_size: usize,
+        hop_size: usize,
+    ) -> Option<Self> {
+        let noise = estimate_noise(noise_audio, frame_size, hop_size);
+        let levels = frame_levels(audio_data, frame_size, hop_size);
+        let peak = levels.iter().copied().fold(0.0, f32::max);
+        if peak < noise.noise_rms * 10f32.powf(MIN_PEAK_SNR_DB / 20.0) {
+            return None;
+        }
+        let gate = noise.noise_rms * 10f32.powf(SPEECH_MARGIN_DB / 20.0);
+        let speech: Vec<usize> = (0..levels.len()).filter(|&i| levels[i] > gate).collect();
+        (!speech.is_empty()).then_some(SpeechFrames { levels, speech })
+    }
+
+    /// Runs of speech frames, bridging single-frame dips
+    fn bursts(&self, pitch: &[f32]) -> Vec<Burst> {
+        let mut bursts: Vec<Burst> = Vec::new();
+        for &i in &self.speech {
+            let voiced = pitch.get(i).is_some_and(|&p| p > 0.0) as usize;
+            match bursts.last_mut() {
+                Some(burst) if i <= burst.end + 1 => {
+                    burst.end = i + 1;
+                    burst.voiced += voiced;
+                    if self.levels[i] > self.levels[burst.peak] {
+                        burst.peak = i;
+                    }
+                }
+                _ => bursts.push(Burst {
+                    start: i,
+                    end: i + 1,
+                    peak: i,
+                    voiced,
+                }),
+            }
+        }
+        bursts
+    }
+}
+
+/// Kind of non-speech sound the loud regions of an attempt look like.
+/// `window` is the analysis window and `frame_period` the hop, in seconds.
+fn non_speech_kind(bursts: &[Burst], window: f32, frame_period: f32) -> NonSpeechKind {
+    let seconds = |b: &Burst| (b.end - b.start - 1) as f32 * frame_period + window;
+    if bursts.is_empty() {
+        return NonSpeechKind::None;
+    }
+    if bursts.iter().all(|b| seconds(b) <= BUMP_MAX_SECONDS) {
+        return NonSpeechKind::Bump;
+    }
+
+    if bursts.len() >= LAUGH_MIN_BURSTS
+        && bursts.iter().all(|b| seconds(b) <= LAUGH_MAX_BURST_SECONDS)
+    {
+        let spacing: Vec<f32> = bursts
+            .windows(2)
+            .map(|w| (w[1].start - w[0].start) as f32 * frame_period)
+            .collect();
+        let mean = spacing.iter().sum::<f32>() / spacing.len() as f32;
+        let std = (spacing.iter().map(|s| (s - mean) * (s - mean)).sum::<f32>()
+            / spacing.len() as f32)
+            .sqrt();
+        if (LAUGH_SPACING_SECONDS.0..=LAUGH_SPACING_SECONDS.1).contains(&mean)
+            && std <= LAUGH_MAX_SPACING_VARIATION * mean
+        {
+            return NonSpeechKind::Laughter;
+        }
+    }
+
+    if bursts.len() <= 2 {
+        let longest = bursts
+            .iter()
+            .max_by_key(|b| b.end - b.start)
+            .expect("bursts is not empty");
+        let frames: usize = bursts.iter().map(|b| b.end - b.start).sum();
+        let voiced: usize = bursts.iter().map(|b| b.voiced).sum();
+        let peak_position =
+            (longest.peak - longest.start) as f32 / (longest.end - longest.start) as f32;
+        if seconds(longest) <= COUGH_MAX_SECONDS
+            && peak_position <= COUGH_PEAK_FRACTION
+            && (voiced as f32) < MAX_COUGH_VOICED_FRACTION * frames as f32
+        {
+            return NonSpeechKind::Cough;
+        }
+    }
+    NonSpeechKind::None
+}
+
+/// Classify an attempt from its audio and the flat [pitch, confidence, tau,
+/// ...] results of analyzing it with the same `frame_size` and `hop_size`.
+/// `noise_audio` is where the noise floor is measured; pass the untrimmed
+/// recording when `audio_data` had its silence cut.
+pub fn attempt_status(
+    audio_data: &[f32],
+    noise_audio: &[f32],
+    results: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> AttemptStatus {
+    let Some(frames) = SpeechFrames::measure(audio_data, noise_audio, frame_size, hop_size) else {
+        return AttemptStatus::Silent;
+    };
+    let pitch = pitch_column(results);
+    let speech = &frames.speech;
+
+    let window = frame_size as f32 / sample_rate;
+    let frame_period = hop_size as f32 / sample_rate;
+    if non_speech_kind(&frames.bursts(&pitch), window, frame_period) != NonSpeechKind::None {
+        return AttemptStatus::NonSpeech;
+    }
+
+    let voiced = speech
+        .iter()
+        .filter(|&&i| pitch.get(i).is_some_and(|&p| p > 0.0))
+        .count();
+    let mut rates: Vec<f32> = speech
+        .iter()
+        .map(|&i| {
+            zero_crossing_rate(
+                &audio_data[i * hop_size..i * hop_size + frame_size],
+                sample_rate,
+            )
+        })
+        .collect();
+    rates.sort_by(|a, b| a.total_cmp(b));
+    let median_rate = rates[rates.len() / 2];
+
+    if (voiced as f32) < MAX_WHISPER_VOICED_FRACTION * speech.len() as f32
+        && median_rate > WHISPER_ZCR_HZ
+    {
+        AttemptStatus::Whispered
+    } else {
+        AttemptStatus::Voiced
+    }
+}
+
+/// Whether an attempt is voiced, silent, whispered or non-speech, from the
+/// audio and the results of `perform_yin_analysis` over it with the same
+/// framing. Only voiced attempts belong in the attempt history and in
+/// calibration.
+#[wasm_bindgen]
+pub fn detect_attempt_status(
+    audio_data: &[f32],
+    results: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> AttemptStatus {
+    attempt_status(
+        audio_data,
+        audio_data,
+        results,
+        sample_rate,
+        frame_size,
+        hop_size,
+    )
+}
+
+/// Which non-speech sound (cough, laughter, bump) an attempt is dominated
+/// by, if any, from the audio and the results of `perform_yin_analysis`
+/// over it with the same framing
+#[wasm_bindgen]
+pub fn detect_non_speech(
+    audio_data: &[f32],
+    results: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> NonSpeechKind {
+    match SpeechFrames::measure(audio_data, audio_data, frame_size, hop_size) {
+        Some(frames) => non_speech_kind(
+            &frames.bursts(&pitch_column(results)),
+            frame_size as f32 / sample_rate,
+            hop_size as f32 / sample_rate,
+        ),
+        None => NonSpeechKind::None,
+    }
+}
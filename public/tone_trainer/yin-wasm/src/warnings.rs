@@ -0,0 +1,161 @@
+//! Non-fatal caveats about an analysis, returned with the results so the UI
+//! can mention them ("your microphone is clipping") without running
+//! separate quality checks.
+
+use wasm_bindgen::prelude::*;
+
+use crate::json::{push_key, push_number, push_string};
+use crate::noise::estimate_noise;
+
+/// Absolute sample value treated as clipped
+pub(crate) const CLIP_LEVEL: f32 = 0.99;
+
+/// Share of clipped samples worth a warning; a stray peak isn't
+const MAX_CLIPPED_FRACTION: f32 = 0.001;
+
+/// Dropped tail (seconds) worth a warning; shorter tails are a few
+/// milliseconds of silence at the end of every recording
+const MIN_DROPPED_TAIL_SECONDS: f32 = 0.02;
+
+/// SNR (dB) below which pitch tracking gets unreliable
+const LOW_SNR_DB: f32 = 15.0;
+
+/// What a warning is about; the warning's value is in the unit given here
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningCode {
+    /// Samples at full scale; value is the clipped share of samples
+    Clipped = 0,
+    /// Audio after the last full frame wasn't analyzed; value in seconds
+    TailDropped = 1,
+    /// The input was resampled to the analysis rate; value is the input
+    /// rate in Hz
+    Resampled = 2,
+    /// Background noise close to the speech level; value is the SNR in dB
+    LowSnr = 3,
+}
+
+/// One caveat about an analysis
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnalysisWarning {
+    code: WarningCode,
+    value: f32,
+}
+
+#[wasm_bindgen]
+impl AnalysisWarning {
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> WarningCode {
+        self.code
+    }
+
+    /// Measurement behind the warning, see `WarningCode`
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// English description for logs and developer tools
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        match self.code {
+            WarningCode::Clipped => format!(
+                "{:.1}% of samples are clipped; lower the input gain",
+                self.value * 100.0
+            ),
+            WarningCode::TailDropped => format!(
+                "the last {:.0} ms are shorter than a frame and weren't analyzed",
+                self.value * 1000.0
+            ),
+            WarningCode::Resampled => {
+                format!("input at {} Hz was resampled for analysis", self.value)
+            }
+            WarningCode::LowSnr => format!(
+                "background noise is high (SNR {:.0} dB); pitch may be unreliable",
+                self.value
+            ),
+        }
+    }
+}
+
+impl AnalysisWarning {
+    pub fn new(code: WarningCode, value: f32) -> Self {
+        AnalysisWarning { code, value }
+    }
+}
+
+/// Warnings about `audio_data` (at `sample_rate`) analyzed with the given
+/// framing: clipping, a dropped tail and a low SNR
+pub fn audio_warnings(
+    audio_data: &[f32],
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> Vec<AnalysisWarning> {
+    if audio_data.is_empty() || frame_size == 0 || hop_size == 0 {
+        return Vec::new();
+    }
+    let snr_db = estimate_noise(audio_data, frame_size, hop_size).snr_db;
+    let mut warnings = recording_warnings(audio_data, snr_db);
+    warnings.extend(tail_warning(
+        audio_data.len(),
+        sample_rate,
+        frame_size,
+        hop_size,
+    ));
+    warnings
+}
+
+/// Clipping and low-SNR warnings for a recording whose SNR was already
+/// measured
+pub(crate) fn recording_warnings(audio_data: &[f32], snr_db: f32) -> Vec<AnalysisWarning> {
+    let mut warnings = Vec::new();
+    if audio_data.is_empty() {
+        return warnings;
+    }
+    let clipped = audio_data.iter().filter(|x| x.abs() >= CLIP_LEVEL).count();
+    let clipped_fraction = clipped as f32 / audio_data.len() as f32;
+    if clipped_fraction >= MAX_CLIPPED_FRACTION {
+        warnings.push(AnalysisWarning::new(WarningCode::Clipped, clipped_fraction));
+    }
+    if snr_db < LOW_SNR_DB {
+        warnings.push(AnalysisWarning::new(WarningCode::LowSnr, snr_db));
+    }
+    warnings
+}
+
+/// Warning for the samples after the last full frame of `audio_len`
+/// analyzed samples, when they are long enough to matter
+pub(crate) fn tail_warning(
+    audio_len: usize,
+    sample_rate: f32,
+    frame_size: usize,
+    hop_size: usize,
+) -> Option<AnalysisWarning> {
+    if audio_len < frame_size || hop_size == 0 || sample_rate <= 0.0 {
+        return None;
+    }
+    let tail_seconds = ((audio_len - frame_size) % hop_size) as f32 / sample_rate;
+    (tail_seconds >= MIN_DROPPED_TAIL_SECONDS)
+        .then(|| AnalysisWarning::new(WarningCode::TailDropped, tail_seconds))
+}
+
+/// Append warnings as a JSON array of `AnalysisWarningObject`s
+pub(crate) fn push_warnings(out: &mut String, warnings: &[AnalysisWarning]) {
+    out.push('[');
+    for (i, warning) in warnings.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push('{');
+        push_key(out, "code", true);
+        push_number(out, warning.code as u8 as f32);
+        push_key(out, "value", false);
+        push_number(out, warning.value);
+        push_key(out, "message", false);
+        push_string(out, &warning.message());
+        out.push('}');
+    }
+    out.push(']');
+}
@@ -0,0 +1,339 @@
+//! Incremental RIFF/WAVE decoding to mono f32 samples
+
+use std::fmt;
+
+/// Largest fmt chunk we are willing to buffer
+const MAX_FMT_CHUNK: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WavError {
+    NotRiff,
+    NotWave,
+    BadFmtChunk,
+    DataBeforeFmt,
+    UnsupportedFormat(u16),
+    UnsupportedBitDepth(u16),
+    /// A compressed or non-WAV container, by name
+    UnsupportedContainer(&'static str),
+    /// The input ended before any audio data
+    Truncated,
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WavError::NotRiff => write!(f, "not a RIFF file"),
+            WavError::NotWave => write!(f, "RIFF file is not WAVE"),
+            WavError::BadFmtChunk => write!(f, "malformed fmt chunk"),
+            WavError::DataBeforeFmt => write!(f, "data chunk before fmt chunk"),
+            WavError::UnsupportedFormat(tag) => write!(f, "unsupported WAVE format tag {}", tag),
+            WavError::UnsupportedBitDepth(bits) => {
+                write!(f, "unsupported bit depth {}", bits)
+            }
+            WavError::UnsupportedContainer(name) => {
+                write!(f, "{} files must be decoded before analysis", name)
+            }
+            WavError::Truncated => write!(f, "input ended before the audio data"),
+        }
+    }
+}
+
+/// Sample encoding of the data chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleFormat {
+    Pcm,
+    Float,
+}
+
+/// Decoded fmt chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WavFormat {
+    pub format: SampleFormat,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+}
+
+impl WavFormat {
+    /// Bytes per multi-channel sample frame
+    pub fn block_align(&self) -> usize {
+        self.channels as usize * (self.bits_per_sample as usize / 8)
+    }
+
+    fn parse(body: &[u8]) -> Result<Self, WavError> {
+        if body.len() < 16 {
+            return Err(WavError::BadFmtChunk);
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([body[i], body[i + 1]]);
+        let mut tag = u16_at(0);
+        let channels = u16_at(2);
+        let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+        let bits_per_sample = u16_at(14);
+
+        // WAVE_FORMAT_EXTENSIBLE: the real tag is the start of the sub-format GUID
+        if tag == 0xFFFE {
+            if body.len() < 26 {
+                return Err(WavError::BadFmtChunk);
+            }
+            tag = u16_at(24);
+        }
+
+        let format = match tag {
+            1 => SampleFormat::Pcm,
+            3 => SampleFormat::Float,
+            other => return Err(WavError::UnsupportedFormat(other)),
+        };
+        let supported = match format {
+            SampleFormat::Pcm => matches!(bits_per_sample, 8 | 16 | 24 | 32),
+            SampleFormat::Float => matches!(bits_per_sample, 32 | 64),
+        };
+        if !supported {
+            return Err(WavError::UnsupportedBitDepth(bits_per_sample));
+        }
+        if channels == 0 || sample_rate == 0 {
+            return Err(WavError::BadFmtChunk);
+        }
+
+        Ok(WavFormat {
+            format,
+            channels,
+            sample_rate,
+            bits_per_sample,
+        })
+    }
+
+    /// Decode one sample (of one channel) to [-1, 1]
+    fn decode_sample(&self, bytes: &[u8]) -> f32 {
+        match (self.format, self.bits_per_sample) {
+            (SampleFormat::Pcm, 8) => (bytes[0] as f32 - 128.0) / 128.0,
+            (SampleFormat::Pcm, 16) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            (SampleFormat::Pcm, 24) => {
+                let v = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+                v as f32 / 8_388_608.0
+            }
+            (SampleFormat::Pcm, _) => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32
+                    / 2_147_483_648.0
+            }
+            (SampleFormat::Float, 32) => {
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            }
+            (SampleFormat::Float, _) => {
+                let mut b = [0u8; 8];
+                b.copy_from_slice(&bytes[..8]);
+                f64::from_le_bytes(b) as f32
+            }
+        }
+    }
+}
+
+/// Name of a known non-WAV container from its first bytes
+fn sniff_container(head: &[u8]) -> Option<&'static str> {
+    match head {
+        [b'O', b'g', b'g', b'S', ..] => Some("Ogg"),
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => Some("WebM"),
+        [b'f', b'L', b'a', b'C', ..] => Some("FLAC"),
+        [b'I', b'D', b'3', ..] => Some("MP3"),
+        [0xFF, b, ..] if b & 0xE0 == 0xE0 => Some("MP3"),
+        [b'F', b'O', b'R', b'M', ..] => Some("AIFF"),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some("MP4"),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Waiting for the 12-byte RIFF/WAVE preamble
+    Preamble,
+    /// Waiting for the next chunk header
+    ChunkHeader,
+    /// Skipping the rest of an uninteresting chunk
+    Skip(usize),
+    /// Buffering the fmt chunk body
+    Fmt(usize),
+    /// Inside the data chunk; `None` means unbounded (streaming writers)
+    Data(Option<usize>),
+    Done,
+}
+
+/// Decodes a WAV file fed in arbitrary byte chunks, producing mono samples
+/// (channels averaged) without ever holding more than one partial sample
+/// frame or chunk header in memory
+pub struct WavStreamDecoder {
+    state: State,
+    pending: Vec<u8>,
+    format: Option<WavFormat>,
+}
+
+impl Default for WavStreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WavStreamDecoder {
+    pub fn new() -> Self {
+        WavStreamDecoder {
+            state: State::Preamble,
+            pending: Vec::new(),
+            format: None,
+        }
+    }
+
+    /// Format, once the fmt chunk has been seen
+    pub fn format(&self) -> Option<WavFormat> {
+        self.format
+    }
+
+    /// True once the end of the data chunk has been reached
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Check the input was complete once no more bytes will arrive. A data
+    /// chunk cut short is accepted (recorders often write bad sizes); input
+    /// that ended before the data chunk is not.
+    pub fn finish(&self) -> Result<(), WavError> {
+        match self.state {
+            State::Data(_) | State::Done => Ok(()),
+            _ => Err(WavError::Truncated),
+        }
+    }
+
+    /// Feed the next chunk of file bytes, appending decoded mono samples to `out`
+    pub fn push(&mut self, mut bytes: &[u8], out: &mut Vec<f32>) -> Result<(), WavError> {
+        while !bytes.is_empty() {
+            match self.state {
+                State::Preamble => {
+                    if !self.fill(&mut bytes, 12) {
+                        break;
+                    }
+                    if &self.pending[0..4] != b"RIFF" {
+                        return Err(sniff_container(&self.pending)
+                            .map_or(WavError::NotRiff, WavError::UnsupportedContainer));
+                    }
+                    if &self.pending[8..12] != b"WAVE" {
+                        return Err(WavError::NotWave);
+                    }
+                    self.pending.clear();
+                    self.state = State::ChunkHeader;
+                }
+                State::ChunkHeader => {
+                    if !self.fill(&mut bytes, 8) {
+                        break;
+                    }
+                    let id = [
+                        self.pending[0],
+                        self.pending[1],
+                        self.pending[2],
+                        self.pending[3],
+                    ];
+                    let size = u32::from_le_bytes([
+                        self.pending[4],
+                        self.pending[5],
+                        self.pending[6],
+                        self.pending[7],
+                    ]) as usize;
+                    self.pending.clear();
+                    // Chunks are padded to an even length
+                    let padded = size + (size & 1);
+                    self.state = match &id {
+                        b"fmt " if size <= MAX_FMT_CHUNK => State::Fmt(padded),
+                        b"fmt " => return Err(WavError::BadFmtChunk),
+                        b"data" if self.format.is_none() => return Err(WavError::DataBeforeFmt),
+                        b"data" if size == 0 || size == u32::MAX as usize => State::Data(None),
+                        b"data" => State::Data(Some(size)),
+                        _ => State::Skip(padded),
+                    };
+                }
+                State::Skip(remaining) => {
+                    let n = remaining.min(bytes.len());
+                    bytes = &bytes[n..];
+                    self.state = if n == remaining {
+                        State::ChunkHeader
+                    } else {
+                        State::Skip(remaining - n)
+                    };
+                }
+                State::Fmt(size) => {
+                    if !self.fill(&mut bytes, size) {
+                        break;
+                    }
+                    self.format = Some(WavFormat::parse(&self.pending)?);
+                    self.pending.clear();
+                    self.state = State::ChunkHeader;
+                }
+                State::Data(remaining) => {
+                    let n = remaining.map_or(bytes.len(), |r| r.min(bytes.len()));
+                    self.decode_data(&bytes[..n], out);
+                    bytes = &bytes[n..];
+                    self.state = match remaining {
+                        Some(r) if r == n => State::Done,
+                        Some(r) => State::Data(Some(r - n)),
+                        None => State::Data(None),
+                    };
+                }
+                State::Done => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Move bytes into `pending` until it holds `target` bytes; returns
+    /// whether the target was reached
+    fn fill(&mut self, bytes: &mut &[u8], target: usize) -> bool {
+        let needed = target.saturating_sub(self.pending.len());
+        let n = needed.min(bytes.len());
+        self.pending.extend_from_slice(&bytes[..n]);
+        *bytes = &bytes[n..];
+        self.pending.len() >= target
+    }
+
+    /// Decode whole sample frames, keeping any partial frame for next time
+    fn decode_data(&mut self, bytes: &[u8], out: &mut Vec<f32>) {
+        let Some(format) = self.format else {
+            return;
+        };
+        let block = format.block_align();
+        let sample_bytes = format.bits_per_sample as usize / 8;
+        let channels = format.channels as usize;
+        let scale = 1.0 / channels as f32;
+
+        let mut decode_block = |frame: &[u8]| {
+            let sum: f32 = frame
+                .chunks_exact(sample_bytes)
+                .map(|s| format.decode_sample(s))
+                .sum();
+            out.push(sum * scale);
+        };
+
+        let mut bytes = bytes;
+        if !self.pending.is_empty() {
+            let needed = block - self.pending.len();
+            let n = needed.min(bytes.len());
+            self.pending.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if self.pending.len() < block {
+                return;
+            }
+            decode_block(&self.pending);
+            self.pending.clear();
+        }
+
+        let whole = bytes.len() / block * block;
+        bytes[..whole]
+            .chunks_exact(block)
+            .for_each(&mut decode_block);
+        self.pending.extend_from_slice(&bytes[whole..]);
+    }
+}
+
+/// Decode a complete WAV file to mono samples, with its format
+pub fn decode_wav(bytes: &[u8]) -> Result<(Vec<f32>, WavFormat), WavError> {
+    let mut decoder = WavStreamDecoder::new();
+    let mut samples = Vec::new();
+    decoder.push(bytes, &mut samples)?;
+    decoder.finish()?;
+    let format = decoder.format().ok_or(WavError::Truncated)?;
+    Ok((samples, format))
+}
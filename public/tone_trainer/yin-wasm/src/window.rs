@@ -0,0 +1,92 @@
+//! Tapering windows applied to each frame before the difference function.
+//! The rectangular window (no tapering) stays the default; with short frames
+//! a tapered window keeps the abrupt frame edges from adding spurious dips.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+/// Window applied to every frame before the difference function
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowFunction {
+    /// No tapering
+    #[default]
+    Rectangular = 0,
+    Hann = 1,
+    Hamming = 2,
+    Blackman = 3,
+}
+
+impl WindowFunction {
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(WindowFunction::Rectangular),
+            1 => Some(WindowFunction::Hann),
+            2 => Some(WindowFunction::Hamming),
+            3 => Some(WindowFunction::Blackman),
+            _ => None,
+        }
+    }
+}
+
+/// Coefficients of `window` for frames of `size` samples (symmetric form)
+#[wasm_bindgen]
+pub fn window_coefficients(window: WindowFunction, size: usize) -> Vec<f32> {
+    if size < 2 {
+        return vec![1.0; size];
+    }
+    let denom = (size - 1) as f32;
+    (0..size)
+        .map(|n| {
+            let phase = 2.0 * PI * n as f32 / denom;
+            match window {
+                WindowFunction::Rectangular => 1.0,
+                WindowFunction::Hann => 0.5 - 0.5 * phase.cos(),
+                WindowFunction::Hamming => 0.54 - 0.46 * phase.cos(),
+                WindowFunction::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+            }
+        })
+        .collect()
+}
+
+thread_local! {
+    /// Coefficients of the last window used, so an analysis computes them
+    /// once rather than per frame
+    static TABLE: RefCell<Option<(WindowFunction, Rc<[f32]>)>> = const { RefCell::new(None) };
+}
+
+/// Coefficients of `window` for `size`, reusing the last table when it fits
+fn coefficients(window: WindowFunction, size: usize) -> Rc<[f32]> {
+    TABLE.with(|table| {
+        let mut table = table.borrow_mut();
+        match &*table {
+            Some((kind, coefficients)) if *kind == window && coefficients.len() == size => {
+                coefficients.clone()
+            }
+            _ => {
+                let coefficients: Rc<[f32]> = window_coefficients(window, size).into();
+                *table = Some((window, coefficients.clone()));
+                coefficients
+            }
+        }
+    })
+}
+
+/// `frame` multiplied by `window`; borrowed unchanged for the rectangular one
+pub(crate) fn apply_window(window: WindowFunction, frame: &[f32]) -> Cow<'_, [f32]> {
+    if window == WindowFunction::Rectangular {
+        return Cow::Borrowed(frame);
+    }
+    let coefficients = coefficients(window, frame.len());
+    Cow::Owned(
+        frame
+            .iter()
+            .zip(coefficients.iter())
+            .map(|(x, w)| x * w)
+            .collect(),
+    )
+}